@@ -0,0 +1,46 @@
+//! Embeds the git commit the binary was built from, for `ccs version --full`.
+
+use std::path::Path;
+use std::process::Command;
+
+fn main() {
+    track_git_head();
+
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|commit| commit.trim().to_string())
+        .filter(|commit| !commit.is_empty());
+    if let Some(commit) = commit {
+        println!("cargo:rustc-env=CCS_GIT_COMMIT={}", commit);
+    }
+}
+
+/// Rerun when the checked-out commit changes: HEAD itself, the branch ref it
+/// points to, and `packed-refs`. A directive for a missing path makes cargo
+/// rerun on every build, so outside a checkout (a packaged crate) only the
+/// build script is tracked.
+fn track_git_head() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    let git = Path::new(".git");
+    let head = git.join("HEAD");
+    let Ok(content) = std::fs::read_to_string(&head) else {
+        return;
+    };
+    println!("cargo:rerun-if-changed={}", head.display());
+
+    if let Some(reference) = content.trim().strip_prefix("ref: ") {
+        let ref_path = git.join(reference);
+        if ref_path.exists() {
+            println!("cargo:rerun-if-changed={}", ref_path.display());
+        }
+    }
+    let packed_refs = git.join("packed-refs");
+    if packed_refs.exists() {
+        println!("cargo:rerun-if-changed={}", packed_refs.display());
+    }
+}
@@ -1,4 +1,4 @@
-use clap::{Args, Parser, Subcommand};
+use clap::{ArgAction, Args, Parser, Subcommand};
 use std::path::PathBuf;
 
 use crate::snapshots::SnapshotScope;
@@ -7,50 +7,202 @@ use crate::snapshots::SnapshotScope;
 #[derive(Parser)]
 #[command(about, version, author, long_about = None)]
 pub struct Cli {
+    /// Subcommand to run. Without one, an interactive menu is shown.
     #[command(subcommand)]
-    pub command: Commands,
+    pub command: Option<Commands>,
+
+    /// Disable colored output (also honored via the NO_COLOR env var)
+    #[arg(long, global = true, help = "Disable colored output")]
+    pub no_color: bool,
+
+    /// Answer yes to every confirmation (also honored via CCS_ASSUME_YES)
+    #[arg(
+        long,
+        short = 'y',
+        global = true,
+        help = "Skip all confirmation prompts"
+    )]
+    pub yes: bool,
+
+    /// Allow writing settings that are empty (`{}`) over a populated file
+    #[arg(
+        long,
+        global = true,
+        help = "Write settings even if they are empty and the file isn't"
+    )]
+    pub force: bool,
+
+    /// Only print errors and requested output (no status messages)
+    #[arg(long, short = 'q', global = true, help = "Suppress status messages")]
+    pub quiet: bool,
+
+    /// Log detail: `-v` shows decisions (merge strategy, key source),
+    /// `-vv` also shows files read and written and HTTP calls
+    #[arg(
+        long,
+        short = 'v',
+        global = true,
+        action = ArgAction::Count,
+        help = "Show more detail (repeat for more: -vv)"
+    )]
+    pub verbose: u8,
+}
+
+impl Cli {
+    /// `apply --settings-path -` prints the settings to stdout, so status
+    /// messages have to go elsewhere
+    pub fn settings_on_stdout(&self) -> bool {
+        matches!(
+            &self.command,
+            Some(Commands::Apply {
+                settings_path: Some(path),
+                ..
+            }) if crate::utils::is_stdio_path(path)
+        )
+    }
 }
 
 /// Available CLI commands
+// Parsed once per run, so `Apply` being much larger than the rest is fine
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 pub enum Commands {
-    /// List and manage snapshots [aliases: l, ls]
-    #[command(alias = "l", alias = "ls")]
+    /// List and manage snapshots
+    #[command(visible_alias = "l", visible_alias = "ls")]
     List,
 
-    /// Apply a snapshot or template [alias: a]
-    #[command(alias = "a")]
+    /// Apply a snapshot or template
+    #[command(visible_alias = "a")]
     Apply {
         /// Snapshot name or template type
         /// (deepseek, glm, k2, k2-thinking, kat-coder, kimi, longcat, fishtrip,
-        /// minimax, seed-code, zenmux, duojie, anyrouter, openrouter, beeapi, day77)
-        target: String,
+        /// minimax, seed-code, zenmux, duojie, anyrouter, openrouter, beeapi, day77).
+        /// Several targets are layered in order, each merging onto the result
+        /// of the previous ones, and written once.
+        /// Defaults to `default-template` from ~/.claude/ccs.toml.
+        #[arg(value_name = "TARGET")]
+        targets: Vec<String>,
+
+        /// Read a full settings JSON document from stdin instead of a target
+        #[arg(
+            long,
+            conflicts_with = "targets",
+            help = "Read settings JSON from stdin and merge it (instead of a target)"
+        )]
+        from_stdin: bool,
 
-        /// What to include (default: common). env = only env vars; common =
-        /// env+model+permissions+hooks; all = everything.
-        #[arg(long, default_value = "common", help = "Scope of settings to include")]
-        scope: SnapshotScope,
+        /// Apply the most recently created snapshot instead of a target
+        #[arg(
+            long,
+            conflicts_with_all = ["targets", "from_stdin", "template_file", "print_env"],
+            help = "Apply the most recently created snapshot"
+        )]
+        latest: bool,
+
+        /// Apply a settings JSON file whose env values may contain `{API_KEY}`.
+        /// An optional TARGET names the provider whose key fills it.
+        #[arg(
+            long,
+            value_name = "PATH",
+            conflicts_with_all = ["from_stdin", "print_env"],
+            help = "Apply a settings JSON file with {API_KEY} placeholders (TARGET picks the key)"
+        )]
+        template_file: Option<PathBuf>,
+
+        /// Pick the template, API key and endpoint step by step, then review
+        /// the changes before writing. Default when no target is given on a TTY.
+        #[arg(
+            long,
+            short = 'i',
+            conflicts_with_all = ["targets", "from_stdin", "print_env", "template_file", "latest"],
+            help = "Choose template, API key and endpoint interactively, then review"
+        )]
+        interactive: bool,
+
+        /// What to include (default: common, or `scope` from ccs.toml). env =
+        /// only env vars; common = env+model+permissions+hooks; all = everything.
+        #[arg(long, help = "Scope of settings to include (default: common)")]
+        scope: Option<SnapshotScope>,
+
+        /// Only include these fields (e.g. model,permissions,env.ANTHROPIC_BASE_URL);
+        /// overrides --scope
+        #[arg(
+            long,
+            conflicts_with = "scope",
+            help = "Only include these fields (e.g. model,env.ANTHROPIC_BASE_URL)"
+        )]
+        fields: Option<String>,
 
         /// Override model setting
         #[arg(long, help = "Override model setting")]
         model: Option<String>,
 
-        /// Path to settings file (default: .claude/settings.json)
-        #[arg(long, help = "Path to settings file (default: .claude/settings.json)")]
+        /// Take the model from another snapshot (an explicit --model wins)
+        #[arg(
+            long,
+            value_name = "SNAPSHOT",
+            conflicts_with_all = ["from_stdin", "template_file", "interactive"],
+            help = "Use another snapshot's model (--model wins)"
+        )]
+        model_from: Option<String>,
+
+        /// Path to settings file (default: .claude/settings.json when a .claude
+        /// directory exists, ~/.claude/settings.json otherwise).
+        /// `-` reads the current settings from stdin and prints the result
+        #[arg(
+            long,
+            help = "Path to settings file (default: project if .claude exists, else global; - for stdin/stdout)"
+        )]
         settings_path: Option<PathBuf>,
 
-        /// Backup current settings before applying
-        #[arg(long, help = "Create backup of current settings before applying")]
+        /// Write to the global ~/.claude/settings.json
+        #[arg(long, conflicts_with_all = ["local", "settings_path"], help = "Use global settings (~/.claude/settings.json)")]
+        global: bool,
+
+        /// Write to the project ./.claude/settings.json
+        #[arg(
+            long,
+            conflicts_with = "settings_path",
+            help = "Use project settings (./.claude/settings.json)"
+        )]
+        local: bool,
+
+        /// Back up current settings before applying, even if `backup = false`
+        /// in ccs.toml (backing up is the default)
+        #[arg(
+            long,
+            help = "Back up current settings before applying (the default unless backup = false)"
+        )]
         backup: bool,
 
-        /// Skip the confirmation prompt (apply directly)
-        #[arg(long, short = 'y', help = "Skip confirmation / apply directly")]
-        yes: bool,
+        /// Don't back up the current settings before applying
+        #[arg(
+            long,
+            conflicts_with = "backup",
+            help = "Skip the backup of the current settings"
+        )]
+        no_backup: bool,
 
         /// Deprecated: non-interactive mode. Now automatic when stdin isn't a TTY.
         #[arg(long, hide = true, help = "Non-interactive mode (deprecated)")]
         cli: bool,
 
+        /// Output style to set (default, concise, explanatory, learning)
+        #[arg(
+            long,
+            value_parser = crate::settings::parse_output_style,
+            help = "Set the output style (default/concise/explanatory/learning)"
+        )]
+        output_style: Option<String>,
+
+        /// Model for subagents (default: the template's small/fast model)
+        #[arg(
+            long,
+            conflicts_with = "template_file",
+            help = "Model for subagents (default: the template's small/fast model)"
+        )]
+        subagent_model: Option<String>,
+
         /// Effort level override (max/xhigh/high/medium/low)
         #[arg(long, help = "Set effort level (overrides the default in your config)")]
         effort: Option<String>,
@@ -60,10 +212,45 @@ pub enum Commands {
             long = "auto-compact",
             visible_alias = "compact",
             alias = "context",
+            conflicts_with = "template_file",
             help = "Set auto-compact threshold for supported 1M providers (896k/768k/512k/256k)"
         )]
         auto_compact: Option<String>,
 
+        /// Turn thinking on for providers that support it (ZAI, DeepSeek, K2 Thinking)
+        #[arg(
+            long,
+            conflicts_with_all = ["no_thinking", "from_stdin", "template_file", "interactive"],
+            help = "Enable thinking (providers that support it)"
+        )]
+        thinking: bool,
+
+        /// Turn thinking off for providers that support it
+        #[arg(
+            long,
+            conflicts_with_all = ["from_stdin", "template_file", "interactive"],
+            help = "Disable thinking (providers that support it)"
+        )]
+        no_thinking: bool,
+
+        /// Override REASONING_EFFORT for providers that support thinking
+        #[arg(
+            long,
+            value_name = "LEVEL",
+            conflicts_with_all = ["no_thinking", "from_stdin", "template_file", "interactive"],
+            help = "Set REASONING_EFFORT (providers that support thinking)"
+        )]
+        reasoning_effort: Option<String>,
+
+        /// Override MAX_THINKING_TOKENS for providers that support thinking
+        #[arg(
+            long,
+            value_name = "N",
+            conflicts_with_all = ["no_thinking", "from_stdin", "template_file", "interactive"],
+            help = "Set MAX_THINKING_TOKENS (providers that support thinking)"
+        )]
+        max_thinking_tokens: Option<u32>,
+
         /// API key to use (skips interactive selection)
         #[arg(
             long,
@@ -72,38 +259,291 @@ pub enum Commands {
         )]
         api_key: Option<String>,
 
+        /// Print only this env variable of the template's settings and exit
+        #[arg(
+            long,
+            value_name = "VAR",
+            conflicts_with = "from_stdin",
+            help = "Print one env value the template would write (e.g. ANTHROPIC_BASE_URL) and exit"
+        )]
+        print_env: Option<String>,
+
+        /// Read the provider API key from a .env file (KEY=VALUE lines)
+        #[arg(
+            long,
+            value_name = "PATH",
+            help = "Read the API key from a .env file (takes precedence over process env)"
+        )]
+        env_file: Option<PathBuf>,
+
         /// Disable co-authored-by attribution in commits/PRs
         #[arg(long, help = "Disable co-authored-by attribution")]
         no_co_author: bool,
 
+        /// Turn co-authored-by attribution back on, even if settings.json
+        /// or your preferences have it off
+        #[arg(
+            long,
+            conflicts_with = "no_co_author",
+            help = "Enable co-authored-by attribution (overrides preferences)"
+        )]
+        co_authored: bool,
+
+        /// How to combine the template with the existing settings
+        #[arg(
+            long,
+            value_enum,
+            default_value_t,
+            help = "merge: keep existing keys; replace: drop the previous provider's env vars and model first"
+        )]
+        merge_strategy: crate::settings::MergeStrategy,
+
         /// Force the API-key picker even if a key is remembered
         #[arg(long, help = "Force the API-key picker (ignore remembered key)")]
         switch_key: bool,
 
+        /// Take the key from the template's env vars (primary first) when set
+        #[arg(
+            long,
+            conflicts_with_all = ["prefer_saved", "switch_key"],
+            help = "Use the key from the template's env vars when one is set"
+        )]
+        prefer_env: bool,
+
+        /// Take the most recently used saved credential when there is one
+        #[arg(
+            long,
+            conflicts_with = "switch_key",
+            help = "Use a saved credential for the template when there is one"
+        )]
+        prefer_saved: bool,
+
         /// Preview the result without writing anything
         #[arg(long, help = "Preview changes without writing settings")]
         dry_run: bool,
 
+        /// Like --dry-run, but print only what would change (API keys masked)
+        #[arg(
+            long,
+            conflicts_with_all = ["from_stdin", "interactive", "print_env"],
+            help = "Print only the changes apply would make, without writing"
+        )]
+        print_diff_only: bool,
+
+        /// Check the provider's base URL is reachable before applying
+        #[arg(long, help = "Warn if the provider's base URL is unreachable")]
+        preflight: bool,
+
+        /// With --preflight, refuse to apply if the base URL is unreachable
+        #[arg(
+            long,
+            requires = "preflight",
+            help = "Fail instead of warning when --preflight can't reach the provider"
+        )]
+        strict: bool,
+
         /// Specific variant alias for generic targets (e.g. zai-china, k2, kat-coder-air)
-        #[arg(long, help = "Specific variant alias (e.g. zai-china, k2)")]
+        #[arg(
+            long,
+            conflicts_with = "template_file",
+            help = "Specific variant alias (e.g. zai-china, k2)"
+        )]
         variant: Option<String>,
+
+        /// Also save the applied settings as a snapshot (scope: all)
+        #[arg(
+            long,
+            value_name = "NAME",
+            help = "Save the applied settings as a snapshot with this name"
+        )]
+        save_as_snapshot: Option<String>,
+
+        /// Add a tool to permissions.allow in the applied settings (repeatable)
+        #[arg(
+            long,
+            value_name = "TOOL",
+            conflicts_with_all = ["from_stdin", "template_file", "interactive"],
+            help = "Allow a tool in the applied settings (repeatable)"
+        )]
+        allow: Vec<String>,
+
+        /// Add a tool to permissions.deny in the applied settings (repeatable).
+        /// Deny wins over --allow and the template's allow list.
+        #[arg(
+            long,
+            value_name = "TOOL",
+            conflicts_with_all = ["from_stdin", "template_file", "interactive"],
+            help = "Deny a tool in the applied settings (repeatable; wins over allow)"
+        )]
+        deny: Vec<String>,
+
+        /// Don't deny WebSearch, which the templates do by default
+        #[arg(
+            long,
+            conflicts_with_all = ["from_stdin", "template_file", "interactive"],
+            help = "Keep WebSearch out of the deny list the templates write"
+        )]
+        allow_web_search: bool,
+
+        /// Keep these fields from the existing settings whatever the
+        /// template or merge would write (repeatable or comma-separated)
+        #[arg(
+            long,
+            value_enum,
+            value_delimiter = ',',
+            value_name = "FIELD",
+            conflicts_with_all = ["from_stdin", "template_file", "interactive"],
+            help = "Keep hooks, status-line, permissions or output-style from the existing settings"
+        )]
+        preserve: Vec<crate::settings::PreserveField>,
+
+        /// Replace an existing snapshot with the --save-as-snapshot name
+        #[arg(
+            long,
+            requires = "save_as_snapshot",
+            help = "Overwrite an existing snapshot with the same name"
+        )]
+        overwrite: bool,
     },
 
-    /// Manage saved credentials [aliases: creds, cred]
-    #[command(alias = "creds", alias = "cred")]
+    /// Save the current settings as a snapshot
+    Snap {
+        /// Snapshot name
+        name: String,
+
+        /// What to capture (env/common/all; default: common, or `scope` from ccs.toml)
+        #[arg(long, help = "Scope of settings to capture (default: common)")]
+        scope: Option<SnapshotScope>,
+
+        /// Only capture these fields (e.g. model,permissions,env.ANTHROPIC_BASE_URL)
+        #[arg(
+            long,
+            conflicts_with = "scope",
+            help = "Only capture these fields (e.g. model,env.ANTHROPIC_BASE_URL)"
+        )]
+        fields: Option<String>,
+
+        /// Path to settings file (default: .claude/settings.json when a .claude
+        /// directory exists, ~/.claude/settings.json otherwise)
+        #[arg(
+            long,
+            help = "Path to settings file (default: project if .claude exists, else global)"
+        )]
+        settings_path: Option<PathBuf>,
+
+        /// Capture the global ~/.claude/settings.json
+        #[arg(long, conflicts_with_all = ["local", "settings_path"], help = "Use global settings (~/.claude/settings.json)")]
+        global: bool,
+
+        /// Capture the project ./.claude/settings.json
+        #[arg(
+            long,
+            conflicts_with = "settings_path",
+            help = "Use project settings (./.claude/settings.json)"
+        )]
+        local: bool,
+
+        /// Description for the snapshot
+        #[arg(long, short = 'd', help = "Description for the snapshot")]
+        description: Option<String>,
+
+        /// Overwrite an existing snapshot with the same name
+        #[arg(long, help = "Overwrite an existing snapshot without asking")]
+        overwrite: bool,
+
+        /// Output style to store in the snapshot
+        #[arg(
+            long,
+            value_parser = crate::settings::parse_output_style,
+            help = "Set the output style in the snapshot (default/concise/explanatory/learning)"
+        )]
+        output_style: Option<String>,
+
+        /// Show what would be captured without saving
+        #[arg(
+            long,
+            alias = "dry-run",
+            help = "Show what would be captured without saving"
+        )]
+        preview: bool,
+    },
+
+    /// Print version information
+    Version {
+        /// Also print the commit, format versions and data directories
+        #[arg(long, help = "Include build and configuration details")]
+        full: bool,
+    },
+
+    /// List the available templates
+    Templates {
+        /// Print every target string each template accepts
+        #[arg(long, help = "Show every accepted alias for each template")]
+        aliases: bool,
+    },
+
+    /// Inspect saved snapshots
+    #[command(visible_alias = "snapshots")]
+    Snapshot {
+        /// Subcommand for snapshot inspection
+        #[command(subcommand)]
+        command: SnapshotCommands,
+    },
+
+    /// Manage saved credentials
+    #[command(visible_alias = "creds", visible_alias = "cred")]
     Credentials {
         /// Subcommand for credential management
         #[command(subcommand)]
         command: CredentialCommands,
     },
 
-    /// View or edit persistent preferences [alias: cfg]
-    #[command(alias = "cfg")]
+    /// View or edit persistent preferences
+    #[command(visible_alias = "cfg")]
     Config(ConfigArgs),
 
-    /// Show the currently-active provider [alias: status]
-    #[command(alias = "status")]
-    Current,
+    /// Show the currently-active provider
+    #[command(visible_alias = "status")]
+    Current {
+        /// Show the merged global + project settings, annotated by source file
+        #[arg(long)]
+        effective: bool,
+
+        /// Settings file to inspect (`-` reads it from stdin)
+        #[arg(long, conflicts_with = "effective")]
+        settings_path: Option<PathBuf>,
+    },
+
+    /// Revert the settings file to its state before the last apply
+    Undo,
+
+    /// Re-apply the last undone apply
+    Redo,
+
+    /// Diagnose settings paths, env vars, snapshots and credentials
+    Doctor,
+
+    /// Find unparseable snapshot/credential files and quarantine them
+    Repair,
+
+    /// Edit permission allow/ask/deny lists
+    #[command(visible_alias = "perm", visible_alias = "permissions")]
+    Perms {
+        /// Subcommand for permission management
+        #[command(subcommand)]
+        command: PermsCommands,
+    },
+
+    /// Print a shell completion script, or install it with --install
+    Completions {
+        /// bash, zsh or fish (default: the shell in $SHELL)
+        #[arg(value_enum)]
+        shell: Option<crate::completions::Shell>,
+
+        /// Write the script to the shell's completions directory
+        #[arg(long, help = "Write the script where the shell looks for completions")]
+        install: bool,
+    },
 }
 
 /// Arguments for `ccs config`
@@ -131,19 +571,346 @@ pub struct ConfigArgs {
     /// Reset all preferences to defaults
     #[arg(long, help = "Reset all preferences to defaults")]
     pub reset: bool,
+
+    /// Change the settings file instead of preferences
+    #[command(subcommand)]
+    pub command: Option<ConfigCommands>,
+}
+
+/// On/off switch argument
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Toggle {
+    On,
+    Off,
+}
+
+/// Settings-file toggles under `ccs config`
+#[derive(Subcommand, Clone, Debug)]
+pub enum ConfigCommands {
+    /// Turn Claude Code's co-authored-by attribution on or off in settings.json
+    CoAuthored {
+        /// on or off
+        #[arg(value_enum)]
+        state: Toggle,
+
+        /// Settings file to change (default: the active settings file)
+        #[arg(long, help = "Path to settings file")]
+        settings_path: Option<PathBuf>,
+    },
+
+    /// Set one env variable in settings.json
+    SetEnv {
+        /// KEY=VALUE (e.g. ANTHROPIC_BASE_URL=https://...)
+        #[arg(value_parser = crate::utils::parse_env_assignment, value_name = "KEY=VALUE")]
+        assignment: (String, String),
+
+        /// Settings file to change (default: the active settings file)
+        #[arg(long, help = "Path to settings file")]
+        settings_path: Option<PathBuf>,
+    },
+
+    /// Remove one env variable from settings.json
+    UnsetEnv {
+        /// Env variable name (e.g. ANTHROPIC_DEFAULT_SONNET_MODEL)
+        key: String,
+
+        /// Settings file to change (default: the active settings file)
+        #[arg(long, help = "Path to settings file")]
+        settings_path: Option<PathBuf>,
+    },
+}
+
+/// Snapshot commands
+#[derive(Subcommand)]
+pub enum SnapshotCommands {
+    /// Print a snapshot's details (settings are masked)
+    Show {
+        /// Snapshot name
+        name: String,
+
+        /// Print the raw snapshot JSON instead
+        #[arg(long, help = "Print the raw snapshot as JSON")]
+        json: bool,
+    },
+
+    /// Show the field-level differences between two snapshots
+    Diff {
+        /// Snapshot to compare from
+        a: String,
+
+        /// Snapshot to compare to
+        b: String,
+    },
+
+    /// Set or clear (with "") a snapshot's description
+    Describe {
+        /// Snapshot name
+        name: String,
+
+        /// New description; an empty string clears it
+        text: String,
+    },
+
+    /// Check that a snapshot has what apply needs (base URL, key, model)
+    Verify {
+        /// Snapshot name
+        name: String,
+
+        /// Also check that the base URL answers
+        #[arg(long, help = "Also check that the snapshot's base URL is reachable")]
+        preflight: bool,
+    },
+
+    /// Delete old snapshots, keeping the newest
+    Prune {
+        /// Keep only this many of the newest snapshots
+        #[arg(long, help = "Keep only the newest N snapshots")]
+        keep: Option<usize>,
+
+        /// Delete snapshots older than this (e.g. 30d, 12h, 2w)
+        #[arg(
+            long,
+            value_parser = crate::utils::parse_duration,
+            help = "Delete snapshots older than this (e.g. 30d, 12h, 2w)"
+        )]
+        older_than: Option<chrono::Duration>,
+    },
+
+    /// Save a template's default settings as a snapshot without applying them
+    FromTemplate {
+        /// Template to snapshot (e.g. zai, deepseek, kimi)
+        template: String,
+
+        /// Snapshot name (default: the template name)
+        #[arg(long, short = 'n', help = "Snapshot name (default: the template name)")]
+        name: Option<String>,
+
+        /// What to capture (env/common/all; default: common)
+        #[arg(long, help = "Scope of settings to capture (default: common)")]
+        scope: Option<SnapshotScope>,
+
+        /// API key to store in the snapshot
+        #[arg(long, help = "API key to store in the snapshot")]
+        api_key: Option<String>,
+
+        /// Pick or enter an API key to store, like apply does
+        #[arg(
+            long,
+            conflicts_with = "api_key",
+            help = "Choose an API key to store (default: leave the key out)"
+        )]
+        with_key: bool,
+
+        /// Store the API key masked
+        #[arg(long, help = "Store the API key masked")]
+        mask_key: bool,
+
+        /// Overwrite an existing snapshot with the same name
+        #[arg(long, help = "Overwrite an existing snapshot without asking")]
+        overwrite: bool,
+    },
+
+    /// Import every snapshot .json file in a directory (e.g. from another machine)
+    ImportDir {
+        /// Directory holding the snapshot files
+        dir: PathBuf,
+
+        /// Replace existing snapshots with the same name instead of adding
+        /// a numbered copy
+        #[arg(
+            long,
+            help = "Replace snapshots with the same name (default: import as name-2, ...)"
+        )]
+        overwrite: bool,
+    },
 }
 
 /// Credential management commands
 #[derive(Subcommand)]
 pub enum CredentialCommands {
-    /// List saved credentials [aliases: l, ls]
-    #[command(alias = "l", alias = "ls")]
-    List,
+    /// List saved credentials
+    #[command(visible_alias = "l", visible_alias = "ls")]
+    List {
+        /// Only show expired credentials
+        #[arg(
+            long,
+            conflicts_with = "hide_expired",
+            help = "Only show expired credentials"
+        )]
+        show_expired: bool,
+
+        /// Hide expired credentials
+        #[arg(long, help = "Hide expired credentials")]
+        hide_expired: bool,
+
+        /// Print CSV/TSV instead of opening the browser
+        #[arg(
+            long,
+            value_enum,
+            help = "Print the list as csv or tsv instead of opening the browser"
+        )]
+        format: Option<crate::credentials::DelimitedFormat>,
+
+        /// Include the masked API key column (with --format)
+        #[arg(long, requires = "format", help = "Add a masked api_key column")]
+        with_key: bool,
+
+        /// Order of the list
+        #[arg(
+            long,
+            value_enum,
+            default_value_t,
+            help = "Sort by name-asc, name-desc, created-asc, created-desc or template-then-name"
+        )]
+        sort: crate::credentials::SortOrder,
+    },
+
+    /// Save an API key from a provider's downloaded key file (.txt or .json)
+    ImportFile {
+        /// Key file; a .json file is read at --pointer, anything else whole
+        path: PathBuf,
+
+        /// Template the key belongs to (e.g. deepseek, zai)
+        #[arg(long, short = 't', help = "Template the key belongs to")]
+        template: String,
+
+        /// Name for the credential (default: the template name)
+        #[arg(long, short = 'n', help = "Name for the credential")]
+        name: Option<String>,
+
+        /// JSON pointer of the key in a .json file
+        #[arg(
+            long,
+            help = "JSON pointer of the key in a .json file (default: /api_key)"
+        )]
+        pointer: Option<String>,
+    },
+
+    /// Rename a saved credential by ID
+    Rename {
+        /// Credential ID (see `ccs creds list --format csv`)
+        id: String,
+
+        /// New name; must be unused among the template's credentials
+        name: String,
+    },
+
+    /// Find saved credentials by template and/or the start of the key
+    Find {
+        /// Only credentials for this template (e.g. deepseek, zai)
+        #[arg(long, short = 't', help = "Only credentials for this template")]
+        template: Option<String>,
+
+        /// Only credentials whose key starts with this (e.g. sk-ab)
+        #[arg(
+            long,
+            value_name = "PREFIX",
+            required_unless_present = "template",
+            help = "Only credentials whose API key starts with PREFIX"
+        )]
+        key_prefix: Option<String>,
+    },
+
+    /// Show which API key `apply <template>` would use, without prompting
+    Which {
+        /// Template type (e.g. deepseek, zai)
+        template: String,
+    },
 
-    /// Clear all saved credentials
+    /// View or edit a credential's metadata (endpoint_id, tags, custom keys)
+    Meta {
+        /// Metadata subcommand
+        #[command(subcommand)]
+        command: CredentialMetaCommands,
+    },
+
+    /// Clear all saved credentials, or only one template's
     Clear {
-        /// Skip confirmation prompt
-        #[arg(long, help = "Skip confirmation prompt")]
-        yes: bool,
+        /// Only remove credentials for this template (e.g. kimi)
+        #[arg(long, short = 't', help = "Only remove credentials for this template")]
+        template: Option<String>,
+    },
+
+    /// Delete credentials that haven't been used for a while
+    Prune {
+        /// Delete credentials not used (or, never used, created) within this (e.g. 90d, 12w)
+        #[arg(
+            long,
+            value_parser = crate::utils::parse_duration,
+            help = "Delete credentials not used within this (e.g. 90d, 12w)"
+        )]
+        unused_for: chrono::Duration,
+    },
+}
+
+/// `ccs creds meta` commands
+#[derive(Subcommand)]
+pub enum CredentialMetaCommands {
+    /// Print a credential's metadata as KEY=VALUE lines
+    Get {
+        /// Credential ID
+        id: String,
+    },
+
+    /// Set one metadata entry (endpoint_id and tags are validated)
+    Set {
+        /// Credential ID
+        id: String,
+
+        /// Entry to set, e.g. tags=work,ci
+        #[arg(value_name = "KEY=VALUE", value_parser = crate::utils::parse_env_assignment)]
+        entry: (String, String),
+    },
+
+    /// Remove one metadata entry
+    Unset {
+        /// Credential ID
+        id: String,
+
+        /// Key to remove
+        key: String,
+    },
+}
+
+/// Permission list commands
+#[derive(Subcommand)]
+pub enum PermsCommands {
+    /// Add tools to the allow, ask or deny list (moving them out of the others)
+    Add {
+        /// Tools to allow
+        #[arg(long, value_name = "TOOL", help = "Tool to allow (repeatable)")]
+        allow: Vec<String>,
+
+        /// Tools that require confirmation
+        #[arg(long, value_name = "TOOL", help = "Tool to ask about (repeatable)")]
+        ask: Vec<String>,
+
+        /// Tools to deny
+        #[arg(long, value_name = "TOOL", help = "Tool to deny (repeatable)")]
+        deny: Vec<String>,
+
+        /// Path to settings file (default: .claude/settings.json)
+        #[arg(long, help = "Path to settings file (default: .claude/settings.json)")]
+        settings_path: Option<PathBuf>,
+    },
+
+    /// Show the allow/ask/deny lists
+    #[command(visible_alias = "l", visible_alias = "ls")]
+    List {
+        /// Path to settings file (default: .claude/settings.json)
+        #[arg(long, help = "Path to settings file (default: .claude/settings.json)")]
+        settings_path: Option<PathBuf>,
+    },
+
+    /// Remove tools from every permission list
+    #[command(visible_alias = "rm")]
+    Remove {
+        /// Tools to remove
+        #[arg(required = true)]
+        tools: Vec<String>,
+
+        /// Path to settings file (default: .claude/settings.json)
+        #[arg(long, help = "Path to settings file (default: .claude/settings.json)")]
+        settings_path: Option<PathBuf>,
     },
 }
@@ -1,22 +1,40 @@
 use crate::{
-    Configurable, CredentialManager, cli,
-    credentials::{CredentialStore, mask_api_key, resolve_api_key},
+    Configurable, CredentialManager, cli, completions,
+    config::Config,
+    credentials::{
+        CredentialStore, DelimitedFormat, KeyPreference, SavedCredentialStore, SortOrder,
+        format_credentials_delimited, resolve_api_key,
+    },
+    history::History,
+    preflight::{EndpointCheck, HttpCheck, Preflight, run_preflight},
     prefs::{KeyRef, Prefs},
-    settings::{Attribution, ClaudeSettings},
+    repair,
+    reporter::Reporter,
+    selectors::{
+        SelectorResult,
+        apply::{ApplyPrompts, InquireApplyPrompts},
+        confirmation::ConfirmationService,
+        template::TemplateSelector,
+    },
+    settings::{Attribution, ClaudeSettings, MergeStrategy, PermissionList, PreserveField},
     snapshots::{self, SnapshotScope, SnapshotStore},
     templates::{
-        AutoCompactWindow, TemplateType, get_all_templates, get_template_instance,
-        get_template_instance_with_input, get_template_type, is_generic_target,
-        supports_auto_compact_option, variant_options,
+        AutoCompactWindow, Template, TemplateType, ThinkingOverrides, WEB_SEARCH_TOOL,
+        get_all_templates, get_template_instance, get_template_instance_with_input,
+        get_template_type, is_generic_target, small_fast_model, supports_auto_compact_option,
+        template_for_base_url, variant_options,
     },
     utils::{
-        backup_settings, confirm_action, get_credentials_dir, get_settings_path, get_snapshots_dir,
+        MaskPolicy, backup_settings, confirm_action, get_credentials_dir, get_global_settings_path,
+        get_local_settings_path, get_settings_path, get_snapshots_dir, is_stdio_path, mask_api_key,
+        resolve_settings_path, status_indicator,
     },
 };
 use anyhow::{Result, anyhow};
 use console::style;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::io::Read;
+use std::path::{Path, PathBuf};
 
 /// Common environment variables that should be added to all templates
 fn get_common_env_vars() -> HashMap<String, String> {
@@ -25,28 +43,28 @@ fn get_common_env_vars() -> HashMap<String, String> {
     env
 }
 
+/// Collect what a template needs besides the key (KatCoder's endpoint ID)
+/// before it builds settings, so an invalid value is an error rather than a
+/// silent fallback.
+fn resolve_additional_config(template: &mut dyn Template) -> Result<()> {
+    if template.requires_additional_config() {
+        let config = template.get_additional_config()?;
+        template.set_additional_config(&config);
+    }
+    Ok(())
+}
+
 /// Inject common environment variables into settings.
 /// Does not overwrite keys that are already set by the template.
 fn inject_common_env_vars(settings: &mut ClaudeSettings) {
-    if let Some(ref mut env) = settings.env {
-        for (key, value) in get_common_env_vars() {
-            env.entry(key).or_insert(value);
-        }
-        #[cfg(target_os = "windows")]
-        {
-            env.entry("CLAUDE_CODE_USE_POWERSHELL_TOOL".to_string())
-                .or_insert_with(|| "1".to_string());
-        }
-    } else {
-        let mut env = get_common_env_vars();
-        #[cfg(target_os = "windows")]
-        {
-            env.insert(
-                "CLAUDE_CODE_USE_POWERSHELL_TOOL".to_string(),
-                "1".to_string(),
-            );
-        }
-        settings.env = Some(env);
+    let env = settings.env.get_or_insert_with(HashMap::new);
+    for (key, value) in get_common_env_vars() {
+        env.entry(key).or_insert(value);
+    }
+    #[cfg(target_os = "windows")]
+    {
+        env.entry("CLAUDE_CODE_USE_POWERSHELL_TOOL".to_string())
+            .or_insert_with(|| "1".to_string());
     }
 }
 
@@ -121,51 +139,581 @@ fn get_git_bash_path(_settings: &ClaudeSettings, _cli_mode: bool) -> Result<Opti
     Ok(None)
 }
 
-/// Run a command based on CLI arguments
-pub fn run_command(args: &crate::Cli) -> Result<()> {
-    match &args.command {
+/// The apply targets, falling back to `default-template` from ccs.toml
+fn apply_targets<'a>(targets: &'a [String], config: &'a Config) -> Result<Vec<&'a str>> {
+    if !targets.is_empty() {
+        return Ok(targets.iter().map(String::as_str).collect());
+    }
+    config
+        .default_template
+        .as_deref()
+        .map(|target| vec![target])
+        .ok_or_else(|| {
+            anyhow!(
+                "Missing target: pass a snapshot/template name, --from-stdin, or set default-template in {}",
+                Config::path().display()
+            )
+        })
+}
+
+/// The snapshot `apply --latest` applies
+fn latest_snapshot(store: &SnapshotStore) -> Result<snapshots::Snapshot> {
+    store
+        .latest()?
+        .ok_or_else(|| anyhow!("No snapshots yet; nothing for --latest to apply"))
+}
+
+/// Model `apply --model-from` takes from the snapshot called `name`: its
+/// `model`, or its `ANTHROPIC_MODEL` env var
+fn snapshot_model(store: &SnapshotStore, name: &str) -> Result<String> {
+    let settings = store.load_by_name(name)?.settings;
+    settings
+        .model
+        .clone()
+        .or_else(|| settings.env.as_ref()?.get("ANTHROPIC_MODEL").cloned())
+        .ok_or_else(|| anyhow!("Snapshot '{}' has no model for --model-from", name))
+}
+
+/// `--fields` (a custom scope) takes precedence over `--scope`.
+fn scope_with_fields(scope: &SnapshotScope, fields: &Option<String>) -> Result<SnapshotScope> {
+    match fields {
+        Some(spec) => Ok(SnapshotScope::Custom(snapshots::parse_field_selectors(
+            spec,
+        )?)),
+        None => Ok(scope.clone()),
+    }
+}
+
+/// Run a command based on CLI arguments, reporting status to `reporter`
+pub fn run_command(args: &crate::Cli, reporter: &dyn Reporter) -> Result<()> {
+    // A broken ccs.toml must not lock the user out of `doctor`/`repair`
+    let config = Config::load()
+        .unwrap_or_else(|e| {
+            reporter.warn(format!("{}; using built-in defaults", e));
+            Config::default()
+        })
+        .with_force(args.force);
+    crate::utils::configure_colors(args.no_color || config.disables_color());
+    ConfirmationService::set_assume_yes(args.yes);
+    let yes = ConfirmationService::assume_yes();
+
+    let Some(command) = &args.command else {
+        return menu_command(&config, reporter);
+    };
+
+    match command {
         cli::Commands::List => list_command()?,
         cli::Commands::Apply {
-            target,
+            from_stdin: true,
             scope,
+            fields,
             model,
             settings_path,
+            global,
+            local,
             backup,
-            yes,
+            no_backup,
+            dry_run,
+            ..
+        } => apply_from_stdin_command(
+            &scope_with_fields(&config.scope_or(scope), fields)?,
+            model,
+            &resolve_settings_path(
+                config.settings_path_or(settings_path, *global, *local),
+                *global,
+                *local,
+            ),
+            &config.clone().with_backup_flags(*backup, *no_backup),
+            *dry_run,
+            reporter,
+        )?,
+        cli::Commands::Apply {
+            targets,
+            print_env: Some(var),
+            api_key,
+            env_file,
+            variant,
+            ..
+        } => print_env_command(
+            match apply_targets(targets, &config)?.as_slice() {
+                [target] => target,
+                _ => return Err(anyhow!("--print-env takes a single template")),
+            },
+            variant,
+            api_key,
+            env_file,
+            var,
+        )?,
+        cli::Commands::Apply {
+            targets,
+            template_file: Some(template_file),
+            scope,
+            fields,
+            model,
+            settings_path,
+            global,
+            local,
+            backup,
+            no_backup,
             cli,
+            output_style,
             effort,
-            auto_compact,
             api_key,
+            env_file,
             no_co_author,
+            co_authored,
+            merge_strategy,
             switch_key,
+            prefer_env,
+            prefer_saved,
             dry_run,
-            variant,
-        } => apply_command(
-            target,
+            print_diff_only,
+            preflight,
+            strict,
+            save_as_snapshot,
+            overwrite,
+            ..
+        } => apply_template_file_command(
+            template_file,
+            match targets.as_slice() {
+                [] => None,
+                [target] => Some(target.as_str()),
+                _ => {
+                    return Err(anyhow!(
+                        "--template-file takes at most one TARGET (the key's provider)"
+                    ));
+                }
+            },
+            api_key,
+            env_file,
+            *switch_key,
+            KeyPreference::from_flags(*prefer_env, *prefer_saved),
+            &scope_with_fields(&config.scope_or(scope), fields)?,
+            &DocumentOverrides {
+                model: model.clone(),
+                output_style: output_style.clone(),
+                effort: effort.clone(),
+                co_authored: (*no_co_author || *co_authored).then_some(*co_authored),
+                merge_strategy: *merge_strategy,
+                preflight: Preflight::from_flags(*preflight, *strict),
+            },
+            &resolve_settings_path(
+                config.settings_path_or(settings_path, *global, *local),
+                *global,
+                *local,
+            ),
+            &config.clone().with_backup_flags(*backup, *no_backup),
+            *cli,
+            *dry_run || *print_diff_only,
+            *print_diff_only,
+            save_as_snapshot
+                .as_deref()
+                .map(|name| SaveAsSnapshot::new(name, *overwrite || yes))
+                .as_ref(),
+            reporter,
+        )?,
+        cli::Commands::Apply {
+            targets,
+            from_stdin: false,
+            print_env: None,
+            interactive,
+            latest: false,
+            scope,
+            fields,
+            settings_path,
+            global,
+            local,
+            backup,
+            no_backup,
+            save_as_snapshot,
+            overwrite,
+            ..
+        } if *interactive
+            || (targets.is_empty()
+                && config.default_template.is_none()
+                && atty::is(atty::Stream::Stdin)) =>
+        {
+            interactive_apply_command(
+                &mut InquireApplyPrompts,
+                &scope_with_fields(&config.scope_or(scope), fields)?,
+                &resolve_settings_path(
+                    config.settings_path_or(settings_path, *global, *local),
+                    *global,
+                    *local,
+                ),
+                &config.clone().with_backup_flags(*backup, *no_backup),
+                &History::open_default(),
+                save_as_snapshot
+                    .as_deref()
+                    .map(|name| SaveAsSnapshot::new(name, *overwrite || yes))
+                    .as_ref(),
+                reporter,
+            )?
+        }
+        cli::Commands::Apply {
+            targets,
+            from_stdin: false,
+            print_env: None,
+            interactive: _,
             scope,
+            fields,
             model,
             settings_path,
-            *backup,
-            *yes,
-            *cli,
+            global,
+            local,
+            backup,
+            no_backup,
+            cli,
+            output_style,
+            subagent_model,
             effort,
             auto_compact,
             api_key,
-            *no_co_author,
-            *switch_key,
-            *dry_run,
+            env_file,
+            no_co_author,
+            co_authored,
+            merge_strategy,
+            switch_key,
+            prefer_env,
+            prefer_saved,
+            dry_run,
+            print_diff_only,
+            preflight,
+            strict,
             variant,
+            save_as_snapshot,
+            overwrite,
+            template_file: _,
+            allow,
+            deny,
+            allow_web_search,
+            thinking,
+            no_thinking,
+            reasoning_effort,
+            max_thinking_tokens,
+            preserve,
+            latest,
+            model_from,
+        } => {
+            let options = ApplyOptions {
+                scope: scope_with_fields(&config.scope_or(scope), fields)?,
+                model: match (model, model_from) {
+                    (None, Some(name)) => Some(snapshot_model(&default_snapshot_store(), name)?),
+                    _ => model.clone(),
+                },
+                cli: *cli,
+                output_style: output_style.clone(),
+                subagent_model: subagent_model.clone(),
+                effort: effort.clone(),
+                auto_compact: auto_compact.clone(),
+                api_key: api_key.clone(),
+                env_file: env_file.clone(),
+                no_co_author: *no_co_author,
+                co_authored: *co_authored,
+                merge_strategy: *merge_strategy,
+                switch_key: *switch_key,
+                key_preference: KeyPreference::from_flags(*prefer_env, *prefer_saved),
+                dry_run: *dry_run || *print_diff_only,
+                diff_only: *print_diff_only,
+                preflight: Preflight::from_flags(*preflight, *strict),
+                variant: variant.clone(),
+                save_as: save_as_snapshot
+                    .as_deref()
+                    .map(|name| SaveAsSnapshot::new(name, *overwrite || yes)),
+                thinking: ThinkingOverrides::from_flags(
+                    *thinking,
+                    *no_thinking,
+                    reasoning_effort.clone(),
+                    *max_thinking_tokens,
+                ),
+                permission_rules: PermissionRules {
+                    allow: allow.clone(),
+                    deny: deny.clone(),
+                    allow_web_search: *allow_web_search,
+                },
+                preserve: preserve.clone(),
+            };
+            let settings_path = Some(resolve_settings_path(
+                config.settings_path_or(settings_path, *global, *local),
+                *global,
+                *local,
+            ));
+            let apply_config = config.clone().with_backup_flags(*backup, *no_backup);
+            if *latest {
+                apply_latest_command(
+                    &default_snapshot_store(),
+                    &History::open_default(),
+                    &get_settings_path(settings_path),
+                    &apply_config,
+                    yes,
+                    &options,
+                    reporter,
+                )?
+            } else {
+                apply_command(
+                    &apply_targets(targets, &config)?,
+                    &settings_path,
+                    &apply_config,
+                    yes,
+                    &options,
+                    reporter,
+                )?
+            }
+        }
+        cli::Commands::Snap {
+            name,
+            scope,
+            fields,
+            settings_path,
+            global,
+            local,
+            description,
+            overwrite,
+            output_style,
+            preview,
+        } => snap_command(
+            name,
+            &scope_with_fields(&config.scope_or(scope), fields)?,
+            &Some(resolve_settings_path(
+                config.settings_path_or(settings_path, *global, *local),
+                *global,
+                *local,
+            )),
+            description,
+            *overwrite,
+            output_style,
+            *preview,
+            reporter,
         )?,
+        cli::Commands::Version { full } => print!("{}", format_version_info(*full)),
+        cli::Commands::Templates { aliases } => templates_command(*aliases)?,
+        cli::Commands::Snapshot { command } => match command {
+            cli::SnapshotCommands::Show { name, json } => snapshot_show_command(name, *json)?,
+            cli::SnapshotCommands::Diff { a, b } => snapshot_diff_command(a, b)?,
+            cli::SnapshotCommands::Describe { name, text } => {
+                snapshot_describe_command(name, text, reporter)?
+            }
+            cli::SnapshotCommands::Verify { name, preflight } => {
+                snapshot_verify_command(name, *preflight, reporter)?
+            }
+            cli::SnapshotCommands::Prune { keep, older_than } => {
+                snapshot_prune_command(*keep, *older_than, yes, reporter)?
+            }
+            cli::SnapshotCommands::ImportDir { dir, overwrite } => {
+                snapshot_import_dir_command(dir, *overwrite, reporter)?
+            }
+            cli::SnapshotCommands::FromTemplate {
+                template,
+                name,
+                scope,
+                api_key,
+                with_key,
+                mask_key,
+                overwrite,
+            } => snapshot_from_template_command(
+                template,
+                name,
+                &config.scope_or(scope),
+                api_key,
+                *with_key,
+                *mask_key,
+                *overwrite || yes,
+                reporter,
+            )?,
+        },
         cli::Commands::Credentials { command } => match command {
-            cli::CredentialCommands::List => credentials_list_command()?,
-            cli::CredentialCommands::Clear { yes } => credentials_clear_command(*yes)?,
+            cli::CredentialCommands::List {
+                show_expired,
+                hide_expired,
+                format,
+                with_key,
+                sort,
+            } => match format {
+                Some(format) => credentials_list_delimited_command(
+                    *show_expired,
+                    *hide_expired,
+                    *format,
+                    *with_key,
+                    *sort,
+                )?,
+                None => credentials_list_command(*show_expired, *hide_expired, *sort)?,
+            },
+            cli::CredentialCommands::ImportFile {
+                path,
+                template,
+                name,
+                pointer,
+            } => credentials_import_file_command(
+                path,
+                template,
+                name.as_deref(),
+                pointer.as_deref(),
+                reporter,
+            )?,
+            cli::CredentialCommands::Rename { id, name } => {
+                credentials_rename_command(id, name, reporter)?
+            }
+            cli::CredentialCommands::Find {
+                template,
+                key_prefix,
+            } => credentials_find_command(template.as_deref(), key_prefix.as_deref())?,
+            cli::CredentialCommands::Which { template } => credentials_which_command(template)?,
+            cli::CredentialCommands::Meta { command } => match command {
+                cli::CredentialMetaCommands::Get { id } => credentials_meta_get_command(id)?,
+                cli::CredentialMetaCommands::Set {
+                    id,
+                    entry: (key, value),
+                } => credentials_meta_set_command(id, key, value, reporter)?,
+                cli::CredentialMetaCommands::Unset { id, key } => {
+                    credentials_meta_unset_command(id, key, reporter)?
+                }
+            },
+            cli::CredentialCommands::Clear { template } => {
+                credentials_clear_command(template.as_deref(), yes, reporter)?
+            }
+            cli::CredentialCommands::Prune { unused_for } => {
+                credentials_prune_command(*unused_for, yes, reporter)?
+            }
         },
+        cli::Commands::Config(cli::ConfigArgs {
+            command:
+                Some(cli::ConfigCommands::CoAuthored {
+                    state,
+                    settings_path,
+                }),
+            ..
+        }) => co_authored_command(
+            &get_settings_path(settings_path.clone()),
+            *state == cli::Toggle::On,
+            &History::open_default(),
+            config.force,
+            reporter,
+        )?,
+        cli::Commands::Config(cli::ConfigArgs {
+            command:
+                Some(cli::ConfigCommands::SetEnv {
+                    assignment: (key, value),
+                    settings_path,
+                }),
+            ..
+        }) => set_env_command(
+            &get_settings_path(settings_path.clone()),
+            key,
+            value,
+            &History::open_default(),
+            reporter,
+        )?,
+        cli::Commands::Config(cli::ConfigArgs {
+            command: Some(cli::ConfigCommands::UnsetEnv { key, settings_path }),
+            ..
+        }) => unset_env_command(
+            &get_settings_path(settings_path.clone()),
+            key,
+            &History::open_default(),
+            reporter,
+        )?,
         cli::Commands::Config(cfg) => config_command(cfg)?,
-        cli::Commands::Current => current_command()?,
+        cli::Commands::Current {
+            effective,
+            settings_path,
+        } => {
+            if *effective {
+                current_effective_command()?
+            } else {
+                current_command(settings_path.clone())?
+            }
+        }
+        cli::Commands::Doctor => doctor_command()?,
+        cli::Commands::Completions { shell, install } => {
+            completions_command(*shell, *install, reporter)?
+        }
+        cli::Commands::Repair => repair_command(yes, reporter)?,
+        cli::Commands::Undo => undo_command(reporter)?,
+        cli::Commands::Redo => redo_command(reporter)?,
+        cli::Commands::Perms { command } => match command {
+            cli::PermsCommands::Add {
+                allow,
+                ask,
+                deny,
+                settings_path,
+            } => perms_add_command(allow, ask, deny, settings_path, reporter)?,
+            cli::PermsCommands::List { settings_path } => perms_list_command(settings_path)?,
+            cli::PermsCommands::Remove {
+                tools,
+                settings_path,
+            } => perms_remove_command(tools, settings_path, reporter)?,
+        },
     }
     Ok(())
 }
 
+/// Top-level interactive menu shown when `ccs` runs without a subcommand.
+/// Outside a terminal there is nothing to pick from, so print help and fail.
+pub fn menu_command(config: &Config, reporter: &dyn Reporter) -> Result<()> {
+    if !atty::is(atty::Stream::Stdin) {
+        <crate::Cli as clap::CommandFactory>::command().print_help()?;
+        return Err(anyhow!("No command given"));
+    }
+
+    let options = vec!["Apply", "Snapshots", "Credentials", "Templates", "Quit"];
+    let choice = match inquire::Select::new("What would you like to do?", options)
+        .with_help_message("↑/↓ navigate, Enter select, Esc quit")
+        .prompt()
+    {
+        Ok(c) => c,
+        Err(_) => return Ok(()),
+    };
+
+    match choice {
+        "Apply" => {
+            let template_type = match TemplateSelector::select_template() {
+                Ok(t) => t,
+                Err(e) if e.is_cancellation() => return Ok(()),
+                Err(e) => return Err(anyhow!("Template selection failed: {}", e)),
+            };
+            let scope = config
+                .scope
+                .clone()
+                .unwrap_or_else(|| Prefs::load_or_default().default_scope);
+            apply_command(
+                &[template_type.to_string().as_str()],
+                &config.settings_path,
+                config,
+                false,
+                &ApplyOptions {
+                    scope,
+                    ..Default::default()
+                },
+                reporter,
+            )
+        }
+        "Snapshots" => list_command(),
+        "Credentials" => credentials_list_command(false, false, SortOrder::default()),
+        "Templates" => {
+            let template_type = match TemplateSelector::select_template() {
+                Ok(t) => t,
+                Err(e) if e.is_cancellation() => return Ok(()),
+                Err(e) => return Err(anyhow!("Template selection failed: {}", e)),
+            };
+            let template = get_template_instance(&template_type);
+            println!();
+            println!("{} {}", style("•").cyan(), template.display_name());
+            println!("  {}", template.description());
+            println!("  apply:    ccs apply {}", template_type);
+            let variants = variant_options(&template_type);
+            if !variants.is_empty() {
+                let aliases: Vec<&str> = variants.iter().map(|(a, _)| *a).collect();
+                println!("  variants: {}", aliases.join(", "));
+            }
+            if let Some(url) = template.api_key_url() {
+                println!("  api keys: {}", url);
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
 /// List available snapshots
 pub fn list_command() -> Result<()> {
     println!("📸 Snapshot Browser");
@@ -189,23 +737,58 @@ pub fn list_command() -> Result<()> {
 }
 
 /// Create a snapshot
+#[allow(clippy::too_many_arguments)]
 pub fn snap_command(
     name: &str,
     scope: &SnapshotScope,
     settings_path: &Option<PathBuf>,
     description: &Option<String>,
     overwrite: bool,
+    output_style: &Option<String>,
+    preview: bool,
+    reporter: &dyn Reporter,
 ) -> Result<()> {
     let settings_path = get_settings_path(settings_path.clone());
-    let settings = ClaudeSettings::from_file(&settings_path)?;
+    let store = SnapshotStore::new(get_snapshots_dir());
+    snap_into_store(
+        &store,
+        name,
+        scope,
+        &settings_path,
+        description,
+        overwrite,
+        output_style,
+        preview,
+        reporter,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn snap_into_store(
+    store: &SnapshotStore,
+    name: &str,
+    scope: &SnapshotScope,
+    settings_path: &Path,
+    description: &Option<String>,
+    overwrite: bool,
+    output_style: &Option<String>,
+    preview: bool,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let settings = ClaudeSettings::from_file(settings_path)?;
 
-    let mut snapshot_settings = settings;
-    if matches!(scope, SnapshotScope::All | SnapshotScope::Env) {
-        snapshot_settings.env = Some(ClaudeSettings::capture_environment());
+    let mut snapshot_settings = snapshots::capture_settings(settings, scope);
+    if let Some(style) = output_style {
+        snapshot_settings.output_style = Some(style.clone());
     }
 
-    let snapshots_dir = get_snapshots_dir();
-    let store = SnapshotStore::new(snapshots_dir);
+    if preview {
+        print!(
+            "{}",
+            format_snapshot_preview(name, scope, &snapshot_settings)
+        );
+        return Ok(());
+    }
 
     if store.exists_by_name(name)
         && !overwrite
@@ -225,131 +808,604 @@ pub fn snap_command(
     );
 
     store.save(&snapshot)?;
-    println!(
-        "{} Snapshot '{}' created successfully!",
-        style("✓").green().bold(),
-        name
-    );
+    reporter.success(format!("Snapshot '{}' created successfully!", name));
 
     Ok(())
 }
 
-// ── apply ────────────────────────────────────────────────────────────────────
+/// What `ccs snap --preview` shows: the masked settings a snapshot would hold
+fn format_snapshot_preview(name: &str, scope: &SnapshotScope, settings: &ClaudeSettings) -> String {
+    let env_keys = settings.env.as_ref().map_or(0, |env| env.len());
+    format!(
+        "Snapshot '{}' would capture (scope: {}):\n{}\nEnv keys captured: {}\nNothing saved (preview).\n",
+        name,
+        scope,
+        crate::settings::format_settings_for_display(
+            &settings.clone().mask_sensitive_data(),
+            false
+        ),
+        env_keys
+    )
+}
 
-/// Apply a snapshot or template
-#[allow(clippy::too_many_arguments)]
-pub fn apply_command(
-    target: &str,
-    scope: &SnapshotScope,
-    model: &Option<String>,
-    settings_path: &Option<PathBuf>,
-    backup: bool,
-    yes: bool,
-    cli: bool,
-    effort: &Option<String>,
-    auto_compact: &Option<String>,
-    api_key: &Option<String>,
-    no_co_author: bool,
-    switch_key: bool,
-    dry_run: bool,
-    variant: &Option<String>,
-) -> Result<()> {
-    let settings_path = get_settings_path(settings_path.clone());
+/// Check a snapshot before applying it; errors if any check fails
+pub fn snapshot_verify_command(name: &str, preflight: bool, reporter: &dyn Reporter) -> Result<()> {
+    let snapshot = load_snapshot_checked(&default_snapshot_store(), name, reporter)?;
+    let checker = HttpCheck::default();
+    let checker: Option<&dyn EndpointCheck> = preflight.then_some(&checker);
 
-    // Try to parse as a template first
-    if let Ok(template_type) = get_template_type(target) {
-        return apply_template_command(
-            &template_type,
-            target,
-            scope,
-            model,
-            &settings_path,
-            backup,
-            yes,
-            cli,
-            effort,
-            auto_compact,
-            api_key,
-            no_co_author,
-            switch_key,
-            dry_run,
-            variant,
-        );
+    println!("🔎 {} ({})", snapshot.name, snapshot.scope);
+    let (lines, failed) = snapshot_verify_report(&snapshot.settings, checker);
+    for line in lines {
+        println!("  {}", line);
     }
-
-    // Otherwise treat as a snapshot name
-    apply_snapshot_command(target, scope, model, &settings_path, backup, yes)
+    if failed > 0 {
+        return Err(anyhow!("Snapshot '{}' failed {} check(s)", name, failed));
+    }
+    Ok(())
 }
 
-/// One-time first-run onboarding for global defaults.
-fn onboard_prefs(prefs: &mut Prefs) -> Result<()> {
-    println!(
-        "{} First run — setting your defaults (saved to {})",
-        style("👋").cyan(),
-        Prefs::path().display()
-    );
+/// Checklist for `snapshot verify`: base URL, API key, model, a well-formed
+/// endpoint ID where the provider needs one and, with `checker`, that the
+/// base URL answers. Returns the lines and how many checks failed.
+pub fn snapshot_verify_report(
+    settings: &ClaudeSettings,
+    checker: Option<&dyn EndpointCheck>,
+) -> (Vec<String>, usize) {
+    let env = |key: &str| settings.env.as_ref().and_then(|env| env.get(key));
+    let base_url = env("ANTHROPIC_BASE_URL");
+    let model = settings.model.as_ref().or(env("ANTHROPIC_MODEL"));
 
-    let effort_options = vec!["max", "xhigh", "high", "medium", "low"];
-    let effort = inquire::Select::new("Default reasoning effort:", effort_options)
-        .with_help_message("Controls Claude Code thinking depth")
-        .prompt()
-        .map_err(|e| anyhow!("Failed to read effort: {}", e))?;
-    prefs.default_effort = Some(effort.to_string());
+    let mut checks = vec![
+        match base_url {
+            Some(url) => (true, format!("base URL: {}", url)),
+            None => (false, "base URL: ANTHROPIC_BASE_URL is not set".to_string()),
+        },
+        match settings.stored_api_key() {
+            Some(key) => (
+                true,
+                format!("API key: {}", mask_api_key(key, MaskPolicy::DEFAULT)),
+            ),
+            None => (false, "API key: no auth token or API key".to_string()),
+        },
+        match model {
+            Some(model) => (true, format!("model: {}", model)),
+            None => (false, "model: not set".to_string()),
+        },
+    ];
 
-    let co_author = inquire::Confirm::new("Enable co-authored-by in git commits/PRs?")
-        .with_default(false)
-        .prompt()
-        .unwrap_or(false);
-    prefs.default_co_author = co_author;
+    if let Some(url) = base_url
+        && template_for_base_url(url) == Some(TemplateType::KatCoder)
+        && let Some(id) = url
+            .split("/endpoints/")
+            .nth(1)
+            .and_then(|rest| rest.split('/').next())
+    {
+        checks.push(
+            match crate::templates::kat_coder::validate_endpoint_id(id) {
+                Ok(()) => (true, format!("endpoint ID: {}", id)),
+                Err(e) => (false, format!("endpoint ID: {}", e)),
+            },
+        );
+    }
+
+    if let (Some(checker), Some(url)) = (checker, base_url) {
+        checks.push(match checker.check(url) {
+            Ok(status) => (true, format!("reachable: HTTP {}", status)),
+            Err(e) => (false, format!("reachable: {}", e)),
+        });
+    }
 
+    let failed = checks.iter().filter(|(ok, _)| !ok).count();
+    let lines = checks
+        .into_iter()
+        .map(|(ok, message)| status_indicator(ok, &message))
+        .collect();
+    (lines, failed)
+}
+
+/// Print one snapshot without entering the interactive browser
+pub fn snapshot_show_command(name: &str, json: bool) -> Result<()> {
+    let store = SnapshotStore::new(get_snapshots_dir());
+    let snapshot = store.load_by_name(name)?;
+    if json {
+        let content = serde_json::to_string_pretty(&snapshot)
+            .map_err(|e| anyhow!("Failed to serialize snapshot: {}", e))?;
+        println!("{}", content);
+    } else {
+        println!("{}", snapshot.format_details());
+    }
     Ok(())
 }
 
-/// Resolve effort: flag → prefs default → (non-interactive fallback) → None.
-fn resolve_effort(flag: Option<&str>, prefs: &Prefs, non_interactive: bool) -> Option<String> {
-    if let Some(e) = flag.map(str::trim).filter(|e| !e.is_empty()) {
-        return Some(e.to_string());
+/// Set or clear a snapshot's description
+pub fn snapshot_describe_command(name: &str, text: &str, reporter: &dyn Reporter) -> Result<()> {
+    let snapshot = default_snapshot_store().describe(name, text)?;
+    match &snapshot.description {
+        Some(description) => reporter.success(format!(
+            "Described snapshot '{}': {}",
+            snapshot.name, description
+        )),
+        None => reporter.success(format!(
+            "Cleared the description of snapshot '{}'",
+            snapshot.name
+        )),
     }
-    if let Some(e) = &prefs.default_effort {
-        return Some(e.clone());
+    Ok(())
+}
+
+/// Compare two saved snapshots
+pub fn snapshot_diff_command(a: &str, b: &str) -> Result<()> {
+    let store = SnapshotStore::new(get_snapshots_dir());
+    let from = store.load_by_name(a)?;
+    let to = store.load_by_name(b)?;
+    print!("{}", from.format_diff(&to));
+    Ok(())
+}
+
+/// Import a directory of snapshot files into the snapshot store
+pub fn snapshot_import_dir_command(
+    dir: &Path,
+    overwrite: bool,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let report = default_snapshot_store().import_directory(dir, overwrite)?;
+    for (path, error) in &report.failed {
+        reporter.warn(format!("Skipped {}: {}", path.display(), error));
     }
-    if non_interactive {
-        return Some("xhigh".to_string());
+    if !report.skipped.is_empty() {
+        reporter.info(format!(
+            "{} Already present: {}",
+            style("•").cyan(),
+            report.skipped.join(", ")
+        ));
     }
-    None
+    reporter.success(format!(
+        "Imported {} snapshot(s){}, {} skipped, {} failed",
+        report.imported.len(),
+        if report.imported.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", report.imported.join(", "))
+        },
+        report.skipped.len(),
+        report.failed.len()
+    ));
+    Ok(())
 }
 
-/// Resolve whether co-author should be OFF: --no-co-author → off; else honor
-/// prefs (default_co_author false means off).
-fn resolve_co_author_off(no_co_author: bool, prefs: &Prefs) -> bool {
-    no_co_author || !prefs.default_co_author
-}
+/// Delete snapshots beyond `keep` and/or older than `older_than`
+pub fn snapshot_prune_command(
+    keep: Option<usize>,
+    older_than: Option<chrono::Duration>,
+    yes: bool,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    if keep.is_none() && older_than.is_none() {
+        return Err(anyhow!("Nothing to prune: pass --keep and/or --older-than"));
+    }
 
-/// Resolve the auto-compaction threshold for providers that expose it.
-/// Returns `None` for providers that do not support it.
-fn resolve_auto_compact_window(
-    template_type: &TemplateType,
-    template: &dyn crate::templates::Template,
-    flag: Option<&str>,
-    prefs: &Prefs,
-) -> Result<Option<AutoCompactWindow>> {
-    if !supports_auto_compact_option(template) {
-        if let Some(value) = flag.map(str::trim).filter(|v| !v.is_empty()) {
-            return Err(anyhow!(
-                "{} does not support auto-compact thresholds (got '{}')",
-                template.display_name(),
-                value
-            ));
-        }
-        return Ok(None);
+    let store = SnapshotStore::new(get_snapshots_dir());
+    let candidates = store.prune_candidates(keep, older_than)?;
+    if candidates.is_empty() {
+        reporter.info("No snapshots to prune.");
+        return Ok(());
     }
 
-    let supported = template.supported_auto_compact_windows();
+    println!("Snapshots to delete ({}):", candidates.len());
+    for snapshot in &candidates {
+        println!("  {} ({})", snapshot.name, snapshot.created_at);
+    }
+    let prompt = format!("Delete {} snapshot(s)?", candidates.len());
+    if !yes && !confirm_action(&prompt, false)? {
+        return Ok(());
+    }
 
-    let parsed = if let Some(value) = flag.map(str::trim).filter(|v| !v.is_empty()) {
-        Some(value.parse::<AutoCompactWindow>()?)
-    } else {
-        prefs
+    let deleted = store.prune(keep, older_than)?;
+    reporter.success(format!("Pruned {} snapshot(s)", deleted.len()));
+    Ok(())
+}
+
+/// Save a template's default settings as a snapshot, leaving the live
+/// settings alone
+#[allow(clippy::too_many_arguments)]
+pub fn snapshot_from_template_command(
+    target: &str,
+    name: &Option<String>,
+    scope: &SnapshotScope,
+    api_key: &Option<String>,
+    with_key: bool,
+    mask_key: bool,
+    overwrite: bool,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let name = name.as_deref().unwrap_or(target);
+    let key = match api_key {
+        Some(key) => Some(key.clone()),
+        None if with_key => {
+            let template_type = get_template_type(target)?;
+            let non_interactive = !atty::is(atty::Stream::Stdin);
+            let choice = resolve_api_key(
+                &template_type,
+                None,
+                None,
+                None,
+                KeyPreference::Auto,
+                false,
+                non_interactive,
+            )?
+            .ok_or_else(|| anyhow!("Cancelled"))?;
+            Some(choice.key)
+        }
+        None => None,
+    };
+
+    let store = SnapshotStore::new(get_snapshots_dir());
+    if store.exists_by_name(name)
+        && !overwrite
+        && !confirm_action(
+            &format!("Snapshot '{}' already exists. Overwrite?", name),
+            false,
+        )?
+    {
+        return Ok(());
+    }
+
+    let snapshot = snapshot_from_template(target, name, scope, key.as_deref(), mask_key)?;
+    store.save(&snapshot)?;
+    reporter.success(format!(
+        "Snapshot '{}' created from template '{}'",
+        name, target
+    ));
+    Ok(())
+}
+
+/// Snapshot of the settings `target`'s template would apply.
+///
+/// Without `api_key` the key variables are left out rather than stored
+/// empty, so applying the snapshot keeps whatever key is already set.
+fn snapshot_from_template(
+    target: &str,
+    name: &str,
+    scope: &SnapshotScope,
+    api_key: Option<&str>,
+    mask_key: bool,
+) -> Result<snapshots::Snapshot> {
+    let template_type = get_template_type(target)?;
+    let mut template = get_template_instance_with_input(&template_type, target);
+    resolve_additional_config(template.as_mut())?;
+
+    let mut settings = template
+        .create_settings(api_key.unwrap_or(""), &scope.template_scope())
+        .filter_by_scope(scope);
+    if api_key.is_none()
+        && let Some(env) = settings.env.as_mut()
+    {
+        env.retain(|_, value| !value.is_empty());
+    }
+    if mask_key {
+        settings = settings.mask_api_keys();
+    }
+
+    Ok(snapshots::Snapshot::new(
+        name.to_string(),
+        settings,
+        scope.clone(),
+        Some(format!(
+            "Defaults of the {} template",
+            template.display_name()
+        )),
+    ))
+}
+
+// ── templates ────────────────────────────────────────────────────────────────
+
+/// List templates, or with `aliases` every target string each one accepts
+pub fn templates_command(aliases: bool) -> Result<()> {
+    let mut table = crate::utils::Table::new()
+        .style(0, console::Style::new().bold())
+        .style(2, console::Style::new().dim());
+    if aliases {
+        for (template_type, names) in crate::templates::TEMPLATE_ALIASES {
+            table.row([template_type.to_string(), names.join(", ")]);
+        }
+        print!("{}", table.render());
+        return Ok(());
+    }
+
+    for template_type in get_all_templates() {
+        let template = get_template_instance(&template_type);
+        table.row([
+            template_type.to_string(),
+            template.display_name().to_string(),
+            template.api_base().unwrap_or("(per endpoint)").to_string(),
+        ]);
+    }
+    print!("{}", table.render());
+    Ok(())
+}
+
+// ── version ──────────────────────────────────────────────────────────────────
+
+/// `ccs version` output; `full` adds what's needed to triage an install
+fn format_version_info(full: bool) -> String {
+    let mut out = format!("ccs {}\n", env!("CARGO_PKG_VERSION"));
+    if !full {
+        return out;
+    }
+    let rows = [
+        (
+            "commit",
+            option_env!("CCS_GIT_COMMIT")
+                .unwrap_or("unknown")
+                .to_string(),
+        ),
+        (
+            "features",
+            if cfg!(feature = "parallel") {
+                "parallel"
+            } else {
+                "default"
+            }
+            .to_string(),
+        ),
+        (
+            "credential format",
+            crate::credentials::CURRENT_CREDENTIAL_VERSION.to_string(),
+        ),
+        (
+            "snapshot format",
+            format!("v{}", snapshots::CURRENT_SNAPSHOT_VERSION),
+        ),
+        ("settings", get_settings_path(None).display().to_string()),
+        ("snapshots", get_snapshots_dir().display().to_string()),
+        ("credentials", get_credentials_dir().display().to_string()),
+        ("config", Config::path().display().to_string()),
+    ];
+    for (label, value) in rows {
+        out.push_str(&format!("  {:<18} {}\n", format!("{}:", label), value));
+    }
+    out
+}
+
+// ── apply ────────────────────────────────────────────────────────────────────
+
+/// `apply --allow/--deny/--allow-web-search`: tool rules added to the
+/// applied settings
+#[derive(Debug, Clone, Default)]
+pub struct PermissionRules {
+    pub allow: Vec<String>,
+    pub deny: Vec<String>,
+    /// Drop the templates' default `WebSearch` denial
+    pub allow_web_search: bool,
+}
+
+impl PermissionRules {
+    /// Add the rules to `settings`; deny wins
+    fn apply_to(&self, settings: &mut ClaudeSettings) {
+        let web_search_denied = settings
+            .permissions
+            .as_ref()
+            .and_then(|perms| perms.list_of(WEB_SEARCH_TOOL))
+            == Some(PermissionList::Deny);
+        if self.allow_web_search && web_search_denied {
+            settings.clear_permission(WEB_SEARCH_TOOL);
+        }
+        settings.apply_permission_rules(&self.allow, &self.deny);
+    }
+}
+
+/// `apply --save-as-snapshot`: keep the applied settings as a named snapshot
+pub struct SaveAsSnapshot {
+    pub name: String,
+    pub overwrite: bool,
+    pub store: SnapshotStore,
+}
+
+impl SaveAsSnapshot {
+    /// Save into the default snapshots directory
+    pub fn new(name: &str, overwrite: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            overwrite,
+            store: default_snapshot_store(),
+        }
+    }
+
+    /// Fail before anything is written if the name is taken and
+    /// `--overwrite` wasn't given
+    fn check(&self) -> Result<()> {
+        if !self.overwrite && self.store.exists_by_name(&self.name) {
+            return Err(anyhow!(
+                "Snapshot '{}' already exists (use --overwrite to replace it)",
+                self.name
+            ));
+        }
+        Ok(())
+    }
+
+    /// Snapshot `settings`, as just written, at scope `all`. Taken from
+    /// memory: with `--settings-path -` the file to read back is stdin.
+    fn save(&self, settings: &ClaudeSettings, label: &str, reporter: &dyn Reporter) -> Result<()> {
+        let _lock = self.store.lock()?;
+        if self.store.exists_by_name(&self.name) {
+            self.store.delete_by_name(&self.name)?;
+        }
+        let snapshot = snapshots::Snapshot::new(
+            self.name.clone(),
+            settings.clone(),
+            SnapshotScope::All,
+            Some(format!("Applied {}", label)),
+        );
+        self.store.save(&snapshot)?;
+        reporter.success(format!("Saved snapshot '{}'", self.name));
+        Ok(())
+    }
+}
+
+/// The `apply` flags, shared by every kind of target
+#[derive(Default)]
+pub struct ApplyOptions {
+    pub scope: SnapshotScope,
+    pub model: Option<String>,
+    /// Never prompt; fail instead of asking
+    pub cli: bool,
+    pub output_style: Option<String>,
+    pub subagent_model: Option<String>,
+    pub effort: Option<String>,
+    pub auto_compact: Option<String>,
+    pub api_key: Option<String>,
+    pub env_file: Option<PathBuf>,
+    pub no_co_author: bool,
+    pub co_authored: bool,
+    pub merge_strategy: MergeStrategy,
+    pub switch_key: bool,
+    pub key_preference: KeyPreference,
+    /// Preview only; also set by `--print-diff-only`
+    pub dry_run: bool,
+    pub diff_only: bool,
+    pub preflight: Preflight,
+    pub variant: Option<String>,
+    pub save_as: Option<SaveAsSnapshot>,
+    pub thinking: ThinkingOverrides,
+    pub permission_rules: PermissionRules,
+    pub preserve: Vec<PreserveField>,
+}
+
+/// Apply a snapshot or template, or layer several of them in order
+pub fn apply_command(
+    targets: &[&str],
+    settings_path: &Option<PathBuf>,
+    config: &Config,
+    yes: bool,
+    options: &ApplyOptions,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let settings_path = get_settings_path(settings_path.clone());
+    reporter.trace(format!("Settings file: {}", settings_path.display()));
+    if let Some(save_as) = &options.save_as
+        && !options.dry_run
+    {
+        save_as.check()?;
+    }
+
+    let target = match targets {
+        [target] => *target,
+        _ => {
+            return apply_layered(
+                targets,
+                &default_snapshot_store(),
+                &History::open_default(),
+                &settings_path,
+                config,
+                yes,
+                options,
+                reporter,
+            );
+        }
+    };
+
+    // Try to parse as a template first
+    if let Ok(template_type) = get_template_type(target) {
+        return apply_template_command(
+            &template_type,
+            target,
+            &settings_path,
+            config,
+            yes,
+            options,
+            None,
+            reporter,
+        );
+    }
+
+    // Otherwise treat as a snapshot name. Its env is applied as saved, so
+    // there's no template to set thinking for.
+    options
+        .thinking
+        .check_supported(false, &format!("Snapshot '{}'", target))?;
+    let store = default_snapshot_store();
+    apply_snapshot_command(
+        load_snapshot_checked(&store, target, reporter)?,
+        &store,
+        &History::open_default(),
+        &settings_path,
+        config,
+        yes,
+        options,
+        reporter,
+    )
+}
+
+/// One-time first-run onboarding for global defaults.
+fn onboard_prefs(prefs: &mut Prefs) -> Result<()> {
+    println!(
+        "{} First run — setting your defaults (saved to {})",
+        style("👋").cyan(),
+        Prefs::path().display()
+    );
+
+    let effort_options = vec!["max", "xhigh", "high", "medium", "low"];
+    let effort = inquire::Select::new("Default reasoning effort:", effort_options)
+        .with_help_message("Controls Claude Code thinking depth")
+        .prompt()
+        .map_err(|e| anyhow!("Failed to read effort: {}", e))?;
+    prefs.default_effort = Some(effort.to_string());
+
+    let co_author = inquire::Confirm::new("Enable co-authored-by in git commits/PRs?")
+        .with_default(false)
+        .prompt()
+        .unwrap_or(false);
+    prefs.default_co_author = co_author;
+
+    Ok(())
+}
+
+/// Resolve effort: flag → prefs default → (non-interactive fallback) → None.
+fn resolve_effort(flag: Option<&str>, prefs: &Prefs, non_interactive: bool) -> Option<String> {
+    if let Some(e) = flag.map(str::trim).filter(|e| !e.is_empty()) {
+        return Some(e.to_string());
+    }
+    if let Some(e) = &prefs.default_effort {
+        return Some(e.clone());
+    }
+    if non_interactive {
+        return Some("xhigh".to_string());
+    }
+    None
+}
+
+/// Resolve whether co-author should be OFF: --no-co-author → off; else honor
+/// prefs (default_co_author false means off).
+fn resolve_co_author_off(no_co_author: bool, prefs: &Prefs) -> bool {
+    no_co_author || !prefs.default_co_author
+}
+
+/// Resolve the auto-compaction threshold for providers that expose it.
+/// Returns `None` for providers that do not support it.
+fn resolve_auto_compact_window(
+    template_type: &TemplateType,
+    template: &dyn crate::templates::Template,
+    flag: Option<&str>,
+    prefs: &Prefs,
+) -> Result<Option<AutoCompactWindow>> {
+    if !supports_auto_compact_option(template) {
+        if let Some(value) = flag.map(str::trim).filter(|v| !v.is_empty()) {
+            return Err(anyhow!(
+                "{} does not support auto-compact thresholds (got '{}')",
+                template.display_name(),
+                value
+            ));
+        }
+        return Ok(None);
+    }
+
+    let supported = template.supported_auto_compact_windows();
+
+    let parsed = if let Some(value) = flag.map(str::trim).filter(|v| !v.is_empty()) {
+        Some(value.parse::<AutoCompactWindow>()?)
+    } else {
+        prefs
             .template_pref(template_type)
             .and_then(|p| {
                 p.last_auto_compact_window
@@ -424,19 +1480,8 @@ fn resolve_variant_alias(
         ));
     }
 
-    let labels: Vec<String> = options
-        .iter()
-        .map(|(alias, label)| format!("{}  ({})", label, alias))
-        .collect();
-    let choice = inquire::Select::new(
-        &format!("Select {} variant:", template_type),
-        labels.clone(),
-    )
-    .with_help_message("↑/↓ navigate, Enter select, Esc cancel")
-    .prompt()
-    .map_err(|e| anyhow!("Variant selection failed: {}", e))?;
-    let idx = labels.iter().position(|l| l == &choice).unwrap();
-    let alias = options[idx].0.to_string();
+    let alias = TemplateSelector::select_variant(template_type, None)
+        .map_err(|e| anyhow!("Variant selection failed: {}", e))?;
     prefs.set_variant(template_type, Some(alias.clone()));
     Ok(Some(alias))
 }
@@ -445,16 +1490,59 @@ fn resolve_variant_alias(
 fn detect_current_provider() -> Option<TemplateType> {
     let settings_path = get_settings_path(None);
     let settings = ClaudeSettings::from_file(&settings_path).ok()?;
-    let base_url = settings
-        .env
-        .as_ref()
-        .and_then(|e| e.get("ANTHROPIC_BASE_URL"))
-        .cloned();
-    get_all_templates().into_iter().find(|tt| {
-        get_template_instance(tt)
-            .api_host()
-            .is_some_and(|host| base_url.as_deref().is_some_and(|u| u.contains(host)))
-    })
+    provider_of(&settings)
+}
+
+/// Provider whose base URL `settings` point at
+fn provider_of(settings: &ClaudeSettings) -> Option<TemplateType> {
+    let base_url = settings.env.as_ref()?.get("ANTHROPIC_BASE_URL")?;
+    template_for_base_url(base_url)
+}
+
+/// Env vars another provider left in the settings that applying the incoming
+/// settings would not overwrite.
+#[derive(Debug, PartialEq)]
+struct ProviderLeftovers {
+    /// Provider the existing base URL belongs to, if known
+    previous: Option<TemplateType>,
+    keys: Vec<String>,
+}
+
+/// Find env vars in `existing` from a provider other than `template_type`:
+/// provider-owned vars (see `settings::PROVIDER_ENV_KEYS`) when the existing
+/// base URL is another provider's, and other templates' API key vars.
+fn provider_leftovers(
+    existing: &ClaudeSettings,
+    template_type: &TemplateType,
+    incoming: &ClaudeSettings,
+) -> Option<ProviderLeftovers> {
+    let existing_env = existing.env.as_ref()?;
+    let incoming_env = incoming.env.clone().unwrap_or_default();
+    let previous = existing_env
+        .get("ANTHROPIC_BASE_URL")
+        .and_then(|url| template_for_base_url(url))
+        .filter(|previous| previous != template_type);
+    let other_key_vars: Vec<&str> = get_all_templates()
+        .iter()
+        .filter(|tt| *tt != template_type)
+        .flat_map(|tt| get_template_instance(tt).env_var_names())
+        .collect();
+    let own_key_vars = get_template_instance(template_type).env_var_names();
+
+    let mut keys: Vec<String> = existing_env
+        .keys()
+        .filter(|key| !incoming_env.contains_key(*key))
+        .filter(|key| {
+            (previous.is_some() && crate::settings::is_provider_env_key(key))
+                || (other_key_vars.contains(&key.as_str()) && !own_key_vars.contains(&key.as_str()))
+        })
+        .cloned()
+        .collect();
+    if keys.is_empty() {
+        return None;
+    }
+    keys.sort();
+    Some(ProviderLeftovers { previous, keys })
 }
 
 fn detect_current_provider_label() -> String {
@@ -469,50 +1557,207 @@ fn print_apply_summary(
     settings: &ClaudeSettings,
     key: &str,
     auto_compact_window: Option<AutoCompactWindow>,
+    reporter: &dyn Reporter,
 ) {
-    println!();
-    println!("{} applying '{}'", style("•").cyan(), template_type);
+    reporter.info("");
+    reporter.info(format!(
+        "{} applying '{}'",
+        style("•").cyan(),
+        template_type
+    ));
     if let Some(m) = &settings.model {
-        println!("  model:  {}", m);
+        reporter.info(format!("  model:  {}", m));
     }
-    println!("  key:    {}", mask_api_key(key));
+    reporter.info(format!(
+        "  key:    {}",
+        mask_api_key(key, MaskPolicy::DEFAULT)
+    ));
     if let Some(e) = &settings.effort_level {
-        println!("  effort: {}", e);
+        reporter.info(format!("  effort: {}", e));
     }
     if let Some(auto_compact_window) = auto_compact_window {
-        println!("  compact: {}", auto_compact_window);
+        reporter.info(format!("  compact: {}", auto_compact_window));
     }
     if let Some(base) = settings
         .env
         .as_ref()
         .and_then(|e| e.get("ANTHROPIC_BASE_URL"))
     {
-        println!("  base:   {}", base);
+        reporter.info(format!("  base:   {}", base));
+    }
+}
+
+/// Apply several targets in order, each merged onto the result of the ones
+/// before it, then preview or write the result once
+#[allow(clippy::too_many_arguments)]
+fn apply_layered(
+    targets: &[&str],
+    store: &SnapshotStore,
+    history: &History,
+    settings_path: &Path,
+    config: &Config,
+    yes: bool,
+    options: &ApplyOptions,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    // Template layers check the flags themselves; with none they'd be lost
+    options.thinking.check_supported(
+        targets
+            .iter()
+            .any(|target| get_template_type(target).is_ok()),
+        &targets.join(" + "),
+    )?;
+    let original = load_settings_for_apply(
+        settings_path,
+        !options.cli && !yes && atty::is(atty::Stream::Stdin),
+        options.dry_run || options.diff_only,
+        reporter,
+    )?;
+    let mut layered = original.clone();
+    for target in targets {
+        match get_template_type(target) {
+            Ok(template_type) => apply_template_command(
+                &template_type,
+                target,
+                settings_path,
+                config,
+                yes,
+                options,
+                Some(&mut layered),
+                reporter,
+            )?,
+            Err(_) => layered = layer_snapshot(store, target, &options.scope, layered, reporter)?,
+        }
+    }
+    if let Some(model_name) = &options.model {
+        layered.model = Some(model_name.clone());
+    }
+    if let Some(style) = &options.output_style {
+        layered.output_style = Some(style.clone());
+    }
+    layered.preserve_from(&original, &options.preserve);
+    options.permission_rules.apply_to(&mut layered);
+    write_layered(
+        targets,
+        &original,
+        &layered,
+        settings_path,
+        config,
+        store,
+        history,
+        options.dry_run,
+        options.diff_only,
+        options.preflight,
+        options.save_as.as_ref(),
+        reporter,
+    )
+}
+
+/// Load the snapshot called `name`, warning if it fails its checksum
+fn load_snapshot_checked(
+    store: &SnapshotStore,
+    name: &str,
+    reporter: &dyn Reporter,
+) -> Result<snapshots::Snapshot> {
+    let snapshot = store.load_by_name(name)?;
+    if let Some(warning) = store.checksum_warning(&snapshot) {
+        reporter.warn(warning);
+    }
+    Ok(snapshot)
+}
+
+/// Merge snapshot `name` (limited to `scope`) onto `base`, as one layer of
+/// a multi-target apply
+fn layer_snapshot(
+    store: &SnapshotStore,
+    name: &str,
+    scope: &SnapshotScope,
+    base: ClaudeSettings,
+    reporter: &dyn Reporter,
+) -> Result<ClaudeSettings> {
+    let snapshot = load_snapshot_checked(store, name, reporter)?;
+    let (settings, warning) = crate::snapshots::settings_for_scope(&snapshot, scope);
+    if let Some(warning) = warning {
+        reporter.warn(warning);
+    }
+    reporter.debug(format!(
+        "Layering snapshot '{}' within {} scope",
+        name, scope
+    ));
+    Ok(ClaudeSettings::merge_by_scope(base, settings, scope))
+}
+
+/// Write the result of layering `targets` (one backup, one history entry)
+#[allow(clippy::too_many_arguments)]
+fn write_layered(
+    targets: &[&str],
+    original: &ClaudeSettings,
+    layered: &ClaudeSettings,
+    settings_path: &Path,
+    config: &Config,
+    store: &SnapshotStore,
+    history: &History,
+    dry_run: bool,
+    diff_only: bool,
+    preflight: Preflight,
+    save_as: Option<&SaveAsSnapshot>,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    if diff_only {
+        reporter.diff(format_changes(original, layered));
+        return Ok(());
+    }
+    if dry_run {
+        println!(
+            "{}",
+            crate::settings::format_settings_for_display(
+                &layered.clone().mask_sensitive_data(),
+                true
+            )
+        );
+        println!("{} (dry-run — no changes written)", style("•").yellow());
+        return Ok(());
+    }
+    if let Some(warning) = run_preflight(&HttpCheck::default(), layered, preflight)? {
+        reporter.warn(warning);
+    }
+
+    let label = targets.join(" + ");
+    protect_settings(config, settings_path, &label, store, reporter)?;
+    write_settings_recorded(&label, settings_path, layered, history, config.force)?;
+    reporter.success(format!(
+        "Applied '{}' — wrote {}",
+        label,
+        settings_path.display()
+    ));
+    if let Some(save_as) = save_as {
+        save_as.save(layered, &label, reporter)?;
     }
+    Ok(())
 }
 
-/// Apply a template
+/// Apply a template. With `layered`, merge onto those settings instead of
+/// the file and leave writing to the caller.
 #[allow(clippy::too_many_arguments)]
 fn apply_template_command(
     template_type: &TemplateType,
     target: &str,
-    scope: &SnapshotScope,
-    model: &Option<String>,
-    settings_path: &PathBuf,
-    backup: bool,
+    settings_path: &Path,
+    config: &Config,
     yes: bool,
-    cli: bool,
-    effort: &Option<String>,
-    auto_compact: &Option<String>,
-    api_key: &Option<String>,
-    no_co_author: bool,
-    switch_key: bool,
-    dry_run: bool,
-    variant: &Option<String>,
+    options: &ApplyOptions,
+    layered: Option<&mut ClaudeSettings>,
+    reporter: &dyn Reporter,
 ) -> Result<()> {
-    let non_interactive = cli || !atty::is(atty::Stream::Stdin);
-    // Interactive TUI when on a TTY, not forced via flags, and not --yes.
-    let use_tui = !non_interactive && !yes;
+    let non_interactive = options.cli || !atty::is(atty::Stream::Stdin);
+    let env_file_vars = options
+        .env_file
+        .as_deref()
+        .map(crate::utils::load_env_file)
+        .transpose()?;
+    // Interactive TUI when on a TTY, not forced via flags, and not --yes /
+    // --env-file.
+    let use_tui = !non_interactive && !yes && env_file_vars.is_none();
     let mut prefs = Prefs::load_or_default();
 
     // Gather intent: (variant alias, key, effort, compact, scope, co-author-off).
@@ -537,6 +1782,7 @@ fn apply_template_command(
                 sel.scope,
                 sel.co_author_off,
             ),
+            None if layered.is_some() => return Err(anyhow!("Cancelled")),
             None => {
                 println!("Cancelled.");
                 return Ok(());
@@ -550,7 +1796,7 @@ fn apply_template_command(
         let va = resolve_variant_alias(
             template_type,
             target,
-            variant.as_deref(),
+            options.variant.as_deref(),
             &mut prefs,
             non_interactive,
         )?;
@@ -559,32 +1805,42 @@ fn apply_template_command(
             .and_then(|p| p.last_key.clone());
         let kc = resolve_api_key(
             template_type,
-            api_key.as_deref(),
+            options.api_key.as_deref(),
+            env_file_vars.as_ref(),
             remembered_key.as_ref(),
-            switch_key,
+            options.key_preference,
+            options.switch_key,
             non_interactive,
         )?
         .ok_or_else(|| anyhow!("Cancelled"))?;
+        if let Some(source) = &kc.source {
+            reporter.info(format!(
+                "{} Using API key from {}",
+                style("•").cyan(),
+                source
+            ));
+        }
         prefs.set_last_key(template_type, kc.source.clone());
-        let eff = resolve_effort(effort.as_deref(), &prefs, non_interactive);
+        let eff = resolve_effort(options.effort.as_deref(), &prefs, non_interactive);
         let preview_template =
             get_template_instance_with_input(template_type, va.as_deref().unwrap_or(target));
         let compact = resolve_auto_compact_window(
             template_type,
             preview_template.as_ref(),
-            auto_compact.as_deref(),
+            options.auto_compact.as_deref(),
             &prefs,
         )?;
-        let cao = resolve_co_author_off(no_co_author, &prefs);
-        (va, kc, eff, compact, scope.clone(), cao)
+        let cao = !options.co_authored && resolve_co_author_off(options.no_co_author, &prefs);
+        (va, kc, eff, compact, options.scope.clone(), cao)
     };
 
     // Build template settings from the resolved alias + key + scope.
-    let template_instance =
+    let mut template_instance =
         get_template_instance_with_input(template_type, variant_alias.as_deref().unwrap_or(target));
+    resolve_additional_config(template_instance.as_mut())?;
     let mut settings = template_instance.create_settings_with_auto_compact(
         &key_choice.key,
-        &scope,
+        &scope.template_scope(),
         auto_compact_window,
     )?;
     inject_common_env_vars(&mut settings);
@@ -599,9 +1855,16 @@ fn apply_template_command(
     }
 
     // --model override
-    if let Some(model_name) = model {
+    if let Some(model_name) = &options.model {
         settings.model = Some(model_name.clone());
     }
+    apply_subagent_model(&mut settings, options.subagent_model.as_deref());
+    options
+        .thinking
+        .apply(template_instance.as_ref(), &mut settings)?;
+    if let Some(style) = &options.output_style {
+        settings.output_style = Some(style.clone());
+    }
 
     // effort + co-author from the resolved selection
     settings.effort_level = effort.clone();
@@ -615,22 +1878,110 @@ fn apply_template_command(
     };
 
     // Merge by scope (preserves unrelated keys/fields).
-    let existing = ClaudeSettings::from_file(settings_path)?;
-    let merged = ClaudeSettings::merge_by_scope(existing, settings, &scope);
-
-    if backup {
-        backup_settings(settings_path)?;
-    }
-
-    print_apply_summary(template_type, &merged, &key_choice.key, auto_compact_window);
+    let mut existing = match &layered {
+        Some(layered) => (*layered).clone(),
+        None => load_settings_for_apply(
+            settings_path,
+            !non_interactive && !yes,
+            options.dry_run || options.diff_only,
+            reporter,
+        )?,
+    };
+    if options.merge_strategy == MergeStrategy::Merge
+        && let Some(leftovers) = provider_leftovers(&existing, template_type, &settings)
+    {
+        let from = leftovers
+            .previous
+            .map(|previous| format!(" from {}", previous))
+            .unwrap_or_default();
+        reporter.warn(format!(
+            "Settings still have env vars{} that {} doesn't set: {}",
+            from,
+            template_type,
+            leftovers.keys.join(", ")
+        ));
+        let clean = !non_interactive
+            && !yes
+            && !options.dry_run
+            && confirm_action("Remove them before applying?", true)?;
+        if clean {
+            if let Some(env) = existing.env.as_mut() {
+                env.retain(|key, _| !leftovers.keys.contains(key));
+            }
+        } else {
+            reporter.warn("Use --merge-strategy replace to drop them");
+        }
+    }
+    reporter.debug(format!(
+        "Merge strategy: {:?} within {} scope",
+        options.merge_strategy, scope
+    ));
+    let before = options.diff_only.then(|| existing.clone());
+    let preserved = (!options.preserve.is_empty()).then(|| existing.clone());
+    let mut merged = options.merge_strategy.apply(existing, settings, &scope);
+    // Merging keeps an existing "off"; --co-authored must clear it
+    if options.co_authored {
+        merged.set_co_authored(true);
+    }
+    options.thinking.drop_stale_budget(&mut merged);
+    if let Some(preserved) = &preserved {
+        merged.preserve_from(preserved, &options.preserve);
+    }
+    // After preserving, so --preserve permissions keeps the file's rules
+    // and --allow/--deny still land on top
+    options.permission_rules.apply_to(&mut merged);
+    // A layer only feeds the next one; the caller previews or writes the
+    // result, so no summary, preflight or prefs here
+    if let Some(layered) = layered {
+        *layered = merged;
+        return Ok(());
+    }
+    if let Some(before) = before {
+        reporter.diff(format_changes(&before, &merged));
+        return Ok(());
+    }
 
-    if dry_run {
+    print_apply_summary(
+        template_type,
+        &merged,
+        &key_choice.key,
+        auto_compact_window,
+        reporter,
+    );
+
+    if options.preflight != Preflight::Off
+        && let Some(base_url) = merged
+            .env
+            .as_ref()
+            .and_then(|env| env.get("ANTHROPIC_BASE_URL"))
+    {
+        reporter.trace(format!("Preflight: HEAD {}", base_url));
+    }
+    if let Some(warning) = run_preflight(&HttpCheck::default(), &merged, options.preflight)? {
+        reporter.warn(warning);
+    }
+
+    if options.dry_run {
         println!("{} (dry-run — no changes written)", style("•").yellow());
         prefs.save()?;
         return Ok(());
     }
 
-    merged.to_file(settings_path)?;
+    let label = template_type.to_string();
+    protect_settings(
+        config,
+        settings_path,
+        &label,
+        &default_snapshot_store(),
+        reporter,
+    )?;
+    write_settings_recorded(
+        &label,
+        settings_path,
+        &merged,
+        &History::open_default(),
+        config.force,
+    )?;
     // Remember this apply for next time.
     prefs.record_apply(
         template_type,
@@ -643,39 +1994,681 @@ fn apply_template_command(
     );
     prefs.save()?;
 
-    println!(
-        "{} Applied '{}' — wrote {}",
-        style("✓").green().bold(),
+    reporter.success(format!(
+        "Applied '{}' — wrote {}",
         template_type,
         settings_path.display()
+    ));
+    if let Some(save_as) = &options.save_as {
+        save_as.save(&merged, &label, reporter)?;
+    }
+    Ok(())
+}
+
+/// Subagent model from `--subagent-model`, else the template's small/fast model
+fn apply_subagent_model(settings: &mut ClaudeSettings, flag: Option<&str>) {
+    let model = flag
+        .map(str::trim)
+        .filter(|model| !model.is_empty())
+        .or_else(|| small_fast_model(settings))
+        .map(str::to_string);
+    if let Some(model) = model {
+        settings.set_subagent_model(&model);
+    }
+}
+
+/// `Ok(None)` when the user cancelled the prompt
+fn unless_cancelled<T>(result: SelectorResult<T>) -> Result<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(e) if e.is_cancellation() => Ok(None),
+        Err(e) => Err(anyhow!("{}", e)),
+    }
+}
+
+/// Guided apply (`apply --interactive`): template → variant (if the template
+/// has several) → API key → endpoint (if the template needs one) → review
+/// the changes → write. Choices are remembered in prefs like a plain apply.
+pub fn interactive_apply_command(
+    prompts: &mut dyn ApplyPrompts,
+    scope: &SnapshotScope,
+    settings_path: &Path,
+    config: &Config,
+    history: &History,
+    save_as: Option<&SaveAsSnapshot>,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    if let Some(save_as) = save_as {
+        save_as.check()?;
+    }
+    let Some(template_type) = unless_cancelled(prompts.select_template())? else {
+        println!("Cancelled.");
+        return Ok(());
+    };
+    let mut prefs = Prefs::load_or_default();
+    let variant_alias = if variant_options(&template_type).is_empty() {
+        None
+    } else {
+        let remembered = prefs
+            .template_pref(&template_type)
+            .and_then(|p| p.variant.clone());
+        let Some(alias) =
+            unless_cancelled(prompts.select_variant(&template_type, remembered.as_deref()))?
+        else {
+            println!("Cancelled.");
+            return Ok(());
+        };
+        Some(alias)
+    };
+    let Some(key_choice) = unless_cancelled(prompts.select_api_key(&template_type))? else {
+        println!("Cancelled.");
+        return Ok(());
+    };
+
+    let target = template_type.to_string();
+    let mut template_instance = get_template_instance_with_input(
+        &template_type,
+        variant_alias.as_deref().unwrap_or(&target),
+    );
+    if template_instance.requires_additional_config() {
+        let Some(endpoint_id) = unless_cancelled(prompts.select_endpoint_id(&template_type))?
+        else {
+            println!("Cancelled.");
+            return Ok(());
+        };
+        if let Some(endpoint_id) = endpoint_id {
+            if template_type == TemplateType::KatCoder
+                && let Err(e) = crate::templates::kat_coder::validate_endpoint_id(&endpoint_id)
+            {
+                reporter.warn(e);
+            }
+            template_instance
+                .set_additional_config(&HashMap::from([("endpoint_id".to_string(), endpoint_id)]));
+        }
+    }
+
+    let mut settings = template_instance.create_settings(&key_choice.key, &scope.template_scope());
+    inject_common_env_vars(&mut settings);
+    apply_subagent_model(&mut settings, None);
+    if let Some(git_bash_path) = get_git_bash_path(&settings, true)? {
+        settings
+            .env
+            .get_or_insert_with(HashMap::new)
+            .insert("CLAUDE_CODE_GIT_BASH_PATH".to_string(), git_bash_path);
+    }
+
+    reporter.trace(format!("Settings file: {}", settings_path.display()));
+    let existing = load_settings_for_apply(settings_path, true, false, reporter)?;
+    reporter.debug(format!("Merging {} within {} scope", template_type, scope));
+    let merged = ClaudeSettings::merge_by_scope(existing.clone(), settings, scope);
+
+    println!("{} {}", style("•").cyan(), template_instance.display_name());
+    print!(
+        "{}",
+        crate::settings::format_settings_comparison(&existing, &merged)
+    );
+    println!();
+    if !unless_cancelled(prompts.confirm_apply())?.unwrap_or(false) {
+        println!("Cancelled.");
+        return Ok(());
+    }
+
+    let label = template_type.to_string();
+    protect_settings(
+        config,
+        settings_path,
+        &label,
+        &default_snapshot_store(),
+        reporter,
+    )?;
+    write_settings_recorded(&label, settings_path, &merged, history, config.force)?;
+    prefs.record_apply(
+        &template_type,
+        variant_alias,
+        key_choice.source.clone(),
+        scope.clone(),
+        merged.effort_level.clone(),
+        merged.co_authored(),
+        None,
     );
+    prefs.save()?;
+    reporter.success(format!(
+        "Applied '{}' — wrote {}",
+        template_type,
+        settings_path.display()
+    ));
+    if let Some(save_as) = save_as {
+        save_as.save(&merged, &label, reporter)?;
+    }
     Ok(())
 }
 
-/// Apply a snapshot (replace-within-scope; snapshots are deliberate restore points)
-fn apply_snapshot_command(
-    snapshot_name: &str,
+/// Print one env value a template would write (`apply --print-env`).
+/// Nothing else goes to stdout, so the output can be captured by scripts.
+fn print_env_command(
+    target: &str,
+    variant: &Option<String>,
+    api_key: &Option<String>,
+    env_file: &Option<PathBuf>,
+    var: &str,
+) -> Result<()> {
+    let template_type = get_template_type(target)?;
+
+    // Never prompt: --api-key, then --env-file, then the process env
+    let env_file_vars = env_file
+        .as_deref()
+        .map(crate::utils::load_env_file)
+        .transpose()?
+        .unwrap_or_default();
+    let key = api_key.clone().or_else(|| {
+        crate::templates::get_env_var_names(&template_type)
+            .into_iter()
+            .find_map(|name| {
+                env_file_vars
+                    .get(name)
+                    .cloned()
+                    .or_else(|| std::env::var(name).ok())
+            })
+    });
+
+    let value = template_env_value(
+        &template_type,
+        variant.as_deref().unwrap_or(target),
+        key.as_deref().unwrap_or_default(),
+        var,
+    )?;
+    println!("{}", value);
+    Ok(())
+}
+
+/// Value of `var` in the env that applying the template (`input` selects the
+/// variant) would write. Errors if the template doesn't set it, or sets it
+/// empty (e.g. the API key variable when no key is known).
+pub fn template_env_value(
+    template_type: &TemplateType,
+    input: &str,
+    api_key: &str,
+    var: &str,
+) -> Result<String> {
+    let mut template_instance = get_template_instance_with_input(template_type, input);
+    resolve_additional_config(template_instance.as_mut())?;
+    let mut settings = template_instance.create_settings(api_key, &SnapshotScope::All);
+    inject_common_env_vars(&mut settings);
+
+    settings
+        .env
+        .as_ref()
+        .and_then(|env| env.get(var))
+        .filter(|value| !value.is_empty())
+        .cloned()
+        .ok_or_else(|| anyhow!("{} does not set {}", template_instance.display_name(), var))
+}
+
+fn default_snapshot_store() -> SnapshotStore {
+    SnapshotStore::new(get_snapshots_dir())
+}
+
+/// Take the safety copies `config` asks for before an apply overwrites
+/// `settings_path`: the `.backup` file and/or an `auto-…` snapshot of the
+/// whole file. Nothing to do if the file doesn't exist yet.
+fn protect_settings(
+    config: &Config,
+    settings_path: &Path,
+    label: &str,
+    snapshots: &SnapshotStore,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    if !settings_path.exists() || is_stdio_path(settings_path) {
+        return Ok(());
+    }
+    if config.backup {
+        backup_settings(settings_path)?;
+    }
+    if config.auto_snapshot {
+        let name = format!("auto-{}", chrono::Local::now().format("%Y%m%d-%H%M%S"));
+        let snapshot = snapshots::Snapshot::new(
+            name.clone(),
+            ClaudeSettings::from_file(settings_path)?,
+            SnapshotScope::All,
+            Some(format!("Before applying {}", label)),
+        );
+        snapshots.save(&snapshot)?;
+        reporter.info(format!("{} Saved snapshot '{}'", style("•").cyan(), name));
+    }
+    Ok(())
+}
+
+/// Refuse to replace a file that has settings with empty ones (`{}`), which
+/// is what aggressive scope filtering or unsetting can leave, unless `force`.
+fn check_empty_overwrite(
+    settings_path: &Path,
+    settings: &ClaudeSettings,
+    force: bool,
+) -> Result<()> {
+    if force || !settings.is_empty() {
+        return Ok(());
+    }
+    match ClaudeSettings::from_file(settings_path) {
+        Ok(existing) if !existing.is_empty() => Err(anyhow!(
+            "Refusing to write empty settings over {}, which has settings. Pass --force to write anyway",
+            settings_path.display()
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Write `settings` and record the change so `ccs undo` can revert it.
+/// Empty settings don't replace a populated file unless `force`.
+fn write_settings_recorded(
+    label: &str,
+    settings_path: &Path,
+    settings: &ClaudeSettings,
+    history: &History,
+    force: bool,
+) -> Result<()> {
+    check_empty_overwrite(settings_path, settings, force)?;
+
+    // Settings printed to stdout have nothing to revert
+    if is_stdio_path(settings_path) {
+        return settings.to_file(settings_path);
+    }
+
+    // An unparseable file can't be restored, so such applies aren't recorded.
+    let before = if settings_path.exists() {
+        ClaudeSettings::from_file(settings_path).ok().map(Some)
+    } else {
+        Some(None)
+    };
+
+    settings.to_file(settings_path)?;
+
+    if let Some(before) = before
+        && let Err(e) = history.record_apply(label, settings_path, before, settings)
+    {
+        eprintln!(
+            "{} Could not record apply history: {}",
+            style("⚠").yellow(),
+            e
+        );
+    }
+    Ok(())
+}
+
+/// Apply a settings JSON document piped on stdin (`apply --from-stdin`)
+fn apply_from_stdin_command(
     scope: &SnapshotScope,
     model: &Option<String>,
-    settings_path: &PathBuf,
-    backup: bool,
-    yes: bool,
+    settings_path: &Path,
+    config: &Config,
+    dry_run: bool,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    if is_stdio_path(settings_path) {
+        return Err(anyhow!(
+            "--from-stdin can't be combined with --settings-path -: both read stdin"
+        ));
+    }
+    let mut input = String::new();
+    std::io::stdin()
+        .read_to_string(&mut input)
+        .map_err(|e| anyhow!("Failed to read settings from stdin: {}", e))?;
+
+    apply_settings_document(
+        &input,
+        "stdin",
+        scope,
+        &DocumentOverrides {
+            model: model.clone(),
+            ..Default::default()
+        },
+        settings_path,
+        config,
+        dry_run,
+        false,
+        None,
+        &History::open_default(),
+        reporter,
+    )
+}
+
+/// Apply a settings JSON file, filling `{API_KEY}` in its env values.
+///
+/// The key comes from `--api-key`, or is resolved the usual way for
+/// `key_target` (env file, environment, saved credentials, prompt).
+#[allow(clippy::too_many_arguments)]
+fn apply_template_file_command(
+    template_file: &Path,
+    key_target: Option<&str>,
+    api_key: &Option<String>,
+    env_file: &Option<PathBuf>,
+    switch_key: bool,
+    key_preference: KeyPreference,
+    scope: &SnapshotScope,
+    overrides: &DocumentOverrides,
+    settings_path: &Path,
+    config: &Config,
+    cli: bool,
+    dry_run: bool,
+    diff_only: bool,
+    save_as: Option<&SaveAsSnapshot>,
+    reporter: &dyn Reporter,
 ) -> Result<()> {
-    let snapshots_dir = get_snapshots_dir();
-    let store = SnapshotStore::new(snapshots_dir);
+    let document = std::fs::read_to_string(template_file)
+        .map_err(|e| anyhow!("Failed to read {}: {}", template_file.display(), e))?;
+
+    let key = match (key_target, api_key.as_deref().map(str::trim)) {
+        (_, Some(key)) if !key.is_empty() => key.to_string(),
+        (Some(target), _) => {
+            let template_type = get_template_type(target)?;
+            let env_file_vars = env_file
+                .as_deref()
+                .map(crate::utils::load_env_file)
+                .transpose()?;
+            resolve_api_key(
+                &template_type,
+                None,
+                env_file_vars.as_ref(),
+                None,
+                key_preference,
+                switch_key,
+                cli || !atty::is(atty::Stream::Stdin),
+            )?
+            .ok_or_else(|| anyhow!("Cancelled"))?
+            .key
+        }
+        (None, _) => {
+            return Err(anyhow!(
+                "--template-file needs a key: pass --api-key or the provider whose key to use"
+            ));
+        }
+    };
 
-    let mut snapshot = store.load_by_name(snapshot_name)?;
+    apply_settings_document(
+        &fill_template_placeholders(&document, &key)?,
+        &template_file.display().to_string(),
+        scope,
+        overrides,
+        settings_path,
+        config,
+        dry_run,
+        diff_only,
+        save_as,
+        &History::open_default(),
+        reporter,
+    )
+}
 
-    snapshot.settings = snapshot.settings.filter_by_scope(scope);
+/// Replace `{API_KEY}` in every env value of a settings JSON document.
+/// Errors if any `{PLACEHOLDER}` is left unfilled.
+pub fn fill_template_placeholders(document: &str, api_key: &str) -> Result<String> {
+    let mut value: serde_json::Value =
+        serde_json::from_str(document).map_err(|e| anyhow!("Invalid settings JSON: {}", e))?;
+    let placeholder = regex::Regex::new(r"\{[A-Z][A-Z0-9_]*\}").expect("valid regex");
 
-    if let Some(model_name) = model {
-        snapshot.settings.model = Some(model_name.clone());
+    if let Some(env) = value.get_mut("env").and_then(|e| e.as_object_mut()) {
+        for (name, entry) in env.iter_mut() {
+            let Some(text) = entry.as_str() else { continue };
+            if let Some(left) = placeholder
+                .find_iter(text)
+                .find(|m| m.as_str() != "{API_KEY}")
+            {
+                return Err(anyhow!(
+                    "env.{} has an unfilled placeholder {}",
+                    name,
+                    left.as_str()
+                ));
+            }
+            *entry = serde_json::Value::String(text.replace("{API_KEY}", api_key));
+        }
     }
+    Ok(value.to_string())
+}
 
-    let existing_settings = ClaudeSettings::from_file(settings_path)?;
+/// Flags `apply --from-stdin`/`--template-file` apply on top of the
+/// settings document
+#[derive(Debug, Clone, Default)]
+struct DocumentOverrides {
+    model: Option<String>,
+    output_style: Option<String>,
+    effort: Option<String>,
+    /// `--co-authored` (true) or `--no-co-author` (false)
+    co_authored: Option<bool>,
+    merge_strategy: MergeStrategy,
+    preflight: Preflight,
+}
 
-    if backup {
-        backup_settings(settings_path)?;
+/// Merge a settings JSON document (`label` names where it came from) into
+/// the settings file within `scope`, then preview or write it
+#[allow(clippy::too_many_arguments)]
+fn apply_settings_document(
+    input: &str,
+    label: &str,
+    scope: &SnapshotScope,
+    overrides: &DocumentOverrides,
+    settings_path: &Path,
+    config: &Config,
+    dry_run: bool,
+    diff_only: bool,
+    save_as: Option<&SaveAsSnapshot>,
+    history: &History,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    if let Some(save_as) = save_as
+        && !dry_run
+    {
+        save_as.check()?;
+    }
+    reporter.trace(format!("Settings file: {}", settings_path.display()));
+    let mut merged = merge_settings_json(input, scope, settings_path, overrides.merge_strategy)?;
+    if let Some(model_name) = &overrides.model {
+        merged.model = Some(model_name.clone());
+    }
+    if let Some(style) = &overrides.output_style {
+        merged.output_style = Some(style.clone());
+    }
+    if let Some(effort) = &overrides.effort {
+        merged.effort_level = Some(effort.clone());
+    }
+    if let Some(on) = overrides.co_authored {
+        merged.set_co_authored(on);
+    }
+
+    if diff_only {
+        let current = ClaudeSettings::from_file(settings_path)?;
+        reporter.diff(format_changes(&current, &merged));
+        return Ok(());
+    }
+    if let Some(warning) = run_preflight(&HttpCheck::default(), &merged, overrides.preflight)? {
+        reporter.warn(warning);
+    }
+    if dry_run {
+        println!(
+            "{}",
+            crate::settings::format_settings_for_display(&merged.mask_sensitive_data(), true)
+        );
+        println!("{} (dry-run — no changes written)", style("•").yellow());
+        return Ok(());
+    }
+
+    protect_settings(
+        config,
+        settings_path,
+        label,
+        &default_snapshot_store(),
+        reporter,
+    )?;
+    write_settings_recorded(label, settings_path, &merged, history, config.force)?;
+
+    reporter.success(format!(
+        "Applied settings from {} — wrote {}",
+        label,
+        settings_path.display()
+    ));
+    if let Some(save_as) = save_as {
+        save_as.save(&merged, label, reporter)?;
+    }
+    Ok(())
+}
+
+/// `apply --print-diff-only` output: the masked changes from `current` to
+/// `new`, one per line
+fn format_changes(current: &ClaudeSettings, new: &ClaudeSettings) -> String {
+    if current.diff(new).is_empty() {
+        return "No changes\n".to_string();
+    }
+    crate::settings::format_settings_comparison(current, new)
+}
+
+/// Read the settings an apply merges into. If the file isn't valid JSON and
+/// `interactive`, offer to move it aside and start from empty settings. A
+/// `preview` (`--dry-run`/`--print-diff-only`) must not touch the file, so
+/// there it fails with the parse error instead.
+fn load_settings_for_apply(
+    settings_path: &Path,
+    interactive: bool,
+    preview: bool,
+    reporter: &dyn Reporter,
+) -> Result<ClaudeSettings> {
+    if preview {
+        return Ok(ClaudeSettings::from_file(settings_path)?);
+    }
+    let (settings, moved) = ClaudeSettings::from_file_or_recover(settings_path, |path| {
+        Ok(interactive
+            && confirm_action(
+                &format!(
+                    "{} is not valid JSON. Back it up and start from empty settings?",
+                    path.display()
+                ),
+                false,
+            )?)
+    })?;
+    if let Some(moved) = moved {
+        reporter.warn(format!(
+            "Moved the invalid {} to {}",
+            settings_path.display(),
+            moved.display()
+        ));
+    }
+    Ok(settings)
+}
+
+/// Parse a settings JSON document and merge it into the file at
+/// `settings_path` within `scope`. Nothing is written.
+pub fn merge_settings_json(
+    json: &str,
+    scope: &SnapshotScope,
+    settings_path: &Path,
+    merge_strategy: MergeStrategy,
+) -> Result<ClaudeSettings> {
+    let incoming: ClaudeSettings =
+        serde_json::from_str(json).map_err(|e| anyhow!("Invalid settings JSON: {}", e))?;
+    let (existing, _) = ClaudeSettings::from_file_or_recover(settings_path, |_| Ok(false))?;
+    Ok(merge_strategy.apply(existing, incoming.filter_by_scope(scope), scope))
+}
+
+/// Set a snapshot's model, along with the `ANTHROPIC_MODEL` it carries so
+/// the two don't disagree
+fn override_snapshot_model(settings: &mut ClaudeSettings, model: &str) {
+    settings.model = Some(model.to_string());
+    if let Some(env_model) = settings
+        .env
+        .as_mut()
+        .and_then(|env| env.get_mut("ANTHROPIC_MODEL"))
+    {
+        *env_model = model.to_string();
+    }
+}
+
+/// `apply --latest`: apply the newest snapshot itself, never a template
+/// that happens to share its name
+fn apply_latest_command(
+    store: &SnapshotStore,
+    history: &History,
+    settings_path: &Path,
+    config: &Config,
+    yes: bool,
+    options: &ApplyOptions,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    reporter.trace(format!("Settings file: {}", settings_path.display()));
+    if let Some(save_as) = &options.save_as
+        && !options.dry_run
+    {
+        save_as.check()?;
+    }
+    let snapshot = latest_snapshot(store)?;
+    options
+        .thinking
+        .check_supported(false, &format!("Snapshot '{}'", snapshot.name))?;
+    if let Some(warning) = store.checksum_warning(&snapshot) {
+        reporter.warn(warning);
+    }
+    apply_snapshot_command(
+        snapshot,
+        store,
+        history,
+        settings_path,
+        config,
+        yes,
+        options,
+        reporter,
+    )
+}
+
+/// Apply a snapshot (replace-within-scope; snapshots are deliberate restore
+/// points). Of `options`, only the flags a snapshot can take are read.
+#[allow(clippy::too_many_arguments)]
+fn apply_snapshot_command(
+    mut snapshot: snapshots::Snapshot,
+    store: &SnapshotStore,
+    history: &History,
+    settings_path: &Path,
+    config: &Config,
+    yes: bool,
+    options: &ApplyOptions,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let snapshot_name = snapshot.name.clone();
+    let snapshot_name = snapshot_name.as_str();
+
+    let (settings, warning) = crate::snapshots::settings_for_scope(&snapshot, &options.scope);
+    if let Some(warning) = warning {
+        reporter.warn(warning);
+    }
+    snapshot.settings = settings;
+
+    if let Some(model_name) = &options.model {
+        override_snapshot_model(&mut snapshot.settings, model_name);
+    }
+    if let Some(style) = &options.output_style {
+        snapshot.settings.output_style = Some(style.clone());
+    }
+
+    reporter.trace(format!("Settings file: {}", settings_path.display()));
+    let existing_settings = load_settings_for_apply(
+        settings_path,
+        !yes && atty::is(atty::Stream::Stdin),
+        options.dry_run || options.diff_only,
+        reporter,
+    )?;
+    snapshot
+        .settings
+        .preserve_from(&existing_settings, &options.preserve);
+    // After preserving, so --preserve permissions keeps the file's rules
+    // and --allow/--deny still land on top
+    options.permission_rules.apply_to(&mut snapshot.settings);
+    reporter.debug(format!(
+        "Replacing the {} scope with snapshot '{}'",
+        options.scope, snapshot_name
+    ));
+    if options.diff_only {
+        reporter.diff(format_changes(&existing_settings, &snapshot.settings));
+        return Ok(());
+    }
+    if options.dry_run {
+        print!("{}", format_changes(&existing_settings, &snapshot.settings));
+        println!("{} (dry-run — no changes written)", style("•").yellow());
+        return Ok(());
     }
 
     if !yes {
@@ -693,8 +2686,8 @@ fn apply_snapshot_command(
             crate::settings::format_settings_for_display(&snapshot_masked, false)
         );
 
-        let options = vec!["Apply", "Cancel"];
-        let selection = inquire::Select::new("Confirm:", options)
+        let choices = vec!["Apply", "Cancel"];
+        let selection = inquire::Select::new("Confirm:", choices)
             .prompt()
             .map_err(|_| anyhow!("Cancelled"))?;
         if selection == "Cancel" {
@@ -702,13 +2695,22 @@ fn apply_snapshot_command(
         }
     }
 
-    snapshot.settings.to_file(settings_path)?;
+    protect_settings(config, settings_path, snapshot_name, store, reporter)?;
+    write_settings_recorded(
+        snapshot_name,
+        settings_path,
+        &snapshot.settings,
+        history,
+        config.force,
+    )?;
 
-    println!(
-        "{} Applied snapshot '{}' successfully!",
-        style("✓").green().bold(),
+    reporter.success(format!(
+        "Applied snapshot '{}' successfully!",
         snapshot_name
-    );
+    ));
+    if let Some(save_as) = &options.save_as {
+        save_as.save(&snapshot.settings, snapshot_name, reporter)?;
+    }
 
     Ok(())
 }
@@ -716,11 +2718,26 @@ fn apply_snapshot_command(
 // ── credentials ──────────────────────────────────────────────────────────────
 
 /// List saved credentials interactively
-pub fn credentials_list_command() -> Result<()> {
+pub fn credentials_list_command(
+    show_expired: bool,
+    hide_expired: bool,
+    sort: SortOrder,
+) -> Result<()> {
+    use crate::selectors::credential::{CredentialSelector, ExpiryFilter};
+
     println!("🔐 Credential Browser");
     println!();
 
-    let mut selector = crate::selectors::credential::CredentialSelector::new_all()?;
+    let filter = if show_expired {
+        ExpiryFilter::OnlyExpired
+    } else if hide_expired {
+        ExpiryFilter::HideExpired
+    } else {
+        ExpiryFilter::All
+    };
+    let mut selector = CredentialSelector::new_all()?
+        .with_expiry_filter(filter)
+        .with_sort(sort);
 
     match selector.run_management() {
         Ok(()) => println!("\n👋 Goodbye!"),
@@ -737,22 +2754,233 @@ pub fn credentials_list_command() -> Result<()> {
     Ok(())
 }
 
-/// Clear all credentials
-pub fn credentials_clear_command(yes: bool) -> Result<()> {
-    if !yes && !confirm_action("Clear all saved credentials?", false)? {
-        return Ok(());
-    }
+/// Print saved credentials as CSV/TSV
+pub fn credentials_list_delimited_command(
+    show_expired: bool,
+    hide_expired: bool,
+    format: DelimitedFormat,
+    with_key: bool,
+    sort: SortOrder,
+) -> Result<()> {
+    use crate::selectors::credential::ExpiryFilter;
 
-    let _credentials_dir = get_credentials_dir();
-    let credential_store = CredentialStore::new()?;
+    let filter = if show_expired {
+        ExpiryFilter::OnlyExpired
+    } else if hide_expired {
+        ExpiryFilter::HideExpired
+    } else {
+        ExpiryFilter::All
+    };
+    let credentials: Vec<_> = SavedCredentialStore::new()?
+        .list_sorted(sort)?
+        .into_iter()
+        .filter(|c| filter.matches(c))
+        .collect();
+    print!(
+        "{}",
+        format_credentials_delimited(&credentials, format, with_key)
+    );
+    Ok(())
+}
+
+/// Save the key from a provider's key file as a credential
+pub fn credentials_import_file_command(
+    path: &Path,
+    template: &str,
+    name: Option<&str>,
+    pointer: Option<&str>,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let template_type = get_template_type(template)?;
+    let credential =
+        CredentialStore::new()?.import_from_file(path, template_type, name, pointer)?;
+    reporter.success(format!(
+        "Imported {} key '{}' ({})",
+        credential.template_type(),
+        credential.name(),
+        mask_api_key(credential.api_key(), MaskPolicy::DEFAULT)
+    ));
+    Ok(())
+}
+
+/// Rename the credential with ID `id`
+pub fn credentials_rename_command(id: &str, name: &str, reporter: &dyn Reporter) -> Result<()> {
+    let credential = CredentialStore::new()?.rename_by_id(id, name)?;
+    reporter.success(format!(
+        "Renamed {} credential {} to '{}'",
+        credential.template_type(),
+        credential.id(),
+        credential.name()
+    ));
+    Ok(())
+}
+
+/// Print the key source `apply <template>` would take (masked), or that it
+/// would prompt. `--api-key` and `--env-file` aren't considered.
+pub fn credentials_which_command(template: &str) -> Result<()> {
+    let template_type = get_template_type(template)?;
+    let sources = crate::credentials::collect_api_key_sources(&template_type)?;
+    let remembered = Prefs::load_or_default()
+        .template_pref(&template_type)
+        .and_then(|pref| pref.last_key.clone());
+    match crate::credentials::pick_api_key_source(
+        &sources,
+        remembered.as_ref(),
+        KeyPreference::Auto,
+    ) {
+        Some(source) => println!("{}", source.display()),
+        None if sources.is_empty() => println!("none — would prompt for a new key"),
+        None => println!(
+            "none — would prompt to choose between {} keys",
+            sources.len()
+        ),
+    }
+    Ok(())
+}
+
+/// List saved credentials matching a template and/or key prefix, keys masked
+pub fn credentials_find_command(template: Option<&str>, key_prefix: Option<&str>) -> Result<()> {
+    let store = CredentialStore::new()?.store;
+    let template_type = template.map(get_template_type).transpose()?;
+    let mut found = match (&template_type, key_prefix) {
+        (_, Some(prefix)) => store.find_by_key_prefix(prefix)?,
+        (Some(template_type), None) => store.find_by_template_type(template_type)?,
+        (None, None) => store.list()?,
+    };
+    if let Some(template_type) = &template_type {
+        found.retain(|c| c.template_type() == template_type);
+    }
+
+    if found.is_empty() {
+        println!("No matching credentials.");
+        return Ok(());
+    }
+    SortOrder::default().sort(&mut found);
+    let mut table = crate::utils::Table::new().headers(["ID", "NAME", "TEMPLATE", "KEY"]);
+    for credential in &found {
+        table.row([
+            credential.id().to_string(),
+            credential.name().to_string(),
+            credential.template_type().to_string(),
+            mask_api_key(credential.api_key(), MaskPolicy::DEFAULT),
+        ]);
+    }
+    print!("{}", table.render());
+    Ok(())
+}
+
+/// Print a credential's metadata, one `key=value` per line
+pub fn credentials_meta_get_command(id: &str) -> Result<()> {
+    let credential = CredentialStore::new()?.store.load(id)?;
+    let mut entries: Vec<_> = credential.metadata().into_iter().flatten().collect();
+    if entries.is_empty() {
+        println!("No metadata for credential '{}'", credential.name());
+        return Ok(());
+    }
+    entries.sort();
+    for (key, value) in entries {
+        println!("{}={}", key, value);
+    }
+    Ok(())
+}
+
+/// Set one metadata entry on a credential
+pub fn credentials_meta_set_command(
+    id: &str,
+    key: &str,
+    value: &str,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let stored = CredentialStore::new()?.set_metadata_entry(id, key, value)?;
+    reporter.success(format!("Set {}={} on credential {}", key, stored, id));
+    Ok(())
+}
+
+/// Remove one metadata entry from a credential
+pub fn credentials_meta_unset_command(id: &str, key: &str, reporter: &dyn Reporter) -> Result<()> {
+    if CredentialStore::new()?.remove_metadata_entry(id, key)? {
+        reporter.success(format!("Removed {} from credential {}", key, id));
+    } else {
+        reporter.info(format!("Credential {} has no '{}' metadata", id, key));
+    }
+    Ok(())
+}
+
+/// Clear all credentials, or only those of `template`
+pub fn credentials_clear_command(
+    template: Option<&str>,
+    yes: bool,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    if let Some(template) = template {
+        let template_type = get_template_type(template)?;
+        let credential_store = CredentialStore::new()?;
+        let count = credential_store
+            .store
+            .find_by_template_type(&template_type)?
+            .len();
+        if count == 0 {
+            reporter.info(format!("No {} credentials to clear.", template_type));
+            return Ok(());
+        }
+        let prompt = format!("Remove {} {} credential(s)?", count, template_type);
+        if !yes && !confirm_action(&prompt, false)? {
+            return Ok(());
+        }
+        let removed = credential_store.clear_template_credentials(&template_type)?;
+        reporter.success(format!(
+            "Cleared {} {} credential(s)",
+            removed, template_type
+        ));
+        return Ok(());
+    }
+
+    if !yes && !confirm_action("Clear all saved credentials?", false)? {
+        return Ok(());
+    }
+
+    let _credentials_dir = get_credentials_dir();
+    let credential_store = CredentialStore::new()?;
 
     credential_store.clear_credentials()?;
 
-    println!("{} Cleared all credentials!", style("✓").green().bold());
+    reporter.success("Cleared all credentials!");
 
     Ok(())
 }
 
+/// Delete credentials not used within `unused_for`
+pub fn credentials_prune_command(
+    unused_for: chrono::Duration,
+    yes: bool,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let credential_store = CredentialStore::new()?;
+    let candidates = credential_store.unused_credentials(unused_for)?;
+    if candidates.is_empty() {
+        reporter.info("No unused credentials to prune.");
+        return Ok(());
+    }
+
+    reporter.info(format!("Credentials to delete ({}):", candidates.len()));
+    for credential in &candidates {
+        reporter.info(format!(
+            "  {} ({}, last used: {})",
+            credential.name(),
+            credential.template_type(),
+            credential.last_used_at().unwrap_or("never")
+        ));
+    }
+    let prompt = format!("Delete {} credential(s)?", candidates.len());
+    if !yes && !confirm_action(&prompt, false)? {
+        return Ok(());
+    }
+
+    let deleted = credential_store.prune_unused(&candidates)?;
+    reporter.success(format!("Pruned {} credential(s)", deleted.len()));
+    Ok(())
+}
+
 // ── config ───────────────────────────────────────────────────────────────────
 
 /// View / edit persistent preferences.
@@ -793,6 +3021,90 @@ pub fn config_command(cfg: &cli::ConfigArgs) -> Result<()> {
     Ok(())
 }
 
+/// `ccs config co-authored on|off`: toggle attribution in the settings file
+pub fn co_authored_command(
+    settings_path: &Path,
+    on: bool,
+    history: &History,
+    force: bool,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let mut settings = ClaudeSettings::from_file(settings_path)?;
+    settings.set_co_authored(on);
+    let state = if on { "on" } else { "off" };
+    write_settings_recorded(
+        &format!("co-authored {}", state),
+        settings_path,
+        &settings,
+        history,
+        force,
+    )?;
+    reporter.success(format!(
+        "Co-authored-by turned {} in {}",
+        state,
+        settings_path.display()
+    ));
+    Ok(())
+}
+
+/// `ccs config set-env KEY=VALUE`: set one env variable in the settings file
+pub fn set_env_command(
+    settings_path: &Path,
+    key: &str,
+    value: &str,
+    history: &History,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let mut settings = ClaudeSettings::from_file(settings_path)?;
+    if crate::settings::is_sensitive_env_key(key) {
+        reporter.warn(format!(
+            "{} holds a secret; it is stored in plain text in {}",
+            key,
+            settings_path.display()
+        ));
+    }
+    let previous = settings.set_env_var(key, value);
+    write_settings_recorded(
+        &format!("set-env {}", key),
+        settings_path,
+        &settings,
+        history,
+        false,
+    )?;
+    let verb = if previous.is_some() { "Updated" } else { "Set" };
+    reporter.success(format!("{} {} in {}", verb, key, settings_path.display()));
+    Ok(())
+}
+
+/// `ccs config unset-env KEY`: drop one env variable from the settings file
+pub fn unset_env_command(
+    settings_path: &Path,
+    key: &str,
+    history: &History,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let mut settings = ClaudeSettings::from_file(settings_path)?;
+    if !settings.remove_env_key(key) {
+        reporter.info(format!(
+            "{} {} is not set in {}",
+            style("•").cyan(),
+            key,
+            settings_path.display()
+        ));
+        return Ok(());
+    }
+    // Removing the last key empties the file on purpose, so no --force
+    write_settings_recorded(
+        &format!("unset-env {}", key),
+        settings_path,
+        &settings,
+        history,
+        true,
+    )?;
+    reporter.success(format!("Removed {} from {}", key, settings_path.display()));
+    Ok(())
+}
+
 fn config_interactive(prefs: &mut Prefs) -> Result<()> {
     let options = vec![
         "Edit default effort",
@@ -868,8 +3180,8 @@ fn print_config(prefs: &Prefs) {
 // ── current ──────────────────────────────────────────────────────────────────
 
 /// Show the currently-active provider detected from settings.json.
-pub fn current_command() -> Result<()> {
-    let settings_path = get_settings_path(None);
+pub fn current_command(settings_path: Option<PathBuf>) -> Result<()> {
+    let settings_path = get_settings_path(settings_path);
     let settings = ClaudeSettings::from_file(&settings_path)?;
 
     println!("📍 {}", settings_path.display());
@@ -880,7 +3192,7 @@ pub fn current_command() -> Result<()> {
         .and_then(|e| e.get("ANTHROPIC_BASE_URL"))
         .cloned();
 
-    match detect_current_provider() {
+    match provider_of(&settings) {
         Some(tt) => println!("Provider: {}", tt),
         None => println!("Provider: {}", style("(unknown / custom)").yellow()),
     }
@@ -888,19 +3200,1770 @@ pub fn current_command() -> Result<()> {
     if let Some(m) = &settings.model {
         println!("Model:    {}", m);
     }
-    if let Some(env) = &settings.env
-        && let Some(k) = env
-            .get("ANTHROPIC_AUTH_TOKEN")
-            .or_else(|| env.get("ANTHROPIC_API_KEY"))
-    {
-        println!("Key:      {}", mask_api_key(k));
+    if let Some(k) = settings.stored_api_key() {
+        println!("Key:      {}", mask_api_key(k, MaskPolicy::DEFAULT));
     }
     if let Some(e) = &settings.effort_level {
         println!("Effort:   {}", e);
     }
+    println!(
+        "Co-author: {}",
+        if settings.co_authored() { "on" } else { "off" }
+    );
     if let Some(base) = &base_url {
         println!("Base URL: {}", base);
     }
 
     Ok(())
 }
+
+/// Show what Claude Code actually sees: global settings overlaid with the
+/// project's `.claude/settings.json`.
+pub fn current_effective_command() -> Result<()> {
+    let global_path = get_global_settings_path();
+    let local_path = get_local_settings_path();
+    let global = ClaudeSettings::from_file(&global_path)?;
+    let local = ClaudeSettings::from_file(&local_path)?;
+
+    println!("🌐 global: {}", global_path.display());
+    println!("📁 local:  {}", local_path.display());
+    let lines = effective_settings_lines(&global, &local);
+    if lines.is_empty() {
+        println!("{}", style("(no settings in either file)").dim());
+    }
+    for line in lines {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// Merge `global` and `local` with local taking priority.
+///
+/// Scalars come from `merge_with` (its `other` wins), but its env priority
+/// runs the other way, so env is re-merged with local keys on top.
+pub fn effective_settings(global: &ClaudeSettings, local: &ClaudeSettings) -> ClaudeSettings {
+    let mut effective = global.clone().merge_with(local.clone());
+    effective.env = match (global.env.clone(), local.env.clone()) {
+        (Some(mut env), Some(overrides)) => {
+            env.extend(overrides);
+            Some(env)
+        }
+        (env, overrides) => overrides.or(env),
+    };
+    effective
+}
+
+/// One line per effective field, annotated with the file it came from.
+/// Env vars are listed per key; sensitive values are masked.
+pub fn effective_settings_lines(global: &ClaudeSettings, local: &ClaudeSettings) -> Vec<String> {
+    let object = |s: &ClaudeSettings| match serde_json::to_value(s) {
+        Ok(serde_json::Value::Object(map)) => map,
+        _ => serde_json::Map::new(),
+    };
+    let (global_fields, local_fields) = (object(global), object(local));
+    let effective = effective_settings(global, local);
+
+    let mut lines = Vec::new();
+    let mut env: Vec<_> = effective.env.iter().flatten().collect();
+    env.sort();
+    for (key, value) in env {
+        let from_local = local.env.as_ref().is_some_and(|e| e.contains_key(key));
+        let shown = if crate::settings::is_sensitive_env_key(key) {
+            mask_api_key(value, MaskPolicy::DEFAULT)
+        } else {
+            value.clone()
+        };
+        lines.push(format!(
+            "env.{} = {}  {}",
+            key,
+            shown,
+            style(if from_local { "(local)" } else { "(global)" }).dim()
+        ));
+    }
+
+    for (field, value) in object(&effective) {
+        if field == "env" {
+            continue;
+        }
+        let source = match (
+            local_fields.contains_key(&field),
+            global_fields.contains_key(&field),
+        ) {
+            (true, true) if matches!(field.as_str(), "permissions" | "hooks") => "(local + global)",
+            (true, _) => "(local)",
+            _ => "(global)",
+        };
+        lines.push(format!("{} = {}  {}", field, value, style(source).dim()));
+    }
+    lines
+}
+
+// ── completions ──────────────────────────────────────────────────────────────
+
+/// Print the completion script for `shell`, or install it under the home
+/// directory
+fn completions_command(
+    shell: Option<completions::Shell>,
+    install: bool,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let shell = shell
+        .or_else(completions::Shell::from_env)
+        .ok_or_else(|| anyhow!("Couldn't tell the shell from $SHELL; pass bash, zsh or fish"))?;
+    let cmd = <crate::Cli as clap::CommandFactory>::command();
+    if !install {
+        print!("{}", completions::generate(shell, &cmd));
+        return Ok(());
+    }
+
+    let home = dirs::home_dir().ok_or_else(|| anyhow!("Couldn't find the home directory"))?;
+    let installed = completions::install(shell, &cmd, &home, |path| {
+        confirm_action(&format!("{} exists. Overwrite it?", path.display()), false)
+    })?;
+    match installed {
+        Some(path) => {
+            reporter.success(format!(
+                "Installed {} completions to {}",
+                shell,
+                path.display()
+            ));
+            if let Some(hint) = shell.activation_hint(&path) {
+                reporter.info(format!("{} {}", style("•").cyan(), hint));
+            }
+        }
+        None => reporter.info("Left the existing completions file unchanged"),
+    }
+    Ok(())
+}
+
+// ── doctor ───────────────────────────────────────────────────────────────────
+
+/// Locations inspected by `ccs doctor`.
+pub struct DoctorPaths {
+    pub active_settings: PathBuf,
+    pub local_settings: PathBuf,
+    pub global_settings: PathBuf,
+    pub snapshots_dir: PathBuf,
+    pub credentials_dir: PathBuf,
+}
+
+impl DoctorPaths {
+    /// The paths ccs would actually use from the current directory.
+    pub fn detect() -> Self {
+        Self {
+            active_settings: get_settings_path(None),
+            local_settings: get_local_settings_path(),
+            global_settings: get_global_settings_path(),
+            snapshots_dir: get_snapshots_dir(),
+            credentials_dir: get_credentials_dir(),
+        }
+    }
+}
+
+/// Diagnose the environment: settings files, env vars, stores, backups.
+pub fn doctor_command() -> Result<()> {
+    println!("🩺 ccs doctor");
+    for line in doctor_report(&DoctorPaths::detect()) {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// Build the doctor report. Read-only: nothing is created or modified.
+pub fn doctor_report(paths: &DoctorPaths) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    lines.push(String::new());
+    lines.push(style("Settings").bold().to_string());
+    for (label, path) in [
+        ("local", &paths.local_settings),
+        ("global", &paths.global_settings),
+    ] {
+        let active = if path == &paths.active_settings {
+            " (active)"
+        } else {
+            ""
+        };
+        let line = match settings_file_status(path) {
+            Ok(true) => status_indicator(true, &format!("{}{}: {}", label, active, path.display())),
+            Ok(false) => status_indicator(
+                false,
+                &format!("{}{}: {} (not found)", label, active, path.display()),
+            ),
+            Err(e) => status_indicator(false, &format!("{}{}: {}", label, active, e)),
+        };
+        lines.push(format!("  {}", line));
+
+        let backup = path.with_extension("json.backup");
+        if backup.exists() {
+            lines.push(format!(
+                "    {}",
+                status_indicator(true, &format!("backup: {}", backup.display()))
+            ));
+        }
+    }
+
+    lines.push(String::new());
+    lines.push(style("Environment").bold().to_string());
+    let env_vars = relevant_env_vars();
+    if env_vars.is_empty() {
+        lines.push("  (no ANTHROPIC_* or provider API key variables set)".to_string());
+    }
+    for (name, value) in env_vars {
+        let shown = if name.contains("KEY") || name.contains("TOKEN") {
+            mask_api_key(&value, MaskPolicy::DEFAULT)
+        } else {
+            value
+        };
+        lines.push(format!("  {} = {}", name, shown));
+    }
+
+    lines.push(String::new());
+    lines.push(style("Storage").bold().to_string());
+    let snapshot_count = SnapshotStore::new(paths.snapshots_dir.clone())
+        .list()
+        .map(|s| s.len())
+        .unwrap_or(0);
+    lines.push(format!(
+        "  {}",
+        status_indicator(
+            paths.snapshots_dir.exists(),
+            &format!(
+                "snapshots: {} ({} snapshots)",
+                paths.snapshots_dir.display(),
+                snapshot_count
+            ),
+        )
+    ));
+    let credential_count = count_json_files(&paths.credentials_dir);
+    lines.push(format!(
+        "  {}",
+        status_indicator(
+            paths.credentials_dir.exists(),
+            &format!(
+                "credentials: {} ({} credentials)",
+                paths.credentials_dir.display(),
+                credential_count
+            ),
+        )
+    ));
+
+    lines
+}
+
+/// `Ok(true)` if the file exists and parses, `Ok(false)` if missing.
+fn settings_file_status(path: &Path) -> Result<bool> {
+    if !path.exists() {
+        return Ok(false);
+    }
+    ClaudeSettings::from_file(path)?;
+    Ok(true)
+}
+
+/// ANTHROPIC_* vars plus every template's API key variables that are set.
+fn relevant_env_vars() -> Vec<(String, String)> {
+    let provider_vars: Vec<&str> = get_all_templates()
+        .iter()
+        .flat_map(|t| get_template_instance(t).env_var_names())
+        .collect();
+    let mut vars: Vec<(String, String)> = std::env::vars()
+        .filter(|(name, _)| {
+            name.starts_with("ANTHROPIC_") || provider_vars.contains(&name.as_str())
+        })
+        .collect();
+    vars.sort();
+    vars
+}
+
+fn count_json_files(dir: &Path) -> usize {
+    std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .filter(|e| e.path().extension().and_then(|s| s.to_str()) == Some("json"))
+                .count()
+        })
+        .unwrap_or(0)
+}
+
+// ── undo / redo ──────────────────────────────────────────────────────────────
+
+/// Revert the settings file to its state before the last apply.
+pub fn undo_command(reporter: &dyn Reporter) -> Result<()> {
+    match History::open_default().undo()? {
+        Some(apply) => reporter.success(format!(
+            "Undid '{}' ({}) — restored {}",
+            apply.label,
+            apply.at,
+            apply.settings_path.display()
+        )),
+        None => reporter.info("Nothing to undo."),
+    }
+    Ok(())
+}
+
+/// Re-apply the last undone apply.
+pub fn redo_command(reporter: &dyn Reporter) -> Result<()> {
+    match History::open_default().redo()? {
+        Some(apply) => reporter.success(format!(
+            "Redid '{}' — wrote {}",
+            apply.label,
+            apply.settings_path.display()
+        )),
+        None => reporter.info("Nothing to redo."),
+    }
+    Ok(())
+}
+
+// ── repair ───────────────────────────────────────────────────────────────────
+
+/// Report unparseable snapshot/credential files and offer to quarantine them.
+pub fn repair_command(yes: bool, reporter: &dyn Reporter) -> Result<()> {
+    let mut findings = repair::scan_snapshots(&get_snapshots_dir())?;
+    findings.extend(repair::scan_credentials(&get_credentials_dir())?);
+
+    if findings.is_empty() {
+        reporter.success("No corrupt snapshot or credential files found.");
+        return Ok(());
+    }
+
+    println!(
+        "{} Found {} unreadable file(s):",
+        style("⚠").yellow().bold(),
+        findings.len()
+    );
+    for finding in &findings {
+        println!(
+            "  {} {}\n    {}",
+            style(finding.kind).bold(),
+            finding.path.display(),
+            style(&finding.error).dim()
+        );
+    }
+
+    if !yes
+        && !confirm_action(
+            &format!("Move them to a '{}' folder?", repair::QUARANTINE_DIR),
+            true,
+        )?
+    {
+        return Ok(());
+    }
+
+    for finding in &findings {
+        let target = finding.quarantine()?;
+        reporter.success(format!("Moved to {}", target.display()));
+    }
+    Ok(())
+}
+
+// ── perms ────────────────────────────────────────────────────────────────────
+
+/// Add tools to the allow/ask/deny lists. A tool only ever lives in one list,
+/// so adding it to one removes it from the others.
+pub fn perms_add_command(
+    allow: &[String],
+    ask: &[String],
+    deny: &[String],
+    settings_path: &Option<PathBuf>,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    if allow.is_empty() && ask.is_empty() && deny.is_empty() {
+        return Err(anyhow!(
+            "Nothing to add. Use --allow, --ask or --deny with a tool name"
+        ));
+    }
+
+    let settings_path = get_settings_path(settings_path.clone());
+    let mut settings = ClaudeSettings::from_file(&settings_path)?;
+
+    let changes = [
+        (PermissionList::Allow, allow),
+        (PermissionList::Ask, ask),
+        (PermissionList::Deny, deny),
+    ];
+    for (list, tools) in changes {
+        for tool in tools {
+            let previous = settings.permissions.as_ref().and_then(|p| p.list_of(tool));
+            settings.set_tool_permission(tool, list);
+            match previous {
+                Some(prev) if prev != list => {
+                    reporter.success(format!("{} moved {} → {}", tool, prev, list))
+                }
+                _ => reporter.success(format!("{} → {}", tool, list)),
+            }
+        }
+    }
+
+    settings.to_file(&settings_path)?;
+    Ok(())
+}
+
+/// Print the allow/ask/deny lists from settings.json.
+pub fn perms_list_command(settings_path: &Option<PathBuf>) -> Result<()> {
+    let settings_path = get_settings_path(settings_path.clone());
+    let settings = ClaudeSettings::from_file(&settings_path)?;
+
+    println!("📍 {}", settings_path.display());
+
+    let Some(perms) = settings.permissions.as_ref() else {
+        println!("No permissions configured.");
+        return Ok(());
+    };
+
+    for (label, tools) in [
+        ("allow", &perms.allow),
+        ("ask", &perms.ask),
+        ("deny", &perms.deny),
+    ] {
+        let tools = tools.as_deref().unwrap_or_default();
+        if tools.is_empty() {
+            println!("{:<6} {}", style(label).bold(), style("(none)").dim());
+        } else {
+            println!("{:<6} {}", style(label).bold(), tools.join(", "));
+        }
+    }
+
+    Ok(())
+}
+
+/// Remove tools from every permission list.
+pub fn perms_remove_command(
+    tools: &[String],
+    settings_path: &Option<PathBuf>,
+    reporter: &dyn Reporter,
+) -> Result<()> {
+    let settings_path = get_settings_path(settings_path.clone());
+    let mut settings = ClaudeSettings::from_file(&settings_path)?;
+
+    let mut changed = false;
+    for tool in tools {
+        if settings.clear_permission(tool) {
+            changed = true;
+            reporter.success(format!("Removed {}", tool));
+        } else {
+            reporter.info(format!(
+                "{} {} is not in any permission list",
+                style("•").yellow(),
+                tool
+            ));
+        }
+    }
+
+    if changed {
+        settings.to_file(&settings_path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reporter::{BufferReporter, ConsoleReporter, Level};
+
+    fn temp_home(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ccs_{}_{}", name, uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn config_backup_applies_unless_no_backup() {
+        let home = temp_home("config_backup");
+        let path = home.join("settings.json");
+        let backup = path.with_extension("json.backup");
+        let snapshots = SnapshotStore::new(home.join("snapshots"));
+        let quiet = ConsoleReporter::new(true);
+        ClaudeSettings::new().to_file(&path).unwrap();
+
+        // A plain apply backs up; --no-backup skips it
+        let config = Config::default();
+        protect_settings(
+            &config.clone().with_backup_flags(false, true),
+            &path,
+            "zai",
+            &snapshots,
+            &quiet,
+        )
+        .unwrap();
+        assert!(!backup.exists());
+
+        protect_settings(
+            &config.with_backup_flags(false, false),
+            &path,
+            "zai",
+            &snapshots,
+            &quiet,
+        )
+        .unwrap();
+        assert!(backup.exists());
+        assert!(snapshots.list().unwrap().is_empty());
+
+        let config = Config::parse("auto-snapshot = true").unwrap();
+        protect_settings(&config, &path, "zai", &snapshots, &quiet).unwrap();
+        let saved = snapshots.list().unwrap();
+        assert_eq!(saved.len(), 1);
+        assert!(saved[0].name.starts_with("auto-"));
+
+        std::fs::remove_dir_all(&home).ok();
+    }
+
+    /// Answers the guided apply prompts from a script
+    struct ScriptedPrompts {
+        template: TemplateType,
+        key: &'static str,
+        variant: &'static str,
+        endpoint_id: Option<&'static str>,
+        confirm: bool,
+        endpoint_asked: bool,
+    }
+
+    impl ApplyPrompts for ScriptedPrompts {
+        fn select_template(&mut self) -> SelectorResult<TemplateType> {
+            Ok(self.template.clone())
+        }
+
+        fn select_variant(
+            &mut self,
+            _template_type: &TemplateType,
+            _remembered: Option<&str>,
+        ) -> SelectorResult<String> {
+            Ok(self.variant.to_string())
+        }
+
+        fn select_api_key(
+            &mut self,
+            _template_type: &TemplateType,
+        ) -> SelectorResult<crate::credentials::ApiKeyChoice> {
+            Ok(crate::credentials::ApiKeyChoice {
+                key: self.key.to_string(),
+                source: None,
+            })
+        }
+
+        fn select_endpoint_id(
+            &mut self,
+            _template_type: &TemplateType,
+        ) -> SelectorResult<Option<String>> {
+            self.endpoint_asked = true;
+            Ok(self.endpoint_id.map(str::to_string))
+        }
+
+        fn confirm_apply(&mut self) -> SelectorResult<bool> {
+            Ok(self.confirm)
+        }
+    }
+
+    #[test]
+    fn interactive_apply_follows_scripted_choices() {
+        let home = temp_home("interactive");
+        let path = home.join("settings.json");
+        let history = History::new(home.join("history.jsonl"));
+        let reporter = ConsoleReporter::with_writer(true, Box::new(std::io::sink()));
+        let env_of = |var: &str| {
+            ClaudeSettings::from_file(&path)
+                .unwrap()
+                .env
+                .and_then(|env| env.get(var).cloned())
+        };
+        let mut prompts = ScriptedPrompts {
+            template: TemplateType::DeepSeek,
+            key: "sk-deepseek-scripted",
+            variant: "",
+            endpoint_id: None,
+            confirm: false,
+            endpoint_asked: false,
+        };
+        let run = |prompts: &mut ScriptedPrompts| {
+            interactive_apply_command(
+                prompts,
+                &SnapshotScope::Common,
+                &path,
+                &Config::default(),
+                &history,
+                None,
+                &reporter,
+            )
+        };
+
+        // Declining the review writes nothing
+        run(&mut prompts).unwrap();
+        assert!(!path.exists());
+
+        prompts.confirm = true;
+        run(&mut prompts).unwrap();
+        assert!(!prompts.endpoint_asked);
+        assert_eq!(
+            env_of("ANTHROPIC_AUTH_TOKEN").as_deref(),
+            Some("sk-deepseek-scripted")
+        );
+        assert!(env_of("ANTHROPIC_BASE_URL").unwrap().contains("deepseek"));
+
+        // Templates that need an endpoint ask for it and use the answer
+        let mut prompts = ScriptedPrompts {
+            template: TemplateType::KatCoder,
+            key: "sk-kat-scripted",
+            variant: "kat-coder-air",
+            endpoint_id: Some("ep-scripted-1"),
+            confirm: true,
+            endpoint_asked: false,
+        };
+        run(&mut prompts).unwrap();
+        assert!(prompts.endpoint_asked);
+        assert!(
+            env_of("ANTHROPIC_BASE_URL")
+                .unwrap()
+                .contains("/endpoints/ep-scripted-1/")
+        );
+        // ... and the variant picked
+        assert_eq!(
+            env_of("ANTHROPIC_MODEL").as_deref(),
+            Some("KAT-Coder-Air-V1")
+        );
+        assert_eq!(history.stacks().unwrap().0.len(), 2);
+
+        std::fs::remove_dir_all(&home).ok();
+    }
+
+    #[test]
+    fn save_as_snapshot_keeps_the_applied_settings() {
+        let home = temp_home("save_as");
+        let path = home.join("settings.json");
+        let history = History::new(home.join("history.jsonl"));
+        let reporter = ConsoleReporter::with_writer(true, Box::new(std::io::sink()));
+        let store = || SnapshotStore::new(home.join("snapshots"));
+        let mut prompts = ScriptedPrompts {
+            template: TemplateType::DeepSeek,
+            key: "sk-deepseek-saved",
+            variant: "",
+            endpoint_id: None,
+            confirm: true,
+            endpoint_asked: false,
+        };
+        let mut run = |overwrite: bool| {
+            let save_as = SaveAsSnapshot {
+                name: "work".to_string(),
+                overwrite,
+                store: store(),
+            };
+            interactive_apply_command(
+                &mut prompts,
+                &SnapshotScope::Common,
+                &path,
+                &Config::default(),
+                &history,
+                Some(&save_as),
+                &reporter,
+            )
+        };
+
+        run(false).unwrap();
+        let snapshot = store().load_by_name("work").unwrap();
+        assert_eq!(snapshot.scope, SnapshotScope::All);
+        assert_eq!(
+            serde_json::to_value(&snapshot.settings).unwrap(),
+            serde_json::to_value(ClaudeSettings::from_file(&path).unwrap()).unwrap()
+        );
+
+        // Taken names are refused before anything is written
+        assert!(run(false).is_err());
+        assert_eq!(history.stacks().unwrap().0.len(), 1);
+
+        run(true).unwrap();
+        assert_eq!(store().list_names().unwrap(), ["work"]);
+        assert_ne!(store().load_by_name("work").unwrap().id, snapshot.id);
+
+        std::fs::remove_dir_all(&home).ok();
+    }
+
+    #[test]
+    fn set_env_and_unset_env_edit_the_file() {
+        let home = temp_home("set_env");
+        let path = home.join("settings.json");
+        let history = History::new(home.join("history.jsonl"));
+        let reporter = ConsoleReporter::with_writer(true, Box::new(std::io::sink()));
+        let env_json = || {
+            let content = std::fs::read_to_string(&path).unwrap();
+            serde_json::from_str::<serde_json::Value>(&content).unwrap()["env"].clone()
+        };
+
+        let (key, value) =
+            crate::utils::parse_env_assignment("ANTHROPIC_BASE_URL=https://a.example.com/x?y=1")
+                .unwrap();
+        assert_eq!(value, "https://a.example.com/x?y=1");
+        set_env_command(&path, &key, &value, &history, &reporter).unwrap();
+        set_env_command(&path, "API_TIMEOUT_MS", "600000", &history, &reporter).unwrap();
+        set_env_command(&path, "API_TIMEOUT_MS", "300000", &history, &reporter).unwrap();
+        assert_eq!(
+            env_json(),
+            serde_json::json!({
+                "ANTHROPIC_BASE_URL": "https://a.example.com/x?y=1",
+                "API_TIMEOUT_MS": "300000"
+            })
+        );
+        assert!(crate::utils::parse_env_assignment("ANTHROPIC_BASE_URL").is_err());
+        assert!(crate::utils::parse_env_assignment("=value").is_err());
+
+        unset_env_command(&path, "API_TIMEOUT_MS", &history, &reporter).unwrap();
+        unset_env_command(&path, "API_TIMEOUT_MS", &history, &reporter).unwrap();
+        unset_env_command(&path, "ANTHROPIC_BASE_URL", &history, &reporter).unwrap();
+        assert!(env_json().is_null());
+        assert_eq!(history.stacks().unwrap().0.len(), 5);
+
+        std::fs::remove_dir_all(&home).ok();
+    }
+
+    #[test]
+    fn subagent_model_sets_field_and_env() {
+        let template = get_template_instance(&TemplateType::DeepSeek);
+        let subagent_env = |settings: &ClaudeSettings| {
+            settings.env.as_ref().unwrap()[crate::settings::SUBAGENT_MODEL_ENV].clone()
+        };
+
+        let mut settings = template.create_settings("sk-test", &SnapshotScope::Common);
+        apply_subagent_model(&mut settings, Some("deepseek-chat"));
+        assert_eq!(settings.subagent_model.as_deref(), Some("deepseek-chat"));
+        assert_eq!(subagent_env(&settings), "deepseek-chat");
+
+        // Without the flag the template's small/fast model is used
+        let mut settings = template.create_settings("sk-test", &SnapshotScope::Common);
+        let small = small_fast_model(&settings).unwrap().to_string();
+        apply_subagent_model(&mut settings, None);
+        assert_eq!(settings.subagent_model.as_deref(), Some(small.as_str()));
+        assert_eq!(subagent_env(&settings), small);
+    }
+
+    #[test]
+    fn quiet_apply_prints_nothing() {
+        use crate::reporter::tests::SharedBuf;
+
+        let home = temp_home("quiet");
+        let path = home.join("settings.json");
+        let history = History::new(home.join("history.jsonl"));
+        let apply = |quiet: bool, buf: &SharedBuf| {
+            apply_settings_document(
+                r#"{"model": "m"}"#,
+                "stdin",
+                &SnapshotScope::Common,
+                &DocumentOverrides::default(),
+                &path,
+                &Config::default(),
+                false,
+                false,
+                None,
+                &history,
+                &ConsoleReporter::with_writer(quiet, Box::new(buf.clone())),
+            )
+        };
+
+        let buf = SharedBuf::default();
+        apply(true, &buf).unwrap();
+        assert!(buf.contents().is_empty());
+        assert_eq!(
+            ClaudeSettings::from_file(&path).unwrap().model.as_deref(),
+            Some("m")
+        );
+
+        let buf = SharedBuf::default();
+        apply(false, &buf).unwrap();
+        assert!(buf.contents().contains("Applied settings from stdin"));
+
+        std::fs::remove_dir_all(&home).ok();
+    }
+
+    #[test]
+    fn empty_settings_do_not_overwrite_a_populated_file() {
+        let home = temp_home("empty_write");
+        let path = home.join("settings.json");
+        let history = History::new(home.join("history.jsonl"));
+        let mut populated = ClaudeSettings::new();
+        populated.model = Some("opus".to_string());
+        populated.to_file(&path).unwrap();
+
+        let err = write_settings_recorded("test", &path, &ClaudeSettings::new(), &history, false)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("--force"), "{err}");
+        assert_eq!(
+            ClaudeSettings::from_file(&path).unwrap().model.as_deref(),
+            Some("opus")
+        );
+
+        assert!(check_empty_overwrite(&path, &ClaudeSettings::new(), true).is_ok());
+        assert!(
+            check_empty_overwrite(&home.join("missing.json"), &ClaudeSettings::new(), false)
+                .is_ok()
+        );
+
+        std::fs::remove_dir_all(&home).ok();
+    }
+
+    #[test]
+    fn apply_reports_success_to_any_reporter() {
+        let home = temp_home("buffer_reporter");
+        let path = home.join("settings.json");
+        let buffer = BufferReporter::new();
+
+        apply_settings_document(
+            r#"{"model": "m"}"#,
+            "stdin",
+            &SnapshotScope::Common,
+            &DocumentOverrides::default(),
+            &path,
+            &Config::default(),
+            false,
+            false,
+            None,
+            &History::new(home.join("history.jsonl")),
+            &buffer,
+        )
+        .unwrap();
+
+        let successes = buffer.messages_at(Level::Success);
+        assert_eq!(successes.len(), 1);
+        assert!(successes[0].contains("Applied settings from stdin"));
+
+        std::fs::remove_dir_all(&home).ok();
+    }
+
+    #[test]
+    fn settings_documents_honor_the_apply_flags() {
+        let home = temp_home("document_flags");
+        let path = home.join("settings.json");
+        let history = History::new(home.join("history.jsonl"));
+        let store = SnapshotStore::new(home.join("snapshots"));
+        let save_as = SaveAsSnapshot {
+            name: "from-file".to_string(),
+            overwrite: false,
+            store: store.clone(),
+        };
+        let overrides = DocumentOverrides {
+            output_style: Some("concise".to_string()),
+            effort: Some("low".to_string()),
+            co_authored: Some(false),
+            ..Default::default()
+        };
+        let apply = |diff_only: bool, buffer: &BufferReporter| {
+            apply_settings_document(
+                r#"{"model": "m"}"#,
+                "provider.json",
+                &SnapshotScope::Common,
+                &overrides,
+                &path,
+                &Config::default(),
+                diff_only,
+                diff_only,
+                Some(&save_as),
+                &history,
+                buffer,
+            )
+            .unwrap()
+        };
+
+        // --print-diff-only previews without writing or saving
+        let preview = BufferReporter::new();
+        apply(true, &preview);
+        assert!(!path.exists());
+        assert!(store.list().unwrap().is_empty());
+        assert!(preview.messages_at(Level::Diff)[0].contains("concise"));
+
+        let buffer = BufferReporter::new();
+        apply(false, &buffer);
+        let written = ClaudeSettings::from_file(&path).unwrap();
+        assert_eq!(written.output_style.as_deref(), Some("concise"));
+        assert_eq!(written.effort_level.as_deref(), Some("low"));
+        assert!(!written.co_authored());
+        assert!(buffer.messages_at(Level::Success)[0].contains("from provider.json"));
+        assert_eq!(
+            store
+                .load_by_name("from-file")
+                .unwrap()
+                .settings
+                .model
+                .as_deref(),
+            Some("m")
+        );
+
+        std::fs::remove_dir_all(&home).ok();
+    }
+
+    #[test]
+    fn verbose_apply_logs_the_settings_path() {
+        use crate::reporter::tests::SharedBuf;
+
+        let home = temp_home("verbose");
+        let path = home.join("settings.json");
+        let history = History::new(home.join("history.jsonl"));
+        let apply = |verbosity: u8| {
+            let buf = SharedBuf::default();
+            apply_settings_document(
+                r#"{"model": "m"}"#,
+                "stdin",
+                &SnapshotScope::Common,
+                &DocumentOverrides::default(),
+                &path,
+                &Config::default(),
+                false,
+                false,
+                None,
+                &history,
+                &ConsoleReporter::with_writer(false, Box::new(buf.clone()))
+                    .with_verbosity(verbosity),
+            )
+            .unwrap();
+            buf.contents()
+        };
+
+        let needle = format!("Settings file: {}", path.display());
+        assert!(!apply(0).contains(&needle));
+        assert!(!apply(1).contains(&needle));
+        assert!(apply(2).contains(&needle));
+
+        std::fs::remove_dir_all(&home).ok();
+    }
+
+    #[test]
+    fn print_diff_only_lists_just_the_changes() {
+        let template = get_template_instance(&TemplateType::DeepSeek);
+        let current = template.create_settings("sk-deepseek-current", &SnapshotScope::Common);
+        let scope = SnapshotScope::Common;
+
+        let same = MergeStrategy::Merge.apply(current.clone(), current.clone(), &scope);
+        assert_eq!(format_changes(&current, &same), "No changes\n");
+
+        let mut incoming = current.clone();
+        incoming.model = Some("deepseek-reasoner".into());
+        let changed = MergeStrategy::Merge.apply(current.clone(), incoming, &scope);
+        let report = format_changes(&current, &changed);
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines.len(), 1, "{}", report);
+        assert!(lines[0].contains("model"));
+        assert!(lines[0].ends_with("deepseek-reasoner"));
+        assert!(!report.contains("sk-deepseek-current"));
+    }
+
+    /// `apply_layered` with the flags a scripted `ccs apply --yes --api-key`
+    /// run would pass
+    /// Flags for a scripted layer: no prompts, the key given
+    fn layer_options() -> ApplyOptions {
+        ApplyOptions {
+            cli: true,
+            effort: Some("high".to_string()),
+            api_key: Some("sk-deepseek-layer".to_string()),
+            no_co_author: true,
+            ..Default::default()
+        }
+    }
+
+    fn run_layered(
+        targets: &[&str],
+        settings_path: &Path,
+        store: &SnapshotStore,
+        history: &History,
+        options: &ApplyOptions,
+    ) -> Result<()> {
+        apply_layered(
+            targets,
+            store,
+            history,
+            settings_path,
+            &Config::default(),
+            true,
+            options,
+            &ConsoleReporter::with_writer(true, Box::new(std::io::sink())),
+        )
+    }
+
+    #[test]
+    fn previews_never_move_an_invalid_settings_file() {
+        let home = temp_home("preview_corrupt");
+        let settings_path = home.join("settings.json");
+        std::fs::write(&settings_path, "{ not json").unwrap();
+        let store = SnapshotStore::new(home.join("snapshots"));
+        let history = History::new(home.join("history.jsonl"));
+        let reporter = ConsoleReporter::with_writer(true, Box::new(std::io::sink()));
+
+        let err = load_settings_for_apply(&settings_path, true, true, &reporter).unwrap_err();
+        assert!(err.to_string().contains("parse"), "{}", err);
+        for (dry_run, diff_only) in [(true, false), (false, true)] {
+            assert!(
+                run_layered(
+                    &["deepseek", "deepseek"],
+                    &settings_path,
+                    &store,
+                    &history,
+                    &ApplyOptions {
+                        dry_run,
+                        diff_only,
+                        ..layer_options()
+                    },
+                )
+                .is_err()
+            );
+        }
+
+        assert_eq!(
+            std::fs::read_to_string(&settings_path).unwrap(),
+            "{ not json"
+        );
+        assert_eq!(std::fs::read_dir(&home).unwrap().count(), 1);
+
+        std::fs::remove_dir_all(&home).ok();
+    }
+
+    #[test]
+    fn permission_rules_land_on_top_of_preserved_permissions() {
+        let home = temp_home("preserve_rules");
+        let settings_path = home.join("settings.json");
+        let store = SnapshotStore::new(home.join("snapshots"));
+        let history = History::new(home.join("history.jsonl"));
+        let reporter = ConsoleReporter::with_writer(true, Box::new(std::io::sink()));
+        let options = ApplyOptions {
+            scope: SnapshotScope::All,
+            permission_rules: PermissionRules {
+                deny: vec!["Bash".to_string()],
+                ..Default::default()
+            },
+            preserve: vec![PreserveField::Permissions],
+            ..layer_options()
+        };
+        let mut existing = ClaudeSettings::new();
+        existing.set_tool_permission("Read", PermissionList::Allow);
+        store
+            .save(&snapshots::Snapshot::new(
+                "base".into(),
+                ClaudeSettings::new(),
+                SnapshotScope::All,
+                None,
+            ))
+            .unwrap();
+        let assert_kept_and_denied = || {
+            let permissions = ClaudeSettings::from_file(&settings_path)
+                .unwrap()
+                .permissions
+                .unwrap();
+            assert_eq!(permissions.list_of("Read"), Some(PermissionList::Allow));
+            assert_eq!(permissions.list_of("Bash"), Some(PermissionList::Deny));
+        };
+
+        existing.to_file(&settings_path).unwrap();
+        apply_snapshot_command(
+            store.load_by_name("base").unwrap(),
+            &store,
+            &history,
+            &settings_path,
+            &Config::default(),
+            true,
+            &options,
+            &reporter,
+        )
+        .unwrap();
+        assert_kept_and_denied();
+
+        existing.to_file(&settings_path).unwrap();
+        run_layered(
+            &["base", "deepseek"],
+            &settings_path,
+            &store,
+            &history,
+            &options,
+        )
+        .unwrap();
+        assert_kept_and_denied();
+
+        std::fs::remove_dir_all(&home).ok();
+    }
+
+    #[test]
+    fn layered_apply_merges_snapshot_then_template() {
+        let home = temp_home("layered");
+        let settings_path = home.join("settings.json");
+        let store = SnapshotStore::new(home.join("snapshots"));
+        let history = History::new(home.join("history.jsonl"));
+
+        let mut base = ClaudeSettings::new();
+        base.env = Some(HashMap::from([
+            ("TEAM_PROXY".to_string(), "http://proxy:8080".to_string()),
+            (
+                "ANTHROPIC_BASE_URL".to_string(),
+                "https://base.example.com".to_string(),
+            ),
+        ]));
+        store
+            .save(&snapshots::Snapshot::new(
+                "base".into(),
+                base,
+                SnapshotScope::All,
+                None,
+            ))
+            .unwrap();
+        ClaudeSettings::new().to_file(&settings_path).unwrap();
+        let untouched = std::fs::read_to_string(&settings_path).unwrap();
+
+        // Previews write nothing: no settings, no history
+        for (dry_run, diff_only) in [(true, false), (false, true)] {
+            run_layered(
+                &["base", "deepseek"],
+                &settings_path,
+                &store,
+                &history,
+                &ApplyOptions {
+                    dry_run,
+                    diff_only,
+                    ..layer_options()
+                },
+            )
+            .unwrap();
+            assert_eq!(std::fs::read_to_string(&settings_path).unwrap(), untouched);
+            assert!(!history.path().exists());
+        }
+
+        assert!(
+            run_layered(
+                &["missing", "deepseek"],
+                &settings_path,
+                &store,
+                &history,
+                &layer_options(),
+            )
+            .is_err()
+        );
+        assert_eq!(std::fs::read_to_string(&settings_path).unwrap(), untouched);
+
+        // Snapshot first, template on top: one write, the template wins
+        run_layered(
+            &["base", "deepseek"],
+            &settings_path,
+            &store,
+            &history,
+            &layer_options(),
+        )
+        .unwrap();
+        let env = ClaudeSettings::from_file(&settings_path)
+            .unwrap()
+            .env
+            .unwrap();
+        assert_eq!(env["TEAM_PROXY"], "http://proxy:8080");
+        assert_eq!(
+            env["ANTHROPIC_BASE_URL"],
+            get_template_instance(&TemplateType::DeepSeek)
+                .api_base()
+                .unwrap()
+        );
+        assert!(env.values().any(|value| value == "sk-deepseek-layer"));
+        assert_eq!(history.stacks().unwrap().0.len(), 1);
+
+        std::fs::remove_dir_all(&home).ok();
+    }
+
+    #[test]
+    fn thinking_flags_are_rejected_for_snapshot_targets() {
+        let home = temp_home("thinking_snapshot");
+        let options = ApplyOptions {
+            cli: true,
+            thinking: ThinkingOverrides::from_flags(true, false, None, None),
+            ..Default::default()
+        };
+        let err = apply_command(
+            &["mysnap"],
+            &Some(home.join("settings.json")),
+            &Config::default(),
+            true,
+            &options,
+            &BufferReporter::new(),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("Snapshot 'mysnap'"), "{err}");
+        assert!(err.contains("--thinking"), "{err}");
+        assert!(!home.join("settings.json").exists());
+
+        // Layering only snapshots: nothing would take the flags either
+        let store = SnapshotStore::new(home.join("snapshots"));
+        let history = History::new(home.join("history.jsonl"));
+        let err = apply_layered(
+            &["base", "extra"],
+            &store,
+            &history,
+            &home.join("settings.json"),
+            &Config::default(),
+            true,
+            &options,
+            &BufferReporter::new(),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("base + extra"), "{err}");
+
+        std::fs::remove_dir_all(&home).ok();
+    }
+
+    #[test]
+    fn tampered_snapshot_warns_through_the_reporter() {
+        let home = temp_home("tampered");
+        let store = SnapshotStore::new(home.join("snapshots"));
+        let snapshot = snapshots::Snapshot::new(
+            "work".into(),
+            ClaudeSettings {
+                model: Some("glm-5".to_string()),
+                ..Default::default()
+            },
+            SnapshotScope::All,
+            None,
+        );
+        store.save(&snapshot).unwrap();
+
+        let reporter = BufferReporter::new();
+        load_snapshot_checked(&store, "work", &reporter).unwrap();
+        assert!(reporter.messages_at(Level::Warn).is_empty());
+
+        let path = store.snapshot_path(&snapshot.id);
+        let edited = std::fs::read_to_string(&path)
+            .unwrap()
+            .replace("glm-5", "glm-9");
+        std::fs::write(&path, edited).unwrap();
+        // A fresh store: the listing cache only notices directory changes
+        let store = SnapshotStore::new(home.join("snapshots"));
+        load_snapshot_checked(&store, "work", &reporter).unwrap();
+        let warnings = reporter.messages_at(Level::Warn);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("checksum"), "{}", warnings[0]);
+
+        std::fs::remove_dir_all(&home).ok();
+    }
+
+    #[test]
+    fn deny_rule_overrides_the_template_allow_list() {
+        let scope = SnapshotScope::Common;
+        let template = get_template_instance(&TemplateType::DeepSeek)
+            .create_settings("sk-deepseek", &scope.template_scope());
+        let mut merged = MergeStrategy::Merge.apply(ClaudeSettings::new(), template, &scope);
+
+        PermissionRules {
+            allow: vec!["Bash".to_string(), "WebFetch".to_string()],
+            deny: vec!["Bash".to_string()],
+            ..Default::default()
+        }
+        .apply_to(&mut merged);
+
+        let permissions = merged.permissions.unwrap();
+        let allow = permissions.allow.unwrap_or_default();
+        assert!(!allow.contains(&"Bash".to_string()));
+        assert!(allow.contains(&"WebFetch".to_string()));
+        assert!(permissions.deny.unwrap().contains(&"Bash".to_string()));
+    }
+
+    #[test]
+    fn allow_web_search_drops_the_default_denial() {
+        let scope = SnapshotScope::Common;
+        let template = get_template_instance(&TemplateType::Kimi)
+            .create_settings("sk-kimi", &scope.template_scope());
+        let mut merged = MergeStrategy::Merge.apply(ClaudeSettings::new(), template, &scope);
+        let rules = PermissionRules {
+            allow_web_search: true,
+            ..Default::default()
+        };
+
+        rules.apply_to(&mut merged);
+        let permissions = merged.permissions.clone().unwrap();
+        assert_eq!(permissions.list_of(WEB_SEARCH_TOOL), None);
+        assert!(permissions.deny.is_none());
+        assert!(permissions.allow.unwrap().contains(&"Bash".to_string()));
+
+        // An explicit --deny still wins
+        let rules = PermissionRules {
+            deny: vec![WEB_SEARCH_TOOL.to_string()],
+            ..rules
+        };
+        rules.apply_to(&mut merged);
+        assert_eq!(
+            merged.permissions.unwrap().list_of(WEB_SEARCH_TOOL),
+            Some(PermissionList::Deny)
+        );
+    }
+
+    #[test]
+    fn preserve_keeps_existing_hooks_at_scope_all() {
+        let scope = SnapshotScope::All;
+        let mut existing = ClaudeSettings::new();
+        existing.hooks = Some(crate::settings::Hooks {
+            pre_command: Some(vec!["./check.sh".to_string()]),
+            post_command: None,
+        });
+        // A template that brings its own (empty) hooks
+        let empty = crate::settings::Hooks {
+            pre_command: None,
+            post_command: None,
+        };
+        let mut template = get_template_instance(&TemplateType::DeepSeek)
+            .create_settings("sk-deepseek", &scope.template_scope());
+        template.hooks = Some(empty.clone());
+
+        let mut merged = MergeStrategy::Merge.apply(existing.clone(), template, &scope);
+        assert_eq!(merged.hooks, Some(empty));
+
+        merged.preserve_from(
+            &existing,
+            &[PreserveField::Hooks, PreserveField::StatusLine],
+        );
+        let hooks = merged.hooks.unwrap();
+        assert_eq!(hooks.pre_command, Some(vec!["./check.sh".to_string()]));
+        assert!(merged.env.unwrap().values().any(|v| v == "sk-deepseek"));
+    }
+
+    #[test]
+    fn latest_applies_the_newest_snapshot() {
+        let home = temp_home("latest");
+        let settings_path = home.join("settings.json");
+        let store = SnapshotStore::new(home.join("snapshots"));
+        let history = History::new(home.join("history.jsonl"));
+        let reporter = BufferReporter::new();
+        assert!(latest_snapshot(&store).is_err());
+
+        // The newest one shares a template's name; --latest still means the snapshot
+        for (name, minutes_ago) in [("old-model", 30), ("deepseek", 1)] {
+            let mut settings = ClaudeSettings::new();
+            settings.model = Some(format!("{}-model", name));
+            let mut snapshot =
+                snapshots::Snapshot::new(name.to_string(), settings, SnapshotScope::All, None);
+            snapshot.created_at = (chrono::Utc::now() - chrono::Duration::minutes(minutes_ago))
+                .format("%Y-%m-%d %H:%M:%S UTC")
+                .to_string();
+            store.save(&snapshot).unwrap();
+        }
+
+        assert_eq!(latest_snapshot(&store).unwrap().name, "deepseek");
+        apply_latest_command(
+            &store,
+            &history,
+            &settings_path,
+            &Config::default(),
+            true,
+            &ApplyOptions {
+                scope: SnapshotScope::All,
+                ..Default::default()
+            },
+            &reporter,
+        )
+        .unwrap();
+        let applied = ClaudeSettings::from_file(&settings_path).unwrap();
+        assert_eq!(applied.model.as_deref(), Some("deepseek-model"));
+        assert!(applied.env.is_none());
+
+        std::fs::remove_dir_all(&home).ok();
+    }
+
+    #[test]
+    fn model_from_takes_the_other_snapshots_model() {
+        let home = temp_home("model-from");
+        let store = SnapshotStore::new(home.join("snapshots"));
+        let mut base = ClaudeSettings::new();
+        base.model = Some("glm-5.2".to_string());
+        base.env = Some(HashMap::from([
+            ("ANTHROPIC_MODEL".to_string(), "glm-5.2".to_string()),
+            ("KEEP_ME".to_string(), "1".to_string()),
+        ]));
+        let mut fast = ClaudeSettings::new();
+        fast.env = Some(HashMap::from([(
+            "ANTHROPIC_MODEL".to_string(),
+            "glm-4.5-air".to_string(),
+        )]));
+        for (name, settings) in [("base", base), ("fast", fast)] {
+            store
+                .save(&snapshots::Snapshot::new(
+                    name.to_string(),
+                    settings,
+                    SnapshotScope::All,
+                    None,
+                ))
+                .unwrap();
+        }
+
+        let model = snapshot_model(&store, "fast").unwrap();
+        let mut applied = store.load_by_name("base").unwrap().settings;
+        override_snapshot_model(&mut applied, &model);
+
+        assert_eq!(applied.model.as_deref(), Some("glm-4.5-air"));
+        let env = applied.env.unwrap();
+        assert_eq!(env["ANTHROPIC_MODEL"], "glm-4.5-air");
+        assert_eq!(env["KEEP_ME"], "1");
+        assert!(snapshot_model(&store, "missing").is_err());
+
+        std::fs::remove_dir_all(&home).ok();
+    }
+
+    #[test]
+    fn variant_alias_skips_the_variant_prompt() {
+        let mut prefs = Prefs::default();
+        let kat = TemplateType::KatCoder;
+
+        // Non-interactive so that reaching the prompt fails instead of blocking
+        let pro = resolve_variant_alias(&kat, "kat-coder-pro", None, &mut prefs, true).unwrap();
+        assert_eq!(pro, None);
+        assert_eq!(
+            get_template_instance_with_input(&kat, "kat-coder-pro").display_name(),
+            "KatCoder Pro (WanQing)"
+        );
+
+        let err = resolve_variant_alias(&kat, "kat-coder", None, &mut prefs, true).unwrap_err();
+        assert!(err.to_string().contains("requires a variant"));
+    }
+
+    #[test]
+    fn snap_preview_saves_nothing() {
+        let home = temp_home("snap_preview");
+        let path = home.join("settings.json");
+        let snapshots_dir = home.join("snapshots");
+        let store = SnapshotStore::new(snapshots_dir.clone());
+        let reporter = ConsoleReporter::with_writer(true, Box::new(std::io::sink()));
+        let mut settings = ClaudeSettings::new();
+        settings.model = Some("glm-5".into());
+        settings.env = Some(
+            [(
+                "ANTHROPIC_AUTH_TOKEN".to_string(),
+                "sk-preview-secret-value".to_string(),
+            )]
+            .into_iter()
+            .collect(),
+        );
+        settings.to_file(&path).unwrap();
+
+        snap_into_store(
+            &store,
+            "preview",
+            &SnapshotScope::Common,
+            &path,
+            &None,
+            false,
+            &None,
+            true,
+            &reporter,
+        )
+        .unwrap();
+        assert!(!snapshots_dir.exists() || std::fs::read_dir(&snapshots_dir).unwrap().count() == 0);
+
+        let captured = snapshots::capture_settings(settings, &SnapshotScope::Common);
+        let preview = format_snapshot_preview("preview", &SnapshotScope::Common, &captured);
+        assert!(preview.contains("glm-5"));
+        assert!(preview.contains("Env keys captured: 1"));
+        assert!(!preview.contains("sk-preview-secret-value"));
+
+        std::fs::remove_dir_all(&home).ok();
+    }
+
+    #[test]
+    fn snapshot_from_zai_template_has_glm_base_url() {
+        let snapshot =
+            snapshot_from_template("zai", "glm", &SnapshotScope::Common, None, false).unwrap();
+        let env = snapshot.settings.env.as_ref().unwrap();
+        assert_eq!(snapshot.name, "glm");
+        assert!(env["ANTHROPIC_BASE_URL"].contains("bigmodel.cn"));
+        // No key given: nothing empty is stored to overwrite the live key
+        assert!(!env.contains_key("ANTHROPIC_AUTH_TOKEN"));
+
+        let snapshot = snapshot_from_template(
+            "zai",
+            "glm",
+            &SnapshotScope::Env,
+            Some("sk-zai-1234567890"),
+            true,
+        )
+        .unwrap();
+        let env = snapshot.settings.env.as_ref().unwrap();
+        assert!(snapshot.settings.model.is_none());
+        assert_ne!(env["ANTHROPIC_AUTH_TOKEN"], "sk-zai-1234567890");
+        assert!(env["ANTHROPIC_AUTH_TOKEN"].ends_with("7890"));
+    }
+
+    #[test]
+    fn deepseek_over_kimi_reports_leftover_env() {
+        let kimi = get_template_instance_with_input(&TemplateType::Kimi, "k2")
+            .create_settings("sk-kimi", &SnapshotScope::Common);
+        let deepseek = get_template_instance(&TemplateType::DeepSeek)
+            .create_settings("sk-deepseek", &SnapshotScope::Common);
+
+        let leftovers = provider_leftovers(&kimi, &TemplateType::DeepSeek, &deepseek).unwrap();
+        assert_eq!(leftovers.previous, Some(TemplateType::Kimi));
+        // Kimi's key would sit next to DeepSeek's
+        assert!(leftovers.keys.contains(&"ANTHROPIC_API_KEY".to_string()));
+        // Keys DeepSeek sets itself are overwritten, not left over
+        assert!(!leftovers.keys.contains(&"ANTHROPIC_BASE_URL".to_string()));
+
+        // Re-applying the same provider, or unrelated vars, is fine
+        assert_eq!(
+            provider_leftovers(&deepseek, &TemplateType::DeepSeek, &deepseek),
+            None
+        );
+        let mut custom = ClaudeSettings::new();
+        custom.env = Some(HashMap::from([("MY_VAR".to_string(), "1".to_string())]));
+        assert_eq!(
+            provider_leftovers(&custom, &TemplateType::DeepSeek, &deepseek),
+            None
+        );
+
+        // Another provider's key var is caught even without its base URL
+        custom
+            .env
+            .as_mut()
+            .unwrap()
+            .insert("MOONSHOT_API_KEY".to_string(), "sk-moon".to_string());
+        let leftovers = provider_leftovers(&custom, &TemplateType::DeepSeek, &deepseek).unwrap();
+        assert_eq!(leftovers.previous, None);
+        assert_eq!(leftovers.keys, ["MOONSHOT_API_KEY"]);
+    }
+
+    #[test]
+    fn full_version_reports_formats() {
+        let short = format_version_info(false);
+        assert_eq!(short, format!("ccs {}\n", env!("CARGO_PKG_VERSION")));
+
+        let full = format_version_info(true);
+        assert!(full.starts_with(&short));
+        assert!(full.contains(env!("CARGO_PKG_VERSION")));
+        assert!(full.contains("credential format: v2"));
+        assert!(full.contains("snapshots:"));
+    }
+
+    #[test]
+    fn print_env_value_is_bare() {
+        let base = template_env_value(
+            &TemplateType::DeepSeek,
+            "deepseek",
+            "",
+            "ANTHROPIC_BASE_URL",
+        )
+        .unwrap();
+        assert_eq!(base, "https://api.deepseek.com/anthropic");
+
+        // The key variable is only produced when a key is known
+        assert!(
+            template_env_value(
+                &TemplateType::DeepSeek,
+                "deepseek",
+                "",
+                "ANTHROPIC_AUTH_TOKEN"
+            )
+            .is_err()
+        );
+        assert_eq!(
+            template_env_value(
+                &TemplateType::DeepSeek,
+                "deepseek",
+                "sk-x",
+                "ANTHROPIC_AUTH_TOKEN"
+            )
+            .unwrap(),
+            "sk-x"
+        );
+        assert!(template_env_value(&TemplateType::DeepSeek, "deepseek", "", "NOPE").is_err());
+    }
+
+    #[test]
+    fn stdin_settings_merge_into_existing_file() {
+        let home = temp_home("stdin");
+        let path = home.join("settings.json");
+        let mut existing = ClaudeSettings::new();
+        existing.model = Some("old-model".to_string());
+        existing.allow_tool("Bash");
+        existing.env = Some(HashMap::from([("KEEP_ME".to_string(), "1".to_string())]));
+        existing.to_file(&path).unwrap();
+
+        let piped = r#"{"model": "piped-model", "env": {"ANTHROPIC_BASE_URL": "https://x"}}"#;
+        let merged =
+            merge_settings_json(piped, &SnapshotScope::Common, &path, MergeStrategy::Merge)
+                .unwrap();
+
+        assert_eq!(merged.model.as_deref(), Some("piped-model"));
+        let env = merged.env.unwrap();
+        assert_eq!(env["KEEP_ME"], "1");
+        assert_eq!(env["ANTHROPIC_BASE_URL"], "https://x");
+        assert!(merged.permissions.is_some());
+
+        assert!(
+            merge_settings_json(
+                "{ not json",
+                &SnapshotScope::Common,
+                &path,
+                MergeStrategy::Merge
+            )
+            .is_err()
+        );
+
+        std::fs::remove_dir_all(&home).ok();
+    }
+
+    #[test]
+    fn dash_settings_path_round_trips_through_stdin_and_stdout() {
+        crate::utils::seed_stdin(r#"{"model": "old-model", "env": {"KEEP_ME": "1"}}"#);
+        let dash = Path::new("-");
+
+        let template = r#"{"model": "new-model", "env": {"ANTHROPIC_BASE_URL": "https://x"}}"#;
+        let merged =
+            merge_settings_json(template, &SnapshotScope::Common, dash, MergeStrategy::Merge)
+                .unwrap();
+        let mut out = Vec::new();
+        merged.to_writer(&mut out).unwrap();
+
+        let written: ClaudeSettings = serde_json::from_slice(&out).unwrap();
+        assert_eq!(written.model.as_deref(), Some("new-model"));
+        let env = written.env.unwrap();
+        assert_eq!(env["KEEP_ME"], "1");
+        assert_eq!(env["ANTHROPIC_BASE_URL"], "https://x");
+        // stdin is read once, so a second load sees the same document
+        assert_eq!(
+            ClaudeSettings::from_file(dash).unwrap().model.as_deref(),
+            Some("old-model")
+        );
+
+        // --save-as-snapshot keeps what was written, not stdin read back
+        let home = temp_home("dash_save_as");
+        let store = SnapshotStore::new(home.join("snapshots"));
+        let options = ApplyOptions {
+            save_as: Some(SaveAsSnapshot {
+                name: "piped".to_string(),
+                overwrite: false,
+                store: SnapshotStore::new(home.join("snapshots")),
+            }),
+            ..layer_options()
+        };
+        run_layered(
+            &["deepseek"],
+            dash,
+            &store,
+            &History::new(home.join("history.jsonl")),
+            &options,
+        )
+        .unwrap();
+        let saved = store.load_by_name("piped").unwrap().settings;
+        assert_ne!(saved.model.as_deref(), Some("old-model"));
+        let env = saved.env.unwrap();
+        assert_eq!(env["KEEP_ME"], "1");
+        assert!(env.values().any(|value| value == "sk-deepseek-layer"));
+
+        std::fs::remove_dir_all(&home).ok();
+    }
+
+    #[test]
+    fn effective_view_prefers_local_and_names_the_source() {
+        let mut global = ClaudeSettings::new();
+        global.model = Some("opus".to_string());
+        global.env = Some(HashMap::from([
+            (
+                "ANTHROPIC_BASE_URL".to_string(),
+                "https://global".to_string(),
+            ),
+            ("KEEP_ME".to_string(), "1".to_string()),
+        ]));
+        let mut local = ClaudeSettings::new();
+        local.env = Some(HashMap::from([(
+            "ANTHROPIC_BASE_URL".to_string(),
+            "https://local".to_string(),
+        )]));
+
+        let effective = effective_settings(&global, &local);
+        assert_eq!(effective.model.as_deref(), Some("opus"));
+        let env = effective.env.unwrap();
+        assert_eq!(env["ANTHROPIC_BASE_URL"], "https://local");
+        assert_eq!(env["KEEP_ME"], "1");
+
+        let lines: Vec<String> = effective_settings_lines(&global, &local)
+            .iter()
+            .map(|l| console::strip_ansi_codes(l).into_owned())
+            .collect();
+        assert!(lines.contains(&"env.ANTHROPIC_BASE_URL = https://local  (local)".to_string()));
+        assert!(lines.contains(&"env.KEEP_ME = 1  (global)".to_string()));
+        assert!(lines.contains(&"model = \"opus\"  (global)".to_string()));
+    }
+
+    #[test]
+    fn template_file_fills_the_api_key_placeholder() {
+        let home = temp_home("template-file");
+        let template = home.join("provider.json");
+        std::fs::write(
+            &template,
+            r#"{"env": {"ANTHROPIC_AUTH_TOKEN": "{API_KEY}", "ANTHROPIC_BASE_URL": "https://p"}}"#,
+        )
+        .unwrap();
+        let document = std::fs::read_to_string(&template).unwrap();
+
+        let filled = fill_template_placeholders(&document, "sk-123").unwrap();
+        let merged = merge_settings_json(
+            &filled,
+            &SnapshotScope::Common,
+            &home.join("settings.json"),
+            MergeStrategy::Merge,
+        )
+        .unwrap();
+        let env = merged.env.unwrap();
+        assert_eq!(env["ANTHROPIC_AUTH_TOKEN"], "sk-123");
+        assert_eq!(env["ANTHROPIC_BASE_URL"], "https://p");
+
+        let err = fill_template_placeholders(r#"{"env": {"X": "{REGION}"}}"#, "sk-123")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("{REGION}"), "{err}");
+
+        std::fs::remove_dir_all(&home).ok();
+    }
+
+    #[test]
+    fn verify_flags_a_snapshot_without_base_url() {
+        let mut settings = ClaudeSettings::new();
+        settings.model = Some("glm-4.6".to_string());
+        settings.env = Some(HashMap::from([(
+            "ANTHROPIC_AUTH_TOKEN".to_string(),
+            "sk-verify-token".to_string(),
+        )]));
+
+        let (lines, failed) = snapshot_verify_report(&settings, None);
+        let plain: Vec<String> = lines
+            .iter()
+            .map(|l| console::strip_ansi_codes(l).into_owned())
+            .collect();
+        assert_eq!(failed, 1);
+        assert!(plain.contains(&"✗ base URL: ANTHROPIC_BASE_URL is not set".to_string()));
+        assert!(plain.contains(&"✓ model: glm-4.6".to_string()));
+
+        settings.env.as_mut().unwrap().insert(
+            "ANTHROPIC_BASE_URL".to_string(),
+            "https://open.bigmodel.cn/api/anthropic".to_string(),
+        );
+        assert_eq!(snapshot_verify_report(&settings, None).1, 0);
+    }
+
+    #[test]
+    fn doctor_reports_snapshot_count() {
+        let home = temp_home("doctor");
+        let claude = home.join(".claude");
+        let paths = DoctorPaths {
+            active_settings: claude.join("settings.json"),
+            local_settings: home.join("project").join(".claude").join("settings.json"),
+            global_settings: claude.join("settings.json"),
+            snapshots_dir: claude.join("snapshots"),
+            credentials_dir: claude.join("credentials"),
+        };
+
+        ClaudeSettings::new()
+            .to_file(&paths.global_settings)
+            .unwrap();
+        let store = SnapshotStore::new(paths.snapshots_dir.clone());
+        for name in ["one", "two"] {
+            store
+                .save(&snapshots::Snapshot::new(
+                    name.to_string(),
+                    ClaudeSettings::new(),
+                    SnapshotScope::Common,
+                    None,
+                ))
+                .unwrap();
+        }
+
+        let report = console::strip_ansi_codes(&doctor_report(&paths).join("\n")).to_string();
+        assert!(report.contains("(2 snapshots)"), "{}", report);
+        assert!(report.contains("(0 credentials)"), "{}", report);
+        assert!(report.contains("global (active)"), "{}", report);
+        assert!(report.contains("(not found)"), "{}", report);
+
+        std::fs::remove_dir_all(&home).ok();
+    }
+}
@@ -0,0 +1,174 @@
+//! Shell completion scripts for `ccs completions`.
+//!
+//! Scripts are generated from the clap command tree by `clap_complete`, so
+//! they follow the parser: nested subcommands, aliases and flags.
+
+use anyhow::{Result, anyhow};
+use clap::Command;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the installed binary the scripts complete
+const BIN: &str = "ccs";
+
+/// Shells `ccs completions` can generate for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl std::fmt::Display for Shell {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Shell::Bash => write!(f, "bash"),
+            Shell::Zsh => write!(f, "zsh"),
+            Shell::Fish => write!(f, "fish"),
+        }
+    }
+}
+
+impl Shell {
+    /// Shell named by `$SHELL`
+    pub fn from_env() -> Option<Self> {
+        Self::from_path(Path::new(&std::env::var_os("SHELL")?))
+    }
+
+    /// Shell for a path like `/usr/bin/zsh`
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.file_name()?.to_str()? {
+            "bash" => Some(Shell::Bash),
+            "zsh" => Some(Shell::Zsh),
+            "fish" => Some(Shell::Fish),
+            _ => None,
+        }
+    }
+
+    /// Conventional per-user location of the script under `home`
+    pub fn install_path(self, home: &Path) -> PathBuf {
+        match self {
+            Shell::Bash => home.join(".bash_completion.d").join(BIN),
+            Shell::Zsh => home.join(".zfunc").join(format!("_{}", BIN)),
+            Shell::Fish => home
+                .join(".config")
+                .join("fish")
+                .join("completions")
+                .join(format!("{}.fish", BIN)),
+        }
+    }
+
+    /// What the user has to add to their shell startup file, if the shell
+    /// doesn't load `path` on its own
+    pub fn activation_hint(self, path: &Path) -> Option<String> {
+        match self {
+            Shell::Bash => Some(format!("Add to ~/.bashrc: source {}", path.display())),
+            Shell::Zsh => Some(format!(
+                "Add to ~/.zshrc before compinit: fpath=({} $fpath)",
+                path.parent().unwrap_or(path).display()
+            )),
+            Shell::Fish => None,
+        }
+    }
+}
+
+impl From<Shell> for clap_complete::Shell {
+    fn from(shell: Shell) -> Self {
+        match shell {
+            Shell::Bash => clap_complete::Shell::Bash,
+            Shell::Zsh => clap_complete::Shell::Zsh,
+            Shell::Fish => clap_complete::Shell::Fish,
+        }
+    }
+}
+
+/// Completion script for `shell`
+pub fn generate(shell: Shell, cmd: &Command) -> String {
+    let mut cmd = cmd.clone();
+    let mut script = Vec::new();
+    clap_complete::generate(
+        clap_complete::Shell::from(shell),
+        &mut cmd,
+        BIN,
+        &mut script,
+    );
+    String::from_utf8_lossy(&script).into_owned()
+}
+
+/// Write the `shell` script to its conventional location under `home`.
+///
+/// `overwrite` is asked before replacing a file with different contents;
+/// `None` is returned when it declines.
+pub fn install(
+    shell: Shell,
+    cmd: &Command,
+    home: &Path,
+    overwrite: impl FnOnce(&Path) -> Result<bool>,
+) -> Result<Option<PathBuf>> {
+    let path = shell.install_path(home);
+    let script = generate(shell, cmd);
+    let unchanged = fs::read_to_string(&path).is_ok_and(|existing| existing == script);
+    if path.exists() && !unchanged && !overwrite(&path)? {
+        return Ok(None);
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|e| anyhow!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    fs::write(&path, script).map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))?;
+    Ok(Some(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_shell_from_path() {
+        assert_eq!(
+            Shell::from_path(Path::new("/usr/bin/zsh")),
+            Some(Shell::Zsh)
+        );
+        assert_eq!(Shell::from_path(Path::new("bash")), Some(Shell::Bash));
+        assert_eq!(Shell::from_path(Path::new("/bin/tcsh")), None);
+    }
+
+    #[test]
+    fn test_install_writes_each_shell_script() {
+        let home = std::env::temp_dir().join(format!("ccs-completions-{}", uuid::Uuid::new_v4()));
+        let cmd = crate::cli::Cli::command();
+
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            let path = install(shell, &cmd, &home, |_| Ok(false)).unwrap().unwrap();
+            assert_eq!(path, shell.install_path(&home));
+            let script = fs::read_to_string(&path).unwrap();
+            assert!(script.contains("snapshot"), "{}", shell);
+            assert!(script.contains("settings-path"), "{}", shell);
+            // Aliases complete like the commands they stand for
+            assert!(script.contains("creds"), "{}", shell);
+            assert!(script.contains("import-file"), "{}", shell);
+        }
+
+        // Same contents: rewritten without asking
+        let asked = std::cell::Cell::new(false);
+        let same = install(Shell::Bash, &cmd, &home, |_| {
+            asked.set(true);
+            Ok(false)
+        });
+        assert!(same.unwrap().is_some());
+        assert!(!asked.get());
+
+        // Different contents: declining leaves the file alone
+        let bash = Shell::Bash.install_path(&home);
+        fs::write(&bash, "# mine").unwrap();
+        assert!(
+            install(Shell::Bash, &cmd, &home, |_| Ok(false))
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(fs::read_to_string(&bash).unwrap(), "# mine");
+
+        fs::remove_dir_all(&home).ok();
+    }
+}
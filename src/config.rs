@@ -0,0 +1,199 @@
+//! User defaults from `~/.claude/ccs.toml`.
+//!
+//! Unlike [`crate::prefs`], which ccs writes to remember choices, this file is
+//! only written by the user. Precedence is CLI flag → config → built-in
+//! default. Example:
+//!
+//! ```toml
+//! scope = "all"
+//! backup = false
+//! auto-snapshot = true
+//! default-template = "zai"
+//! color = false
+//! settings-path = "~/work/.claude/settings.json"
+//! ```
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Deserializer};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::snapshots::SnapshotScope;
+
+/// Defaults read from `ccs.toml`.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    /// Scope for `apply` and `snap` when `--scope`/`--fields` are not given
+    #[serde(deserialize_with = "deserialize_scope")]
+    pub scope: Option<SnapshotScope>,
+
+    /// Back up the settings file before every apply (on unless set to false)
+    pub backup: bool,
+
+    /// Save the settings file as an `auto-…` snapshot before every apply
+    pub auto_snapshot: bool,
+
+    /// Target for `ccs apply` without one
+    pub default_template: Option<String>,
+
+    /// `false` disables colored output, like `--no-color`
+    pub color: Option<bool>,
+
+    /// Settings file used when no path/`--global`/`--local` is given
+    pub settings_path: Option<PathBuf>,
+
+    /// `--force`: write settings even where a safety check would refuse.
+    /// Only set from the command line.
+    #[serde(skip)]
+    pub force: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            scope: None,
+            backup: true,
+            auto_snapshot: false,
+            default_template: None,
+            color: None,
+            settings_path: None,
+            force: false,
+        }
+    }
+}
+
+fn deserialize_scope<'de, D>(
+    deserializer: D,
+) -> std::result::Result<Option<SnapshotScope>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = Option::<String>::deserialize(deserializer)?;
+    value
+        .map(|s| s.parse().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+impl Config {
+    /// Path to the config file: `~/.claude/ccs.toml`
+    pub fn path() -> PathBuf {
+        let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
+        home.join(".claude").join("ccs.toml")
+    }
+
+    /// Load the config file, or defaults if there is none
+    pub fn load() -> Result<Self> {
+        Self::load_from(&Self::path())
+    }
+
+    /// Load a config file from `path`, or defaults if it doesn't exist
+    pub fn load_from(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read config {}: {}", path.display(), e))?;
+        Self::parse(&content).map_err(|e| anyhow!("Invalid config {}: {}", path.display(), e))
+    }
+
+    /// Parse config TOML
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut config: Config = toml::from_str(content).map_err(|e| anyhow!("{}", e))?;
+        config.settings_path = config.settings_path.map(|p| expand_home(&p));
+        Ok(config)
+    }
+
+    /// Apply `--backup` / `--no-backup`
+    pub fn with_backup_flags(mut self, backup: bool, no_backup: bool) -> Self {
+        if backup {
+            self.backup = true;
+        }
+        if no_backup {
+            self.backup = false;
+        }
+        self
+    }
+
+    /// Apply `--force`
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Scope from the flag, else the config, else the built-in default
+    pub fn scope_or(&self, flag: &Option<SnapshotScope>) -> SnapshotScope {
+        flag.clone()
+            .or_else(|| self.scope.clone())
+            .unwrap_or_default()
+    }
+
+    /// Explicit settings path from the flag, else the config's unless
+    /// `--global`/`--local` was given
+    pub fn settings_path_or(
+        &self,
+        flag: &Option<PathBuf>,
+        global: bool,
+        local: bool,
+    ) -> Option<PathBuf> {
+        if flag.is_some() || global || local {
+            return flag.clone();
+        }
+        self.settings_path.clone()
+    }
+
+    /// Whether colors are turned off by the config
+    pub fn disables_color(&self) -> bool {
+        self.color == Some(false)
+    }
+}
+
+fn expand_home(path: &Path) -> PathBuf {
+    match path.strip_prefix("~") {
+        Ok(rest) => dirs::home_dir()
+            .map(|home| home.join(rest))
+            .unwrap_or_else(|| path.to_path_buf()),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config_and_flag_precedence() {
+        let config = Config::parse(
+            "scope = \"env\"\nbackup = true\nauto-snapshot = true\n\
+             default-template = \"zai\"\ncolor = false\n",
+        )
+        .unwrap();
+        assert_eq!(config.scope, Some(SnapshotScope::Env));
+        assert_eq!(config.default_template.as_deref(), Some("zai"));
+        assert!(config.auto_snapshot);
+        assert!(config.disables_color());
+
+        assert_eq!(config.scope_or(&None), SnapshotScope::Env);
+        assert_eq!(
+            config.scope_or(&Some(SnapshotScope::All)),
+            SnapshotScope::All
+        );
+        assert_eq!(Config::default().scope_or(&None), SnapshotScope::Common);
+
+        assert!(config.clone().with_backup_flags(false, false).backup);
+        assert!(!config.with_backup_flags(false, true).backup);
+        assert!(Config::default().with_backup_flags(true, false).backup);
+        assert!(Config::default().backup);
+        assert!(Config::parse("").unwrap().backup);
+        assert!(!Config::parse("backup = false").unwrap().backup);
+
+        assert!(Config::parse("scope = \"nope\"").is_err());
+        assert!(Config::parse("unknown = 1").is_err());
+    }
+
+    #[test]
+    fn test_missing_config_is_default() {
+        let path = std::env::temp_dir().join(format!("ccs_{}.toml", uuid::Uuid::new_v4()));
+        assert_eq!(Config::load_from(&path).unwrap(), Config::default());
+    }
+}
@@ -8,17 +8,19 @@
 //! - Future versions should increment the version number when format changes are needed
 
 use anyhow::{Result, anyhow};
-use chrono::Utc;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
 use inquire::{Confirm, Select, Text};
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use uuid::Uuid;
 
+use crate::CredentialManager;
+use crate::error::{CcsError, CcsResult};
 use crate::prefs::KeyRef;
 use crate::templates::TemplateType;
-use crate::CredentialManager;
+use crate::utils::{MaskPolicy, RetryPolicy, mask_api_key};
 
 /// Current credential data format version
 pub const CURRENT_CREDENTIAL_VERSION: &str = "v2";
@@ -42,6 +44,9 @@ pub struct CredentialData {
     pub updated_at: String,
     /// Last usage timestamp in UTC (None if never used)
     pub last_used_at: Option<String>,
+    /// Expiry timestamp in UTC (None if the key never expires)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
     /// Optional metadata for future extensibility
     pub metadata: Option<std::collections::HashMap<String, String>>,
 }
@@ -58,6 +63,7 @@ impl Default for CredentialData {
             created_at: now.clone(),
             updated_at: now,
             last_used_at: None,
+            expires_at: None,
             metadata: None,
         }
     }
@@ -76,6 +82,7 @@ impl CredentialData {
             created_at: now.clone(),
             updated_at: now,
             last_used_at: None,
+            expires_at: None,
             metadata: None,
         }
     }
@@ -120,6 +127,56 @@ impl CredentialData {
         &self.updated_at
     }
 
+    /// Get expiry timestamp as stored
+    pub fn expires_at(&self) -> Option<&str> {
+        self.expires_at.as_deref()
+    }
+
+    /// Whether the credential has expired as of now
+    pub fn is_expired(&self) -> bool {
+        self.is_expired_at(Utc::now())
+    }
+
+    /// Whether the credential was last used (or, never used, created) before
+    /// `cutoff`. An unparseable timestamp never counts as stale.
+    pub fn is_unused_since(&self, cutoff: DateTime<Utc>) -> bool {
+        parse_expiry(self.last_used_at().unwrap_or(&self.created_at))
+            .is_some_and(|last| last < cutoff)
+    }
+
+    /// Whether the credential has expired as of `now`. Credentials without an
+    /// expiry (or with an unparseable one) never expire.
+    pub fn is_expired_at(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at
+            .as_deref()
+            .and_then(parse_expiry)
+            .is_some_and(|expiry| expiry <= now)
+    }
+
+    /// Rename the credential and bump its update timestamp
+    pub fn rename(&mut self, new_name: String) {
+        self.name = new_name;
+        self.update_timestamp();
+    }
+
+    /// Set (or clear, with `None`/empty input) the expiry, normalizing the format
+    pub fn set_expiry(&mut self, expires_at: Option<&str>) -> Result<()> {
+        self.expires_at = match expires_at.map(str::trim).filter(|e| !e.is_empty()) {
+            Some(value) => {
+                let expiry = parse_expiry(value).ok_or_else(|| {
+                    anyhow!(
+                        "Invalid expiry '{}'. Use YYYY-MM-DD or an RFC 3339 timestamp",
+                        value
+                    )
+                })?;
+                Some(expiry.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+            }
+            None => None,
+        };
+        self.update_timestamp();
+        Ok(())
+    }
+
     /// Get metadata
     pub fn metadata(&self) -> Option<&std::collections::HashMap<String, String>> {
         self.metadata.as_ref()
@@ -147,6 +204,29 @@ impl CredentialData {
         }
         self.update_timestamp();
     }
+
+    /// Validate `endpoint_id` for this credential's template and store it
+    pub fn set_endpoint_id(&mut self, endpoint_id: &str) -> Result<()> {
+        let endpoint_id = validate_metadata(self.template_type(), "endpoint_id", endpoint_id)?;
+        self.set_metadata_value("endpoint_id".to_string(), endpoint_id);
+        Ok(())
+    }
+}
+
+/// Parse an expiry timestamp. Accepts the stored `%Y-%m-%d %H:%M:%S UTC`
+/// format, RFC 3339, or a bare `%Y-%m-%d` date (expiring at the end of that day).
+pub fn parse_expiry(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    if let Ok(dt) = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S UTC") {
+        return Some(dt.and_utc());
+    }
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Some(dt.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(23, 59, 59))
+        .map(|dt| dt.and_utc())
 }
 
 /// Result type for credential operations
@@ -155,6 +235,9 @@ pub type SavedCredential = CredentialData;
 /// Storage backend for credential files
 pub struct SavedCredentialStore {
     pub credentials_dir: PathBuf,
+
+    /// Retry policy for transient write errors
+    pub retry: RetryPolicy,
 }
 
 impl SavedCredentialStore {
@@ -163,14 +246,23 @@ impl SavedCredentialStore {
         let home_dir = dirs::home_dir().ok_or_else(|| anyhow!("Could not find home directory"))?;
         let credentials_dir = home_dir.join(".claude").join("credentials");
 
-        let store = Self { credentials_dir };
+        let store = Self::new_with_dir(credentials_dir);
         store.ensure_dir()?;
         Ok(store)
     }
 
     /// Create a new credential store with custom directory (for backward compatibility)
     pub fn new_with_dir(credentials_dir: PathBuf) -> Self {
-        Self { credentials_dir }
+        Self {
+            credentials_dir,
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    /// Use `retry` for transient write errors
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
     }
 
     /// Ensure the credentials directory exists
@@ -182,6 +274,14 @@ impl SavedCredentialStore {
         Ok(())
     }
 
+    /// Hold the store's [`FileLock`](crate::utils::FileLock), e.g. across a
+    /// load → modify → save
+    pub fn lock(&self) -> Result<crate::utils::FileLock> {
+        self.ensure_dir()?;
+        crate::utils::FileLock::acquire(&self.credentials_dir, crate::utils::LOCK_TIMEOUT)
+            .map_err(|e| anyhow!("Failed to lock credentials: {}", e))
+    }
+
     /// Get the file path for a credential
     pub fn credential_path(&self, credential_id: &str) -> PathBuf {
         self.credentials_dir.join(format!("{}.json", credential_id))
@@ -195,26 +295,28 @@ impl SavedCredentialStore {
         let content = serde_json::to_string_pretty(credential)
             .map_err(|e| anyhow!("Failed to serialize credential: {}", e))?;
 
-        fs::write(&path, content)
+        crate::utils::write_atomic_retrying(&path, content.as_bytes(), &self.retry)
             .map_err(|e| anyhow!("Failed to write credential file {}: {}", path.display(), e))?;
 
         Ok(())
     }
 
     /// Load a credential from disk
-    pub fn load(&self, credential_id: &str) -> Result<SavedCredential> {
+    pub fn load(&self, credential_id: &str) -> CcsResult<SavedCredential> {
         let path = self.credential_path(credential_id);
 
         if !path.exists() {
-            return Err(anyhow!("Credential '{}' not found", credential_id));
+            return Err(CcsError::CredentialNotFound(credential_id.to_string()));
         }
 
-        let content = fs::read_to_string(&path)
-            .map_err(|e| anyhow!("Failed to read credential file {}: {}", path.display(), e))?;
+        let content = fs::read_to_string(&path).map_err(|source| CcsError::Io {
+            path: path.clone(),
+            source,
+        })?;
 
         // Parse as current format
         serde_json::from_str::<CredentialData>(&content)
-            .map_err(|e| anyhow!("Failed to parse credential file {}: {}", path.display(), e))
+            .map_err(|source| CcsError::SettingsParse { path, source })
     }
 
     /// List all saved credentials
@@ -250,11 +352,18 @@ impl SavedCredentialStore {
         }
 
         // Sort by creation time (newest first)
-        credentials.sort_by(|a, b| b.created_at().cmp(a.created_at()));
+        SortOrder::CreatedDesc.sort(&mut credentials);
 
         Ok(credentials)
     }
 
+    /// List all credentials in the given order
+    pub fn list_sorted(&self, order: SortOrder) -> Result<Vec<SavedCredential>> {
+        let mut credentials = self.list()?;
+        order.sort(&mut credentials);
+        Ok(credentials)
+    }
+
     /// Delete a credential
     pub fn delete(&self, credential_id: &str) -> Result<()> {
         let path = self.credential_path(credential_id);
@@ -263,6 +372,7 @@ impl SavedCredentialStore {
             return Err(anyhow!("Credential '{}' not found", credential_id));
         }
 
+        let _lock = self.lock()?;
         fs::remove_file(&path)
             .map_err(|e| anyhow!("Failed to delete credential file {}: {}", path.display(), e))?;
 
@@ -283,6 +393,15 @@ impl SavedCredentialStore {
             .collect())
     }
 
+    /// Find credentials with exactly this name
+    pub fn find_by_name(&self, name: &str) -> Result<Vec<SavedCredential>> {
+        let credentials = self.list()?;
+        Ok(credentials
+            .into_iter()
+            .filter(|c| c.name() == name)
+            .collect())
+    }
+
     /// Find credentials by template type
     pub fn find_by_template_type(
         &self,
@@ -294,6 +413,36 @@ impl SavedCredentialStore {
             .filter(|c| c.template_type() == template_type)
             .collect())
     }
+
+    /// Find credentials whose API key starts with `prefix`
+    pub fn find_by_key_prefix(&self, prefix: &str) -> Result<Vec<SavedCredential>> {
+        let prefix = prefix.trim();
+        if prefix.is_empty() {
+            return Err(anyhow!("Key prefix cannot be empty"));
+        }
+        let credentials = self.list()?;
+        Ok(credentials
+            .into_iter()
+            .filter(|c| c.api_key().starts_with(prefix))
+            .collect())
+    }
+}
+
+impl crate::Storage<Vec<SavedCredential>> for SavedCredentialStore {
+    fn load(&self) -> Result<Vec<SavedCredential>> {
+        self.list()
+    }
+
+    fn save(&self, data: &Vec<SavedCredential>) -> Result<()> {
+        for credential in data {
+            SavedCredentialStore::save(self, credential)?;
+        }
+        Ok(())
+    }
+
+    fn path(&self) -> PathBuf {
+        self.credentials_dir.clone()
+    }
 }
 
 /// High-level credential management
@@ -309,6 +458,11 @@ impl CredentialStore {
         })
     }
 
+    /// List all saved credentials in the given order
+    pub fn list_sorted(&self, order: SortOrder) -> Result<Vec<SavedCredential>> {
+        self.store.list_sorted(order)
+    }
+
     /// Generate a smart credential name with auto-incrementing numbers
     pub fn generate_smart_name(
         &self,
@@ -341,9 +495,10 @@ impl CredentialStore {
             }
         }
 
-        // Generate name with auto-incrementing number if base already exists
+        // Generate name with auto-incrementing number if base already exists;
+        // the unnumbered name counts as the first, so the next one is `-2`
         if has_base_name {
-            Ok(format!("{}-{}", base, max_number + 1))
+            Ok(format!("{}-{}", base, max_number.max(1) + 1))
         } else {
             Ok(base.to_string())
         }
@@ -377,6 +532,25 @@ impl CredentialStore {
         Ok(credential)
     }
 
+    /// Save the API key found in a provider's key file (see [`read_key_file`])
+    pub fn import_from_file(
+        &self,
+        path: &Path,
+        template_type: TemplateType,
+        name: Option<&str>,
+        pointer: Option<&str>,
+    ) -> Result<SavedCredential> {
+        let api_key = read_key_file(path, pointer)?;
+        if self.has_api_key(&api_key, &template_type) {
+            return Err(anyhow!(
+                "The key in {} is already saved for {}",
+                path.display(),
+                template_type
+            ));
+        }
+        self.create_credential_smart(&api_key, template_type, name.filter(|n| !n.is_empty()))
+    }
+
     /// Get the API key from a credential
     pub fn get_api_key(&self, credential: &SavedCredential) -> Result<String> {
         Ok(credential.api_key().to_string())
@@ -410,6 +584,7 @@ impl CredentialStore {
 
     /// Save endpoint ID to credential metadata
     pub fn save_endpoint_id(&self, credential_id: &str, endpoint_id: &str) -> Result<()> {
+        let _lock = self.store.lock()?;
         let mut credential = self.store.load(credential_id)?;
         credential.set_metadata_value("endpoint_id".to_string(), endpoint_id.to_string());
         self.store.save(&credential)?;
@@ -430,17 +605,101 @@ impl CredentialStore {
         false
     }
 
-    /// Update credential name
-    pub fn update_name(&self, credential_id: &str, new_name: String) -> Result<()> {
+    /// Rename the credential with ID `credential_id`.
+    /// Fails if `new_name` is empty or already used by another credential.
+    pub fn rename_by_id(&self, credential_id: &str, new_name: &str) -> Result<SavedCredential> {
+        let _lock = self.store.lock()?;
+        let credential = self.store.load(credential_id)?;
+        self.rename_checked(credential, new_name)
+    }
+
+    /// Rename the credential called `name`.
+    /// Fails if no credential or more than one has that name, or if
+    /// `new_name` is already used by another credential.
+    pub fn rename_by_name(&self, name: &str, new_name: &str) -> Result<SavedCredential> {
+        let _lock = self.store.lock()?;
+        let mut matches = self.store.find_by_name(name)?;
+        let credential = match matches.len() {
+            0 => return Err(anyhow!("Credential '{}' not found", name)),
+            1 => matches.remove(0),
+            n => {
+                return Err(anyhow!(
+                    "{} credentials are named '{}'; run dedupe first",
+                    n,
+                    name
+                ));
+            }
+        };
+        self.rename_checked(credential, new_name)
+    }
+
+    /// Rename `credential`, keeping names unique across all credentials
+    fn rename_checked(
+        &self,
+        mut credential: SavedCredential,
+        new_name: &str,
+    ) -> Result<SavedCredential> {
+        let new_name = new_name.trim();
+        if new_name.is_empty() {
+            return Err(anyhow!("Credential name cannot be empty"));
+        }
+        let taken = self
+            .store
+            .find_by_name(new_name)?
+            .iter()
+            .any(|other| other.id() != credential.id());
+        if taken {
+            return Err(anyhow!("Credential '{}' already exists", new_name));
+        }
+
+        credential.rename(new_name.to_string());
+        self.store.save(&credential)?;
+        Ok(credential)
+    }
+
+    /// Give every credential a distinct name. The oldest credential keeps a
+    /// shared name; later ones get the first free `-N` suffix.
+    /// Returns `(id, new_name)` for each renamed credential.
+    pub fn dedupe_names(&self) -> Result<Vec<(String, String)>> {
+        let _lock = self.store.lock()?;
+        let mut credentials = self.store.list()?;
+        // list() is newest first; the oldest should keep its name
+        credentials.reverse();
+
+        let mut taken: HashSet<String> = HashSet::new();
+        let mut duplicates = Vec::new();
+        for credential in credentials {
+            if taken.insert(credential.name().to_string()) {
+                continue;
+            }
+            duplicates.push(credential);
+        }
+
+        let mut renamed = Vec::new();
+        for mut credential in duplicates {
+            let new_name =
+                crate::utils::unique_name(credential.name(), |candidate| taken.contains(candidate));
+            taken.insert(new_name.clone());
+
+            credential.rename(new_name.clone());
+            self.store.save(&credential)?;
+            renamed.push((credential.id().to_string(), new_name));
+        }
+        Ok(renamed)
+    }
+
+    /// Set or clear the expiry of a credential
+    pub fn set_expires_at(&self, credential_id: &str, expires_at: Option<&str>) -> Result<()> {
+        let _lock = self.store.lock()?;
         let mut credential = self.store.load(credential_id)?;
-        credential.name = new_name;
-        credential.update_timestamp();
+        credential.set_expiry(expires_at)?;
         self.store.save(&credential)?;
         Ok(())
     }
 
     /// Update last_used_at timestamp for a credential
     pub fn touch_last_used(&self, credential_id: &str) -> Result<()> {
+        let _lock = self.store.lock()?;
         let mut credential = self.store.load(credential_id)?;
         credential.last_used_at = Some(Utc::now().format("%Y-%m-%d %H:%M:%S UTC").to_string());
         self.store.save(&credential)?;
@@ -453,11 +712,137 @@ impl CredentialStore {
         credential_id: &str,
         metadata: std::collections::HashMap<String, String>,
     ) -> Result<()> {
+        let _lock = self.store.lock()?;
         let mut credential = self.store.load(credential_id)?;
         credential.set_metadata(metadata);
         self.store.save(&credential)?;
         Ok(())
     }
+
+    /// Set one metadata entry on a credential, returning the stored value.
+    /// The keys ccs reads itself, `endpoint_id` and `tags`, are validated
+    /// (and `tags` normalized) first.
+    pub fn set_metadata_entry(
+        &self,
+        credential_id: &str,
+        key: &str,
+        value: &str,
+    ) -> Result<String> {
+        let _lock = self.store.lock()?;
+        let credential = self.store.load(credential_id)?;
+        let value = validate_metadata(credential.template_type(), key, value)?;
+        let mut metadata = credential.metadata().cloned().unwrap_or_default();
+        metadata.insert(key.to_string(), value.clone());
+        self.update_metadata(credential_id, metadata)?;
+        Ok(value)
+    }
+
+    /// Credentials not used in the last `unused_for`, for `prune_unused`.
+    /// An `unused_for` reaching past the earliest representable time
+    /// matches nothing.
+    pub fn unused_credentials(&self, unused_for: chrono::Duration) -> Result<Vec<SavedCredential>> {
+        let Some(cutoff) = crate::utils::cutoff_before(unused_for) else {
+            return Ok(Vec::new());
+        };
+        Ok(self
+            .store
+            .list()?
+            .into_iter()
+            .filter(|credential| credential.is_unused_since(cutoff))
+            .collect())
+    }
+
+    /// Delete the `candidates` [`Self::unused_credentials`] listed, skipping
+    /// any already gone, and return the deleted names
+    pub fn prune_unused(&self, candidates: &[SavedCredential]) -> Result<Vec<String>> {
+        let _lock = self.store.lock()?;
+        let mut deleted = Vec::new();
+        for credential in candidates {
+            if self.store.exists(credential.id()) {
+                self.store.delete(credential.id())?;
+                deleted.push(credential.name.clone());
+            }
+        }
+        Ok(deleted)
+    }
+
+    /// Delete every credential of `template_type`; returns how many were removed
+    pub fn clear_template_credentials(&self, template_type: &TemplateType) -> Result<usize> {
+        let _lock = self.store.lock()?;
+        let credentials = self.store.find_by_template_type(template_type)?;
+        for credential in &credentials {
+            self.store.delete(credential.id())?;
+        }
+        Ok(credentials.len())
+    }
+
+    /// Remove one metadata entry; false if the credential didn't have it
+    pub fn remove_metadata_entry(&self, credential_id: &str, key: &str) -> Result<bool> {
+        let _lock = self.store.lock()?;
+        let credential = self.store.load(credential_id)?;
+        let mut metadata = credential.metadata().cloned().unwrap_or_default();
+        if metadata.remove(key).is_none() {
+            return Ok(false);
+        }
+        self.update_metadata(credential_id, metadata)?;
+        Ok(true)
+    }
+}
+
+/// Check a metadata entry for a `template_type` credential and return the
+/// value to store
+fn validate_metadata(template_type: &TemplateType, key: &str, value: &str) -> Result<String> {
+    let valid_key = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    if !valid_key {
+        return Err(anyhow!(
+            "Invalid metadata key '{}' (use letters, digits, '_', '-' or '.')",
+            key
+        ));
+    }
+
+    match key {
+        "endpoint_id" => {
+            let value = value.trim();
+            if !crate::templates::get_template_instance(template_type).requires_additional_config()
+            {
+                return Err(anyhow!(
+                    "{} credentials don't use an endpoint ID",
+                    template_type
+                ));
+            }
+            if *template_type == TemplateType::KatCoder {
+                crate::templates::kat_coder::validate_endpoint_id(value)?;
+            } else if value.is_empty() || value.contains(char::is_whitespace) {
+                return Err(anyhow!("Invalid endpoint ID '{}'", value));
+            }
+            Ok(value.to_string())
+        }
+        "tags" => {
+            let mut tags: Vec<&str> = Vec::new();
+            for tag in value
+                .split(',')
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty())
+            {
+                if tag.contains(char::is_whitespace) {
+                    return Err(anyhow!("Tag '{}' contains whitespace", tag));
+                }
+                if !tags.contains(&tag) {
+                    tags.push(tag);
+                }
+            }
+            if tags.is_empty() {
+                return Err(anyhow!(
+                    "tags must be a comma-separated list; use `creds meta unset` to remove them"
+                ));
+            }
+            Ok(tags.join(","))
+        }
+        _ => Ok(value.to_string()),
+    }
 }
 
 impl crate::CredentialManager for CredentialStore {
@@ -530,36 +915,81 @@ impl ApiKeySource {
             ApiKeySource::EnvVar {
                 env_var_name,
                 api_key,
-            } => format!("🌐 {} = {}", env_var_name, mask_api_key(api_key)),
+            } => format!(
+                "🌐 {} = {}",
+                env_var_name,
+                mask_api_key(api_key, MaskPolicy::DEFAULT)
+            ),
             ApiKeySource::Saved { credential } => format!(
                 "🔑 {} ({}) - {}",
                 credential.name(),
                 credential.template_type(),
-                mask_api_key(credential.api_key())
+                mask_api_key(credential.api_key(), MaskPolicy::DEFAULT)
             ),
         }
     }
 }
 
-/// Collect unified API key sources (env vars + saved credentials) for a
-/// template, sorted by last usage and de-duplicated. Env var keys win.
-pub fn collect_api_key_sources(template_type: &TemplateType) -> Result<Vec<ApiKeySource>> {
-    let mut sources: Vec<ApiKeySource> = Vec::new();
+/// Which kind of key source `apply` takes without asking, when there is one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyPreference {
+    /// Remembered source, else a single source, else ask
+    #[default]
+    Auto,
+    /// The template's env vars (primary first), before anything else
+    Env,
+    /// Saved credentials (most recently used first), before anything else
+    Saved,
+}
 
-    // 1. environment variables
-    let env_var_names = crate::templates::get_env_var_names(template_type);
-    for env_var_name in &env_var_names {
-        if let Some(api_key) = std::env::var(env_var_name)
-            .ok()
-            .filter(|key| !key.trim().is_empty())
-        {
-            sources.push(ApiKeySource::EnvVar {
-                env_var_name: env_var_name.to_string(),
-                api_key,
-            });
+impl KeyPreference {
+    /// Preference from `--prefer-env` / `--prefer-saved`
+    pub fn from_flags(prefer_env: bool, prefer_saved: bool) -> Self {
+        match (prefer_env, prefer_saved) {
+            (true, _) => KeyPreference::Env,
+            (false, true) => KeyPreference::Saved,
+            (false, false) => KeyPreference::Auto,
         }
     }
 
+    /// The first source of the preferred kind in `sources`
+    fn pick(self, sources: &[ApiKeySource]) -> Option<&ApiKeySource> {
+        sources.iter().find(|source| {
+            matches!(
+                (self, source),
+                (KeyPreference::Env, ApiKeySource::EnvVar { .. })
+                    | (KeyPreference::Saved, ApiKeySource::Saved { .. })
+            )
+        })
+    }
+}
+
+/// Env var sources for a template, read through `lookup`. The order is the
+/// precedence: the primary env var first, then the alternates in the order
+/// the template lists them. Blank values are skipped.
+fn env_key_sources(
+    template_type: &TemplateType,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Vec<ApiKeySource> {
+    crate::templates::get_env_var_names(template_type)
+        .into_iter()
+        .filter_map(|env_var_name| {
+            lookup(env_var_name)
+                .filter(|key| !key.trim().is_empty())
+                .map(|api_key| ApiKeySource::EnvVar {
+                    env_var_name: env_var_name.to_string(),
+                    api_key,
+                })
+        })
+        .collect()
+}
+
+/// Collect unified API key sources (env vars + saved credentials) for a
+/// template, sorted by last usage and de-duplicated. Env var keys win.
+pub fn collect_api_key_sources(template_type: &TemplateType) -> Result<Vec<ApiKeySource>> {
+    // 1. environment variables, in precedence order
+    let mut sources = env_key_sources(template_type, |name| std::env::var(name).ok());
+
     // 2. saved credentials for this template type
     if let Ok(store) = CredentialStore::new()
         && let Ok(all) = store.load_credentials()
@@ -569,7 +999,8 @@ pub fn collect_api_key_sources(template_type: &TemplateType) -> Result<Vec<ApiKe
         }
     }
 
-    // 3. sort: used creds (last_used desc) → env vars → unused creds (created desc)
+    // 3. sort: used creds (last_used desc) → env vars → unused creds (created desc);
+    //    the sort is stable, so env vars keep their precedence order
     sources.sort_by(|a, b| {
         let priority = |s: &ApiKeySource| match s {
             ApiKeySource::Saved { credential } => match credential.last_used_at() {
@@ -606,6 +1037,41 @@ pub fn collect_api_key_sources(template_type: &TemplateType) -> Result<Vec<ApiKe
     Ok(deduped)
 }
 
+/// The source `apply` takes without asking: the preferred kind
+/// (`--prefer-env` / `--prefer-saved`), else the remembered source if it is
+/// still there, else the only source. `None` means apply would prompt.
+pub fn pick_api_key_source<'a>(
+    sources: &'a [ApiKeySource],
+    remembered: Option<&KeyRef>,
+    preference: KeyPreference,
+) -> Option<&'a ApiKeySource> {
+    preference
+        .pick(sources)
+        .or_else(|| remembered.and_then(|key_ref| find_source_by_ref(sources, key_ref)))
+        .or(match sources {
+            [only] => Some(only),
+            _ => None,
+        })
+}
+
+/// Mark the credential behind `src` (if it is a saved one) as just used
+fn record_use(store: &CredentialStore, src: &ApiKeySource) {
+    if let ApiKeySource::Saved { credential } = src {
+        let _ = store.touch_last_used(credential.id());
+    }
+}
+
+/// Take `src` without asking, marking a saved credential as used
+fn use_source(src: &ApiKeySource) -> ApiKeyChoice {
+    if let Ok(store) = CredentialStore::new() {
+        record_use(&store, src);
+    }
+    ApiKeyChoice {
+        key: src.api_key().to_string(),
+        source: Some(src.to_key_ref()),
+    }
+}
+
 /// Find a source matching a remembered [`KeyRef`].
 fn find_source_by_ref<'a>(
     sources: &'a [ApiKeySource],
@@ -649,10 +1115,8 @@ fn prompt_api_key_choice(
         .ok_or_else(|| anyhow!("Selected source not found"))?;
     let source = &sources[index];
 
-    if let ApiKeySource::Saved { credential } = source
-        && let Ok(store) = CredentialStore::new()
-    {
-        let _ = store.touch_last_used(credential.id());
+    if let Ok(store) = CredentialStore::new() {
+        record_use(&store, source);
     }
 
     Ok(Some(ApiKeyChoice {
@@ -710,33 +1174,115 @@ fn save_credential_if_desired(
         return Ok(None);
     }
 
-    let default_name = format!("{} API Key", template_type);
+    let Ok(store) = CredentialStore::new() else {
+        return Ok(None);
+    };
+    let default_name = store.generate_smart_name(template_type, None)?;
     let name = Text::new("Save as (alias):")
         .with_default(&default_name)
-        .with_help_message(format!("Alias for {}", mask_api_key(api_key)).as_str())
+        .with_help_message(
+            format!("Alias for {}", mask_api_key(api_key, MaskPolicy::DEFAULT)).as_str(),
+        )
         .prompt()
         .unwrap_or(default_name);
-    let name = name.trim().to_string();
 
-    if let Ok(store) = CredentialStore::new() {
-        let cred = store.create_credential(name, api_key, template_type.clone())?;
-        println!("✓ API key saved.");
-        return Ok(Some(KeyRef::Credential(cred.id().to_string())));
+    let endpoint_id = endpoint_id_from_env(template_type);
+    let cred = store_new_key(
+        &store,
+        template_type,
+        api_key,
+        Some(name.trim()),
+        endpoint_id.as_deref(),
+    )?;
+    println!("✓ API key saved.");
+    Ok(Some(KeyRef::Credential(cred.id().to_string())))
+}
+
+/// Endpoint ID chosen through the environment for templates that need one
+/// (KatCoder's `WANQING_ENDPOINT_ID`).
+fn endpoint_id_from_env(template_type: &TemplateType) -> Option<String> {
+    match template_type {
+        TemplateType::KatCoder => std::env::var("WANQING_ENDPOINT_ID")
+            .ok()
+            .filter(|id| !id.trim().is_empty()),
+        _ => None,
+    }
+}
+
+/// Save a freshly entered key. An empty `name` falls back to the next free
+/// smart name (`deepseek`, `deepseek-2`, …); `endpoint_id` goes into the
+/// credential's metadata.
+fn store_new_key(
+    store: &CredentialStore,
+    template_type: &TemplateType,
+    api_key: &str,
+    name: Option<&str>,
+    endpoint_id: Option<&str>,
+) -> Result<SavedCredential> {
+    let name = name.filter(|n| !n.is_empty());
+    let mut credential = store.create_credential_smart(api_key, template_type.clone(), name)?;
+    if let Some(endpoint_id) = endpoint_id {
+        store.save_endpoint_id(credential.id(), endpoint_id)?;
+        credential.set_metadata_value("endpoint_id".to_string(), endpoint_id.to_string());
+    }
+    Ok(credential)
+}
+
+/// JSON pointer of the key in a `.json` key file unless one is given
+pub const DEFAULT_KEY_POINTER: &str = "/api_key";
+
+/// Read the API key from a key file downloaded from a provider.
+///
+/// A `.json` file is parsed and the string at `pointer` (default
+/// [`DEFAULT_KEY_POINTER`]) is used; any other file is taken whole, trimmed.
+pub fn read_key_file(path: &Path, pointer: Option<&str>) -> Result<String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read key file {}: {}", path.display(), e))?;
+
+    let is_json = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+    let key = if is_json {
+        let pointer = pointer.unwrap_or(DEFAULT_KEY_POINTER);
+        let value: serde_json::Value = serde_json::from_str(&content)
+            .map_err(|e| anyhow!("Failed to parse {}: {}", path.display(), e))?;
+        value
+            .pointer(pointer)
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| {
+                anyhow!(
+                    "No string at '{}' in {} (choose another with --pointer)",
+                    pointer,
+                    path.display()
+                )
+            })?
+            .trim()
+            .to_string()
+    } else {
+        content.trim().to_string()
+    };
+
+    if key.is_empty() {
+        return Err(anyhow!("No API key found in {}", path.display()));
     }
-    Ok(None)
+    Ok(key)
 }
 
 /// Resolve an API key for applying a template.
 ///
-/// Decision order: explicit `api_key_param` → a remembered source that still
-/// exists → a single available source → interactive prompt. Returns the key and
+/// Decision order: explicit `api_key_param` → the template's env vars in
+/// `env_file` (from `--env-file`) → the first source of the `preference`
+/// kind → a remembered source that still exists → a single available source
+/// → interactive prompt. Returns the key and
 /// its source so the caller can remember it. `Ok(None)` means the user
 /// cancelled. In `non_interactive` mode this never prompts and errors if no key
 /// is available.
 pub fn resolve_api_key(
     template_type: &TemplateType,
     api_key_param: Option<&str>,
+    env_file: Option<&HashMap<String, String>>,
     remembered: Option<&KeyRef>,
+    preference: KeyPreference,
     force_prompt: bool,
     non_interactive: bool,
 ) -> Result<Option<ApiKeyChoice>> {
@@ -748,32 +1294,26 @@ pub fn resolve_api_key(
         }));
     }
 
-    let sources = collect_api_key_sources(template_type)?;
-
-    if !force_prompt {
-        // remembered source still present?
-        if let Some(kr) = remembered
-            && let Some(src) = find_source_by_ref(&sources, kr)
-        {
+    // then a key from --env-file; not remembered, the file may not be there
+    // next time
+    if let Some(vars) = env_file {
+        let found = crate::templates::get_env_var_names(template_type)
+            .into_iter()
+            .filter_map(|name| vars.get(name))
+            .map(|key| key.trim())
+            .find(|key| !key.is_empty());
+        if let Some(key) = found {
             return Ok(Some(ApiKeyChoice {
-                key: src.api_key().to_string(),
-                source: Some(src.to_key_ref()),
+                key: key.to_string(),
+                source: None,
             }));
         }
+    }
 
-        // exactly one source → use silently
-        if sources.len() == 1 {
-            let src = &sources[0];
-            if let ApiKeySource::Saved { credential } = src
-                && let Ok(store) = CredentialStore::new()
-            {
-                let _ = store.touch_last_used(credential.id());
-            }
-            return Ok(Some(ApiKeyChoice {
-                key: src.api_key().to_string(),
-                source: Some(src.to_key_ref()),
-            }));
-        }
+    let sources = collect_api_key_sources(template_type)?;
+
+    if !force_prompt && let Some(src) = pick_api_key_source(&sources, remembered, preference) {
+        return Ok(Some(use_source(src)));
     }
 
     // otherwise we need a prompt
@@ -792,17 +1332,112 @@ pub fn resolve_api_key(
     }
 }
 
-/// Mask API key for display (show first 4 and last 4 characters)
-pub(crate) fn mask_api_key(api_key: &str) -> String {
-    if api_key.len() <= 8 {
-        "••••••••".to_string()
-    } else {
-        format!(
-            "{}{}{}",
-            &api_key[..4],
-            "•".repeat(api_key.len() - 8),
-            &api_key[api_key.len() - 4..]
-        )
+/// Order for `credentials list --sort`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum SortOrder {
+    /// By name, A to Z
+    #[value(alias = "name")]
+    NameAsc,
+    /// By name, Z to A
+    NameDesc,
+    /// Oldest first
+    #[value(alias = "oldest")]
+    CreatedAsc,
+    /// Newest first
+    #[default]
+    #[value(alias = "newest")]
+    CreatedDesc,
+    /// Grouped by template, then by name
+    #[value(alias = "template")]
+    TemplateThenName,
+}
+
+impl SortOrder {
+    /// Sort `credentials` in place. Names compare case-insensitively; ties
+    /// keep the newest credential first.
+    pub fn sort(self, credentials: &mut [SavedCredential]) {
+        let name = |c: &SavedCredential| c.name().to_lowercase();
+        credentials.sort_by(|a, b| {
+            let order = match self {
+                SortOrder::NameAsc => name(a).cmp(&name(b)),
+                SortOrder::NameDesc => name(b).cmp(&name(a)),
+                SortOrder::CreatedAsc => a.created_at().cmp(b.created_at()),
+                SortOrder::CreatedDesc => std::cmp::Ordering::Equal,
+                SortOrder::TemplateThenName => a
+                    .template_type()
+                    .to_string()
+                    .cmp(&b.template_type().to_string())
+                    .then_with(|| name(a).cmp(&name(b))),
+            };
+            order.then_with(|| b.created_at().cmp(a.created_at()))
+        });
+    }
+}
+
+/// Delimited text output for `credentials list --format`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum DelimitedFormat {
+    Csv,
+    Tsv,
+}
+
+/// Render credentials as CSV or TSV with a header row: id, name, template,
+/// created_at, updated_at and, if `with_key`, the masked API key.
+pub fn format_credentials_delimited(
+    credentials: &[SavedCredential],
+    format: DelimitedFormat,
+    with_key: bool,
+) -> String {
+    let mut header = vec![
+        "id",
+        "name",
+        "template",
+        "created_at",
+        "updated_at",
+        "last_used_at",
+    ];
+    if with_key {
+        header.push("api_key");
+    }
+
+    let mut rows = vec![header.iter().map(|h| h.to_string()).collect::<Vec<_>>()];
+    for credential in credentials {
+        let mut row = vec![
+            credential.id().to_string(),
+            credential.name().to_string(),
+            credential.template_type().to_string(),
+            credential.created_at().to_string(),
+            credential.updated_at().to_string(),
+            credential.last_used_at().unwrap_or_default().to_string(),
+        ];
+        if with_key {
+            row.push(mask_api_key(credential.api_key(), MaskPolicy::DEFAULT));
+        }
+        rows.push(row);
+    }
+
+    let mut output = String::new();
+    for row in rows {
+        let fields: Vec<String> = row.iter().map(|f| delimited_field(f, format)).collect();
+        let separator = match format {
+            DelimitedFormat::Csv => ",",
+            DelimitedFormat::Tsv => "\t",
+        };
+        output.push_str(&fields.join(separator));
+        output.push('\n');
+    }
+    output
+}
+
+/// CSV quotes fields per RFC 4180; TSV has no quoting, so tabs and newlines
+/// become spaces
+fn delimited_field(field: &str, format: DelimitedFormat) -> String {
+    match format {
+        DelimitedFormat::Csv if field.contains([',', '"', '\n', '\r']) => {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        }
+        DelimitedFormat::Csv => field.to_string(),
+        DelimitedFormat::Tsv => field.replace(['\t', '\n', '\r'], " "),
     }
 }
 
@@ -811,9 +1446,7 @@ mod tests {
     use super::*;
     fn create_test_store() -> CredentialStore {
         let temp_dir = std::env::temp_dir().join("ccs_test");
-        let store = SavedCredentialStore {
-            credentials_dir: temp_dir,
-        };
+        let store = SavedCredentialStore::new_with_dir(temp_dir);
         CredentialStore { store }
     }
 
@@ -843,9 +1476,660 @@ mod tests {
         assert_eq!(credential.api_key(), loaded.api_key());
     }
 
+    #[test]
+    fn test_saved_credential_store_as_dyn_storage() {
+        use crate::Storage;
+
+        let dir = std::env::temp_dir().join(format!("ccs_credstore_{}", uuid::Uuid::new_v4()));
+        let store: &dyn Storage<Vec<SavedCredential>> =
+            &SavedCredentialStore::new_with_dir(dir.clone());
+
+        assert_eq!(store.path(), dir);
+        assert!(store.load().unwrap().is_empty());
+
+        let credential = CredentialData::new(
+            "trait".to_string(),
+            "sk-trait".to_string(),
+            TemplateType::Zai,
+        );
+        store.save(&vec![credential.clone()]).unwrap();
+
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].id(), credential.id());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    /// Minimal RFC 4180 reader for checking CSV output
+    fn parse_csv(text: &str) -> Vec<Vec<String>> {
+        let mut rows = Vec::new();
+        let mut row = Vec::new();
+        let mut field = String::new();
+        let mut in_quotes = false;
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (c, in_quotes) {
+                ('"', true) if chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                }
+                ('"', _) => in_quotes = !in_quotes,
+                (',', false) => row.push(std::mem::take(&mut field)),
+                ('\n', false) => {
+                    row.push(std::mem::take(&mut field));
+                    rows.push(std::mem::take(&mut row));
+                }
+                (c, _) => field.push(c),
+            }
+        }
+        rows
+    }
+
+    #[test]
+    fn test_sort_orders() {
+        let credential = |name: &str, template: TemplateType, created_at: &str| {
+            let mut credential = CredentialData::new(name.into(), "sk-test".into(), template);
+            credential.created_at = created_at.into();
+            credential
+        };
+        let fixtures = vec![
+            credential("work", TemplateType::Zai, "2025-01-02 00:00:00 UTC"),
+            credential("Alpha", TemplateType::Zai, "2025-01-03 00:00:00 UTC"),
+            credential("beta", TemplateType::DeepSeek, "2025-01-01 00:00:00 UTC"),
+        ];
+        let sorted = |order: SortOrder| {
+            let mut credentials = fixtures.clone();
+            order.sort(&mut credentials);
+            credentials
+                .iter()
+                .map(|c| c.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(sorted(SortOrder::NameAsc), ["Alpha", "beta", "work"]);
+        assert_eq!(sorted(SortOrder::NameDesc), ["work", "beta", "Alpha"]);
+        assert_eq!(sorted(SortOrder::CreatedAsc), ["beta", "work", "Alpha"]);
+        assert_eq!(sorted(SortOrder::CreatedDesc), ["Alpha", "work", "beta"]);
+        assert_eq!(
+            sorted(SortOrder::TemplateThenName),
+            ["beta", "Alpha", "work"]
+        );
+    }
+
+    #[test]
+    fn test_delimited_credentials_round_trip() {
+        let mut credential = CredentialData::new(
+            "work, \"main\"".into(),
+            "sk-1234567890".into(),
+            TemplateType::Zai,
+        );
+        credential.created_at = "2025-01-01 00:00:00 UTC".into();
+        credential.updated_at = "2025-01-02 00:00:00 UTC".into();
+        let credentials = vec![credential.clone()];
+
+        let csv = format_credentials_delimited(&credentials, DelimitedFormat::Csv, false);
+        let rows = parse_csv(&csv);
+        assert_eq!(
+            rows[0],
+            vec![
+                "id",
+                "name",
+                "template",
+                "created_at",
+                "updated_at",
+                "last_used_at"
+            ]
+        );
+        assert_eq!(
+            rows[1],
+            vec![
+                credential.id(),
+                "work, \"main\"",
+                "zai",
+                "2025-01-01 00:00:00 UTC",
+                "2025-01-02 00:00:00 UTC",
+                ""
+            ]
+        );
+        assert_eq!(rows.len(), 2);
+
+        let tsv = format_credentials_delimited(&credentials, DelimitedFormat::Tsv, true);
+        let line = tsv.lines().nth(1).unwrap();
+        let fields: Vec<&str> = line.split('\t').collect();
+        assert_eq!(fields[1], "work, \"main\"");
+        assert_eq!(fields[6], "sk-1•••••7890");
+        assert!(!tsv.contains("sk-1234567890"));
+    }
+
+    #[test]
+    fn test_dedupe_names_keeps_ids() {
+        let dir = std::env::temp_dir().join(format!("ccs_dedupe_{}", uuid::Uuid::new_v4()));
+        let store = CredentialStore {
+            store: SavedCredentialStore::new_with_dir(dir.clone()),
+        };
+
+        let mut first = CredentialData::new("work".into(), "sk-a".into(), TemplateType::Zai);
+        first.created_at = "2025-01-01 00:00:00 UTC".into();
+        let mut second = CredentialData::new("work".into(), "sk-b".into(), TemplateType::Kimi);
+        second.created_at = "2025-02-01 00:00:00 UTC".into();
+        store.store.save(&first).unwrap();
+        store.store.save(&second).unwrap();
+
+        assert_eq!(store.store.find_by_name("work").unwrap().len(), 2);
+        assert!(store.rename_by_name("work", "home").is_err());
+
+        let renamed = store.dedupe_names().unwrap();
+        assert_eq!(
+            renamed,
+            vec![(second.id().to_string(), "work-2".to_string())]
+        );
+        assert_eq!(store.store.load(first.id()).unwrap().name(), "work");
+        assert_eq!(store.store.load(second.id()).unwrap().name(), "work-2");
+        assert!(store.dedupe_names().unwrap().is_empty());
+
+        let moved = store.rename_by_name("work-2", "home").unwrap();
+        assert_eq!(moved.id(), second.id());
+        assert!(store.rename_by_name("home", "work").is_err());
+        assert!(store.rename_by_name("missing", "x").is_err());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn concurrent_metadata_updates_are_not_lost() {
+        let dir = std::env::temp_dir().join(format!("ccs_meta_lock_{}", uuid::Uuid::new_v4()));
+        let store = CredentialStore {
+            store: SavedCredentialStore::new_with_dir(dir.clone()),
+        };
+        let credential = CredentialData::new("work".into(), "sk-a".into(), TemplateType::Zai);
+        store.store.save(&credential).unwrap();
+
+        // Each thread's load → modify → save holds the store lock, so no
+        // thread overwrites another's entry with a stale copy
+        std::thread::scope(|scope| {
+            for thread in 0..4 {
+                let store = &store;
+                let id = credential.id();
+                scope.spawn(move || {
+                    for i in 0..5 {
+                        store
+                            .set_metadata_entry(id, &format!("k{}-{}", thread, i), "v")
+                            .unwrap();
+                    }
+                });
+            }
+        });
+        let metadata = store
+            .store
+            .load(credential.id())
+            .unwrap()
+            .metadata()
+            .cloned();
+        assert_eq!(metadata.unwrap().len(), 20);
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_rename_by_id() {
+        let dir = std::env::temp_dir().join(format!("ccs_rename_{}", uuid::Uuid::new_v4()));
+        let store = CredentialStore {
+            store: SavedCredentialStore::new_with_dir(dir.clone()),
+        };
+
+        let mut work = CredentialData::new("work".into(), "sk-a".into(), TemplateType::Zai);
+        work.updated_at = "2025-01-01 00:00:00 UTC".into();
+        let home = CredentialData::new("home".into(), "sk-b".into(), TemplateType::Zai);
+        let other = CredentialData::new("personal".into(), "sk-c".into(), TemplateType::Kimi);
+        for credential in [&work, &home, &other] {
+            store.store.save(credential).unwrap();
+        }
+
+        store.rename_by_id(work.id(), " office ").unwrap();
+        let reloaded = store.store.load(work.id()).unwrap();
+        assert_eq!(reloaded.name(), "office");
+        assert!(reloaded.updated_at() > "2025-01-01 00:00:00 UTC");
+
+        // Names are unique across templates, not just within one
+        assert!(store.rename_by_id(work.id(), "home").is_err());
+        assert!(store.rename_by_id(work.id(), "personal").is_err());
+        assert!(store.rename_by_id(work.id(), "  ").is_err());
+        assert!(store.rename_by_id("missing", "x").is_err());
+        // Keeping its own name is fine
+        store.rename_by_id(work.id(), "office").unwrap();
+        assert_eq!(store.store.load(work.id()).unwrap().name(), "office");
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_find_by_template_and_key_prefix() {
+        let dir = std::env::temp_dir().join(format!("ccs_find_{}", uuid::Uuid::new_v4()));
+        let store = SavedCredentialStore::new_with_dir(dir.clone());
+        let fixtures = [
+            CredentialData::new("ds-work".into(), "sk-ab12".into(), TemplateType::DeepSeek),
+            CredentialData::new("ds-home".into(), "sk-cd34".into(), TemplateType::DeepSeek),
+            CredentialData::new("zai".into(), "sk-ab56".into(), TemplateType::Zai),
+        ];
+        for credential in &fixtures {
+            store.save(credential).unwrap();
+        }
+        let names = |found: Vec<SavedCredential>| {
+            let mut names: Vec<String> = found.iter().map(|c| c.name().to_string()).collect();
+            names.sort();
+            names
+        };
+
+        let deepseek = store
+            .find_by_template_type(&TemplateType::DeepSeek)
+            .unwrap();
+        assert_eq!(names(deepseek), ["ds-home", "ds-work"]);
+
+        let by_prefix = store.find_by_key_prefix("sk-ab").unwrap();
+        assert_eq!(names(by_prefix), ["ds-work", "zai"]);
+        assert!(store.find_by_key_prefix("sk-zz").unwrap().is_empty());
+        assert!(store.find_by_key_prefix(" ").is_err());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_selection_records_use_and_prune_drops_stale() {
+        let dir = std::env::temp_dir().join(format!("ccs_prune_{}", uuid::Uuid::new_v4()));
+        let store = CredentialStore {
+            store: SavedCredentialStore::new_with_dir(dir.clone()),
+        };
+        let long_ago = "2020-01-01 00:00:00 UTC";
+        for name in ["picked", "stale", "fresh"] {
+            let mut credential =
+                CredentialData::new(name.into(), format!("sk-{}", name), TemplateType::Zai);
+            if name != "fresh" {
+                credential.created_at = long_ago.into();
+            }
+            store.store.save(&credential).unwrap();
+        }
+        let picked = store.store.find_by_name("picked").unwrap().remove(0);
+        assert_eq!(picked.last_used_at(), None);
+
+        record_use(
+            &store,
+            &ApiKeySource::Saved {
+                credential: picked.clone(),
+            },
+        );
+        let used = store.store.load(picked.id()).unwrap();
+        assert!(used.last_used_at().is_some());
+        assert!(!used.is_unused_since(Utc::now() - chrono::Duration::days(90)));
+
+        let candidates = store
+            .unused_credentials(chrono::Duration::days(90))
+            .unwrap();
+        let pruned = store.prune_unused(&candidates).unwrap();
+        assert_eq!(pruned, ["stale"]);
+        // Already gone: nothing left to delete
+        assert!(store.prune_unused(&candidates).unwrap().is_empty());
+        // Too long ago to represent: nothing is that old
+        assert!(
+            store
+                .unused_credentials(chrono::TimeDelta::MAX)
+                .unwrap()
+                .is_empty()
+        );
+        let mut left: Vec<String> = store
+            .store
+            .list()
+            .unwrap()
+            .into_iter()
+            .map(|credential| credential.name)
+            .collect();
+        left.sort();
+        assert_eq!(left, ["fresh", "picked"]);
+
+        // Files written before last_used_at existed still load
+        let legacy: CredentialData = serde_json::from_str(&format!(
+            r#"{{"version": "v2", "id": "x", "name": "old", "api_key": "sk-old",
+                "template_type": "Zai", "created_at": "{0}", "updated_at": "{0}",
+                "metadata": null}}"#,
+            long_ago
+        ))
+        .unwrap();
+        assert_eq!(legacy.last_used_at(), None);
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_clear_template_credentials_keeps_others() {
+        let dir = std::env::temp_dir().join(format!("ccs_clear_{}", uuid::Uuid::new_v4()));
+        let store = CredentialStore {
+            store: SavedCredentialStore::new_with_dir(dir.clone()),
+        };
+        for (name, template_type) in [
+            ("kimi-old", TemplateType::Kimi),
+            ("kimi-new", TemplateType::Kimi),
+            ("zai", TemplateType::Zai),
+        ] {
+            let credential =
+                CredentialData::new(name.into(), format!("sk-{}", name), template_type);
+            store.store.save(&credential).unwrap();
+        }
+
+        assert_eq!(
+            store
+                .clear_template_credentials(&TemplateType::Kimi)
+                .unwrap(),
+            2
+        );
+        let left = store.store.list().unwrap();
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].name(), "zai");
+        assert_eq!(
+            store
+                .clear_template_credentials(&TemplateType::Kimi)
+                .unwrap(),
+            0
+        );
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_metadata_entries() {
+        let dir = std::env::temp_dir().join(format!("ccs_meta_{}", uuid::Uuid::new_v4()));
+        let store = CredentialStore {
+            store: SavedCredentialStore::new_with_dir(dir.clone()),
+        };
+        let zai = CredentialData::new("work".into(), "sk-a".into(), TemplateType::Zai);
+        let kat = CredentialData::new("kat".into(), "sk-b".into(), TemplateType::KatCoder);
+        store.store.save(&zai).unwrap();
+        store.store.save(&kat).unwrap();
+
+        store.set_metadata_entry(zai.id(), "team", "infra").unwrap();
+        let reloaded = store.store.load(zai.id()).unwrap();
+        assert_eq!(reloaded.get_metadata("team").as_deref(), Some("infra"));
+
+        // Reserved keys are validated and normalized
+        assert_eq!(
+            store
+                .set_metadata_entry(zai.id(), "tags", " work, ci ,work,")
+                .unwrap(),
+            "work,ci"
+        );
+        assert!(store.set_metadata_entry(zai.id(), "tags", " , ").is_err());
+        assert!(
+            store
+                .set_metadata_entry(zai.id(), "endpoint_id", "ep-1")
+                .is_err()
+        );
+        assert!(
+            store
+                .set_metadata_entry(kat.id(), "endpoint_id", "nope")
+                .is_err()
+        );
+        store
+            .set_metadata_entry(kat.id(), "endpoint_id", "ep-abc-123")
+            .unwrap();
+        assert_eq!(store.get_endpoint_ids(&TemplateType::KatCoder).len(), 1);
+        assert!(store.set_metadata_entry(zai.id(), "bad key", "x").is_err());
+
+        assert!(store.remove_metadata_entry(zai.id(), "team").unwrap());
+        assert!(!store.remove_metadata_entry(zai.id(), "team").unwrap());
+        let reloaded = store.store.load(zai.id()).unwrap();
+        assert_eq!(reloaded.get_metadata("team"), None);
+        assert_eq!(reloaded.get_metadata("tags").as_deref(), Some("work,ci"));
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_env_file_key_used_without_prompting() {
+        let vars = crate::utils::parse_env_file("# keys\nDEEPSEEK_API_KEY=sk-x\n");
+        // interactive mode: reaching a prompt would fail under the test harness
+        let choice = resolve_api_key(
+            &TemplateType::DeepSeek,
+            None,
+            Some(&vars),
+            None,
+            KeyPreference::Auto,
+            false,
+            false,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(choice.key, "sk-x");
+        assert!(choice.source.is_none());
+
+        // --api-key still wins over the file
+        let choice = resolve_api_key(
+            &TemplateType::DeepSeek,
+            Some("sk-flag"),
+            Some(&vars),
+            None,
+            KeyPreference::Auto,
+            false,
+            true,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(choice.key, "sk-flag");
+    }
+
+    #[test]
+    fn test_pick_api_key_source() {
+        let env: HashMap<&str, &str> = [("DEEPSEEK_API_KEY", "sk-env")].into_iter().collect();
+        let env_sources = env_key_sources(&TemplateType::DeepSeek, |name| {
+            env.get(name).map(|v| v.to_string())
+        });
+        let work = CredentialData::new("work".into(), "sk-work".into(), TemplateType::DeepSeek);
+        let saved = ApiKeySource::Saved {
+            credential: work.clone(),
+        };
+        let auto = KeyPreference::Auto;
+
+        // Env var present and nothing else: taken without asking
+        let picked = pick_api_key_source(&env_sources, None, auto).unwrap();
+        assert_eq!(
+            picked.to_key_ref(),
+            KeyRef::EnvVar("DEEPSEEK_API_KEY".into())
+        );
+        assert!(picked.display().contains("DEEPSEEK_API_KEY"));
+        assert!(!picked.display().contains("sk-env"));
+
+        // A single saved credential
+        let only_saved = [saved.clone()];
+        let picked = pick_api_key_source(&only_saved, None, auto).unwrap();
+        assert_eq!(picked.to_key_ref(), KeyRef::Credential(work.id().into()));
+        assert!(picked.display().contains("work"));
+
+        // Both: remembered or preferred source wins, otherwise apply prompts
+        let both = [saved, env_sources[0].clone()];
+        assert!(pick_api_key_source(&both, None, auto).is_none());
+        let remembered = KeyRef::Credential(work.id().into());
+        assert_eq!(
+            pick_api_key_source(&both, Some(&remembered), auto)
+                .unwrap()
+                .api_key(),
+            "sk-work"
+        );
+        assert_eq!(
+            pick_api_key_source(&both, Some(&remembered), KeyPreference::Env)
+                .unwrap()
+                .api_key(),
+            "sk-env"
+        );
+        assert!(pick_api_key_source(&[], None, KeyPreference::Saved).is_none());
+    }
+
+    #[test]
+    fn test_env_key_precedence() {
+        let env: HashMap<&str, &str> = [
+            ("DEEPSEEK_TOKEN", "sk-alternate"),
+            ("DEEPSEEK_API_KEY", "sk-primary"),
+            ("DEEPSEEK_API_TOKEN", "  "),
+        ]
+        .into_iter()
+        .collect();
+        let sources = env_key_sources(&TemplateType::DeepSeek, |name| {
+            env.get(name).map(|v| v.to_string())
+        });
+        let names: Vec<String> = sources
+            .iter()
+            .map(|s| match s.to_key_ref() {
+                KeyRef::EnvVar(name) => name,
+                other => panic!("unexpected {:?}", other),
+            })
+            .collect();
+        assert_eq!(names, ["DEEPSEEK_API_KEY", "DEEPSEEK_TOKEN"]);
+
+        let saved = ApiKeySource::Saved {
+            credential: CredentialData::new(
+                "work".into(),
+                "sk-saved".into(),
+                TemplateType::DeepSeek,
+            ),
+        };
+        let mixed = [saved, sources[0].clone(), sources[1].clone()];
+        assert_eq!(
+            KeyPreference::Env.pick(&mixed).unwrap().api_key(),
+            "sk-primary"
+        );
+        assert_eq!(
+            KeyPreference::Saved.pick(&mixed).unwrap().api_key(),
+            "sk-saved"
+        );
+        assert!(KeyPreference::Auto.pick(&mixed).is_none());
+        assert!(KeyPreference::Saved.pick(&sources).is_none());
+    }
+
+    #[test]
+    fn test_import_key_from_txt_and_json() {
+        let dir = std::env::temp_dir().join(format!("ccs_import_{}", uuid::Uuid::new_v4()));
+        let store = CredentialStore {
+            store: SavedCredentialStore::new_with_dir(dir.join("credentials")),
+        };
+        fs::create_dir_all(&dir).unwrap();
+
+        let txt = dir.join("deepseek-key.txt");
+        fs::write(&txt, "  sk-from-text-file\n").unwrap();
+        let imported = store
+            .import_from_file(&txt, TemplateType::DeepSeek, None, None)
+            .unwrap();
+        assert_eq!(imported.api_key(), "sk-from-text-file");
+        assert_eq!(imported.name(), "deepseek");
+        // The same key isn't saved twice
+        assert!(
+            store
+                .import_from_file(&txt, TemplateType::DeepSeek, None, None)
+                .is_err()
+        );
+
+        let json = dir.join("key.json");
+        fs::write(
+            &json,
+            r#"{"api_key": "sk-from-json", "data": {"key": "sk-nested"}}"#,
+        )
+        .unwrap();
+        let imported = store
+            .import_from_file(&json, TemplateType::Zai, Some("work"), None)
+            .unwrap();
+        assert_eq!(imported.api_key(), "sk-from-json");
+        assert_eq!(imported.name(), "work");
+        assert_eq!(
+            read_key_file(&json, Some("/data/key")).unwrap(),
+            "sk-nested"
+        );
+        assert!(read_key_file(&json, Some("/missing")).is_err());
+
+        let empty = dir.join("empty.txt");
+        fs::write(&empty, " \n").unwrap();
+        assert!(read_key_file(&empty, None).is_err());
+        assert_eq!(store.store.list().unwrap().len(), 2);
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_new_keys_get_distinct_smart_names() {
+        let dir = std::env::temp_dir().join(format!("ccs_smart_{}", uuid::Uuid::new_v4()));
+        let store = CredentialStore {
+            store: SavedCredentialStore::new_with_dir(dir.clone()),
+        };
+
+        let first = store_new_key(&store, &TemplateType::DeepSeek, "sk-one", None, None).unwrap();
+        let second = store_new_key(&store, &TemplateType::DeepSeek, "sk-two", None, None).unwrap();
+        assert_ne!(first.name(), second.name());
+        assert_eq!(second.name(), format!("{}-2", first.name()));
+
+        let kat = store_new_key(
+            &store,
+            &TemplateType::KatCoder,
+            "sk-kat",
+            Some(""),
+            Some("ep-123"),
+        )
+        .unwrap();
+        let loaded = store.store.load(kat.id()).unwrap();
+        assert_eq!(
+            loaded.get_metadata("endpoint_id").as_deref(),
+            Some("ep-123")
+        );
+        assert_eq!(store.store.list().unwrap().len(), 3);
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
     #[test]
     fn test_mask_api_key() {
-        assert_eq!(mask_api_key("sk-1234567890"), "sk-1•••••7890");
-        assert_eq!(mask_api_key("short"), "••••••••");
+        assert_eq!(
+            mask_api_key("sk-1234567890", MaskPolicy::DEFAULT),
+            "sk-1•••••7890"
+        );
+        assert_eq!(mask_api_key("short", MaskPolicy::DEFAULT), "••••••••");
+    }
+
+    #[test]
+    fn test_credential_expiry() {
+        let now = parse_expiry("2026-06-01 12:00:00 UTC").unwrap();
+        let mut credential = CredentialData::new(
+            "trial".to_string(),
+            "sk-trial".to_string(),
+            TemplateType::Zai,
+        );
+
+        // no expiry → never expired
+        assert!(!credential.is_expired_at(now));
+
+        credential.expires_at = Some("2026-05-31 23:00:00 UTC".to_string());
+        assert!(credential.is_expired_at(now));
+
+        credential.expires_at = Some("2026-06-01".to_string());
+        assert!(!credential.is_expired_at(now));
+
+        credential.expires_at = Some("2026-07-01T00:00:00Z".to_string());
+        assert!(!credential.is_expired_at(now));
+    }
+
+    #[test]
+    fn test_v2_credential_without_expiry_deserializes() {
+        let json = r#"{
+            "version": "v2",
+            "id": "abc",
+            "name": "old",
+            "api_key": "sk-old",
+            "template_type": "DeepSeek",
+            "created_at": "2025-01-01 00:00:00 UTC",
+            "updated_at": "2025-01-01 00:00:00 UTC",
+            "last_used_at": null,
+            "metadata": null
+        }"#;
+
+        let credential: CredentialData = serde_json::from_str(json).unwrap();
+        assert_eq!(credential.expires_at(), None);
+        assert!(!credential.is_expired());
+
+        // and the field stays out of the file until set
+        let out = serde_json::to_string(&credential).unwrap();
+        assert!(!out.contains("expires_at"));
     }
 }
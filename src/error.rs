@@ -0,0 +1,43 @@
+//! Typed errors for the store and template APIs.
+//!
+//! Library-style code (snapshot/credential stores, settings I/O, template
+//! lookup) returns [`CcsError`] so callers can tell a missing snapshot from a
+//! corrupt file. Commands keep using `anyhow`; `?` converts at the boundary.
+
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Errors returned by the store and template APIs.
+#[derive(Debug, Error)]
+pub enum CcsError {
+    #[error("Snapshot '{0}' not found")]
+    SnapshotNotFound(String),
+
+    #[error("Credential '{0}' not found")]
+    CredentialNotFound(String),
+
+    #[error(
+        "Unknown template: {0}. Available templates: deepseek, glm, k2, k2-thinking, kat-coder, kimi, longcat, fishtrip, fish, minimax, seed-code, zenmux, duojie, anyrouter, openrouter, beeapi, day77"
+    )]
+    InvalidTemplate(String),
+
+    #[error("Failed to parse {}: {source}", path.display())]
+    SettingsParse {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    #[error("Failed to read {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+/// Result alias for [`CcsError`]
+pub type CcsResult<T> = std::result::Result<T, CcsError>;
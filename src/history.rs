@@ -0,0 +1,524 @@
+//! Apply history for `ccs undo` / `ccs redo`.
+//!
+//! Every apply appends a record to `~/.claude/ccs-history.jsonl` holding the
+//! settings file's content before and after the write. Undo and redo are
+//! appended as records too, so the log is append-only; the undo/redo stacks
+//! are rebuilt by replaying it. The file is kept owner-only on Unix.
+//!
+//! Secret env values (see [`is_sensitive_env_key`]) are stored as their
+//! sha256 only. Undo and redo look the real value up again in the settings
+//! file, the saved credentials and the snapshots.
+
+use anyhow::{Result, anyhow};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::credentials::SavedCredentialStore;
+use crate::settings::{ClaudeSettings, is_sensitive_env_key};
+use crate::snapshots::SnapshotStore;
+
+/// Number of applies kept; older ones are dropped when the log is compacted.
+pub const HISTORY_LIMIT: usize = 50;
+
+/// Prefix of a secret env value stored as its hash
+const SECRET_HASH_PREFIX: &str = "sha256:";
+
+fn secret_hash(value: &str) -> String {
+    use sha2::{Digest, Sha256};
+    let hex: String = Sha256::digest(value.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("{}{}", SECRET_HASH_PREFIX, hex)
+}
+
+/// `settings` with secret env values replaced by their hashes
+fn redact(settings: &ClaudeSettings) -> ClaudeSettings {
+    let mut redacted = settings.clone();
+    if let Some(env) = redacted.env.as_mut() {
+        for (key, value) in env.iter_mut() {
+            if is_sensitive_env_key(key) && !value.starts_with(SECRET_HASH_PREFIX) {
+                *value = secret_hash(value);
+            }
+        }
+    }
+    redacted
+}
+
+/// One apply: which file was written and its content before/after.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ApplyRecord {
+    /// When the apply happened (UTC)
+    pub at: String,
+    /// What was applied (template or snapshot name)
+    pub label: String,
+    /// Settings file that was written
+    pub settings_path: PathBuf,
+    /// Content before the apply; `None` if the file did not exist
+    pub before: Option<ClaudeSettings>,
+    /// Content written by the apply
+    pub after: ClaudeSettings,
+}
+
+/// A line of the history log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum Record {
+    Apply(Box<ApplyRecord>),
+    Undo { at: String },
+    Redo { at: String },
+}
+
+/// The apply history log.
+pub struct History {
+    path: PathBuf,
+    /// Where undo and redo look up secret values besides the settings file
+    key_stores: Option<(SavedCredentialStore, SnapshotStore)>,
+}
+
+impl History {
+    /// History stored at `path`
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            key_stores: None,
+        }
+    }
+
+    /// Also look up secret values in `credentials` and `snapshots`
+    pub fn with_key_stores(
+        mut self,
+        credentials: SavedCredentialStore,
+        snapshots: SnapshotStore,
+    ) -> Self {
+        self.key_stores = Some((credentials, snapshots));
+        self
+    }
+
+    /// History at the default location, `~/.claude/ccs-history.jsonl`,
+    /// using the default credential and snapshot stores
+    pub fn open_default() -> Self {
+        let home_dir = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
+        Self::new(home_dir.join(".claude").join("ccs-history.jsonl")).with_key_stores(
+            SavedCredentialStore::new_with_dir(crate::utils::get_credentials_dir()),
+            SnapshotStore::new(crate::utils::get_snapshots_dir()),
+        )
+    }
+
+    /// Path of the log file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Record an apply of `after` to `settings_path`.
+    pub fn record_apply(
+        &self,
+        label: &str,
+        settings_path: &Path,
+        before: Option<ClaudeSettings>,
+        after: &ClaudeSettings,
+    ) -> Result<()> {
+        let settings_path = fs::canonicalize(settings_path).unwrap_or(settings_path.to_path_buf());
+        self.append(&Record::Apply(Box::new(ApplyRecord {
+            at: now(),
+            label: label.to_string(),
+            settings_path,
+            before: before.as_ref().map(redact),
+            after: redact(after),
+        })))?;
+        self.compact_if_needed()
+    }
+
+    /// Applies that can be undone (oldest first) and redone (next redo last)
+    pub fn stacks(&self) -> Result<(Vec<ApplyRecord>, Vec<ApplyRecord>)> {
+        let mut done = Vec::new();
+        let mut undone = Vec::new();
+        for record in self.read()? {
+            match record {
+                Record::Apply(apply) => {
+                    done.push(*apply);
+                    undone.clear();
+                }
+                Record::Undo { .. } => {
+                    if let Some(apply) = done.pop() {
+                        undone.push(apply);
+                    }
+                }
+                Record::Redo { .. } => {
+                    if let Some(apply) = undone.pop() {
+                        done.push(apply);
+                    }
+                }
+            }
+        }
+        Ok((done, undone))
+    }
+
+    /// Restore the settings file to its state before the most recent apply.
+    /// Returns the undone apply, or `None` if there is nothing to undo.
+    pub fn undo(&self) -> Result<Option<ApplyRecord>> {
+        let (done, _) = self.stacks()?;
+        let Some(apply) = done.last().cloned() else {
+            return Ok(None);
+        };
+
+        match &apply.before {
+            Some(settings) => self
+                .restore_secrets(settings, &apply.settings_path)?
+                .to_file(&apply.settings_path)?,
+            None => {
+                if apply.settings_path.exists() {
+                    fs::remove_file(&apply.settings_path).map_err(|e| {
+                        anyhow!("Failed to remove {}: {}", apply.settings_path.display(), e)
+                    })?;
+                }
+            }
+        }
+        self.append(&Record::Undo { at: now() })?;
+        Ok(Some(apply))
+    }
+
+    /// Re-apply the most recently undone apply.
+    /// Returns it, or `None` if there is nothing to redo.
+    pub fn redo(&self) -> Result<Option<ApplyRecord>> {
+        let (_, undone) = self.stacks()?;
+        let Some(apply) = undone.last().cloned() else {
+            return Ok(None);
+        };
+
+        self.restore_secrets(&apply.after, &apply.settings_path)?
+            .to_file(&apply.settings_path)?;
+        self.append(&Record::Redo { at: now() })?;
+        Ok(Some(apply))
+    }
+
+    /// `settings` with hashed secret values replaced by the real ones
+    fn restore_secrets(
+        &self,
+        settings: &ClaudeSettings,
+        settings_path: &Path,
+    ) -> Result<ClaudeSettings> {
+        let mut restored = settings.clone();
+        let Some(env) = restored.env.as_mut() else {
+            return Ok(restored);
+        };
+        if !env.values().any(|v| v.starts_with(SECRET_HASH_PREFIX)) {
+            return Ok(restored);
+        }
+
+        let known = self.known_secrets(settings_path)?;
+        for (key, value) in env.iter_mut() {
+            if !value.starts_with(SECRET_HASH_PREFIX) {
+                continue;
+            }
+            *value = known.get(value.as_str()).cloned().ok_or_else(|| {
+                anyhow!(
+                    "Can't restore {}: its value is no longer in {}, a saved credential or a snapshot",
+                    key,
+                    settings_path.display()
+                )
+            })?;
+        }
+        Ok(restored)
+    }
+
+    /// Secret values that can be restored, by hash
+    fn known_secrets(&self, settings_path: &Path) -> Result<HashMap<String, String>> {
+        let mut values = Vec::new();
+        if settings_path.exists() {
+            let current = ClaudeSettings::from_file(settings_path)?;
+            values.extend(current.env.into_iter().flat_map(|env| env.into_values()));
+        }
+        if let Some((credentials, snapshots)) = &self.key_stores {
+            if credentials.credentials_dir.exists() {
+                values.extend(
+                    credentials
+                        .list()?
+                        .iter()
+                        .map(|credential| credential.api_key().to_string()),
+                );
+            }
+            for snapshot in snapshots.list()? {
+                values.extend(
+                    snapshot
+                        .settings
+                        .env
+                        .into_iter()
+                        .flat_map(|env| env.into_values()),
+                );
+            }
+        }
+        Ok(values
+            .into_iter()
+            .map(|value| (secret_hash(&value), value))
+            .collect())
+    }
+
+    fn read(&self) -> Result<Vec<Record>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let content = fs::read_to_string(&self.path)
+            .map_err(|e| anyhow!("Failed to read history {}: {}", self.path.display(), e))?;
+        // Skip lines that don't parse (e.g. a torn final write)
+        Ok(content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+
+    fn append(&self, record: &Record) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| anyhow!("Failed to create history dir {}: {}", parent.display(), e))?;
+        }
+        let line = serde_json::to_string(record)
+            .map_err(|e| anyhow!("Failed to serialize history record: {}", e))?;
+
+        let mut options = OpenOptions::new();
+        options.create(true).append(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options
+            .open(&self.path)
+            .map_err(|e| anyhow!("Failed to open history {}: {}", self.path.display(), e))?;
+        writeln!(file, "{}", line)
+            .map_err(|e| anyhow!("Failed to write history {}: {}", self.path.display(), e))
+    }
+
+    /// Keep the log bounded: once it holds more than twice the limit, rewrite
+    /// it with the last `HISTORY_LIMIT` undoable applies. Only called right
+    /// after an apply, when the redo stack is empty anyway.
+    fn compact_if_needed(&self) -> Result<()> {
+        let records = self.read()?;
+        if records.len() <= HISTORY_LIMIT * 2 {
+            return Ok(());
+        }
+
+        let (done, _) = self.stacks()?;
+        let keep = &done[done.len().saturating_sub(HISTORY_LIMIT)..];
+        let mut content = String::new();
+        for apply in keep {
+            // Logs written before secrets were hashed get hashed here
+            let apply = ApplyRecord {
+                before: apply.before.as_ref().map(redact),
+                after: redact(&apply.after),
+                ..apply.clone()
+            };
+            let line = serde_json::to_string(&Record::Apply(Box::new(apply)))
+                .map_err(|e| anyhow!("Failed to serialize history record: {}", e))?;
+            content.push_str(&line);
+            content.push('\n');
+        }
+        crate::utils::write_atomic(&self.path, content.as_bytes())
+            .map_err(|e| anyhow!("Failed to compact history {}: {}", self.path.display(), e))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&self.path, fs::Permissions::from_mode(0o600)).map_err(|e| {
+                anyhow!("Failed to restrict history {}: {}", self.path.display(), e)
+            })?;
+        }
+        Ok(())
+    }
+}
+
+fn now() -> String {
+    Utc::now().format("%Y-%m-%d %H:%M:%S UTC").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings_with_model(model: &str) -> ClaudeSettings {
+        ClaudeSettings {
+            model: Some(model.to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// Write `next` to the settings file the way apply does, recording it.
+    fn apply(history: &History, path: &Path, next: &ClaudeSettings) {
+        let before = path
+            .exists()
+            .then(|| ClaudeSettings::from_file(path).unwrap());
+        next.to_file(path).unwrap();
+        history.record_apply("test", path, before, next).unwrap();
+    }
+
+    #[test]
+    fn test_undo_redo_walks_through_states() {
+        let dir = std::env::temp_dir().join(format!("ccs_history_{}", uuid::Uuid::new_v4()));
+        let settings_path = dir.join("settings.json");
+        let history = History::new(dir.join("ccs-history.jsonl"));
+        let current = || ClaudeSettings::from_file(&settings_path).unwrap().model;
+
+        settings_with_model("base").to_file(&settings_path).unwrap();
+        apply(&history, &settings_path, &settings_with_model("first"));
+        apply(&history, &settings_path, &settings_with_model("second"));
+        assert_eq!(current().as_deref(), Some("second"));
+
+        assert_eq!(
+            history.undo().unwrap().unwrap().after.model.as_deref(),
+            Some("second")
+        );
+        assert_eq!(current().as_deref(), Some("first"));
+        history.undo().unwrap().unwrap();
+        assert_eq!(current().as_deref(), Some("base"));
+        assert!(history.undo().unwrap().is_none());
+
+        history.redo().unwrap().unwrap();
+        assert_eq!(current().as_deref(), Some("first"));
+        history.redo().unwrap().unwrap();
+        assert_eq!(current().as_deref(), Some("second"));
+        assert!(history.redo().unwrap().is_none());
+
+        // A new apply after an undo discards the redo stack
+        history.undo().unwrap();
+        apply(&history, &settings_path, &settings_with_model("third"));
+        assert!(history.redo().unwrap().is_none());
+        history.undo().unwrap();
+        assert_eq!(current().as_deref(), Some("first"));
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_undo_removes_file_created_by_apply() {
+        let dir = std::env::temp_dir().join(format!("ccs_history_{}", uuid::Uuid::new_v4()));
+        let settings_path = dir.join("settings.json");
+        let history = History::new(dir.join("ccs-history.jsonl"));
+
+        apply(&history, &settings_path, &settings_with_model("new"));
+        assert!(settings_path.exists());
+        history.undo().unwrap();
+        assert!(!settings_path.exists());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    fn settings_with_token(token: &str) -> ClaudeSettings {
+        ClaudeSettings {
+            env: Some(HashMap::from([(
+                "ANTHROPIC_AUTH_TOKEN".to_string(),
+                token.to_string(),
+            )])),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_secrets_are_hashed_and_restored_from_stores() {
+        let dir = std::env::temp_dir().join(format!("ccs_history_{}", uuid::Uuid::new_v4()));
+        let settings_path = dir.join("settings.json");
+        let credentials = SavedCredentialStore::new_with_dir(dir.join("credentials"));
+        let snapshots = SnapshotStore::new(dir.join("snapshots"));
+        credentials
+            .save(&crate::credentials::CredentialData::new(
+                "old".to_string(),
+                "sk-old".to_string(),
+                crate::templates::TemplateType::Zai,
+            ))
+            .unwrap();
+        let history =
+            History::new(dir.join("ccs-history.jsonl")).with_key_stores(credentials, snapshots);
+        let token = || {
+            ClaudeSettings::from_file(&settings_path)
+                .unwrap()
+                .env
+                .unwrap()["ANTHROPIC_AUTH_TOKEN"]
+                .clone()
+        };
+
+        settings_with_token("sk-old")
+            .to_file(&settings_path)
+            .unwrap();
+        apply(&history, &settings_path, &settings_with_token("sk-new"));
+        let log = fs::read_to_string(history.path()).unwrap();
+        assert!(
+            !log.contains("sk-old") && !log.contains("sk-new"),
+            "{}",
+            log
+        );
+
+        // The old key comes back from the credential store
+        history.undo().unwrap();
+        assert_eq!(token(), "sk-old");
+
+        // The new key is nowhere any more: redo refuses instead of writing a hash
+        assert!(history.redo().is_err());
+        assert_eq!(token(), "sk-old");
+
+        let (_, snapshots) = history.key_stores.as_ref().unwrap();
+        snapshots
+            .save(&crate::snapshots::Snapshot::new(
+                "new".to_string(),
+                settings_with_token("sk-new"),
+                crate::snapshots::SnapshotScope::Env,
+                None,
+            ))
+            .unwrap();
+        history.redo().unwrap();
+        assert_eq!(token(), "sk-new");
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_compacted_history_stays_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("ccs_history_{}", uuid::Uuid::new_v4()));
+        let settings_path = dir.join("settings.json");
+        let history = History::new(dir.join("ccs-history.jsonl"));
+
+        apply(&history, &settings_path, &settings_with_model("0"));
+        fs::set_permissions(history.path(), fs::Permissions::from_mode(0o644)).unwrap();
+        for i in 1..=(HISTORY_LIMIT * 2) {
+            apply(
+                &history,
+                &settings_path,
+                &settings_with_model(&i.to_string()),
+            );
+        }
+        assert!(history.read().unwrap().len() <= HISTORY_LIMIT);
+        let mode = fs::metadata(history.path()).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let dir = std::env::temp_dir().join(format!("ccs_history_{}", uuid::Uuid::new_v4()));
+        let settings_path = dir.join("settings.json");
+        let history = History::new(dir.join("ccs-history.jsonl"));
+
+        for i in 0..(HISTORY_LIMIT * 2 + 5) {
+            apply(
+                &history,
+                &settings_path,
+                &settings_with_model(&i.to_string()),
+            );
+        }
+        let (done, _) = history.stacks().unwrap();
+        assert!(done.len() <= HISTORY_LIMIT * 2);
+        assert!(history.read().unwrap().len() <= HISTORY_LIMIT * 2);
+        assert_eq!(
+            done.last().unwrap().after.model.as_deref(),
+            Some((HISTORY_LIMIT * 2 + 4).to_string().as_str())
+        );
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -9,11 +9,19 @@ use crate::cli::Cli;
 
 pub mod cli;
 pub mod commands;
+pub mod completions;
+pub mod config;
 pub mod credentials;
+pub mod error;
+pub mod history;
+pub mod preflight;
 pub mod prefs;
+pub mod repair;
+pub mod reporter;
 pub mod selectors;
 pub mod settings;
 pub mod snapshots;
+pub mod storage;
 pub mod templates;
 pub mod tui;
 pub mod utils;
@@ -64,7 +72,13 @@ fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
 
     // Run the command
-    commands::run_command(&cli)?;
+    let reporter = if cli.settings_on_stdout() {
+        reporter::ConsoleReporter::with_writer(cli.quiet, Box::new(std::io::stderr()))
+    } else {
+        reporter::ConsoleReporter::new(cli.quiet)
+    }
+    .with_verbosity(cli.verbose);
+    commands::run_command(&cli, &reporter)?;
 
     Ok(())
 }
@@ -86,6 +100,15 @@ mod tests {
         assert_eq!(format!("{}", TemplateType::Duojie), "duojie");
     }
 
+    #[test]
+    fn test_bare_invocation_has_no_command() {
+        let cli = Cli::parse_from(["ccs"]);
+        assert!(cli.command.is_none());
+
+        let cli = Cli::parse_from(["ccs", "current"]);
+        assert!(matches!(cli.command, Some(cli::Commands::Current { .. })));
+    }
+
     #[test]
     fn test_selector_error_creation() {
         let cancelled_error = SelectorError::Cancelled;
@@ -171,4 +194,68 @@ mod tests {
         );
         assert_eq!("dj".parse::<TemplateType>().unwrap(), TemplateType::Duojie);
     }
+
+    #[test]
+    fn test_gateway_templates_registered() {
+        use crate::selectors::template::TemplateSelector;
+        use crate::templates::{get_all_templates, get_template_instance, get_template_type};
+
+        let choices = TemplateSelector::template_choices();
+        for name in ["anyrouter", "fishtrip", "duojie"] {
+            let template_type = get_template_type(name).unwrap();
+            // Display round-trips back to the same type
+            assert_eq!(template_type.to_string(), name);
+            assert_eq!(
+                get_template_type(&template_type.to_string()).unwrap(),
+                template_type
+            );
+            assert!(get_all_templates().contains(&template_type));
+            assert_eq!(
+                get_template_instance(&template_type).template_type(),
+                template_type
+            );
+            assert!(choices.iter().any(|(t, _)| t == &template_type));
+        }
+    }
+
+    #[test]
+    fn test_every_template_produces_serializable_settings() {
+        use crate::settings::ClaudeSettings;
+        use crate::templates::{
+            get_all_templates, get_template_instance_with_input, variant_options,
+        };
+
+        for template_type in get_all_templates() {
+            let mut inputs: Vec<String> = variant_options(&template_type)
+                .into_iter()
+                .map(|(alias, _)| alias.to_string())
+                .collect();
+            inputs.push(template_type.to_string());
+
+            for input in inputs {
+                let template = get_template_instance_with_input(&template_type, &input);
+                for scope in [
+                    snapshots::SnapshotScope::Env,
+                    snapshots::SnapshotScope::Common,
+                    snapshots::SnapshotScope::All,
+                ] {
+                    let settings = template.create_settings("sk-test", &scope);
+                    let json = serde_json::to_value(&settings).unwrap();
+                    // Only the env-map shape of ClaudeSettings is used
+                    for legacy in ["provider", "endpoint", "http", "environment"] {
+                        assert!(json.get(legacy).is_none(), "{} set {}", input, legacy);
+                    }
+                    let env = settings.env.as_ref().expect("templates always set env");
+                    assert!(
+                        env.contains_key("ANTHROPIC_BASE_URL"),
+                        "{} has no base URL",
+                        input
+                    );
+
+                    let round_trip: ClaudeSettings = serde_json::from_value(json).unwrap();
+                    assert_eq!(round_trip, settings, "{} did not round-trip", input);
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,162 @@
+//! Connectivity check for `apply --preflight`.
+//!
+//! Before a template is written, its `ANTHROPIC_BASE_URL` is probed with a
+//! short-timeout HEAD request. Any HTTP response counts as reachable (an
+//! API root often answers 404 or 401); only connection failures and timeouts
+//! don't.
+
+use anyhow::{Result, anyhow};
+use std::time::Duration;
+
+use crate::settings::ClaudeSettings;
+
+/// How long the probe waits before calling the endpoint unreachable
+pub const PREFLIGHT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What `apply` does with the preflight result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Preflight {
+    /// No check
+    #[default]
+    Off,
+    /// Warn if unreachable, then apply anyway
+    Warn,
+    /// Refuse to apply if unreachable
+    Strict,
+}
+
+impl Preflight {
+    /// Mode from `--preflight` / `--strict`
+    pub fn from_flags(preflight: bool, strict: bool) -> Self {
+        match (preflight, strict) {
+            (_, true) => Preflight::Strict,
+            (true, false) => Preflight::Warn,
+            (false, false) => Preflight::Off,
+        }
+    }
+}
+
+/// Probes whether an endpoint answers at all.
+pub trait EndpointCheck {
+    /// HTTP status of the response, or an error if nothing answered
+    fn check(&self, url: &str) -> Result<u16>;
+}
+
+/// [`EndpointCheck`] over HTTP with a short timeout.
+pub struct HttpCheck {
+    pub timeout: Duration,
+}
+
+impl Default for HttpCheck {
+    fn default() -> Self {
+        Self {
+            timeout: PREFLIGHT_TIMEOUT,
+        }
+    }
+}
+
+impl EndpointCheck for HttpCheck {
+    fn check(&self, url: &str) -> Result<u16> {
+        let client = reqwest::blocking::Client::builder()
+            .timeout(self.timeout)
+            .build()
+            .map_err(|e| anyhow!("Failed to create HTTP client: {}", e))?;
+        let response = client
+            .head(url)
+            .header("User-Agent", "claude-code-switcher")
+            .send()
+            .map_err(|e| anyhow!("{}", e))?;
+        Ok(response.status().as_u16())
+    }
+}
+
+/// Check the base URL `settings` would point Claude Code at.
+///
+/// Returns a warning to show if it is unreachable, or an error instead when
+/// `mode` is [`Preflight::Strict`]. Settings without a base URL (or
+/// [`Preflight::Off`]) pass without a check.
+pub fn run_preflight(
+    checker: &dyn EndpointCheck,
+    settings: &ClaudeSettings,
+    mode: Preflight,
+) -> Result<Option<String>> {
+    if mode == Preflight::Off {
+        return Ok(None);
+    }
+    let Some(base_url) = settings
+        .env
+        .as_ref()
+        .and_then(|env| env.get("ANTHROPIC_BASE_URL"))
+    else {
+        return Ok(None);
+    };
+
+    match checker.check(base_url) {
+        Ok(_) => Ok(None),
+        Err(e) if mode == Preflight::Strict => Err(anyhow!(
+            "{} is unreachable ({}); not applying (drop --strict to apply anyway)",
+            base_url,
+            e
+        )),
+        Err(e) => Ok(Some(format!("{} is unreachable: {}", base_url, e))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct MockCheck(std::result::Result<u16, &'static str>);
+
+    impl EndpointCheck for MockCheck {
+        fn check(&self, _url: &str) -> Result<u16> {
+            self.0.map_err(|e| anyhow!(e))
+        }
+    }
+
+    fn settings_with_base_url() -> ClaudeSettings {
+        ClaudeSettings {
+            env: Some(
+                [(
+                    "ANTHROPIC_BASE_URL".to_string(),
+                    "https://api.example.com/anthropic".to_string(),
+                )]
+                .into_iter()
+                .collect(),
+            ),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_preflight_warns_only_when_unreachable() {
+        let settings = settings_with_base_url();
+        let ok = MockCheck(Ok(200));
+        let refused = MockCheck(Err("connection refused"));
+
+        assert_eq!(
+            run_preflight(&ok, &settings, Preflight::Warn).unwrap(),
+            None
+        );
+        assert_eq!(
+            run_preflight(&ok, &settings, Preflight::Strict).unwrap(),
+            None
+        );
+
+        let warning = run_preflight(&refused, &settings, Preflight::Warn)
+            .unwrap()
+            .unwrap();
+        assert!(warning.contains("https://api.example.com/anthropic"));
+        assert!(warning.contains("connection refused"));
+
+        assert!(run_preflight(&refused, &settings, Preflight::Strict).is_err());
+        assert_eq!(
+            run_preflight(&refused, &settings, Preflight::Off).unwrap(),
+            None
+        );
+        assert_eq!(
+            run_preflight(&refused, &ClaudeSettings::new(), Preflight::Strict).unwrap(),
+            None
+        );
+    }
+}
@@ -26,6 +26,15 @@ pub enum KeyRef {
     EnvVar(String),
 }
 
+impl std::fmt::Display for KeyRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeyRef::Credential(id) => write!(f, "saved credential {}", id),
+            KeyRef::EnvVar(name) => write!(f, "${}", name),
+        }
+    }
+}
+
 /// Remembered choices for a single template type.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TemplatePref {
@@ -215,6 +224,7 @@ impl Prefs {
     }
 
     /// Record everything from a completed apply in one go.
+    #[allow(clippy::too_many_arguments)]
     pub fn record_apply(
         &mut self,
         template_type: &TemplateType,
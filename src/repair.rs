@@ -0,0 +1,173 @@
+//! Detection and quarantine of unparseable snapshot/credential files.
+//!
+//! `SnapshotStore::list` and `SavedCredentialStore::list` skip files they
+//! cannot parse; `ccs repair` surfaces those files and moves them aside.
+
+use crate::credentials::CredentialData;
+use crate::snapshots::Snapshot;
+use anyhow::{Result, anyhow};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the quarantine subfolder created inside each scanned directory.
+pub const QUARANTINE_DIR: &str = ".corrupt";
+
+/// Which store a finding belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepairKind {
+    Snapshot,
+    Credential,
+}
+
+impl std::fmt::Display for RepairKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RepairKind::Snapshot => write!(f, "snapshot"),
+            RepairKind::Credential => write!(f, "credential"),
+        }
+    }
+}
+
+/// A file that could not be read or parsed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepairFinding {
+    pub kind: RepairKind,
+    pub path: PathBuf,
+    pub error: String,
+}
+
+impl RepairFinding {
+    /// Move the file into the `.corrupt` folder next to it.
+    /// Returns the new location.
+    pub fn quarantine(&self) -> Result<PathBuf> {
+        let dir = self
+            .path
+            .parent()
+            .ok_or_else(|| anyhow!("{} has no parent directory", self.path.display()))?
+            .join(QUARANTINE_DIR);
+        fs::create_dir_all(&dir)
+            .map_err(|e| anyhow!("Failed to create {}: {}", dir.display(), e))?;
+
+        let file_name = self
+            .path
+            .file_name()
+            .ok_or_else(|| anyhow!("Invalid file path {}", self.path.display()))?;
+        let mut target = dir.join(file_name);
+        if target.exists() {
+            let stamp = chrono::Utc::now().format("%Y%m%d%H%M%S");
+            target = dir.join(format!("{}.{}", file_name.to_string_lossy(), stamp));
+        }
+
+        fs::rename(&self.path, &target).map_err(|e| {
+            anyhow!(
+                "Failed to move {} to {}: {}",
+                self.path.display(),
+                target.display(),
+                e
+            )
+        })?;
+        Ok(target)
+    }
+}
+
+/// Scan a snapshots directory for unparseable files.
+pub fn scan_snapshots(dir: &Path) -> Result<Vec<RepairFinding>> {
+    scan_dir(dir, RepairKind::Snapshot, |content| {
+        serde_json::from_str::<Snapshot>(content).map(|_| ())
+    })
+}
+
+/// Scan a credentials directory for unparseable files.
+pub fn scan_credentials(dir: &Path) -> Result<Vec<RepairFinding>> {
+    scan_dir(dir, RepairKind::Credential, |content| {
+        serde_json::from_str::<CredentialData>(content).map(|_| ())
+    })
+}
+
+fn scan_dir(
+    dir: &Path,
+    kind: RepairKind,
+    parse: impl Fn(&str) -> serde_json::Result<()>,
+) -> Result<Vec<RepairFinding>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut findings = Vec::new();
+    let entries =
+        fs::read_dir(dir).map_err(|e| anyhow!("Failed to read {}: {}", dir.display(), e))?;
+    for entry in entries {
+        let path = entry?.path();
+        if !path.is_file() || path.extension().and_then(|s| s.to_str()) != Some("json") {
+            continue;
+        }
+
+        let error = match fs::read_to_string(&path) {
+            Ok(content) => parse(&content).err().map(|e| e.to_string()),
+            Err(e) => Some(e.to_string()),
+        };
+        if let Some(error) = error {
+            findings.push(RepairFinding { kind, path, error });
+        }
+    }
+
+    findings.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(findings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::ClaudeSettings;
+    use crate::snapshots::{SnapshotScope, SnapshotStore};
+
+    #[test]
+    fn test_corrupt_snapshot_detected_and_quarantined() {
+        let dir = std::env::temp_dir().join(format!("ccs_repair_{}", uuid::Uuid::new_v4()));
+        let store = SnapshotStore::new(dir.clone());
+        store
+            .save(&Snapshot::new(
+                "good".to_string(),
+                ClaudeSettings::new(),
+                SnapshotScope::Common,
+                None,
+            ))
+            .unwrap();
+        let broken = dir.join("broken.json");
+        fs::write(&broken, "{ \"id\": \"broken\", ").unwrap();
+
+        let findings = scan_snapshots(&dir).unwrap();
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].kind, RepairKind::Snapshot);
+        assert_eq!(findings[0].path, broken);
+        assert!(!findings[0].error.is_empty());
+
+        let moved = findings[0].quarantine().unwrap();
+        assert!(!broken.exists());
+        assert_eq!(moved, dir.join(QUARANTINE_DIR).join("broken.json"));
+        assert!(scan_snapshots(&dir).unwrap().is_empty());
+        assert_eq!(store.list().unwrap().len(), 1);
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_credential_scan_ignores_valid_files() {
+        let dir = std::env::temp_dir().join(format!("ccs_repair_{}", uuid::Uuid::new_v4()));
+        let store = crate::credentials::SavedCredentialStore::new_with_dir(dir.clone());
+        store
+            .save(&CredentialData::new(
+                "ok".to_string(),
+                "sk-ok".to_string(),
+                crate::templates::TemplateType::Zai,
+            ))
+            .unwrap();
+        fs::write(dir.join("bad.json"), "[]").unwrap();
+
+        let findings = scan_credentials(&dir).unwrap();
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].kind, RepairKind::Credential);
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,257 @@
+//! Status output for commands.
+//!
+//! Commands report progress ("✓ Applied …") through a [`Reporter`] instead of
+//! printing directly, so `--quiet` can silence it and the commands can run
+//! behind another front end. [`ConsoleReporter`] is the terminal
+//! implementation; [`BufferReporter`] collects messages in memory. Output that
+//! is the point of a command (listings, `--json`, `--print-env`, dry-run
+//! previews) is still printed directly, except `--print-diff-only` diffs. `-v`
+//! / `-vv` raise the verbosity so `debug` and `trace` lines show up.
+
+use console::style;
+use std::cell::RefCell;
+use std::fmt::Display;
+use std::io::Write;
+
+/// Kind of a reported message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    /// A completed action
+    Success,
+    /// Progress or detail
+    Info,
+    /// Decisions made along the way; `-v`
+    Debug,
+    /// Files read or written, HTTP calls made; `-vv`
+    Trace,
+    /// Something the user should look at
+    Warn,
+    /// A failure that didn't abort the command
+    Error,
+    /// A settings diff (`--print-diff-only`), already formatted
+    Diff,
+}
+
+/// Destination for command status output
+pub trait Reporter {
+    /// Record one message
+    fn report(&self, level: Level, message: &str);
+}
+
+/// Convenience methods, so callers don't format messages themselves
+impl dyn Reporter + '_ {
+    /// A completed action: `✓ message`
+    pub fn success(&self, message: impl Display) {
+        self.report(Level::Success, &message.to_string());
+    }
+
+    /// Progress or detail line, printed as-is
+    pub fn info(&self, message: impl Display) {
+        self.report(Level::Info, &message.to_string());
+    }
+
+    /// Decisions made along the way (merge strategy, key source); shown with `-v`
+    pub fn debug(&self, message: impl Display) {
+        self.report(Level::Debug, &message.to_string());
+    }
+
+    /// Files read or written and HTTP calls made; shown with `-vv`
+    pub fn trace(&self, message: impl Display) {
+        self.report(Level::Trace, &message.to_string());
+    }
+
+    /// A warning
+    pub fn warn(&self, message: impl Display) {
+        self.report(Level::Warn, &message.to_string());
+    }
+
+    /// An error that didn't stop the command
+    pub fn error(&self, message: impl Display) {
+        self.report(Level::Error, &message.to_string());
+    }
+
+    /// A formatted settings diff
+    pub fn diff(&self, diff: impl Display) {
+        self.report(Level::Diff, &diff.to_string());
+    }
+}
+
+/// Writes status messages to stdout unless quiet; warnings and errors go to
+/// stderr.
+pub struct ConsoleReporter {
+    quiet: bool,
+    verbosity: u8,
+    out: RefCell<Box<dyn Write>>,
+}
+
+impl ConsoleReporter {
+    /// Reporter writing to stdout
+    pub fn new(quiet: bool) -> Self {
+        Self::with_writer(quiet, Box::new(std::io::stdout()))
+    }
+
+    /// Reporter writing to `out` (e.g. a buffer in tests)
+    pub fn with_writer(quiet: bool, out: Box<dyn Write>) -> Self {
+        Self {
+            quiet,
+            verbosity: 0,
+            out: RefCell::new(out),
+        }
+    }
+
+    /// Set the verbosity (number of `-v` flags)
+    pub fn with_verbosity(mut self, verbosity: u8) -> Self {
+        self.verbosity = verbosity;
+        self
+    }
+
+    /// Whether status messages are suppressed
+    pub fn is_quiet(&self) -> bool {
+        self.quiet
+    }
+
+    fn write(&self, text: std::fmt::Arguments<'_>) {
+        let mut out = self.out.borrow_mut();
+        // Status output is best-effort (e.g. a closed pipe)
+        let _ = out.write_fmt(text);
+    }
+}
+
+impl Reporter for ConsoleReporter {
+    fn report(&self, level: Level, message: &str) {
+        match level {
+            Level::Warn => eprintln!("{} {}", style("⚠").yellow(), message),
+            Level::Error => eprintln!("{} {}", style("✗").red().bold(), message),
+            // The diff is the output --print-diff-only asked for
+            Level::Diff => self.write(format_args!("{}", message)),
+            _ if self.quiet => {}
+            Level::Success => {
+                self.write(format_args!("{} {}\n", style("✓").green().bold(), message))
+            }
+            Level::Info => self.write(format_args!("{}\n", message)),
+            Level::Debug if self.verbosity >= 1 => {
+                self.write(format_args!("{}\n", style(message).dim()))
+            }
+            Level::Trace if self.verbosity >= 2 => {
+                self.write(format_args!("{}\n", style(message).dim()))
+            }
+            Level::Debug | Level::Trace => {}
+        }
+    }
+}
+
+/// Keeps every message in memory, for embedding or inspecting output
+#[derive(Default)]
+pub struct BufferReporter {
+    messages: RefCell<Vec<(Level, String)>>,
+}
+
+impl BufferReporter {
+    /// An empty buffer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Messages reported so far, oldest first
+    pub fn messages(&self) -> Vec<(Level, String)> {
+        self.messages.borrow().clone()
+    }
+
+    /// Messages of one level
+    pub fn messages_at(&self, level: Level) -> Vec<String> {
+        self.messages
+            .borrow()
+            .iter()
+            .filter(|(l, _)| *l == level)
+            .map(|(_, message)| message.clone())
+            .collect()
+    }
+}
+
+impl Reporter for BufferReporter {
+    fn report(&self, level: Level, message: &str) {
+        self.messages
+            .borrow_mut()
+            .push((level, message.to_string()));
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    /// Writer whose contents stay readable after handing it to a Reporter
+    #[derive(Clone, Default)]
+    pub(crate) struct SharedBuf(pub Rc<RefCell<Vec<u8>>>);
+
+    impl SharedBuf {
+        pub(crate) fn contents(&self) -> String {
+            String::from_utf8_lossy(&self.0.borrow()).into_owned()
+        }
+    }
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_quiet_reporter_writes_nothing() {
+        let buf = SharedBuf::default();
+        let reporter: &dyn Reporter = &ConsoleReporter::with_writer(false, Box::new(buf.clone()));
+        reporter.success("done");
+        reporter.info("detail");
+        assert!(buf.contents().contains("done"));
+        assert!(buf.contents().contains("detail\n"));
+
+        let buf = SharedBuf::default();
+        let reporter: &dyn Reporter = &ConsoleReporter::with_writer(true, Box::new(buf.clone()));
+        reporter.success("done");
+        reporter.info("detail");
+        assert!(buf.contents().is_empty());
+    }
+
+    #[test]
+    fn test_verbosity_levels() {
+        let log = |verbosity: u8| {
+            let buf = SharedBuf::default();
+            let reporter: &dyn Reporter =
+                &ConsoleReporter::with_writer(false, Box::new(buf.clone()))
+                    .with_verbosity(verbosity);
+            reporter.debug("decision");
+            reporter.trace("file read");
+            buf.contents()
+        };
+
+        assert!(log(0).is_empty());
+        assert!(log(1).contains("decision"));
+        assert!(!log(1).contains("file read"));
+        assert!(log(2).contains("file read"));
+    }
+
+    #[test]
+    fn test_buffer_reporter_keeps_levels() {
+        let buffer = BufferReporter::new();
+        let reporter: &dyn Reporter = &buffer;
+        reporter.success("applied");
+        reporter.warn("careful");
+        reporter.trace("file read");
+
+        assert_eq!(
+            buffer.messages(),
+            vec![
+                (Level::Success, "applied".to_string()),
+                (Level::Warn, "careful".to_string()),
+                (Level::Trace, "file read".to_string()),
+            ]
+        );
+        assert_eq!(buffer.messages_at(Level::Warn), vec!["careful"]);
+    }
+}
@@ -0,0 +1,78 @@
+//! Prompts of the guided `ccs apply --interactive` flow.
+//!
+//! The flow itself lives in `commands`; it asks its questions through
+//! [`ApplyPrompts`] so tests can script the answers.
+
+use crate::credentials::{ApiKeyChoice, KeyPreference, resolve_api_key};
+use crate::selectors::confirmation::ConfirmationService;
+use crate::selectors::error::{SelectorError, SelectorResult};
+use crate::selectors::template::TemplateSelector;
+use crate::templates::TemplateType;
+
+/// The questions asked by the guided apply flow, in order.
+pub trait ApplyPrompts {
+    /// Which provider template to apply
+    fn select_template(&mut self) -> SelectorResult<TemplateType>;
+
+    /// Which variant to use, for templates that have several; `remembered`
+    /// is the one picked last time
+    fn select_variant(
+        &mut self,
+        template_type: &TemplateType,
+        remembered: Option<&str>,
+    ) -> SelectorResult<String>;
+
+    /// Which API key to use (saved, from the env, or newly entered)
+    fn select_api_key(&mut self, template_type: &TemplateType) -> SelectorResult<ApiKeyChoice>;
+
+    /// Endpoint ID, for templates that need one
+    fn select_endpoint_id(
+        &mut self,
+        template_type: &TemplateType,
+    ) -> SelectorResult<Option<String>>;
+
+    /// Whether to write the changes just shown
+    fn confirm_apply(&mut self) -> SelectorResult<bool>;
+}
+
+/// [`ApplyPrompts`] backed by the interactive selectors.
+pub struct InquireApplyPrompts;
+
+impl ApplyPrompts for InquireApplyPrompts {
+    fn select_template(&mut self) -> SelectorResult<TemplateType> {
+        TemplateSelector::select_template()
+    }
+
+    fn select_variant(
+        &mut self,
+        template_type: &TemplateType,
+        remembered: Option<&str>,
+    ) -> SelectorResult<String> {
+        TemplateSelector::select_variant(template_type, remembered)
+    }
+
+    fn select_api_key(&mut self, template_type: &TemplateType) -> SelectorResult<ApiKeyChoice> {
+        resolve_api_key(
+            template_type,
+            None,
+            None,
+            None,
+            KeyPreference::Auto,
+            true,
+            false,
+        )
+        .map_err(|e| SelectorError::Failed(e.to_string()))?
+        .ok_or(SelectorError::Cancelled)
+    }
+
+    fn select_endpoint_id(
+        &mut self,
+        template_type: &TemplateType,
+    ) -> SelectorResult<Option<String>> {
+        TemplateSelector::get_endpoint_id_for_template(template_type)
+    }
+
+    fn confirm_apply(&mut self) -> SelectorResult<bool> {
+        ConfirmationService::confirm("Apply these changes?", true)
+    }
+}
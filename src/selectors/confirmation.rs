@@ -1,15 +1,66 @@
 //! Confirmation dialogs with consistent minimalist UI
 
 use crate::selectors::error::{SelectorError, SelectorResult};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Env var that answers yes to every confirmation (e.g. `CCS_ASSUME_YES=1`)
+pub const ASSUME_YES_ENV: &str = "CCS_ASSUME_YES";
+
+/// Set by the global `--yes` flag
+static ASSUME_YES: AtomicBool = AtomicBool::new(false);
+
+/// Whether `CCS_ASSUME_YES` holds a truthy value (anything but empty, `0`,
+/// `false` or `no`).
+fn env_assumes_yes(value: Option<&std::ffi::OsStr>) -> bool {
+    value.is_some_and(|v| {
+        let v = v.to_string_lossy().trim().to_ascii_lowercase();
+        !matches!(v.as_str(), "" | "0" | "false" | "no")
+    })
+}
+
+/// Answer given without prompting, or `None` when the user has to be asked.
+///
+/// `--yes` (`assume_yes`) or a truthy `CCS_ASSUME_YES` (`env`) answer yes;
+/// without a terminal to prompt on, `default` is the answer.
+fn preset_answer(
+    assume_yes: bool,
+    env: Option<&std::ffi::OsStr>,
+    interactive: bool,
+    default: bool,
+) -> Option<bool> {
+    if assume_yes || env_assumes_yes(env) {
+        Some(true)
+    } else if !interactive {
+        Some(default)
+    } else {
+        None
+    }
+}
 
 /// Service for handling confirmation dialogs
 pub struct ConfirmationService;
 
 impl ConfirmationService {
+    /// Answer yes to all confirmations for the rest of the process (`--yes`)
+    pub fn set_assume_yes(assume_yes: bool) {
+        ASSUME_YES.store(assume_yes, Ordering::Relaxed);
+    }
+
+    /// Whether confirmations are bypassed, via `--yes` or `CCS_ASSUME_YES`
+    pub fn assume_yes() -> bool {
+        ASSUME_YES.load(Ordering::Relaxed)
+            || env_assumes_yes(std::env::var_os(ASSUME_YES_ENV).as_deref())
+    }
+
     /// Core confirmation using inquire
     fn confirm_impl(message: &str, default: bool) -> SelectorResult<bool> {
-        if !atty::is(atty::Stream::Stdin) {
-            return Ok(default);
+        if let Some(answer) = preset_answer(
+            ASSUME_YES.load(Ordering::Relaxed),
+            std::env::var_os(ASSUME_YES_ENV).as_deref(),
+            atty::is(atty::Stream::Stdin),
+            default,
+        ) {
+            return Ok(answer);
         }
 
         inquire::Confirm::new(message)
@@ -48,3 +99,30 @@ impl ConfirmationService {
         Self::confirm_impl(action_description, false)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn test_assume_yes_env_overrides_default() {
+        assert!(env_assumes_yes(Some(OsStr::new("1"))));
+        assert!(env_assumes_yes(Some(OsStr::new("yes"))));
+        assert!(!env_assumes_yes(Some(OsStr::new("0"))));
+        assert!(!env_assumes_yes(Some(OsStr::new("false"))));
+        assert!(!env_assumes_yes(None));
+
+        // The env var answers yes even where the default is no
+        let yes = Some(OsStr::new("1"));
+        assert_eq!(preset_answer(false, yes, true, false), Some(true));
+        assert_eq!(preset_answer(false, yes, false, false), Some(true));
+        assert_eq!(preset_answer(true, None, true, false), Some(true));
+
+        // Otherwise: the default without a terminal, a prompt with one
+        let no = Some(OsStr::new("0"));
+        assert_eq!(preset_answer(false, no, false, false), Some(false));
+        assert_eq!(preset_answer(false, None, false, true), Some(true));
+        assert_eq!(preset_answer(false, None, true, false), None);
+    }
+}
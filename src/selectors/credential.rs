@@ -3,18 +3,45 @@
 //! Used by `ccs credentials list`. The API-key *acquisition* used by `apply`
 //! lives in [`crate::credentials`] (`resolve_api_key`), not here.
 
-use crate::credentials::{CredentialStore, SavedCredential, mask_api_key};
+use crate::credentials::{SavedCredential, SavedCredentialStore, SortOrder};
 use crate::selectors::{
     confirmation::ConfirmationService,
     error::{SelectorError, SelectorResult},
 };
+use crate::storage::ItemStorage;
+use crate::templates;
 use crate::templates::get_template_instance;
-use crate::{CredentialManager, templates};
+use crate::utils::{MaskPolicy, mask_api_key};
 use inquire::InquireError;
 
+/// Which credentials to show with respect to expiry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExpiryFilter {
+    /// Show everything (expired ones are marked)
+    #[default]
+    All,
+    /// Show only expired credentials
+    OnlyExpired,
+    /// Hide expired credentials
+    HideExpired,
+}
+
+impl ExpiryFilter {
+    pub fn matches(&self, cred: &SavedCredential) -> bool {
+        match self {
+            ExpiryFilter::All => true,
+            ExpiryFilter::OnlyExpired => cred.is_expired(),
+            ExpiryFilter::HideExpired => !cred.is_expired(),
+        }
+    }
+}
+
 /// Credential management browser.
 pub struct CredentialSelector {
     credentials: Vec<SavedCredential>,
+    expiry_filter: ExpiryFilter,
+    sort: SortOrder,
+    store: Box<dyn ItemStorage<SavedCredential>>,
 }
 
 /// Inquire selection wrapper that carries its own index (so selection is
@@ -22,6 +49,38 @@ pub struct CredentialSelector {
 struct Choice {
     index: usize,
     label: String,
+    /// Lowercased name and template, the part of `label` typing filters on
+    search: String,
+}
+
+impl Choice {
+    fn new(index: usize, cred: &SavedCredential, label: String) -> Self {
+        Self {
+            index,
+            label,
+            search: search_text(cred),
+        }
+    }
+
+    /// Whether every word of `query` appears in the name or template
+    fn matches(&self, query: &str) -> bool {
+        query
+            .to_lowercase()
+            .split_whitespace()
+            .all(|word| self.search.contains(word))
+    }
+}
+
+fn search_text(cred: &SavedCredential) -> String {
+    format!("{} {}", cred.name(), cred.template_type()).to_lowercase()
+}
+
+/// Rows the list may use: the terminal height minus room for the prompt and
+/// help line, or inquire's default when the height is unknown.
+fn list_page_size() -> usize {
+    crossterm::terminal::size()
+        .map(|(_, rows)| usize::from(rows).saturating_sub(4).max(5))
+        .unwrap_or(inquire::Select::<Choice>::DEFAULT_PAGE_SIZE)
 }
 
 impl std::fmt::Display for Choice {
@@ -30,31 +89,66 @@ impl std::fmt::Display for Choice {
     }
 }
 
-fn format_credential_line(cred: &SavedCredential) -> String {
-    let env_vars = get_template_instance(cred.template_type()).env_var_names();
-    let env_indicator = if env_vars.len() > 1 {
-        format!(" (+{})", env_vars.len())
-    } else {
-        String::new()
-    };
-    format!(
-        "{} ({}){} - {}",
-        cred.name(),
-        cred.template_type(),
-        env_indicator,
-        mask_api_key(cred.api_key())
-    )
+/// List rows for `creds`: name, template, masked key, last use and expiry in
+/// aligned columns
+fn format_credential_lines(creds: &[SavedCredential]) -> Vec<String> {
+    let mut table = crate::utils::Table::new();
+    for cred in creds {
+        let env_vars = get_template_instance(cred.template_type()).env_var_names();
+        let template = if env_vars.len() > 1 {
+            format!("{} (+{})", cred.template_type(), env_vars.len())
+        } else {
+            cred.template_type().to_string()
+        };
+        table.row([
+            cred.name().to_string(),
+            template,
+            mask_api_key(cred.api_key(), MaskPolicy::DEFAULT),
+            match cred.last_used_at() {
+                // Just the date of the stored `%Y-%m-%d %H:%M:%S UTC`
+                Some(last) => format!("used {}", last.get(..10).unwrap_or(last)),
+                None => "never used".to_string(),
+            },
+            if cred.is_expired() { "⚠ expired" } else { "" }.to_string(),
+        ]);
+    }
+    table.lines()
 }
 
 impl CredentialSelector {
     /// Create a browser over all saved credentials.
     pub fn new_all() -> SelectorResult<Self> {
-        let store = CredentialStore::new()
-            .map_err(|e| SelectorError::Storage(format!("Failed to create credential store: {}", e)))?;
+        let store = SavedCredentialStore::new().map_err(|e| {
+            SelectorError::Storage(format!("Failed to create credential store: {}", e))
+        })?;
+        Self::with_store(Box::new(store))
+    }
+
+    /// Create a browser over any credential storage backend.
+    pub fn with_store(store: Box<dyn ItemStorage<SavedCredential>>) -> SelectorResult<Self> {
         let credentials = store
-            .load_credentials()
+            .load()
             .map_err(|e| SelectorError::Storage(format!("Failed to load credentials: {}", e)))?;
-        Ok(Self { credentials })
+        Ok(Self {
+            credentials,
+            expiry_filter: ExpiryFilter::All,
+            sort: SortOrder::default(),
+            store,
+        })
+    }
+
+    /// Restrict the browser by expiry.
+    pub fn with_expiry_filter(mut self, filter: ExpiryFilter) -> Self {
+        self.expiry_filter = filter;
+        self.credentials.retain(|c| filter.matches(c));
+        self
+    }
+
+    /// Show credentials in `order`.
+    pub fn with_sort(mut self, order: SortOrder) -> Self {
+        self.sort = order;
+        order.sort(&mut self.credentials);
+        self
     }
 
     /// Create a browser filtered to a single template type.
@@ -75,7 +169,7 @@ impl CredentialSelector {
             self.manage_credential(index)?;
 
             // Reload after a possible mutation so indices stay valid.
-            self.credentials = Self::load_all()?;
+            self.reload()?;
             if self.credentials.is_empty() {
                 println!("No more credentials found.");
                 break;
@@ -85,29 +179,83 @@ impl CredentialSelector {
         Ok(())
     }
 
-    fn load_all() -> SelectorResult<Vec<SavedCredential>> {
-        let store = CredentialStore::new()
-            .map_err(|e| SelectorError::Storage(format!("Failed to create credential store: {}", e)))?;
-        store
-            .load_credentials()
-            .map_err(|e| SelectorError::Storage(format!("Failed to load credentials: {}", e)))
+    /// Re-read credentials from storage, keeping the expiry filter.
+    fn reload(&mut self) -> SelectorResult<()> {
+        let filter = self.expiry_filter;
+        self.credentials = self
+            .store
+            .load()
+            .map_err(|e| SelectorError::Storage(format!("Failed to load credentials: {}", e)))?;
+        self.credentials.retain(|c| filter.matches(c));
+        self.sort.sort(&mut self.credentials);
+        Ok(())
     }
 
-    /// Pick a credential from the list. Returns `None` on Esc/back.
-    fn pick_credential(&self) -> SelectorResult<Option<usize>> {
-        let choices: Vec<Choice> = self
-            .credentials
+    fn credential_at(&self, index: usize) -> SelectorResult<&SavedCredential> {
+        self.credentials.get(index).ok_or(SelectorError::NotFound)
+    }
+
+    /// Delete the credential at `index` from storage.
+    fn remove_at(&self, index: usize) -> SelectorResult<()> {
+        let cred = self.credential_at(index)?;
+        self.store.remove(cred.id()).map_err(|e| {
+            SelectorError::OperationFailed(format!("Failed to delete credential: {}", e))
+        })
+    }
+
+    /// Rename the credential at `index` in storage.
+    fn rename_at(&self, index: usize, new_name: String) -> SelectorResult<()> {
+        let mut cred = self.credential_at(index)?.clone();
+        cred.rename(new_name);
+        self.store.upsert(&cred).map_err(|e| {
+            SelectorError::OperationFailed(format!("Failed to rename credential: {}", e))
+        })
+    }
+
+    /// Set or clear (empty input) the expiry of the credential at `index`.
+    fn set_expiry_at(&self, index: usize, input: &str) -> SelectorResult<()> {
+        let mut cred = self.credential_at(index)?.clone();
+        cred.set_expiry(Some(input))
+            .map_err(|e| SelectorError::InvalidInput(e.to_string()))?;
+        self.store.upsert(&cred).map_err(|e| {
+            SelectorError::OperationFailed(format!("Failed to update credential: {}", e))
+        })
+    }
+
+    /// Set the endpoint ID of the credential at `index`; rejected for
+    /// templates that don't use one and, for KatCoder, unless it's `ep-…`.
+    fn set_endpoint_id_at(&self, index: usize, input: &str) -> SelectorResult<()> {
+        let mut cred = self.credential_at(index)?.clone();
+        cred.set_endpoint_id(input)
+            .map_err(|e| SelectorError::InvalidInput(e.to_string()))?;
+        self.store.upsert(&cred).map_err(|e| {
+            SelectorError::OperationFailed(format!("Failed to update credential: {}", e))
+        })
+    }
+
+    fn choices(&self) -> Vec<Choice> {
+        self.credentials
             .iter()
+            .zip(format_credential_lines(&self.credentials))
             .enumerate()
-            .map(|(index, cred)| Choice {
-                index,
-                label: format_credential_line(cred),
-            })
-            .collect();
+            .map(|(index, (cred, label))| Choice::new(index, cred, label))
+            .collect()
+    }
+
+    /// Pick a credential from the list. Returns `None` on Esc/back.
+    fn pick_credential(&self) -> SelectorResult<Option<usize>> {
+        // Matching keeps the list order; the masked key is never matched
+        let scorer = |input: &str, choice: &Choice, _: &str, _: usize| {
+            choice.matches(input).then_some(-(choice.index as i64))
+        };
 
         let title = format!("Select a credential to manage ({} total):", self.credentials.len());
-        match inquire::Select::new(&title, choices)
-            .with_help_message("↑/↓ navigate, Enter select, Esc exit")
+        match inquire::Select::new(&title, self.choices())
+            .with_page_size(list_page_size())
+            .with_scorer(&scorer)
+            .with_help_message(
+                "Type to filter by name or template, ↑/↓ navigate, Enter select, Esc exit",
+            )
             .prompt()
         {
             Ok(choice) => Ok(Some(choice.index)),
@@ -128,12 +276,37 @@ impl CredentialSelector {
         println!();
         println!("🔑 {}", cred.name());
         println!("   type: {}", cred.template_type());
-        println!("   key:  {}", mask_api_key(cred.api_key()));
+        println!(
+            "   key:  {}",
+            mask_api_key(cred.api_key(), MaskPolicy::DEFAULT)
+        );
         if let Some(last) = cred.last_used_at() {
             println!("   last used: {}", last);
         }
+        if let Some(expires) = cred.expires_at() {
+            let marker = if cred.is_expired() {
+                " ⚠ expired"
+            } else {
+                ""
+            };
+            println!("   expires: {}{}", expires, marker);
+        }
+
+        let uses_endpoint =
+            get_template_instance(cred.template_type()).requires_additional_config();
+        if uses_endpoint {
+            println!(
+                "   endpoint: {}",
+                cred.get_metadata("endpoint_id")
+                    .unwrap_or_else(|| "(not set)".to_string())
+            );
+        }
 
-        let options = vec!["✏️  Rename", "🗑️  Delete", "⬅️  Back"];
+        let mut options = vec!["✏️  Rename", "⏳ Set expiry"];
+        if uses_endpoint {
+            options.push("🔗 Edit endpoint ID");
+        }
+        options.extend(["🗑️  Delete", "⬅️  Back"]);
         let action = match inquire::Select::new("Action:", options)
             .with_help_message("↑/↓ navigate, Enter select, Esc back")
             .prompt()
@@ -147,6 +320,8 @@ impl CredentialSelector {
 
         match action {
             "✏️  Rename" => self.rename_credential(index)?,
+            "⏳ Set expiry" => self.set_expiry(index)?,
+            "🔗 Edit endpoint ID" => self.edit_endpoint_id(index)?,
             "🗑️  Delete" => self.delete_credential(index)?,
             _ => {}
         }
@@ -159,11 +334,7 @@ impl CredentialSelector {
         }
         let cred = &self.credentials[index];
         if ConfirmationService::confirm_deletion(cred.name(), "credential")? {
-            let store = CredentialStore::new()
-                .map_err(|e| SelectorError::Storage(format!("Failed to create credential store: {}", e)))?;
-            store
-                .delete_credential(cred.id())
-                .map_err(|e| SelectorError::OperationFailed(format!("Failed to delete credential: {}", e)))?;
+            self.remove_at(index)?;
             println!("✓ Credential deleted.");
         } else {
             println!("Deletion cancelled.");
@@ -193,12 +364,189 @@ impl CredentialSelector {
             return Ok(());
         }
 
-        let store = CredentialStore::new()
-            .map_err(|e| SelectorError::Storage(format!("Failed to create credential store: {}", e)))?;
-        store
-            .update_name(cred.id(), new_name.clone())
-            .map_err(|e| SelectorError::OperationFailed(format!("Failed to rename credential: {}", e)))?;
+        self.rename_at(index, new_name.clone())?;
         println!("✓ Credential renamed to '{}'.", new_name);
         Ok(())
     }
+
+    fn set_expiry(&self, index: usize) -> SelectorResult<()> {
+        if index >= self.credentials.len() {
+            return Err(SelectorError::NotFound);
+        }
+        let cred = &self.credentials[index];
+        let input = match inquire::Text::new(&format!("Expiry for '{}':", cred.name()))
+            .with_placeholder("YYYY-MM-DD")
+            .with_initial_value(cred.expires_at().unwrap_or_default())
+            .with_help_message("Leave empty to clear, Esc to cancel")
+            .prompt()
+        {
+            Ok(s) => s,
+            Err(InquireError::OperationCanceled) | Err(InquireError::OperationInterrupted) => {
+                return Ok(());
+            }
+            Err(e) => return Err(SelectorError::Failed(format!("Input failed: {}", e))),
+        };
+
+        self.set_expiry_at(index, &input)?;
+        if input.trim().is_empty() {
+            println!("✓ Expiry cleared.");
+        } else {
+            println!("✓ Expiry set.");
+        }
+        Ok(())
+    }
+
+    fn edit_endpoint_id(&self, index: usize) -> SelectorResult<()> {
+        if index >= self.credentials.len() {
+            return Err(SelectorError::NotFound);
+        }
+        let cred = &self.credentials[index];
+        let input = match inquire::Text::new(&format!("Endpoint ID for '{}':", cred.name()))
+            .with_placeholder("ep-xxx-xxx")
+            .with_initial_value(&cred.get_metadata("endpoint_id").unwrap_or_default())
+            .with_help_message("Esc to cancel")
+            .prompt()
+        {
+            Ok(s) => s,
+            Err(InquireError::OperationCanceled) | Err(InquireError::OperationInterrupted) => {
+                return Ok(());
+            }
+            Err(e) => return Err(SelectorError::Failed(format!("Input failed: {}", e))),
+        };
+
+        match self.set_endpoint_id_at(index, &input) {
+            Ok(()) => println!("✓ Endpoint ID set to '{}'.", input.trim()),
+            Err(SelectorError::InvalidInput(e)) => println!("{}", e),
+            Err(e) => return Err(e),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::InMemoryStore;
+    use crate::templates::TemplateType;
+
+    fn selector_with(creds: Vec<SavedCredential>) -> CredentialSelector {
+        CredentialSelector::with_store(Box::new(InMemoryStore::with_items(creds))).unwrap()
+    }
+
+    /// Indices of the credentials the list shows while `query` is typed
+    fn filtered(sel: &CredentialSelector, query: &str) -> Vec<usize> {
+        sel.choices()
+            .into_iter()
+            .filter(|choice| choice.matches(query))
+            .map(|choice| choice.index)
+            .collect()
+    }
+
+    fn cred(name: &str) -> SavedCredential {
+        SavedCredential::new(name.to_string(), format!("sk-{}", name), TemplateType::Zai)
+    }
+
+    #[test]
+    fn test_expiry_filter_against_memory_store() {
+        let mut expired = cred("old");
+        expired.set_expiry(Some("2000-01-01")).unwrap();
+        let fresh = cred("new");
+
+        let sel = selector_with(vec![expired, fresh.clone()])
+            .with_expiry_filter(ExpiryFilter::HideExpired);
+        assert_eq!(sel.credentials.len(), 1);
+        assert_eq!(sel.credentials[0].id(), fresh.id());
+    }
+
+    #[test]
+    fn test_filter_narrows_by_name_and_template() {
+        let creds = (0..50)
+            .map(|i| {
+                let template = if i % 2 == 0 {
+                    TemplateType::Zai
+                } else {
+                    TemplateType::DeepSeek
+                };
+                SavedCredential::new(format!("key-{:02}", i), format!("sk-{}", i), template)
+            })
+            .collect();
+        let sel = selector_with(creds);
+
+        assert_eq!(filtered(&sel, "").len(), 50);
+        assert_eq!(filtered(&sel, "deepseek").len(), 25);
+        // key-40..key-49
+        assert_eq!(filtered(&sel, "key-4").len(), 10);
+        let hits = filtered(&sel, "KEY-4 zai");
+        assert_eq!(hits.len(), 5);
+        assert!(hits.iter().all(|&i| {
+            let cred = &sel.credentials[i];
+            cred.name().starts_with("key-4") && *cred.template_type() == TemplateType::Zai
+        }));
+        // The masked key is shown but not matched
+        assert!(filtered(&sel, "sk-").is_empty());
+    }
+
+    #[test]
+    fn test_rename_expiry_and_remove_against_memory_store() {
+        let mut sel = selector_with(vec![cred("work")]);
+
+        sel.rename_at(0, "personal".to_string()).unwrap();
+        sel.reload().unwrap();
+        sel.set_expiry_at(0, "2999-12-31").unwrap();
+        sel.reload().unwrap();
+        assert_eq!(sel.credentials[0].name(), "personal");
+        assert_eq!(
+            sel.credentials[0].expires_at(),
+            Some("2999-12-31 23:59:59 UTC")
+        );
+
+        assert!(matches!(
+            sel.set_expiry_at(0, "not a date"),
+            Err(SelectorError::InvalidInput(_))
+        ));
+
+        sel.remove_at(0).unwrap();
+        sel.reload().unwrap();
+        assert!(sel.credentials.is_empty());
+        assert_eq!(sel.remove_at(0), Err(SelectorError::NotFound));
+    }
+
+    #[test]
+    fn test_edit_endpoint_id_against_memory_store() {
+        let kat = SavedCredential::new("kat".into(), "sk-kat".into(), TemplateType::KatCoder);
+        let mut sel = selector_with(vec![kat, cred("zai")]);
+        let kat_index = sel
+            .credentials
+            .iter()
+            .position(|c| c.name() == "kat")
+            .unwrap();
+        let zai_index = 1 - kat_index;
+
+        sel.set_endpoint_id_at(kat_index, " ep-abc123-xyz ")
+            .unwrap();
+        sel.reload().unwrap();
+        assert_eq!(
+            sel.credentials[kat_index]
+                .get_metadata("endpoint_id")
+                .as_deref(),
+            Some("ep-abc123-xyz")
+        );
+
+        assert!(matches!(
+            sel.set_endpoint_id_at(kat_index, "not-an-endpoint"),
+            Err(SelectorError::InvalidInput(_))
+        ));
+        // Templates without an endpoint ID reject it
+        assert!(matches!(
+            sel.set_endpoint_id_at(zai_index, "ep-abc123"),
+            Err(SelectorError::InvalidInput(_))
+        ));
+        sel.reload().unwrap();
+        assert_eq!(
+            sel.credentials[kat_index]
+                .get_metadata("endpoint_id")
+                .as_deref(),
+            Some("ep-abc123-xyz")
+        );
+    }
 }
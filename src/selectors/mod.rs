@@ -3,6 +3,7 @@
 //! The old custom crossterm full-screen Selector was removed in favor of a
 //! single, consistent prompt style via `inquire`.
 
+pub mod apply;
 pub mod confirmation;
 pub mod credential;
 pub mod error;
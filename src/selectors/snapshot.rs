@@ -10,7 +10,8 @@ use crate::selectors::{
 use crate::{
     Configurable,
     settings::{ClaudeSettings, format_settings_for_display},
-    snapshots::{Snapshot, SnapshotScope, SnapshotStore},
+    snapshots::{Snapshot, SnapshotScope, SnapshotStore, capture_settings},
+    storage::ItemStorage,
     utils::get_snapshots_dir,
 };
 use inquire::InquireError;
@@ -30,12 +31,26 @@ impl std::fmt::Display for Choice {
     }
 }
 
+/// List rows for `snapshots`: name, scope and description in aligned columns
+fn snapshot_labels(snapshots: &[Snapshot]) -> Vec<String> {
+    let mut table = crate::utils::Table::new();
+    for snapshot in snapshots {
+        table.row([
+            snapshot.name.clone(),
+            snapshot.scope.to_string(),
+            snapshot.description.clone().unwrap_or_default(),
+        ]);
+    }
+    table.lines()
+}
+
 /// Action for snapshot management.
 #[derive(Debug, Clone)]
 pub enum SnapshotManagementAction {
     Apply(usize),
     Delete(usize),
     Rename(usize),
+    Describe(usize),
     CreateSnapshot,
     Back,
     Exit,
@@ -44,27 +59,39 @@ pub enum SnapshotManagementAction {
 /// Snapshot management browser.
 pub struct SnapshotSelector {
     snapshots: Vec<Snapshot>,
-    store: SnapshotStore,
+    store: Box<dyn ItemStorage<Snapshot>>,
 }
 
 impl SnapshotSelector {
     /// Create a new snapshot selector
     pub fn new() -> SelectorResult<Self> {
         let snapshots_dir = get_snapshots_dir();
-        let store = SnapshotStore::new(snapshots_dir);
+        Self::with_store(Box::new(SnapshotStore::new(snapshots_dir)))
+    }
+
+    /// Create a snapshot selector over any snapshot storage backend
+    pub fn with_store(store: Box<dyn ItemStorage<Snapshot>>) -> SelectorResult<Self> {
         let snapshots = store
-            .list()
+            .load()
             .map_err(|e| SelectorError::Storage(format!("Failed to load snapshots: {}", e)))?;
 
         Ok(Self { snapshots, store })
     }
 
+    /// Check whether a stored snapshot already uses `name`
+    fn name_taken(&self, name: &str) -> bool {
+        self.store
+            .load()
+            .map(|snapshots| snapshots.iter().any(|s| s.name == name))
+            .unwrap_or(false)
+    }
+
     /// Run interactive snapshot management
     pub fn run_management(&mut self) -> SelectorResult<()> {
         if self.snapshots.is_empty() {
             println!("No snapshots found. Let's create your first snapshot!");
             if self.create_snapshot()? {
-                self.snapshots = self.store.list().map_err(|e| {
+                self.snapshots = self.store.load().map_err(|e| {
                     SelectorError::Storage(format!("Failed to reload snapshots: {}", e))
                 })?;
             }
@@ -88,14 +115,21 @@ impl SnapshotSelector {
                 }
                 Some(SnapshotManagementAction::CreateSnapshot) => {
                     if self.create_snapshot()? {
-                        self.snapshots = self.store.list().map_err(|e| {
+                        self.snapshots = self.store.load().map_err(|e| {
                             SelectorError::Storage(format!("Failed to reload snapshots: {}", e))
                         })?;
                     }
                 }
                 Some(SnapshotManagementAction::Rename(index)) => {
                     if let Some(true) = self.rename_snapshot(index)? {
-                        self.snapshots = self.store.list().map_err(|e| {
+                        self.snapshots = self.store.load().map_err(|e| {
+                            SelectorError::Storage(format!("Failed to reload snapshots: {}", e))
+                        })?;
+                    }
+                }
+                Some(SnapshotManagementAction::Describe(index)) => {
+                    if self.describe_snapshot(index)? {
+                        self.snapshots = self.store.load().map_err(|e| {
                             SelectorError::Storage(format!("Failed to reload snapshots: {}", e))
                         })?;
                     }
@@ -123,14 +157,13 @@ impl SnapshotSelector {
             return Ok(None);
         }
 
-        let choices: Vec<Choice> = selector
-            .snapshots
-            .iter()
+        let choices: Vec<Choice> = snapshot_labels(&selector.snapshots)
+            .into_iter()
             .enumerate()
-            .map(|(index, s)| Choice {
+            .map(|(index, label)| Choice {
                 index,
                 is_create: false,
-                label: format!("{} ({})", s.name, s.scope),
+                label,
             })
             .collect();
 
@@ -149,14 +182,13 @@ impl SnapshotSelector {
     /// Pick a snapshot from the list, then open its action menu.
     /// Returns `None` on Esc (exits the management loop).
     fn select_snapshot_action(&mut self) -> SelectorResult<Option<SnapshotManagementAction>> {
-        let mut choices: Vec<Choice> = self
-            .snapshots
-            .iter()
+        let mut choices: Vec<Choice> = snapshot_labels(&self.snapshots)
+            .into_iter()
             .enumerate()
-            .map(|(index, s)| Choice {
+            .map(|(index, label)| Choice {
                 index,
                 is_create: false,
-                label: format!("{} ({})", s.name, s.scope),
+                label,
             })
             .collect();
         choices.push(Choice {
@@ -188,41 +220,50 @@ impl SnapshotSelector {
     }
 
     /// Show snapshot details and action menu
-    fn manage_snapshot(&self, index: usize) -> SelectorResult<SnapshotManagementAction> {
+    fn manage_snapshot(&mut self, index: usize) -> SelectorResult<SnapshotManagementAction> {
         if index >= self.snapshots.len() {
             return Err(SelectorError::NotFound);
         }
 
-        let snapshot = &self.snapshots[index];
+        loop {
+            let snapshot = &self.snapshots[index];
 
-        // Print snapshot details
-        println!("\n📋 Snapshot: {} ({})", snapshot.name, snapshot.scope);
-        println!("  Created: {}", snapshot.created_at);
-        println!("  Updated: {}", snapshot.updated_at);
-        if let Some(ref desc) = snapshot.description {
-            println!("  Description: {}", desc);
-        }
+            // Print snapshot details
+            println!("\n{}", snapshot.format_details());
 
-        // Show action menu
-        let options = vec!["Apply", "Rename", "Delete", "Back"];
+            // Show action menu
+            let toggle = if snapshot.show_api_key {
+                "Hide API keys"
+            } else {
+                "Reveal API keys"
+            };
+            let options = vec!["Apply", "Rename", "Describe", "Delete", toggle, "Back"];
 
-        let action = inquire::Select::new(&format!("Action for '{}':", snapshot.name), options)
-            .with_help_message("↑/↓: Navigate, Enter: Select, Esc: Back")
-            .prompt()
-            .map_err(|e| {
-                let msg = e.to_string();
-                if msg.contains("canceled") || msg.contains("cancelled") {
-                    SelectorError::Cancelled
-                } else {
-                    SelectorError::Failed(format!("Selection failed: {}", e))
-                }
-            })?;
+            let action = inquire::Select::new(&format!("Action for '{}':", snapshot.name), options)
+                .with_help_message("↑/↓: Navigate, Enter: Select, Esc: Back")
+                .prompt()
+                .map_err(|e| {
+                    let msg = e.to_string();
+                    if msg.contains("canceled") || msg.contains("cancelled") {
+                        SelectorError::Cancelled
+                    } else {
+                        SelectorError::Failed(format!("Selection failed: {}", e))
+                    }
+                })?;
 
-        match action {
-            "Apply" => Ok(SnapshotManagementAction::Apply(index)),
-            "Rename" => Ok(SnapshotManagementAction::Rename(index)),
-            "Delete" => Ok(SnapshotManagementAction::Delete(index)),
-            _ => Ok(SnapshotManagementAction::Back),
+            match action {
+                "Apply" => return Ok(SnapshotManagementAction::Apply(index)),
+                "Rename" => return Ok(SnapshotManagementAction::Rename(index)),
+                "Describe" => return Ok(SnapshotManagementAction::Describe(index)),
+                "Delete" => return Ok(SnapshotManagementAction::Delete(index)),
+                "Reveal API keys" => {
+                    if ConfirmationService::confirm_action("Show API keys in plain text?")? {
+                        self.snapshots[index].show_api_key = true;
+                    }
+                }
+                "Hide API keys" => self.snapshots[index].show_api_key = false,
+                _ => return Ok(SnapshotManagementAction::Back),
+            }
         }
     }
 
@@ -392,7 +433,7 @@ impl SnapshotSelector {
         }
 
         // Check if snapshot already exists
-        if self.store.exists_by_name(&name) {
+        if self.name_taken(&name) {
             let overwrite_confirmation = ConfirmationService::confirm_overwrite(&name, "snapshot")?;
 
             if !overwrite_confirmation {
@@ -409,15 +450,12 @@ impl SnapshotSelector {
             ClaudeSettings::default()
         };
 
-        // Capture environment variables if needed
-        let mut snapshot_settings = settings;
-        if matches!(scope, SnapshotScope::All | SnapshotScope::Env) {
-            snapshot_settings.env = Some(ClaudeSettings::capture_environment());
-        }
+        // Capture environment variables if needed and keep only the scope's fields
+        let snapshot_settings = capture_settings(settings, &scope);
 
         let snapshot = Snapshot::new(name.clone(), snapshot_settings, scope, description);
 
-        self.store.save(&snapshot).map_err(|e| {
+        self.store.upsert(&snapshot).map_err(|e| {
             SelectorError::OperationFailed(format!("Failed to save snapshot: {}", e))
         })?;
 
@@ -445,12 +483,11 @@ impl SnapshotSelector {
             })?;
 
             // Backup current settings
-            let backup_path = settings_path.with_extension("json.backup");
-            std::fs::copy(&settings_path, &backup_path).map_err(|e| {
-                SelectorError::OperationFailed(format!("Failed to create backup: {}", e))
-            })?;
-
-            println!("✓ Settings backed up to: {}", backup_path.display());
+            if let Some(backup_path) = crate::utils::backup_settings(&settings_path)
+                .map_err(|e| SelectorError::OperationFailed(e.to_string()))?
+            {
+                println!("✓ Settings backed up to: {}", backup_path.display());
+            }
 
             // Apply snapshot settings
             snapshot
@@ -480,7 +517,7 @@ impl SnapshotSelector {
         let confirmation = ConfirmationService::confirm_deletion(&snapshot.name, "snapshot")?;
 
         if confirmation {
-            self.store.delete(&snapshot.id).map_err(|e| {
+            self.store.remove(&snapshot.id).map_err(|e| {
                 SelectorError::OperationFailed(format!("Failed to delete snapshot: {}", e))
             })?;
             println!("✓ Snapshot deleted successfully!");
@@ -509,7 +546,7 @@ impl SnapshotSelector {
 
         if new_name != snapshot.name {
             // Check if snapshot already exists with new name
-            if self.store.exists_by_name(&new_name) {
+            if self.name_taken(&new_name) {
                 let overwrite_confirmation =
                     ConfirmationService::confirm_overwrite(&new_name, "snapshot")?;
                 if !overwrite_confirmation {
@@ -518,26 +555,65 @@ impl SnapshotSelector {
                 }
             }
 
-            // Create new snapshot with updated name
-            let mut updated_snapshot = snapshot.clone();
-            updated_snapshot.name = new_name.clone();
-            updated_snapshot.updated_at = chrono::Utc::now().to_rfc3339();
-
-            // Save updated snapshot
-            self.store.save(&updated_snapshot).map_err(|e| {
-                SelectorError::OperationFailed(format!("Failed to rename snapshot: {}", e))
-            })?;
-
-            // Delete old snapshot
-            self.store.delete(&snapshot.id).map_err(|e| {
-                SelectorError::OperationFailed(format!("Failed to delete old snapshot: {}", e))
-            })?;
+            self.rename_at(index, new_name.clone())?;
 
             println!("✓ Snapshot renamed to '{}' successfully!", new_name);
         }
 
         Ok(Some(true))
     }
+
+    /// Edit a snapshot's description. Returns whether it was saved.
+    fn describe_snapshot(&self, index: usize) -> SelectorResult<bool> {
+        let snapshot = self.snapshots.get(index).ok_or(SelectorError::NotFound)?;
+        let text = match inquire::Text::new("Description:")
+            .with_default(snapshot.description.as_deref().unwrap_or_default())
+            .with_help_message("Leave empty to clear, Esc to cancel")
+            .prompt()
+        {
+            Ok(text) => text,
+            Err(InquireError::OperationCanceled) | Err(InquireError::OperationInterrupted) => {
+                println!("Edit cancelled.");
+                return Ok(false);
+            }
+            Err(e) => return Err(SelectorError::Failed(format!("Input failed: {}", e))),
+        };
+
+        self.describe_at(index, &text)?;
+        println!("✓ Description updated");
+        Ok(true)
+    }
+}
+
+impl SnapshotSelector {
+    /// Rename the snapshot at `index` in place (same id, so nothing to delete)
+    fn rename_at(&self, index: usize, new_name: String) -> SelectorResult<()> {
+        let mut updated_snapshot = self
+            .snapshots
+            .get(index)
+            .ok_or(SelectorError::NotFound)?
+            .clone();
+        updated_snapshot.name = new_name;
+        updated_snapshot.touch();
+
+        self.store.upsert(&updated_snapshot).map_err(|e| {
+            SelectorError::OperationFailed(format!("Failed to rename snapshot: {}", e))
+        })
+    }
+
+    /// Set the description of the snapshot at `index` (blank clears it)
+    fn describe_at(&self, index: usize, text: &str) -> SelectorResult<()> {
+        let mut updated_snapshot = self
+            .snapshots
+            .get(index)
+            .ok_or(SelectorError::NotFound)?
+            .clone();
+        updated_snapshot.set_description(text);
+
+        self.store.upsert(&updated_snapshot).map_err(|e| {
+            SelectorError::OperationFailed(format!("Failed to update snapshot: {}", e))
+        })
+    }
 }
 
 /// Prompt for a new name. Esc cancels (returns [`SelectorError::Cancelled`]).
@@ -561,3 +637,49 @@ fn prompt_rename(current: &str) -> SelectorResult<String> {
         Err(e) => Err(SelectorError::Failed(format!("Input failed: {}", e))),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::InMemoryStore;
+
+    fn snapshot(name: &str) -> Snapshot {
+        Snapshot::new(
+            name.to_string(),
+            ClaudeSettings::new(),
+            SnapshotScope::Common,
+            None,
+        )
+    }
+
+    #[test]
+    fn test_label_shows_description() {
+        let mut described = snapshot("a-much-longer-name");
+        described.description = Some("Before applying zai".to_string());
+        assert_eq!(
+            snapshot_labels(&[snapshot("work"), described]),
+            [
+                "work                common",
+                "a-much-longer-name  common  Before applying zai"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rename_against_memory_store() {
+        let store = InMemoryStore::with_items(vec![snapshot("work")]);
+        let mut selector = SnapshotSelector::with_store(Box::new(store)).unwrap();
+        assert!(selector.name_taken("work"));
+
+        selector.rename_at(0, "home".to_string()).unwrap();
+        selector.snapshots = selector.store.load().unwrap();
+
+        assert_eq!(selector.snapshots.len(), 1);
+        assert_eq!(selector.snapshots[0].name, "home");
+        assert!(!selector.name_taken("work"));
+        assert_eq!(
+            selector.rename_at(5, "x".to_string()),
+            Err(SelectorError::NotFound)
+        );
+    }
+}
@@ -3,13 +3,78 @@
 use crate::selectors::error::{SelectorError, SelectorResult};
 use crate::{
     credentials::CredentialStore,
-    templates::{TemplateType, get_template_instance},
+    templates::{TemplateType, get_all_templates, get_template_instance, variant_options},
 };
 
 /// Template selector for choosing AI provider templates
 pub struct TemplateSelector;
 
 impl TemplateSelector {
+    /// One `(type, label)` entry per registered template, in registry order
+    pub fn template_choices() -> Vec<(TemplateType, String)> {
+        get_all_templates()
+            .into_iter()
+            .map(|template_type| {
+                let instance = get_template_instance(&template_type);
+                let label = format!(
+                    "{} ({}) — {}",
+                    instance.display_name(),
+                    template_type,
+                    instance.description()
+                );
+                (template_type, label)
+            })
+            .collect()
+    }
+
+    /// Pick a template from every registered provider
+    pub fn select_template() -> SelectorResult<TemplateType> {
+        let choices = Self::template_choices();
+        let labels: Vec<String> = choices.iter().map(|(_, label)| label.clone()).collect();
+
+        let selection = inquire::Select::new("Select a provider template:", labels)
+            .with_help_message("↑/↓: Navigate, type to filter, Enter: Select, Esc: Cancel")
+            .prompt()
+            .map_err(inquire_to_selector_error)?;
+
+        choices
+            .into_iter()
+            .find(|(_, label)| label == &selection)
+            .map(|(template_type, _)| template_type)
+            .ok_or(SelectorError::NotFound)
+    }
+
+    /// Pick one of a template's variants, starting on `remembered` if given.
+    /// Returns the variant's alias.
+    pub fn select_variant(
+        template_type: &TemplateType,
+        remembered: Option<&str>,
+    ) -> SelectorResult<String> {
+        let options = variant_options(template_type);
+        let labels: Vec<String> = options
+            .iter()
+            .map(|(alias, label)| format!("{}  ({})", label, alias))
+            .collect();
+        let start = remembered
+            .and_then(|remembered| options.iter().position(|(alias, _)| *alias == remembered))
+            .unwrap_or(0);
+
+        let selection = inquire::Select::new(
+            &format!("Select {} variant:", template_type),
+            labels.clone(),
+        )
+        .with_starting_cursor(start)
+        .with_help_message("↑/↓ navigate, Enter select, Esc cancel")
+        .prompt()
+        .map_err(inquire_to_selector_error)?;
+
+        labels
+            .iter()
+            .position(|label| label == &selection)
+            .map(|index| options[index].0.to_string())
+            .ok_or(SelectorError::NotFound)
+    }
+
     /// Get endpoint ID for templates that require it
     pub fn get_endpoint_id_for_template(
         template_type: &TemplateType,
@@ -89,3 +154,18 @@ pub fn get_endpoint_id_interactively(template_type: &TemplateType) -> SelectorRe
         None => Err(SelectorError::NotFound),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_choices_show_template_description() {
+        let choices = TemplateSelector::template_choices();
+        assert_eq!(choices.len(), get_all_templates().len());
+        for (template_type, label) in choices {
+            let description = get_template_instance(&template_type).description();
+            assert!(label.ends_with(&format!("— {}", description)), "{}", label);
+        }
+    }
+}
@@ -1,13 +1,16 @@
 use anyhow::{Result, anyhow};
 use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs;
-use std::path::Path;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 
 use crate::Configurable;
-use crate::snapshots::SnapshotScope;
+use crate::error::{CcsError, CcsResult};
+use crate::snapshots::{FieldSelector, SnapshotScope};
 use crate::templates::TemplateType;
+use crate::utils::{MaskPolicy, mask_api_key};
 
 /// Main Claude Code settings structure
 #[derive(Debug, Clone, Serialize, PartialEq, Default)]
@@ -151,51 +154,8 @@ where
     }
 }
 
-/// Snapshot structure
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Snapshot {
-    pub id: String,
-    pub name: String,
-    pub created_at: chrono::DateTime<chrono::Utc>,
-    pub scope: SnapshotScope,
-    pub settings: ClaudeSettings,
-    pub description: Option<String>,
-    #[serde(skip)]
-    pub show_api_key: bool,
-}
-
-/// Snapshot storage
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
-pub struct SnapshotStore {
-    pub snapshots: Vec<Snapshot>,
-}
-
-impl SnapshotStore {
-    pub fn new() -> Self {
-        Self::default()
-    }
-
-    pub fn find_snapshot(&self, name: &str) -> Option<&Snapshot> {
-        self.snapshots.iter().find(|s| s.name == name)
-    }
-
-    pub fn add_snapshot(&mut self, snapshot: Snapshot) {
-        self.snapshots.push(snapshot);
-    }
-
-    pub fn delete_snapshot(&mut self, name: &str) -> Result<()> {
-        let index = self
-            .snapshots
-            .iter()
-            .position(|s| s.name == name)
-            .ok_or_else(|| anyhow!("Snapshot '{}' not found", name))?;
-        self.snapshots.remove(index);
-        Ok(())
-    }
-}
-
 /// Permissions configuration
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 pub struct Permissions {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub allow: Option<Vec<String>>,
@@ -211,6 +171,97 @@ pub struct Permissions {
     pub disable_bypass_permissions_mode: Option<String>,
 }
 
+/// One of the allow/ask/deny tool lists in [`Permissions`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PermissionList {
+    Allow,
+    Ask,
+    Deny,
+}
+
+impl std::fmt::Display for PermissionList {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PermissionList::Allow => write!(f, "allow"),
+            PermissionList::Ask => write!(f, "ask"),
+            PermissionList::Deny => write!(f, "deny"),
+        }
+    }
+}
+
+impl Permissions {
+    /// Mutable access to one of the tool lists
+    fn list_mut(&mut self, list: PermissionList) -> &mut Option<Vec<String>> {
+        match list {
+            PermissionList::Allow => &mut self.allow,
+            PermissionList::Ask => &mut self.ask,
+            PermissionList::Deny => &mut self.deny,
+        }
+    }
+
+    /// Which list (if any) currently contains `tool`
+    pub fn list_of(&self, tool: &str) -> Option<PermissionList> {
+        [
+            (PermissionList::Allow, &self.allow),
+            (PermissionList::Ask, &self.ask),
+            (PermissionList::Deny, &self.deny),
+        ]
+        .into_iter()
+        .find(|(_, tools)| tools.as_ref().is_some_and(|t| t.iter().any(|x| x == tool)))
+        .map(|(list, _)| list)
+    }
+
+    /// Remove `tool` from allow/ask/deny. Lists that become empty are dropped.
+    /// Returns true if the tool was present in any list.
+    pub fn remove_tool(&mut self, tool: &str) -> bool {
+        let mut removed = false;
+        for list in [
+            PermissionList::Allow,
+            PermissionList::Ask,
+            PermissionList::Deny,
+        ] {
+            let slot = self.list_mut(list);
+            if let Some(tools) = slot {
+                let before = tools.len();
+                tools.retain(|t| t != tool);
+                removed |= tools.len() != before;
+                if tools.is_empty() {
+                    *slot = None;
+                }
+            }
+        }
+        removed
+    }
+
+    /// Put `tool` in `list`, removing it from the other two so a tool only
+    /// ever appears once.
+    pub fn set_tool(&mut self, tool: &str, list: PermissionList) {
+        self.remove_tool(tool);
+        self.list_mut(list)
+            .get_or_insert_with(Vec::new)
+            .push(tool.to_string());
+    }
+
+    /// True when every field is unset
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Drop tools from allow/ask that are also denied; deny wins.
+    fn resolve_conflicts(&mut self) {
+        let denied = self.deny.clone().unwrap_or_default();
+        for list in [PermissionList::Allow, PermissionList::Ask] {
+            let slot = self.list_mut(list);
+            if let Some(tools) = slot {
+                tools.retain(|t| !denied.contains(t));
+                if tools.is_empty() {
+                    *slot = None;
+                }
+            }
+        }
+    }
+}
+
 /// Hooks configuration
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Hooks {
@@ -238,33 +289,343 @@ pub struct Attribution {
     pub pr: Option<String>,
 }
 
+/// Output styles accepted by `--output-style`
+pub const OUTPUT_STYLES: &[&str] = &["default", "concise", "explanatory", "learning"];
+
+/// Validate an output style name (case-insensitive) against [`OUTPUT_STYLES`]
+pub fn parse_output_style(name: &str) -> Result<String> {
+    let name = name.trim().to_lowercase();
+    if OUTPUT_STYLES.contains(&name.as_str()) {
+        Ok(name)
+    } else {
+        Err(anyhow!(
+            "Unknown output style '{}'. Use one of: {}",
+            name,
+            OUTPUT_STYLES.join(", ")
+        ))
+    }
+}
+
+/// Env var for a key sent as `x-api-key`
+pub const API_KEY_ENV: &str = "ANTHROPIC_API_KEY";
+
+/// Env var for a key sent as a bearer token
+pub const AUTH_TOKEN_ENV: &str = "ANTHROPIC_AUTH_TOKEN";
+
+/// Which env vars a provider reads the API key from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AuthMode {
+    /// [`API_KEY_ENV`] only
+    ApiKey,
+    /// [`AUTH_TOKEN_ENV`] only
+    #[default]
+    AuthToken,
+    /// Both, for providers that accept either header
+    Both,
+}
+
+impl AuthMode {
+    /// Env vars holding the key, in the order they are read back
+    pub fn env_keys(self) -> &'static [&'static str] {
+        match self {
+            AuthMode::ApiKey => &[API_KEY_ENV],
+            AuthMode::AuthToken => &[AUTH_TOKEN_ENV],
+            AuthMode::Both => &[AUTH_TOKEN_ENV, API_KEY_ENV],
+        }
+    }
+
+    /// Set `api_key` in every env var this mode uses
+    pub fn insert_key(self, env: &mut HashMap<String, String>, api_key: &str) {
+        for key in self.env_keys() {
+            env.insert(key.to_string(), api_key.to_string());
+        }
+    }
+}
+
+/// Env var Claude Code reads the subagent model from
+pub const SUBAGENT_MODEL_ENV: &str = "CLAUDE_CODE_SUBAGENT_MODEL";
+
+/// How `apply` combines a template with the existing settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum MergeStrategy {
+    /// Template keys override; everything else already there is kept
+    #[default]
+    Merge,
+    /// Provider-owned settings left over from the previous provider are
+    /// dropped first, so only the template's remain; everything else is kept
+    Replace,
+}
+
+/// Fields `apply --preserve` keeps from the existing settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum PreserveField {
+    Hooks,
+    #[value(alias = "status_line")]
+    StatusLine,
+    Permissions,
+    #[value(alias = "output_style")]
+    OutputStyle,
+}
+
+/// Env vars owned by the provider: any `ANTHROPIC_*` variable plus these.
+/// `--merge-strategy replace` removes them before applying a template.
+pub const PROVIDER_ENV_KEYS: &[&str] = &[
+    "API_TIMEOUT_MS",
+    "AUTH_HEADER_MODE",
+    "CLAUDE_CODE_AUTO_COMPACT_WINDOW",
+    "CLAUDE_CODE_DISABLE_EXPERIMENTAL_BETAS",
+    "CLAUDE_CODE_DISABLE_NONESSENTIAL_TRAFFIC",
+    "CLAUDE_CODE_DISABLE_NONSTREAMING_FALLBACK",
+    "CLAUDE_CODE_EFFORT_LEVEL",
+    "CLAUDE_CODE_MAX_OUTPUT_TOKENS",
+    SUBAGENT_MODEL_ENV,
+    "ENABLE_STREAMING",
+    "ENABLE_THINKING",
+    "ENABLE_TOOL_SEARCH",
+    "MAX_MCP_OUTPUT_TOKENS",
+    "MAX_OUTPUT_TOKENS",
+    "MAX_THINKING_TOKENS",
+    "REASONING_EFFORT",
+];
+
+/// Top-level fields owned by the provider (besides its env vars)
+pub const PROVIDER_FIELDS: &[ProviderField] = &[ProviderField::Model, ProviderField::SubagentModel];
+
+/// A top-level settings field set by provider templates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProviderField {
+    Model,
+    SubagentModel,
+}
+
+impl ProviderField {
+    /// Field name in settings.json
+    pub fn name(self) -> &'static str {
+        match self {
+            ProviderField::Model => "model",
+            ProviderField::SubagentModel => "subagent_model",
+        }
+    }
+
+    fn clear(self, settings: &mut ClaudeSettings) {
+        match self {
+            ProviderField::Model => settings.model = None,
+            ProviderField::SubagentModel => settings.subagent_model = None,
+        }
+    }
+}
+
+/// Whether an env var is set by provider templates
+pub fn is_provider_env_key(key: &str) -> bool {
+    key.starts_with("ANTHROPIC_") || PROVIDER_ENV_KEYS.contains(&key)
+}
+
+impl MergeStrategy {
+    /// Combine `existing` with `template` at `scope`. Whatever the strategy,
+    /// settings outside `scope` are left alone.
+    pub fn apply(
+        self,
+        existing: ClaudeSettings,
+        template: ClaudeSettings,
+        scope: &SnapshotScope,
+    ) -> ClaudeSettings {
+        let existing = match self {
+            MergeStrategy::Merge => existing,
+            MergeStrategy::Replace => existing.without_provider_settings(scope),
+        };
+        ClaudeSettings::merge_by_scope(existing, template, scope)
+    }
+}
+
 impl ClaudeSettings {
+    /// Drop the provider-owned env vars and fields (see [`PROVIDER_ENV_KEYS`]
+    /// and [`PROVIDER_FIELDS`]) that `scope` covers
+    pub fn without_provider_settings(mut self, scope: &SnapshotScope) -> Self {
+        let covers = |selector: FieldSelector| scope.covers(&SnapshotScope::Custom(vec![selector]));
+        if let Some(env) = self.env.as_mut() {
+            env.retain(|key, _| {
+                !(is_provider_env_key(key) && covers(FieldSelector::EnvKey(key.clone())))
+            });
+        }
+        for field in PROVIDER_FIELDS {
+            if covers(FieldSelector::Field(field.name().to_string())) {
+                field.clear(&mut self);
+            }
+        }
+        self
+    }
+
     /// Create empty settings
     pub fn new() -> Self {
         Self::default()
     }
 
-    /// Read settings from file
-    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let path = path.as_ref();
-        if !path.exists() {
-            return Ok(Self::new());
+    /// Whether Claude Code adds its co-authored-by trailer and PR attribution.
+    /// Off means both attribution texts are set to empty.
+    pub fn co_authored(&self) -> bool {
+        !self
+            .attribution
+            .as_ref()
+            .is_some_and(|a| a.commit.as_deref() == Some("") && a.pr.as_deref() == Some(""))
+    }
+
+    /// Turn co-author attribution on or off. Turning it on drops the empty
+    /// texts that disable it but keeps custom attribution text.
+    pub fn set_co_authored(&mut self, on: bool) {
+        if !on {
+            self.attribution = Some(Attribution {
+                commit: Some(String::new()),
+                pr: Some(String::new()),
+            });
+            return;
         }
+        if let Some(attribution) = self.attribution.as_mut() {
+            attribution.commit.take_if(|text| text.is_empty());
+            attribution.pr.take_if(|text| text.is_empty());
+            if attribution.commit.is_none() && attribution.pr.is_none() {
+                self.attribution = None;
+            }
+        }
+    }
 
-        let content = fs::read_to_string(path)
-            .map_err(|e| anyhow!("Failed to read settings file {}: {}", path.display(), e))?;
+    /// Set `key` in `env`, creating the map if needed. Returns the previous
+    /// value.
+    pub fn set_env_var(&mut self, key: &str, value: &str) -> Option<String> {
+        self.env
+            .get_or_insert_with(HashMap::new)
+            .insert(key.to_string(), value.to_string())
+    }
+
+    /// Remove `key` from `env`, dropping `env` once it is empty so the field
+    /// is left out of the JSON. Returns whether the key was set.
+    pub fn remove_env_key(&mut self, key: &str) -> bool {
+        let Some(env) = self.env.as_mut() else {
+            return false;
+        };
+        let removed = env.remove(key).is_some();
+        if env.is_empty() {
+            self.env = None;
+        }
+        removed
+    }
+
+    /// Set the model used for subagents, both the `subagent_model` field and
+    /// the `CLAUDE_CODE_SUBAGENT_MODEL` env var
+    pub fn set_subagent_model(&mut self, model: &str) {
+        self.subagent_model = Some(model.to_string());
+        self.env
+            .get_or_insert_with(HashMap::new)
+            .insert(SUBAGENT_MODEL_ENV.to_string(), model.to_string());
+    }
 
+    /// Parse settings JSON from any reader. Blank input is empty settings;
+    /// read failures come back as I/O-category errors (`Error::is_io`).
+    pub fn from_reader<R: Read>(mut reader: R) -> serde_json::Result<Self> {
+        let mut content = String::new();
+        reader
+            .read_to_string(&mut content)
+            .map_err(serde_json::Error::io)?;
         if content.trim().is_empty() {
             return Ok(Self::new());
         }
-
         serde_json::from_str(&content)
-            .map_err(|e| anyhow!("Failed to parse settings file {}: {}", path.display(), e))
+    }
+
+    /// Serialize settings as pretty JSON to any writer (the `to_file` format)
+    pub fn to_writer<W: Write>(&self, writer: W) -> Result<()> {
+        serde_json::to_writer_pretty(writer, self)
+            .map_err(|e| anyhow!("Failed to serialize settings: {}", e))
+    }
+
+    /// Read settings from file (`-` reads them from stdin)
+    pub fn from_file<P: AsRef<Path>>(path: P) -> CcsResult<Self> {
+        let path = path.as_ref();
+        let io_error = |source| CcsError::Io {
+            path: path.to_path_buf(),
+            source,
+        };
+        let parsed = if crate::utils::is_stdio_path(path) {
+            Self::from_reader(
+                crate::utils::read_stdin_once()
+                    .map_err(io_error)?
+                    .as_bytes(),
+            )
+        } else if !path.exists() {
+            return Ok(Self::new());
+        } else {
+            Self::from_reader(fs::File::open(path).map_err(io_error)?)
+        };
+
+        parsed.map_err(|source| {
+            if source.is_io() {
+                io_error(source.into())
+            } else {
+                CcsError::SettingsParse {
+                    path: path.to_path_buf(),
+                    source,
+                }
+            }
+        })
+    }
+
+    /// [`Self::from_file`] with a way out for a file that isn't valid JSON.
+    ///
+    /// `recover` is asked whether to move the bad file aside. If it agrees,
+    /// the file is renamed to `<name>.corrupt-<timestamp>` and empty settings
+    /// are returned along with that path; otherwise the error names the file
+    /// and the line of the parse failure.
+    pub fn from_file_or_recover(
+        path: &Path,
+        recover: impl FnOnce(&Path) -> Result<bool>,
+    ) -> Result<(Self, Option<PathBuf>)> {
+        match Self::from_file(path) {
+            Ok(settings) => Ok((settings, None)),
+            // Piped-in settings have no file to move aside
+            Err(CcsError::SettingsParse { path, source })
+                if !crate::utils::is_stdio_path(&path) =>
+            {
+                if !recover(&path)? {
+                    return Err(anyhow!(
+                        "{} is not valid JSON ({}). Fix or remove it, or run the command \
+                         in a terminal to back it up and start fresh",
+                        path.display(),
+                        source
+                    ));
+                }
+                let file_name = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| "settings.json".to_string());
+                let aside = path.with_file_name(format!(
+                    "{}.corrupt-{}",
+                    file_name,
+                    chrono::Local::now().format("%Y%m%d-%H%M%S")
+                ));
+                fs::rename(&path, &aside)
+                    .map_err(|e| anyhow!("Failed to move {} aside: {}", path.display(), e))?;
+                Ok((Self::new(), Some(aside)))
+            }
+            Err(e) => Err(e.into()),
+        }
     }
 
     /// Write settings to file
     pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.to_file_with_retry(path, &crate::utils::RetryPolicy::default())
+    }
+
+    /// [`Self::to_file`] with an explicit retry policy for transient write errors
+    pub fn to_file_with_retry<P: AsRef<Path>>(
+        &self,
+        path: P,
+        retry: &crate::utils::RetryPolicy,
+    ) -> Result<()> {
         let path = path.as_ref();
+        if crate::utils::is_stdio_path(path) {
+            let mut stdout = std::io::stdout().lock();
+            self.to_writer(&mut stdout)?;
+            return writeln!(stdout).map_err(|e| anyhow!("Failed to write settings: {}", e));
+        }
         let parent = path.parent().ok_or_else(|| {
             anyhow!(
                 "Settings file path {} has no parent directory",
@@ -280,26 +641,26 @@ impl ClaudeSettings {
             )
         })?;
 
-        let content = serde_json::to_string_pretty(self)
-            .map_err(|e| anyhow!("Failed to serialize settings: {}", e))?;
+        let mut content = Vec::new();
+        self.to_writer(&mut content)?;
 
-        fs::write(path, content)
+        crate::utils::write_atomic_retrying(path, &content, retry)
             .map_err(|e| anyhow!("Failed to write settings file {}: {}", path.display(), e))
     }
 
     /// Capture environment variables relevant to Claude Code
     pub fn capture_environment() -> HashMap<String, String> {
-        let mut env = HashMap::new();
-
-        // Claude Code specific environment variables
-        if let Ok(value) = std::env::var("CLAUDE_CODE_API_KEY") {
-            env.insert("CLAUDE_CODE_API_KEY".to_string(), value);
-        }
-        if let Ok(value) = std::env::var("ANTHROPIC_API_KEY") {
-            env.insert("ANTHROPIC_API_KEY".to_string(), value);
-        }
+        Self::capture_environment_from(std::env::vars())
+    }
 
-        env
+    /// The Claude Code variables among `vars`: `CLAUDE_CODE_API_KEY` and
+    /// the provider vars (see [`is_provider_env_key`])
+    pub fn capture_environment_from(
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> HashMap<String, String> {
+        vars.into_iter()
+            .filter(|(key, _)| key == "CLAUDE_CODE_API_KEY" || is_provider_env_key(key))
+            .collect()
     }
 
     /// Capture environment variables for a specific template type
@@ -332,36 +693,136 @@ impl ClaudeSettings {
 
             for key in keys_to_mask {
                 if let Some(value) = env.get(&key) {
-                    env.insert(key, mask_api_key(value));
+                    env.insert(key, mask_api_key(value, MaskPolicy::DEFAULT));
                 }
             }
         }
         masked
     }
 
+    /// API key stored in `env` under `mode`'s variables; empty values are
+    /// skipped
+    pub fn api_key_for(&self, mode: AuthMode) -> Option<&str> {
+        let env = self.env.as_ref()?;
+        mode.env_keys()
+            .iter()
+            .filter_map(|key| env.get(*key))
+            .map(String::as_str)
+            .find(|value| !value.is_empty())
+    }
+
+    /// API key stored in these settings, read the way the provider at
+    /// `ANTHROPIC_BASE_URL` expects it, falling back to either variable
+    pub fn stored_api_key(&self) -> Option<&str> {
+        let mode = self
+            .env
+            .as_ref()
+            .and_then(|env| env.get("ANTHROPIC_BASE_URL"))
+            .and_then(|url| crate::templates::template_for_base_url(url))
+            .map(|tt| crate::templates::get_template_instance(&tt).auth_mode())
+            .unwrap_or_default();
+        self.api_key_for(mode)
+            .or_else(|| self.api_key_for(AuthMode::Both))
+    }
+
     /// Get API key from settings or environment
     pub fn get_api_key(&self) -> Option<String> {
-        // First try from settings
-        if let Some(ref env) = self.env {
-            if let Some(key) = env.get("ANTHROPIC_API_KEY") {
-                return Some(key.clone());
-            }
-            if let Some(key) = env.get("ANTHROPIC_AUTH_TOKEN") {
-                return Some(key.clone());
-            }
+        if let Some(key) = self.stored_api_key() {
+            return Some(key.to_string());
         }
 
         // Then try environment variables
         if let Ok(key) = std::env::var("CLAUDE_CODE_API_KEY") {
             return Some(key);
         }
-        if let Ok(key) = std::env::var("ANTHROPIC_API_KEY") {
+        if let Ok(key) = std::env::var(API_KEY_ENV) {
             return Some(key);
         }
 
         None
     }
 
+    /// Move `tool` into the allow list (removing it from ask/deny)
+    pub fn allow_tool(&mut self, tool: &str) {
+        self.set_tool_permission(tool, PermissionList::Allow);
+    }
+
+    /// Move `tool` into the ask list (removing it from allow/deny)
+    pub fn ask_tool(&mut self, tool: &str) {
+        self.set_tool_permission(tool, PermissionList::Ask);
+    }
+
+    /// Move `tool` into the deny list (removing it from allow/ask)
+    pub fn deny_tool(&mut self, tool: &str) {
+        self.set_tool_permission(tool, PermissionList::Deny);
+    }
+
+    /// Allow every tool in `allow`, then deny every tool in `deny`, so a
+    /// tool named in both ends up denied
+    pub fn apply_permission_rules(&mut self, allow: &[String], deny: &[String]) {
+        for tool in allow.iter().filter(|tool| !deny.contains(tool)) {
+            self.allow_tool(tool);
+        }
+        for tool in deny {
+            self.deny_tool(tool);
+        }
+    }
+
+    /// Put back `fields` from `existing` after a merge, whatever the merge
+    /// did to them. Fields `existing` doesn't set are left as merged.
+    pub fn preserve_from(&mut self, existing: &ClaudeSettings, fields: &[PreserveField]) {
+        for field in fields {
+            match field {
+                PreserveField::Hooks if existing.hooks.is_some() => {
+                    self.hooks = existing.hooks.clone()
+                }
+                PreserveField::StatusLine if existing.status_line.is_some() => {
+                    self.status_line = existing.status_line.clone()
+                }
+                PreserveField::Permissions if existing.permissions.is_some() => {
+                    self.permissions = existing.permissions.clone()
+                }
+                PreserveField::OutputStyle if existing.output_style.is_some() => {
+                    self.output_style = existing.output_style.clone()
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Put `tool` in exactly one of the allow/ask/deny lists
+    pub fn set_tool_permission(&mut self, tool: &str, list: PermissionList) {
+        self.permissions
+            .get_or_insert_with(Permissions::default)
+            .set_tool(tool, list);
+    }
+
+    /// Remove `tool` from every permission list. Returns true if it was present.
+    pub fn clear_permission(&mut self, tool: &str) -> bool {
+        let Some(perms) = self.permissions.as_mut() else {
+            return false;
+        };
+        let removed = perms.remove_tool(tool);
+        if perms.is_empty() {
+            self.permissions = None;
+        }
+        removed
+    }
+
+    /// True when nothing is set: every field is unset or an empty map/list,
+    /// so the file would serialize to `{}`
+    pub fn is_empty(&self) -> bool {
+        fn empty(value: &Value) -> bool {
+            match value {
+                Value::Null => true,
+                Value::Array(items) => items.is_empty(),
+                Value::Object(map) => map.values().all(empty),
+                _ => false,
+            }
+        }
+        settings_object(self).values().all(empty)
+    }
+
     /// Merge `template` settings into `existing`, bounded by `scope`.
     ///
     /// The template wins for the fields its scope owns; `existing` fills the
@@ -377,6 +838,15 @@ impl ClaudeSettings {
         template: Self,
         scope: &SnapshotScope,
     ) -> Self {
+        // Custom scopes own exactly their selected fields.
+        if let SnapshotScope::Custom(_) = scope {
+            return Self::merge_by_scope(
+                existing,
+                template.filter_by_scope(scope),
+                &SnapshotScope::All,
+            );
+        }
+
         let mut merged = existing;
 
         // env is always a key-level union (template overrides per key)
@@ -427,10 +897,44 @@ impl ClaudeSettings {
                 merged.aws_credential_export =
                     template.aws_credential_export.or(merged.aws_credential_export);
             }
+            SnapshotScope::Custom(_) => {
+                // handled above
+            }
         }
 
         merged
     }
+
+    /// Keep only the fields named by a custom scope.
+    fn retain_fields(self, fields: &[FieldSelector]) -> Self {
+        let env = if fields.contains(&FieldSelector::Field("env".to_string())) {
+            self.env.clone()
+        } else {
+            let selected: HashMap<String, String> = self
+                .env
+                .iter()
+                .flatten()
+                .filter(|(key, _)| fields.contains(&FieldSelector::EnvKey((*key).clone())))
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect();
+            (!selected.is_empty()).then_some(selected)
+        };
+
+        let mut retained = match serde_json::to_value(&self) {
+            Ok(Value::Object(map)) => {
+                let kept: serde_json::Map<String, Value> = map
+                    .into_iter()
+                    .filter(|(key, _)| {
+                        key != "env" && fields.contains(&FieldSelector::Field(key.clone()))
+                    })
+                    .collect();
+                serde_json::from_value(Value::Object(kept)).unwrap_or_default()
+            }
+            _ => ClaudeSettings::default(),
+        };
+        retained.env = env;
+        retained
+    }
 }
 
 impl crate::Configurable for ClaudeSettings {
@@ -468,24 +972,9 @@ impl crate::Configurable for ClaudeSettings {
     }
 
     fn filter_by_scope(self, scope: &SnapshotScope) -> Self {
-        match scope {
-            SnapshotScope::Env => ClaudeSettings {
-                env: self.env,
-                ..Default::default()
-            },
-            SnapshotScope::All => self,
-            SnapshotScope::Common => ClaudeSettings {
-                env: self.env,
-                model: self.model,
-                output_style: self.output_style,
-                attribution: self.attribution,
-                permissions: self.permissions,
-                hooks: self.hooks,
-                status_line: self.status_line,
-                subagent_model: self.subagent_model,
-                effort_level: self.effort_level,
-                ..Default::default()
-            },
+        match scope.selectors() {
+            Some(fields) => self.retain_fields(&fields),
+            None => self,
         }
     }
 
@@ -530,19 +1019,23 @@ fn merge_permissions(
     override_settings: Option<Permissions>,
 ) -> Option<Permissions> {
     match (base, override_settings) {
-        (Some(base_perms), Some(override_perms)) => Some(Permissions {
-            allow: merge_vec(base_perms.allow, override_perms.allow),
-            ask: merge_vec(base_perms.ask, override_perms.ask),
-            deny: merge_vec(base_perms.deny, override_perms.deny),
-            additional_directories: merge_vec(
-                base_perms.additional_directories,
-                override_perms.additional_directories,
-            ),
-            default_mode: override_perms.default_mode.or(base_perms.default_mode),
-            disable_bypass_permissions_mode: override_perms
-                .disable_bypass_permissions_mode
-                .or(base_perms.disable_bypass_permissions_mode),
-        }),
+        (Some(base_perms), Some(override_perms)) => {
+            let mut merged = Permissions {
+                allow: merge_unique(base_perms.allow, override_perms.allow),
+                ask: merge_unique(base_perms.ask, override_perms.ask),
+                deny: merge_unique(base_perms.deny, override_perms.deny),
+                additional_directories: merge_unique(
+                    base_perms.additional_directories,
+                    override_perms.additional_directories,
+                ),
+                default_mode: override_perms.default_mode.or(base_perms.default_mode),
+                disable_bypass_permissions_mode: override_perms
+                    .disable_bypass_permissions_mode
+                    .or(base_perms.disable_bypass_permissions_mode),
+            };
+            merged.resolve_conflicts();
+            Some(merged)
+        }
         (Some(base_perms), None) => Some(base_perms),
         (None, Some(override_perms)) => Some(override_perms),
         (None, None) => None,
@@ -588,8 +1081,34 @@ fn merge_vec<T: Clone>(base: Option<Vec<T>>, override_settings: Option<Vec<T>>)
     }
 }
 
+/// Like [`merge_vec`], but keeps only the first occurrence of each entry
+fn merge_unique<T: Clone + PartialEq>(
+    base: Option<Vec<T>>,
+    override_settings: Option<Vec<T>>,
+) -> Option<Vec<T>> {
+    merge_vec(base, override_settings).map(|merged| {
+        let mut unique = Vec::with_capacity(merged.len());
+        for item in merged {
+            if !unique.contains(&item) {
+                unique.push(item);
+            }
+        }
+        unique
+    })
+}
+
 /// Get display formatting for settings
 pub fn format_settings_for_display(settings: &ClaudeSettings, verbose: bool) -> String {
+    format_settings_with_keys(settings, verbose, false)
+}
+
+/// Like [`format_settings_for_display`], optionally showing sensitive env
+/// values (API keys, tokens) unmasked
+pub fn format_settings_with_keys(
+    settings: &ClaudeSettings,
+    verbose: bool,
+    reveal_keys: bool,
+) -> String {
     let mut output = String::new();
 
     if verbose {
@@ -597,37 +1116,110 @@ pub fn format_settings_for_display(settings: &ClaudeSettings, verbose: bool) ->
             "{} Settings\n",
             console::style("Current").bold().cyan()
         ));
-        output.push_str(&format!(
-            "{} {}\n",
-            console::style("Provider:").bold(),
-            settings.model.as_deref().unwrap_or("None")
-        ));
         output.push_str(&format!(
             "{} {}\n",
             console::style("Model:").bold(),
             settings.model.as_deref().unwrap_or("None")
         ));
+        push_field(&mut output, "Subagent model", &settings.subagent_model);
+        push_field(&mut output, "Output style", &settings.output_style);
+        push_field(&mut output, "Effort", &settings.effort_level);
 
         if let Some(ref env) = settings.env {
             output.push_str(&format!(
                 "{}\n",
                 console::style("Environment Variables:").bold()
             ));
-            for (key, value) in env {
-                let display_value = if key.contains("API_KEY")
-                    || key.contains("AUTH_TOKEN")
-                    || key.contains("TOKEN")
-                    || key.contains("SECRET")
-                    || key.contains("PASSWORD")
-                    || key.contains("PRIVATE_KEY")
-                {
-                    mask_api_key(value)
+            let mut keys: Vec<&String> = env.keys().collect();
+            keys.sort();
+            for key in keys {
+                let value = &env[key];
+                let display_value = if !reveal_keys && is_sensitive_env_key(key) {
+                    mask_api_key(value, MaskPolicy::DEFAULT)
                 } else {
                     value.clone()
                 };
                 output.push_str(&format!("  {} = {}\n", key, display_value));
             }
         }
+
+        if let Some(ref permissions) = settings.permissions {
+            output.push_str(&format!("{}\n", console::style("Permissions:").bold()));
+            push_list(&mut output, "allow", &permissions.allow);
+            push_list(&mut output, "ask", &permissions.ask);
+            push_list(&mut output, "deny", &permissions.deny);
+            push_list(
+                &mut output,
+                "additional directories",
+                &permissions.additional_directories,
+            );
+            push_sub_field(&mut output, "default mode", &permissions.default_mode);
+            push_sub_field(
+                &mut output,
+                "disable bypass mode",
+                &permissions.disable_bypass_permissions_mode,
+            );
+        }
+
+        if let Some(ref hooks) = settings.hooks {
+            output.push_str(&format!("{}\n", console::style("Hooks:").bold()));
+            push_list(&mut output, "pre_command", &hooks.pre_command);
+            push_list(&mut output, "post_command", &hooks.post_command);
+        }
+
+        if let Some(ref status_line) = settings.status_line {
+            output.push_str(&format!("{}\n", console::style("Status line:").bold()));
+            push_sub_field(&mut output, "type", &status_line.r#type);
+            push_sub_field(&mut output, "command", &status_line.command);
+        }
+
+        if let Some(ref attribution) = settings.attribution {
+            output.push_str(&format!("{}\n", console::style("Attribution:").bold()));
+            push_sub_field(&mut output, "commit", &attribution.commit);
+            push_sub_field(&mut output, "pr", &attribution.pr);
+        }
+
+        if settings.enable_all_project_mcp_servers.is_some()
+            || settings.enabled_mcpjson_servers.is_some()
+            || settings.disabled_mcpjson_servers.is_some()
+        {
+            output.push_str(&format!("{}\n", console::style("MCP servers:").bold()));
+            push_sub_field(
+                &mut output,
+                "enable all project servers",
+                &settings.enable_all_project_mcp_servers,
+            );
+            push_list(&mut output, "enabled", &settings.enabled_mcpjson_servers);
+            push_list(&mut output, "disabled", &settings.disabled_mcpjson_servers);
+        }
+
+        push_field(&mut output, "API key helper", &settings.api_key_helper);
+        push_field(
+            &mut output,
+            "Cleanup period (days)",
+            &settings.cleanup_period_days,
+        );
+        push_field(
+            &mut output,
+            "Disable all hooks",
+            &settings.disable_all_hooks,
+        );
+        push_field(
+            &mut output,
+            "Force login method",
+            &settings.force_login_method,
+        );
+        push_field(
+            &mut output,
+            "Force login org",
+            &settings.force_login_org_uuid,
+        );
+        push_field(&mut output, "AWS auth refresh", &settings.aws_auth_refresh);
+        push_field(
+            &mut output,
+            "AWS credential export",
+            &settings.aws_credential_export,
+        );
     } else {
         output.push_str(&format!(
             "{}: {} | {}: {}\n",
@@ -641,75 +1233,222 @@ pub fn format_settings_for_display(settings: &ClaudeSettings, verbose: bool) ->
     output
 }
 
-/// Compare two settings and return a formatted string showing differences
-pub fn format_settings_comparison(current: &ClaudeSettings, new: &ClaudeSettings) -> String {
-    let current_provider = "default";
-    let new_provider = "default";
-    let current_model = current.model.as_deref().unwrap_or("default");
-    let new_model = new.model.as_deref().unwrap_or("default");
-
-    // Only show comparison if there are differences
-    if current_provider == new_provider && current_model == new_model {
-        "Settings are identical.".to_string()
-    } else {
-        let mut output = String::new();
+/// Env keys whose values are masked in displays
+pub fn is_sensitive_env_key(key: &str) -> bool {
+    ["API_KEY", "TOKEN", "SECRET", "PASSWORD", "PRIVATE_KEY"]
+        .iter()
+        .any(|marker| key.contains(marker))
+}
 
+/// `Label: value` line for a populated top-level field
+fn push_field<T: std::fmt::Display>(output: &mut String, label: &str, value: &Option<T>) {
+    if let Some(value) = value {
         output.push_str(&format!(
-            "{}: {} → {}\n",
-            console::style("Provider").bold(),
-            current_provider,
-            new_provider
+            "{} {}\n",
+            console::style(format!("{}:", label)).bold(),
+            value
         ));
+    }
+}
 
-        output.push_str(&format!(
-            "{}: {} → {}\n",
-            console::style("Model").bold(),
-            current_model,
-            new_model
-        ));
+/// Indented `label: value` line inside a section
+fn push_sub_field<T: std::fmt::Display>(output: &mut String, label: &str, value: &Option<T>) {
+    if let Some(value) = value {
+        output.push_str(&format!("  {}: {}\n", label, value));
+    }
+}
 
-        output
+/// Indented `label (n):` header followed by one entry per line
+fn push_list(output: &mut String, label: &str, items: &Option<Vec<String>>) {
+    if let Some(items) = items {
+        output.push_str(&format!("  {} ({}):\n", label, items.len()));
+        for item in items {
+            output.push_str(&format!("    - {}\n", item));
+        }
     }
 }
 
-/// Mask API key for display
-fn mask_api_key(api_key: &str) -> String {
-    if let Some(actual_key) = api_key.strip_prefix("sk-") {
-        let actual_len = actual_key.len();
+// ── structured diff ──────────────────────────────────────────────────────────
 
-        if actual_len <= 6 {
-            format!("sk-{}", "*".repeat(actual_len))
-        } else if actual_len <= 14 {
-            format!(
-                "sk-{}***{}",
-                &actual_key[..2],
-                &actual_key[actual_len - 3..]
-            )
+/// How a single value differs between two settings
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change<T> {
+    Added(T),
+    Removed(T),
+    Changed { old: T, new: T },
+}
+
+impl<T: PartialEq + Clone> Change<T> {
+    /// Compare two optional values; `None` when they are equal
+    pub fn between(old: Option<&T>, new: Option<&T>) -> Option<Self> {
+        match (old, new) {
+            (None, None) => None,
+            (None, Some(n)) => Some(Change::Added(n.clone())),
+            (Some(o), None) => Some(Change::Removed(o.clone())),
+            (Some(o), Some(n)) if o == n => None,
+            (Some(o), Some(n)) => Some(Change::Changed {
+                old: o.clone(),
+                new: n.clone(),
+            }),
+        }
+    }
+}
+
+/// Structured differences between two [`ClaudeSettings`]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SettingsDiff {
+    /// Field changes keyed by settings.json name (`model`, `permissions.default_mode`, ...).
+    /// `env` and the allow/ask/deny tool lists are reported separately.
+    pub fields: BTreeMap<String, Change<Value>>,
+    /// Per-key env changes
+    pub env: BTreeMap<String, Change<String>>,
+    /// Per-tool permission changes (which list the tool is in)
+    pub permissions: BTreeMap<String, Change<PermissionList>>,
+}
+
+impl SettingsDiff {
+    /// True when the two settings are equivalent
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty() && self.env.is_empty() && self.permissions.is_empty()
+    }
+}
+
+impl ClaudeSettings {
+    /// Structured diff from `self` (old) to `other` (new)
+    pub fn diff(&self, other: &Self) -> SettingsDiff {
+        let mut diff = SettingsDiff::default();
+
+        let old = settings_object(self);
+        let new = settings_object(other);
+        let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+        for key in keys {
+            match key.as_str() {
+                "env" => {}
+                "permissions" => {
+                    let empty = serde_json::Map::new();
+                    let old_perms = old.get(key).and_then(Value::as_object).unwrap_or(&empty);
+                    let new_perms = new.get(key).and_then(Value::as_object).unwrap_or(&empty);
+                    let sub_keys: BTreeSet<&String> =
+                        old_perms.keys().chain(new_perms.keys()).collect();
+                    for sub in sub_keys {
+                        if matches!(sub.as_str(), "allow" | "ask" | "deny") {
+                            continue;
+                        }
+                        if let Some(change) =
+                            Change::between(old_perms.get(sub), new_perms.get(sub))
+                        {
+                            diff.fields.insert(format!("permissions.{}", sub), change);
+                        }
+                    }
+                }
+                _ => {
+                    if let Some(change) = Change::between(old.get(key), new.get(key)) {
+                        diff.fields.insert(key.clone(), change);
+                    }
+                }
+            }
+        }
+
+        let empty_env = HashMap::new();
+        let old_env = self.env.as_ref().unwrap_or(&empty_env);
+        let new_env = other.env.as_ref().unwrap_or(&empty_env);
+        let env_keys: BTreeSet<&String> = old_env.keys().chain(new_env.keys()).collect();
+        for key in env_keys {
+            if let Some(change) = Change::between(old_env.get(key), new_env.get(key)) {
+                diff.env.insert(key.clone(), change);
+            }
+        }
+
+        let default_perms = Permissions::default();
+        let old_perms = self.permissions.as_ref().unwrap_or(&default_perms);
+        let new_perms = other.permissions.as_ref().unwrap_or(&default_perms);
+        let tools: BTreeSet<&String> = [old_perms, new_perms]
+            .into_iter()
+            .flat_map(|p| [&p.allow, &p.ask, &p.deny])
+            .flatten()
+            .flatten()
+            .collect();
+        for tool in tools {
+            let change = Change::between(
+                old_perms.list_of(tool).as_ref(),
+                new_perms.list_of(tool).as_ref(),
+            );
+            if let Some(change) = change {
+                diff.permissions.insert(tool.clone(), change);
+            }
+        }
+
+        diff
+    }
+}
+
+/// Serialized top-level fields of `settings`
+fn settings_object(settings: &ClaudeSettings) -> serde_json::Map<String, Value> {
+    match serde_json::to_value(settings) {
+        Ok(Value::Object(map)) => map,
+        _ => serde_json::Map::new(),
+    }
+}
+
+/// Compact display of a JSON value (strings without quotes)
+fn display_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Compare two settings and return a formatted string showing differences
+pub fn format_settings_comparison(current: &ClaudeSettings, new: &ClaudeSettings) -> String {
+    let diff = current.diff(new);
+    if diff.is_empty() {
+        return "Settings are identical.".to_string();
+    }
+
+    let mut output = String::new();
+    for (field, change) in &diff.fields {
+        let label = console::style(field).bold();
+        let line = match change {
+            Change::Added(v) => format!("{}: (none) → {}", label, display_value(v)),
+            Change::Removed(v) => format!("{}: {} → (none)", label, display_value(v)),
+            Change::Changed { old, new } => {
+                format!("{}: {} → {}", label, display_value(old), display_value(new))
+            }
+        };
+        output.push_str(&line);
+        output.push('\n');
+    }
+
+    let shown = |key: &str, value: &str| {
+        if is_sensitive_env_key(key) {
+            mask_api_key(value, MaskPolicy::DEFAULT)
         } else {
-            format!(
-                "sk-{}{}...{} ({} chars)",
-                &actual_key[..3],
-                "*".repeat(std::cmp::min(actual_len - 7, 8)),
-                &actual_key[actual_len - 4..],
-                api_key.len()
-            )
+            value.to_string()
         }
-    } else if api_key.len() <= 8 {
-        "*".repeat(api_key.len())
-    } else if api_key.len() <= 16 {
-        format!("{}***{}", &api_key[..3], &api_key[api_key.len() - 3..])
-    } else {
-        let visible_start = &api_key[..4];
-        let visible_end = &api_key[api_key.len() - 4..];
-        let masked_length = api_key.len() - 8;
-        format!(
-            "{}{}...{} ({} chars)",
-            visible_start,
-            "*".repeat(std::cmp::min(masked_length, 8)),
-            visible_end,
-            api_key.len()
-        )
+    };
+    for (key, change) in &diff.env {
+        let line = match change {
+            Change::Added(v) => format!("+ env.{} = {}", key, shown(key, v)),
+            Change::Removed(v) => format!("- env.{} = {}", key, shown(key, v)),
+            Change::Changed { old, new } => {
+                format!("~ env.{}: {} → {}", key, shown(key, old), shown(key, new))
+            }
+        };
+        output.push_str(&line);
+        output.push('\n');
     }
+
+    for (tool, change) in &diff.permissions {
+        let line = match change {
+            Change::Added(list) => format!("+ {} ({})", tool, list),
+            Change::Removed(list) => format!("- {} ({})", tool, list),
+            Change::Changed { old, new } => format!("~ {}: {} → {}", tool, old, new),
+        };
+        output.push_str(&line);
+        output.push('\n');
+    }
+
+    output
 }
 
 // Environment field compatibility (for backward compatibility)
@@ -737,6 +1476,144 @@ mod tests {
     use super::*;
     use serde_json;
 
+    #[test]
+    fn test_co_authored_toggle_serializes() {
+        let mut settings = ClaudeSettings::new();
+        assert!(settings.co_authored());
+
+        settings.set_co_authored(false);
+        assert!(!settings.co_authored());
+        let json = serde_json::to_value(&settings).unwrap();
+        assert_eq!(json["attribution"]["commit"], "");
+        assert_eq!(json["attribution"]["pr"], "");
+
+        settings.set_co_authored(true);
+        assert!(settings.co_authored());
+        let json = serde_json::to_value(&settings).unwrap();
+        assert!(json.get("attribution").is_none());
+
+        // Custom attribution text survives turning it on
+        settings.attribution = Some(Attribution {
+            commit: Some("Reviewed-by: bot".into()),
+            pr: Some(String::new()),
+        });
+        settings.set_co_authored(true);
+        let json = serde_json::to_value(&settings).unwrap();
+        assert_eq!(json["attribution"]["commit"], "Reviewed-by: bot");
+        assert!(json["attribution"].get("pr").is_none());
+    }
+
+    #[test]
+    fn test_remove_env_key() {
+        let mut settings = ClaudeSettings::new();
+        assert!(!settings.remove_env_key("ANTHROPIC_DEFAULT_SONNET_MODEL"));
+
+        settings.env = Some(HashMap::from([
+            (
+                "ANTHROPIC_DEFAULT_SONNET_MODEL".to_string(),
+                "old".to_string(),
+            ),
+            (
+                "ANTHROPIC_BASE_URL".to_string(),
+                "https://example.com".to_string(),
+            ),
+        ]));
+        assert!(settings.remove_env_key("ANTHROPIC_DEFAULT_SONNET_MODEL"));
+        assert!(!settings.remove_env_key("ANTHROPIC_DEFAULT_SONNET_MODEL"));
+        assert_eq!(settings.env.as_ref().unwrap().len(), 1);
+
+        assert!(settings.remove_env_key("ANTHROPIC_BASE_URL"));
+        assert!(settings.env.is_none());
+        let json = serde_json::to_value(&settings).unwrap();
+        assert!(json.get("env").is_none());
+    }
+
+    #[test]
+    fn test_reader_writer_round_trip() {
+        use std::io::Cursor;
+
+        let mut settings = ClaudeSettings::new();
+        settings.model = Some("glm-5.2".to_string());
+        settings.allow_tool("Bash");
+        settings.env = Some(HashMap::from([(
+            "ANTHROPIC_BASE_URL".to_string(),
+            "https://x".to_string(),
+        )]));
+
+        let mut buffer = Cursor::new(Vec::new());
+        settings.to_writer(&mut buffer).unwrap();
+        assert_eq!(
+            String::from_utf8(buffer.get_ref().clone()).unwrap(),
+            serde_json::to_string_pretty(&settings).unwrap()
+        );
+        buffer.set_position(0);
+        assert_eq!(ClaudeSettings::from_reader(buffer).unwrap(), settings);
+
+        assert_eq!(
+            ClaudeSettings::from_reader(Cursor::new(b"  \n".to_vec())).unwrap(),
+            ClaudeSettings::new()
+        );
+        let err = ClaudeSettings::from_reader(Cursor::new(b"{ nope".to_vec())).unwrap_err();
+        assert!(!err.is_io());
+    }
+
+    #[test]
+    fn test_from_file_or_recover() {
+        let dir = std::env::temp_dir().join(format!("ccs_recover_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("settings.json");
+        let never = |_: &Path| -> Result<bool> { panic!("only invalid files need recovery") };
+
+        for content in ["", "  \n", "{}", "{}\n"] {
+            fs::write(&path, content).unwrap();
+            let (settings, moved) = ClaudeSettings::from_file_or_recover(&path, never).unwrap();
+            assert_eq!(settings, ClaudeSettings::new());
+            assert!(moved.is_none());
+        }
+
+        let malformed = "{\n  \"model\": \"glm\",\n  oops\n}";
+        fs::write(&path, malformed).unwrap();
+        let err = ClaudeSettings::from_file_or_recover(&path, |_| Ok(false))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains(&path.display().to_string()));
+        assert!(err.contains("line 3"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), malformed);
+
+        let (settings, moved) = ClaudeSettings::from_file_or_recover(&path, |_| Ok(true)).unwrap();
+        assert_eq!(settings, ClaudeSettings::new());
+        assert!(!path.exists());
+        assert_eq!(fs::read_to_string(moved.unwrap()).unwrap(), malformed);
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_output_style_persists_through_save_and_load() {
+        assert_eq!(parse_output_style("Concise").unwrap(), "concise");
+        assert!(parse_output_style("fancy").is_err());
+
+        let path = std::env::temp_dir()
+            .join(format!("ccs_output_style_{}", uuid::Uuid::new_v4()))
+            .join("settings.json");
+        let settings = ClaudeSettings {
+            output_style: Some(parse_output_style("concise").unwrap()),
+            ..Default::default()
+        };
+        settings.to_file(&path).unwrap();
+        let loaded = ClaudeSettings::from_file(&path).unwrap();
+        assert_eq!(loaded.output_style.as_deref(), Some("concise"));
+        assert!(format_settings_for_display(&loaded, true).contains("concise"));
+
+        let changed = ClaudeSettings {
+            output_style: Some("explanatory".into()),
+            ..Default::default()
+        };
+        assert!(loaded.diff(&changed).fields.contains_key("output_style"));
+
+        fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
     #[test]
     fn test_deserialize_env_with_integer_values() {
         let json_with_int = r#"{
@@ -876,6 +1753,82 @@ mod tests {
         assert!(merged.permissions.is_some()); // permissions untouched
     }
 
+    #[test]
+    fn test_replace_strategy_drops_stale_provider_env() {
+        use crate::snapshots::SnapshotScope;
+
+        let existing = ClaudeSettings {
+            env: Some(
+                [
+                    ("ANTHROPIC_DEFAULT_SONNET_MODEL", "old-sonnet"),
+                    ("ANTHROPIC_BASE_URL", "https://old.example.com"),
+                    ("API_TIMEOUT_MS", "600000"),
+                    ("MY_CUSTOM_VAR", "keep"),
+                ]
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            ),
+            status_line: Some(StatusLine {
+                r#type: Some("command".to_string()),
+                command: Some("line.sh".to_string()),
+            }),
+            ..Default::default()
+        };
+        let template = ClaudeSettings {
+            env: Some(HashMap::from([(
+                "ANTHROPIC_BASE_URL".to_string(),
+                "https://new.example.com".to_string(),
+            )])),
+            ..Default::default()
+        };
+
+        let merged =
+            MergeStrategy::Merge.apply(existing.clone(), template.clone(), &SnapshotScope::Common);
+        let env = merged.env.unwrap();
+        assert_eq!(env["ANTHROPIC_DEFAULT_SONNET_MODEL"], "old-sonnet");
+        assert_eq!(env["API_TIMEOUT_MS"], "600000");
+
+        let replaced = MergeStrategy::Replace.apply(existing, template, &SnapshotScope::Common);
+        let env = replaced.env.unwrap();
+        assert!(!env.contains_key("ANTHROPIC_DEFAULT_SONNET_MODEL"));
+        assert!(!env.contains_key("API_TIMEOUT_MS"));
+        assert_eq!(env["ANTHROPIC_BASE_URL"], "https://new.example.com");
+        // Non-provider settings survive
+        assert_eq!(env["MY_CUSTOM_VAR"], "keep");
+        assert!(replaced.status_line.is_some());
+    }
+
+    #[test]
+    fn test_replace_strategy_respects_scope() {
+        use crate::snapshots::SnapshotScope;
+
+        let existing = ClaudeSettings {
+            model: Some("old-model".to_string()),
+            env: Some(HashMap::from([(
+                "ANTHROPIC_MODEL".to_string(),
+                "old-model".to_string(),
+            )])),
+            ..Default::default()
+        };
+
+        // The env scope doesn't own `model`
+        let replaced = MergeStrategy::Replace.apply(
+            existing.clone(),
+            ClaudeSettings::default(),
+            &SnapshotScope::Env,
+        );
+        assert_eq!(replaced.model.as_deref(), Some("old-model"));
+        assert!(replaced.env.unwrap_or_default().is_empty());
+
+        let replaced = MergeStrategy::Replace.apply(
+            existing,
+            ClaudeSettings::default(),
+            &SnapshotScope::Common,
+        );
+        assert!(replaced.model.is_none());
+    }
+
     #[test]
     fn test_merge_by_scope_template_none_keeps_existing() {
         use crate::snapshots::SnapshotScope;
@@ -892,4 +1845,151 @@ mod tests {
         let merged = ClaudeSettings::merge_by_scope(existing, template, &SnapshotScope::Common);
         assert_eq!(merged.model, Some("keep".to_string()));
     }
+
+    #[test]
+    fn test_permission_move_between_lists() {
+        let mut settings = ClaudeSettings::new();
+        settings.allow_tool("WebSearch");
+        settings.allow_tool("Read");
+        settings.deny_tool("WebSearch");
+
+        let perms = settings.permissions.as_ref().unwrap();
+        assert_eq!(perms.allow, Some(vec!["Read".to_string()]));
+        assert_eq!(perms.deny, Some(vec!["WebSearch".to_string()]));
+        assert_eq!(perms.list_of("WebSearch"), Some(PermissionList::Deny));
+
+        settings.ask_tool("WebSearch");
+        let perms = settings.permissions.as_ref().unwrap();
+        assert_eq!(perms.deny, None);
+        assert_eq!(perms.ask, Some(vec!["WebSearch".to_string()]));
+
+        // Re-adding to the same list doesn't duplicate
+        settings.ask_tool("WebSearch");
+        let perms = settings.permissions.as_ref().unwrap();
+        assert_eq!(perms.ask, Some(vec!["WebSearch".to_string()]));
+    }
+
+    #[test]
+    fn test_is_empty() {
+        let mut settings = ClaudeSettings::new();
+        assert!(settings.is_empty());
+        settings.env = Some(HashMap::new());
+        settings.permissions = Some(Permissions::default());
+        assert!(settings.is_empty());
+        settings.cleanup_period_days = Some(0);
+        assert!(!settings.is_empty());
+    }
+
+    #[test]
+    fn test_merge_permissions_dedups_and_deny_wins() {
+        let list = |tools: &[&str]| Some(tools.iter().map(|t| t.to_string()).collect());
+        let base = Permissions {
+            allow: list(&["Bash", "Read", "Bash"]),
+            deny: list(&["WebFetch"]),
+            ..Default::default()
+        };
+        let incoming = Permissions {
+            allow: list(&["Read", "Edit", "WebFetch"]),
+            ask: list(&["Edit"]),
+            deny: list(&["Edit", "WebFetch"]),
+            ..Default::default()
+        };
+
+        let merged = merge_permissions(Some(base), Some(incoming)).unwrap();
+        assert_eq!(merged.allow, list(&["Bash", "Read"]));
+        assert_eq!(merged.ask, None);
+        assert_eq!(merged.deny, list(&["WebFetch", "Edit"]));
+    }
+
+    #[test]
+    fn test_clear_permission() {
+        let mut settings = ClaudeSettings::new();
+        settings.deny_tool("Bash");
+
+        assert!(settings.clear_permission("Bash"));
+        assert!(!settings.clear_permission("Bash"));
+        assert_eq!(settings.permissions, None);
+    }
+
+    #[test]
+    fn test_verbose_display_shows_permissions_and_hooks() {
+        let mut settings = ClaudeSettings::new();
+        settings.model = Some("glm-5".to_string());
+        settings.allow_tool("Bash");
+        settings.allow_tool("Read");
+        settings.deny_tool("WebSearch");
+        settings.hooks = Some(Hooks {
+            pre_command: Some(vec!["echo pre".to_string()]),
+            post_command: None,
+        });
+        settings.subagent_model = Some("glm-5-air".to_string());
+        settings.env = Some(HashMap::from([(
+            "ANTHROPIC_AUTH_TOKEN".to_string(),
+            "sk-1234567890abcdef".to_string(),
+        )]));
+
+        let output =
+            console::strip_ansi_codes(&format_settings_for_display(&settings, true)).to_string();
+        assert!(output.contains("Permissions:"), "{}", output);
+        assert!(output.contains("allow (2):"));
+        assert!(output.contains("    - Read"));
+        assert!(output.contains("deny (1):"));
+        assert!(output.contains("Hooks:"));
+        assert!(output.contains("pre_command (1):"));
+        assert!(output.contains("    - echo pre"));
+        assert!(output.contains("Subagent model: glm-5-air"));
+        assert!(!output.contains("sk-1234567890abcdef"));
+        assert!(!output.contains("Provider:"));
+    }
+
+    #[test]
+    fn test_settings_diff_categorizes_changes() {
+        let mut old = ClaudeSettings::new();
+        old.model = Some("glm-4.6".to_string());
+        old.allow_tool("Bash");
+        old.allow_tool("WebSearch");
+        old.env = Some(HashMap::from([(
+            "API_TIMEOUT_MS".to_string(),
+            "600000".to_string(),
+        )]));
+
+        let mut new = old.clone();
+        new.model = Some("glm-5".to_string());
+        new.clear_permission("WebSearch");
+        new.env.as_mut().unwrap().insert(
+            "ANTHROPIC_BASE_URL".to_string(),
+            "https://open.bigmodel.cn/api/anthropic".to_string(),
+        );
+
+        let diff = old.diff(&new);
+        assert_eq!(
+            diff.env.get("ANTHROPIC_BASE_URL"),
+            Some(&Change::Added(
+                "https://open.bigmodel.cn/api/anthropic".to_string()
+            ))
+        );
+        assert!(!diff.env.contains_key("API_TIMEOUT_MS"));
+        assert_eq!(
+            diff.permissions.get("WebSearch"),
+            Some(&Change::Removed(PermissionList::Allow))
+        );
+        assert!(!diff.permissions.contains_key("Bash"));
+        assert_eq!(
+            diff.fields.get("model"),
+            Some(&Change::Changed {
+                old: Value::String("glm-4.6".to_string()),
+                new: Value::String("glm-5".to_string()),
+            })
+        );
+        assert!(old.diff(&old).is_empty());
+
+        let text = console::strip_ansi_codes(&format_settings_comparison(&old, &new)).to_string();
+        assert!(text.contains("model: glm-4.6 → glm-5"), "{}", text);
+        assert!(text.contains("+ env.ANTHROPIC_BASE_URL"));
+        assert!(text.contains("- WebSearch (allow)"));
+        assert_eq!(
+            format_settings_comparison(&old, &old),
+            "Settings are identical."
+        );
+    }
 }
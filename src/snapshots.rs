@@ -1,9 +1,16 @@
+use crate::Configurable;
+use crate::error::{CcsError, CcsResult};
 use crate::settings::ClaudeSettings;
+use crate::utils::{FileLock, LOCK_TIMEOUT, RetryPolicy, write_atomic_retrying};
 use anyhow::{Result, anyhow};
-use chrono::Utc;
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 use uuid::Uuid;
 
 /// Scope for snapshots
@@ -11,23 +18,74 @@ use uuid::Uuid;
 pub enum SnapshotScope {
     /// Only environment variables
     Env,
-    /// Common settings (exclude environment)
+    /// Common settings: model, permissions, hooks, etc. plus the settings
+    /// file's own `env` block (the process environment is not captured)
     #[default]
     Common,
     /// All settings
     All,
+    /// Only the selected top-level fields and env keys
+    Custom(Vec<FieldSelector>),
+}
+
+impl SnapshotScope {
+    /// Scope to generate template settings with. Custom scopes start from
+    /// everything and are narrowed by `filter_by_scope` afterwards.
+    pub fn template_scope(&self) -> SnapshotScope {
+        match self {
+            SnapshotScope::Custom(_) => SnapshotScope::All,
+            other => other.clone(),
+        }
+    }
+
+    /// Fields this scope keeps, or `None` for `all`
+    pub(crate) fn selectors(&self) -> Option<Vec<FieldSelector>> {
+        let fields = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| FieldSelector::Field(name.to_string()))
+                .collect()
+        };
+        match self {
+            SnapshotScope::Env => Some(fields(&["env"])),
+            SnapshotScope::Common => Some(fields(COMMON_FIELDS)),
+            SnapshotScope::All => None,
+            SnapshotScope::Custom(selectors) => Some(selectors.clone()),
+        }
+    }
+
+    /// Whether everything `other` keeps is also kept by this scope
+    pub fn covers(&self, other: &SnapshotScope) -> bool {
+        let Some(mine) = self.selectors() else {
+            return true;
+        };
+        let Some(theirs) = other.selectors() else {
+            return false;
+        };
+        let whole_env = FieldSelector::Field("env".to_string());
+        theirs.iter().all(|selector| {
+            mine.contains(selector)
+                || matches!(selector, FieldSelector::EnvKey(_)) && mine.contains(&whole_env)
+        })
+    }
 }
 
 impl std::str::FromStr for SnapshotScope {
     type Err = anyhow::Error;
 
+    /// Accepts `env`, `common`, `all` (case-insensitive, trimmed) and the
+    /// aliases `e`; `c`, `base`; `a`, `full`, `everything`
     fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if let Some(spec) = s.strip_prefix("custom:") {
+            return Ok(SnapshotScope::Custom(parse_field_selectors(spec)?));
+        }
         match s.to_lowercase().as_str() {
-            "env" => Ok(SnapshotScope::Env),
-            "common" => Ok(SnapshotScope::Common),
-            "all" => Ok(SnapshotScope::All),
+            "env" | "e" => Ok(SnapshotScope::Env),
+            "common" | "c" | "base" => Ok(SnapshotScope::Common),
+            "all" | "a" | "full" | "everything" => Ok(SnapshotScope::All),
             _ => Err(anyhow!(
-                "Invalid scope '{}'. Must be one of: env, common, all",
+                "Invalid scope '{}'. Must be one of: env, common, all, custom:<fields>",
                 s
             )),
         }
@@ -40,10 +98,120 @@ impl std::fmt::Display for SnapshotScope {
             SnapshotScope::Env => write!(f, "env"),
             SnapshotScope::Common => write!(f, "common"),
             SnapshotScope::All => write!(f, "all"),
+            SnapshotScope::Custom(fields) => {
+                let spec: Vec<String> = fields.iter().map(|f| f.to_string()).collect();
+                write!(f, "custom:{}", spec.join(","))
+            }
+        }
+    }
+}
+
+/// Top-level settings.json keys a custom scope can select
+pub const SETTINGS_FIELDS: &[&str] = &[
+    "env",
+    "model",
+    "output_style",
+    "attribution",
+    "permissions",
+    "hooks",
+    "api_key_helper",
+    "cleanup_period_days",
+    "disable_all_hooks",
+    "force_login_method",
+    "force_login_org_uuid",
+    "enable_all_project_mcp_servers",
+    "enabled_mcpjson_servers",
+    "disabled_mcpjson_servers",
+    "aws_auth_refresh",
+    "aws_credential_export",
+    "status_line",
+    "subagent_model",
+    "effortLevel",
+];
+
+/// Top-level fields (as named in settings.json) kept by the `common` scope
+const COMMON_FIELDS: &[&str] = &[
+    "env",
+    "model",
+    "output_style",
+    "attribution",
+    "permissions",
+    "hooks",
+    "status_line",
+    "subagent_model",
+    "effortLevel",
+];
+
+/// One entry of a custom scope
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum FieldSelector {
+    /// A whole top-level field, e.g. `model` or `env`
+    Field(String),
+    /// A single environment variable, e.g. `env.ANTHROPIC_BASE_URL`
+    EnvKey(String),
+}
+
+impl std::str::FromStr for FieldSelector {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if let Some(key) = s.strip_prefix("env.") {
+            if key.is_empty() {
+                return Err(anyhow!("Missing env var name in '{}'", s));
+            }
+            return Ok(FieldSelector::EnvKey(key.to_string()));
         }
+
+        // Accept snake_case, camelCase and kebab-case spellings
+        let normalize = |name: &str| name.replace(['_', '-'], "").to_lowercase();
+        SETTINGS_FIELDS
+            .iter()
+            .find(|field| normalize(field) == normalize(s))
+            .map(|field| FieldSelector::Field(field.to_string()))
+            .ok_or_else(|| {
+                anyhow!(
+                    "Unknown settings field '{}'. Use one of: {}, or env.<VAR>",
+                    s,
+                    SETTINGS_FIELDS.join(", ")
+                )
+            })
     }
 }
 
+impl std::fmt::Display for FieldSelector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FieldSelector::Field(name) => write!(f, "{}", name),
+            FieldSelector::EnvKey(key) => write!(f, "env.{}", key),
+        }
+    }
+}
+
+/// Parse a comma-separated field list such as `model,permissions,env.ANTHROPIC_BASE_URL`
+pub fn parse_field_selectors(spec: &str) -> Result<Vec<FieldSelector>> {
+    let fields = spec
+        .split(',')
+        .filter(|part| !part.trim().is_empty())
+        .map(|part| part.parse())
+        .collect::<Result<Vec<FieldSelector>>>()?;
+    if fields.is_empty() {
+        return Err(anyhow!("Custom scope needs at least one field"));
+    }
+    Ok(fields)
+}
+
+/// Current snapshot format version
+///
+/// - v1: original format
+/// - v2: `ANTHROPIC_SMALL_FAST_MODEL` renamed to `ANTHROPIC_DEFAULT_HAIKU_MODEL`,
+///   following Claude Code
+pub const CURRENT_SNAPSHOT_VERSION: u32 = 2;
+
+/// Env var Claude Code replaced with `ANTHROPIC_DEFAULT_HAIKU_MODEL`
+const LEGACY_SMALL_FAST_MODEL: &str = "ANTHROPIC_SMALL_FAST_MODEL";
+const DEFAULT_HAIKU_MODEL: &str = "ANTHROPIC_DEFAULT_HAIKU_MODEL";
+
 /// A snapshot of Claude Code settings
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Snapshot {
@@ -70,6 +238,15 @@ pub struct Snapshot {
 
     /// Version for future compatibility
     pub version: u32,
+
+    /// sha256 of `settings`, written on save and checked on load. Missing in
+    /// snapshots saved by older versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+
+    /// Show API keys unmasked when displaying the settings (runtime only)
+    #[serde(skip)]
+    pub show_api_key: bool,
 }
 
 impl Snapshot {
@@ -90,8 +267,79 @@ impl Snapshot {
             created_at: now.clone(),
             updated_at: now,
             scope,
-            version: 1,
+            version: CURRENT_SNAPSHOT_VERSION,
+            checksum: None,
+            show_api_key: false,
+        }
+    }
+
+    /// Hex sha256 of the settings serialized with sorted keys
+    pub fn settings_checksum(&self) -> String {
+        use sha2::{Digest, Sha256};
+        let canonical = serde_json::to_value(&self.settings)
+            .map(|value| value.to_string())
+            .unwrap_or_default();
+        Sha256::digest(canonical.as_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    /// Whether the stored checksum matches the settings; `None` when the
+    /// snapshot has no checksum yet
+    pub fn checksum_matches(&self) -> Option<bool> {
+        self.checksum
+            .as_ref()
+            .map(|checksum| *checksum == self.settings_checksum())
+    }
+
+    /// Format the snapshot's metadata and settings for display
+    pub fn format_details(&self) -> String {
+        let mut output = format!("📋 Snapshot: {} ({})\n", self.name, self.scope);
+        output.push_str(&format!("  ID: {}\n", self.id));
+        output.push_str(&format!("  Created: {}\n", self.created_at));
+        output.push_str(&format!("  Updated: {}\n", self.updated_at));
+        if let Some(ref desc) = self.description {
+            output.push_str(&format!("  Description: {}\n", desc));
         }
+        output.push_str(&format!("  Version: {}\n\n", self.version));
+        output.push_str(&self.format_settings(true));
+        output
+    }
+
+    /// Field-level differences from `self` to `other`, API keys masked. The
+    /// header notes when the two were captured with different scopes, since
+    /// fields outside the narrower scope then show up as added/removed.
+    pub fn format_diff(&self, other: &Snapshot) -> String {
+        let mut output = format!(
+            "🔍 {} ({}) → {} ({})\n",
+            self.name, self.scope, other.name, other.scope
+        );
+        if self.scope != other.scope {
+            output.push_str(&format!(
+                "  Note: scopes differ ({} vs {}); fields outside the narrower scope may show as added/removed\n",
+                self.scope, other.scope
+            ));
+        }
+        output.push('\n');
+        output.push_str(&crate::settings::format_settings_comparison(
+            &self.settings,
+            &other.settings,
+        ));
+        output
+    }
+
+    /// Format the snapshot's settings for display, masking API keys unless
+    /// `show_api_key` is set
+    pub fn format_settings(&self, verbose: bool) -> String {
+        crate::settings::format_settings_with_keys(&self.settings, verbose, self.show_api_key)
+    }
+
+    /// Replace the description (blank clears it) and bump `updated_at`
+    pub fn set_description(&mut self, text: &str) {
+        let text = text.trim();
+        self.description = (!text.is_empty()).then(|| text.to_string());
+        self.touch();
     }
 
     /// Update the timestamp
@@ -99,6 +347,66 @@ impl Snapshot {
         let now = Utc::now().format("%Y-%m-%d %H:%M:%S UTC").to_string();
         self.updated_at = now;
     }
+
+    /// Upgrade a snapshot written by an older ccs to
+    /// [`CURRENT_SNAPSHOT_VERSION`], one version bump at a time. Errors on
+    /// snapshots from a newer ccs rather than guessing at their format.
+    pub fn migrate(mut self) -> Result<Self> {
+        if self.version > CURRENT_SNAPSHOT_VERSION {
+            return Err(anyhow!(
+                "Snapshot '{}' has format version {}, newer than this ccs supports ({}); please upgrade ccs",
+                self.name,
+                self.version,
+                CURRENT_SNAPSHOT_VERSION
+            ));
+        }
+
+        if self.version < 2 {
+            // v1 → v2: ANTHROPIC_SMALL_FAST_MODEL → ANTHROPIC_DEFAULT_HAIKU_MODEL
+            if let Some(env) = self.settings.env.as_mut()
+                && let Some(model) = env.remove(LEGACY_SMALL_FAST_MODEL)
+            {
+                env.entry(DEFAULT_HAIKU_MODEL.to_string()).or_insert(model);
+            }
+            if let SnapshotScope::Custom(fields) = &mut self.scope {
+                for field in fields {
+                    if *field == FieldSelector::EnvKey(LEGACY_SMALL_FAST_MODEL.to_string()) {
+                        *field = FieldSelector::EnvKey(DEFAULT_HAIKU_MODEL.to_string());
+                    }
+                }
+            }
+            self.version = 2;
+        }
+
+        Ok(self)
+    }
+
+    /// Creation time, or `None` if `created_at` doesn't parse
+    pub fn created_time(&self) -> Option<DateTime<Utc>> {
+        NaiveDateTime::parse_from_str(&self.created_at, "%Y-%m-%d %H:%M:%S UTC")
+            .ok()
+            .map(|t| t.and_utc())
+    }
+}
+
+/// Collections at least this large are parsed in parallel (`parallel` feature)
+#[cfg(feature = "parallel")]
+const PARALLEL_LOAD_THRESHOLD: usize = 64;
+
+/// Newest first; ties (same-second creation) are broken by id so the order is stable
+fn sort_newest_first(snapshots: &mut [Snapshot]) {
+    snapshots.sort_by(|a, b| {
+        b.created_at
+            .cmp(&a.created_at)
+            .then_with(|| a.id.cmp(&b.id))
+    });
+}
+
+/// Parsed snapshots together with the directory mtime they were read at
+#[derive(Debug)]
+struct ListCache {
+    dir_mtime: SystemTime,
+    snapshots: Vec<Snapshot>,
 }
 
 /// Store for managing snapshots
@@ -106,12 +414,44 @@ impl Snapshot {
 pub struct SnapshotStore {
     /// Directory where snapshots are stored
     pub snapshots_dir: PathBuf,
+
+    /// Result of the last `list`, reused while the directory is unchanged
+    cache: Arc<Mutex<Option<ListCache>>>,
+
+    /// Number of snapshot files parsed (for cache diagnostics)
+    parse_count: Arc<AtomicUsize>,
+
+    /// Retry policy for transient write errors
+    pub retry: RetryPolicy,
 }
 
 impl SnapshotStore {
     /// Create a new snapshot store
     pub fn new(snapshots_dir: PathBuf) -> Self {
-        Self { snapshots_dir }
+        Self {
+            snapshots_dir,
+            cache: Arc::new(Mutex::new(None)),
+            parse_count: Arc::new(AtomicUsize::new(0)),
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    /// Use `retry` for transient write errors
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// How many snapshot files have been parsed by this store
+    pub fn parse_count(&self) -> usize {
+        self.parse_count.load(Ordering::Relaxed)
+    }
+
+    /// Drop the cached `list` result
+    pub fn invalidate_cache(&self) {
+        if let Ok(mut cache) = self.cache.lock() {
+            *cache = None;
+        }
     }
 
     /// Ensure the snapshots directory exists
@@ -128,6 +468,13 @@ impl SnapshotStore {
         Ok(())
     }
 
+    /// Hold the store's [`FileLock`], e.g. across a load → modify → save
+    pub fn lock(&self) -> Result<FileLock> {
+        self.ensure_dir()?;
+        FileLock::acquire(&self.snapshots_dir, LOCK_TIMEOUT)
+            .map_err(|e| anyhow!("Failed to lock snapshots: {}", e))
+    }
+
     /// Get the path for a snapshot file
     pub fn snapshot_path(&self, snapshot_id: &str) -> PathBuf {
         self.snapshots_dir.join(format!("{}.json", snapshot_id))
@@ -138,10 +485,15 @@ impl SnapshotStore {
         self.ensure_dir()?;
 
         let path = self.snapshot_path(&snapshot.id);
-        let content = serde_json::to_string_pretty(snapshot)
+        let snapshot = Snapshot {
+            checksum: Some(snapshot.settings_checksum()),
+            ..snapshot.clone()
+        };
+        let content = serde_json::to_string_pretty(&snapshot)
             .map_err(|e| anyhow!("Failed to serialize snapshot: {}", e))?;
 
-        fs::write(&path, content)
+        self.invalidate_cache();
+        write_atomic_retrying(&path, content.as_bytes(), &self.retry)
             .map_err(|e| anyhow!("Failed to write snapshot file {}: {}", path.display(), e))?;
 
         Ok(())
@@ -158,14 +510,52 @@ impl SnapshotStore {
         let content = fs::read_to_string(&path)
             .map_err(|e| anyhow!("Failed to read snapshot file {}: {}", path.display(), e))?;
 
+        self.parse_count.fetch_add(1, Ordering::Relaxed);
         let snapshot: Snapshot = serde_json::from_str(&content)
             .map_err(|e| anyhow!("Failed to parse snapshot file {}: {}", path.display(), e))?;
 
+        let loaded_version = snapshot.version;
+        let snapshot = snapshot.migrate()?;
+        if snapshot.version != loaded_version {
+            // Best-effort: a read-only or busy snapshots dir (the lock may be
+            // held by the caller on another rayon thread) just migrates on
+            // every load
+            if let Ok(_lock) = FileLock::acquire(&self.snapshots_dir, std::time::Duration::ZERO) {
+                let _ = self.save(&snapshot);
+            }
+        }
+
+        Ok(snapshot)
+    }
+
+    /// Warning for a snapshot whose settings don't match its stored
+    /// checksum. Not an error: the file may just have been edited by hand.
+    pub fn checksum_warning(&self, snapshot: &Snapshot) -> Option<String> {
+        (snapshot.checksum_matches() == Some(false)).then(|| {
+            format!(
+                "Snapshot '{}' does not match its checksum; {} may have been modified or corrupted",
+                snapshot.name,
+                self.snapshot_path(&snapshot.id).display()
+            )
+        })
+    }
+
+    /// The most recently created snapshot, if any
+    pub fn latest(&self) -> Result<Option<Snapshot>> {
+        Ok(self.list()?.into_iter().next())
+    }
+
+    /// Set the description of the snapshot called `name` (blank clears it)
+    pub fn describe(&self, name: &str, text: &str) -> Result<Snapshot> {
+        let _lock = self.lock()?;
+        let mut snapshot = self.load_by_name(name)?;
+        snapshot.set_description(text);
+        self.save(&snapshot)?;
         Ok(snapshot)
     }
 
     /// Load a snapshot by name
-    pub fn load_by_name(&self, name: &str) -> Result<Snapshot> {
+    pub fn load_by_name(&self, name: &str) -> CcsResult<Snapshot> {
         let snapshots = self.list()?;
 
         for snapshot in snapshots {
@@ -174,36 +564,84 @@ impl SnapshotStore {
             }
         }
 
-        Err(anyhow!("Snapshot '{}' not found", name))
+        Err(CcsError::SnapshotNotFound(name.to_string()))
     }
 
     /// List all snapshots
+    ///
+    /// Results are cached per store and reused until `save`/`delete` runs or
+    /// the directory's modification time changes.
     pub fn list(&self) -> Result<Vec<Snapshot>> {
         if !self.snapshots_dir.exists() {
             return Ok(Vec::new());
         }
 
-        let mut snapshots = Vec::new();
+        let dir_mtime = fs::metadata(&self.snapshots_dir)
+            .and_then(|m| m.modified())
+            .ok();
+        if let Some(mtime) = dir_mtime
+            && let Ok(cache) = self.cache.lock()
+            && let Some(cached) = cache.as_ref()
+            && cached.dir_mtime == mtime
+        {
+            return Ok(cached.snapshots.clone());
+        }
+
+        let snapshots = self.read_all()?;
+
+        if let Some(dir_mtime) = dir_mtime
+            && let Ok(mut cache) = self.cache.lock()
+        {
+            *cache = Some(ListCache {
+                dir_mtime,
+                snapshots: snapshots.clone(),
+            });
+        }
+
+        Ok(snapshots)
+    }
+
+    /// Parse every snapshot file in the directory, newest first
+    fn read_all(&self) -> Result<Vec<Snapshot>> {
+        let ids = self.snapshot_ids()?;
+
+        #[cfg(feature = "parallel")]
+        let mut snapshots = if ids.len() >= PARALLEL_LOAD_THRESHOLD {
+            self.parse_parallel(&ids)
+        } else {
+            self.parse_serial(&ids)
+        };
+        #[cfg(not(feature = "parallel"))]
+        let mut snapshots = self.parse_serial(&ids);
+
+        sort_newest_first(&mut snapshots);
+        Ok(snapshots)
+    }
 
+    /// IDs (file stems) of every `.json` file in the snapshots directory
+    fn snapshot_ids(&self) -> Result<Vec<String>> {
+        let mut ids = Vec::new();
         for entry in fs::read_dir(&self.snapshots_dir)? {
-            let entry = entry?;
-            let path = entry.path();
-
-            if path.extension().and_then(|s| s.to_str()) == Some("json") {
-                match self.load(path.file_stem().and_then(|s| s.to_str()).unwrap_or("")) {
-                    Ok(snapshot) => snapshots.push(snapshot),
-                    Err(_) => {
-                        // Skip invalid snapshot files
-                        continue;
-                    }
-                }
+            let path = entry?.path();
+            if path.extension().and_then(|s| s.to_str()) == Some("json")
+                && let Some(stem) = path.file_stem().and_then(|s| s.to_str())
+            {
+                ids.push(stem.to_string());
             }
         }
+        Ok(ids)
+    }
 
-        // Sort by creation date (newest first)
-        snapshots.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    /// Load snapshots one by one, skipping invalid files
+    fn parse_serial(&self, ids: &[String]) -> Vec<Snapshot> {
+        ids.iter().filter_map(|id| self.load(id).ok()).collect()
+    }
 
-        Ok(snapshots)
+    /// Load snapshots on the rayon pool, skipping invalid files
+    #[cfg(feature = "parallel")]
+    fn parse_parallel(&self, ids: &[String]) -> Vec<Snapshot> {
+        use rayon::prelude::*;
+        ids.par_iter().filter_map(|id| self.load(id).ok()).collect()
     }
 
     /// Delete a snapshot
@@ -214,6 +652,8 @@ impl SnapshotStore {
             return Err(anyhow!("Snapshot '{}' not found", snapshot_id));
         }
 
+        let _lock = self.lock()?;
+        self.invalidate_cache();
         fs::remove_file(&path)
             .map_err(|e| anyhow!("Failed to delete snapshot file {}: {}", path.display(), e))?;
 
@@ -221,16 +661,17 @@ impl SnapshotStore {
     }
 
     /// Delete a snapshot by name
-    pub fn delete_by_name(&self, name: &str) -> Result<()> {
+    pub fn delete_by_name(&self, name: &str) -> CcsResult<()> {
+        let _lock = self.lock()?;
         let snapshots = self.list()?;
 
         for snapshot in snapshots {
             if snapshot.name == name {
-                return self.delete(&snapshot.id);
+                return Ok(self.delete(&snapshot.id)?);
             }
         }
 
-        Err(anyhow!("Snapshot '{}' not found", name))
+        Err(CcsError::SnapshotNotFound(name.to_string()))
     }
 
     /// Check if a snapshot exists
@@ -250,26 +691,834 @@ impl SnapshotStore {
         let snapshots = self.list()?;
         Ok(snapshots.into_iter().map(|s| s.name).collect())
     }
+
+    /// Snapshots `prune` would delete: all but the newest `keep_count`, plus
+    /// any created more than `older_than` ago. Snapshots whose creation time
+    /// doesn't parse, or an `older_than` reaching past the earliest
+    /// representable time, never count as too old.
+    pub fn prune_candidates(
+        &self,
+        keep_count: Option<usize>,
+        older_than: Option<Duration>,
+    ) -> Result<Vec<Snapshot>> {
+        let cutoff = older_than.and_then(crate::utils::cutoff_before);
+        Ok(self
+            .list()?
+            .into_iter()
+            .enumerate()
+            .filter(|(index, snapshot)| {
+                let over_count = keep_count.is_some_and(|keep| *index >= keep);
+                let too_old = cutoff
+                    .zip(snapshot.created_time())
+                    .is_some_and(|(cutoff, created)| created < cutoff);
+                over_count || too_old
+            })
+            .map(|(_, snapshot)| snapshot)
+            .collect())
+    }
+
+    /// Delete old snapshots (see [`Self::prune_candidates`]) and return the
+    /// deleted names, newest first
+    pub fn prune(
+        &self,
+        keep_count: Option<usize>,
+        older_than: Option<Duration>,
+    ) -> Result<Vec<String>> {
+        let _lock = self.lock()?;
+        let mut deleted = Vec::new();
+        for snapshot in self.prune_candidates(keep_count, older_than)? {
+            self.delete(&snapshot.id)?;
+            deleted.push(snapshot.name);
+        }
+        Ok(deleted)
+    }
+
+    /// Import every `.json` snapshot file in `dir` with a fresh ID.
+    ///
+    /// A file whose name and settings match an existing snapshot is skipped.
+    /// Other name collisions replace the existing snapshot if `overwrite`,
+    /// else the import gets the first free `-N` suffix.
+    pub fn import_directory(&self, dir: &Path, overwrite: bool) -> Result<ImportReport> {
+        let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+            .map_err(|e| anyhow!("Failed to read {}: {}", dir.display(), e))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("json"))
+            .collect();
+        paths.sort();
+
+        let _lock = self.lock()?;
+        let mut report = ImportReport::default();
+        for path in paths {
+            let mut snapshot = match read_snapshot_file(&path) {
+                Ok(snapshot) => snapshot,
+                Err(e) => {
+                    report.failed.push((path, e.to_string()));
+                    continue;
+                }
+            };
+
+            let existing = self.list()?;
+            let same_name = existing.iter().find(|s| s.name == snapshot.name);
+            if let Some(same) = same_name {
+                if same.scope == snapshot.scope
+                    && serde_json::to_value(&same.settings)?
+                        == serde_json::to_value(&snapshot.settings)?
+                {
+                    report.skipped.push(snapshot.name);
+                    continue;
+                }
+                if overwrite {
+                    self.delete(&same.id)?;
+                } else {
+                    snapshot.name = crate::utils::unique_name(&snapshot.name, |candidate| {
+                        existing.iter().any(|s| s.name == candidate)
+                    });
+                }
+            }
+
+            snapshot.id = Uuid::new_v4().to_string();
+            snapshot.touch();
+            self.save(&snapshot)?;
+            report.imported.push(snapshot.name);
+        }
+        Ok(report)
+    }
+}
+
+/// Parse and migrate one snapshot file for [`SnapshotStore::import_directory`]
+fn read_snapshot_file(path: &Path) -> Result<Snapshot> {
+    let content = fs::read_to_string(path)?;
+    let snapshot: Snapshot = serde_json::from_str(&content)?;
+    if snapshot.name.trim().is_empty() {
+        return Err(anyhow!("snapshot has no name"));
+    }
+    snapshot.migrate()
+}
+
+/// What [`SnapshotStore::import_directory`] did
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    /// Names the imported snapshots were saved under
+    pub imported: Vec<String>,
+    /// Names of files identical to an existing snapshot
+    pub skipped: Vec<String>,
+    /// Files that couldn't be read as a snapshot, with the reason
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+impl crate::Storage<Vec<Snapshot>> for SnapshotStore {
+    fn load(&self) -> Result<Vec<Snapshot>> {
+        self.list()
+    }
+
+    fn save(&self, data: &Vec<Snapshot>) -> Result<()> {
+        for snapshot in data {
+            SnapshotStore::save(self, snapshot)?;
+        }
+        Ok(())
+    }
+
+    fn path(&self) -> PathBuf {
+        self.snapshots_dir.clone()
+    }
 }
 
 /// Filter settings by scope
+///
+/// Thin wrapper over [`Configurable::filter_by_scope`], which is the single
+/// definition of what each scope keeps.
 pub fn filter_settings_by_scope(settings: ClaudeSettings, scope: &SnapshotScope) -> ClaudeSettings {
-    match scope {
-        SnapshotScope::Env => ClaudeSettings {
-            env: settings.env,
-            ..Default::default()
-        },
-        SnapshotScope::Common => ClaudeSettings {
-            env: settings.env,
-            model: settings.model,
-            output_style: settings.output_style,
-            attribution: settings.attribution,
-            permissions: settings.permissions,
-            hooks: settings.hooks,
-            status_line: settings.status_line,
-            subagent_model: settings.subagent_model,
-            ..Default::default()
-        },
-        SnapshotScope::All => settings,
+    settings.filter_by_scope(scope)
+}
+
+/// Settings of `snapshot` to apply at the `requested` scope.
+///
+/// A snapshot only holds what its own scope captured. A requested scope
+/// inside it filters as usual; one reaching beyond it also returns a warning
+/// naming the stored scope, since the missing settings can't be applied.
+pub fn settings_for_scope(
+    snapshot: &Snapshot,
+    requested: &SnapshotScope,
+) -> (ClaudeSettings, Option<String>) {
+    let settings = snapshot.settings.clone().filter_by_scope(requested);
+    let warning = (!snapshot.scope.covers(requested)).then(|| {
+        format!(
+            "Snapshot '{}' was taken with scope '{}', which does not include everything in scope '{}'; only the settings it captured will be applied",
+            snapshot.name, snapshot.scope, requested
+        )
+    });
+    (settings, warning)
+}
+
+/// Build the settings stored in a snapshot taken at `scope`.
+///
+/// `Env` and `All` add the Claude Code variables of the process environment
+/// (see [`ClaudeSettings::capture_environment`]) to the settings file's own
+/// `env`. Where both set a key the settings file wins, as it does when Claude
+/// Code starts. Every scope is then filtered with
+/// [`Configurable::filter_by_scope`], the same filter used when the snapshot
+/// is applied.
+pub fn capture_settings(settings: ClaudeSettings, scope: &SnapshotScope) -> ClaudeSettings {
+    capture_settings_with(settings, scope, ClaudeSettings::capture_environment())
+}
+
+/// [`capture_settings`] with `process_env` standing in for the captured
+/// process environment
+fn capture_settings_with(
+    settings: ClaudeSettings,
+    scope: &SnapshotScope,
+    process_env: HashMap<String, String>,
+) -> ClaudeSettings {
+    let mut settings = settings;
+    if matches!(scope, SnapshotScope::All | SnapshotScope::Env) {
+        let mut env = process_env;
+        env.extend(settings.env.take().unwrap_or_default());
+        settings.env = (!env.is_empty()).then_some(env);
+    }
+    settings.filter_by_scope(scope)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Storage;
+
+    #[test]
+    fn test_snapshot_store_as_dyn_storage() {
+        let dir = std::env::temp_dir().join(format!("ccs_snapstore_{}", Uuid::new_v4()));
+        let store: Box<dyn Storage<Vec<Snapshot>>> = Box::new(SnapshotStore::new(dir.clone()));
+
+        assert_eq!(store.path(), dir);
+        assert!(store.load().unwrap().is_empty());
+
+        let snapshots = vec![
+            Snapshot::new(
+                "a".into(),
+                ClaudeSettings::new(),
+                SnapshotScope::Common,
+                None,
+            ),
+            Snapshot::new("b".into(), ClaudeSettings::new(), SnapshotScope::Env, None),
+        ];
+        store.save(&snapshots).unwrap();
+
+        let mut names: Vec<String> = store.load().unwrap().into_iter().map(|s| s.name).collect();
+        names.sort();
+        assert_eq!(names, vec!["a", "b"]);
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_missing_snapshot_is_typed_error() {
+        let dir = std::env::temp_dir().join(format!("ccs_snapmissing_{}", Uuid::new_v4()));
+        let store = SnapshotStore::new(dir.clone());
+
+        let err = store.load_by_name("nope").unwrap_err();
+        assert!(matches!(err, CcsError::SnapshotNotFound(ref name) if name == "nope"));
+        assert_eq!(err.to_string(), "Snapshot 'nope' not found");
+
+        // Still reads as a plain message once it reaches a command
+        let err: anyhow::Error = store.delete_by_name("nope").unwrap_err().into();
+        assert_eq!(err.to_string(), "Snapshot 'nope' not found");
+    }
+
+    #[test]
+    fn test_import_directory() {
+        let root = std::env::temp_dir().join(format!("ccs_snapimport_{}", Uuid::new_v4()));
+        let source = root.join("incoming");
+        fs::create_dir_all(&source).unwrap();
+        let store = SnapshotStore::new(root.join("snapshots"));
+
+        let existing = Snapshot::new(
+            "work".into(),
+            ClaudeSettings::new(),
+            SnapshotScope::Common,
+            None,
+        );
+        store.save(&existing).unwrap();
+
+        let mut work = existing.clone();
+        work.settings.model = Some("glm-4.6".into());
+        let home = Snapshot::new(
+            "home".into(),
+            ClaudeSettings::new(),
+            SnapshotScope::All,
+            None,
+        );
+        fs::write(source.join("a.json"), serde_json::to_string(&work).unwrap()).unwrap();
+        fs::write(source.join("b.json"), serde_json::to_string(&home).unwrap()).unwrap();
+        fs::write(source.join("c.json"), "{ not json").unwrap();
+        fs::write(source.join("notes.txt"), "ignored").unwrap();
+
+        let report = store.import_directory(&source, false).unwrap();
+        assert_eq!(report.imported, ["work-2", "home"]);
+        assert!(report.skipped.is_empty());
+        assert_eq!(report.failed.len(), 1);
+        assert!(report.failed[0].0.ends_with("c.json"));
+
+        let imported = store.load_by_name("work-2").unwrap();
+        assert_ne!(imported.id, existing.id);
+        assert_eq!(imported.settings.model.as_deref(), Some("glm-4.6"));
+        assert_eq!(store.list().unwrap().len(), 3);
+
+        // Importing again skips the identical files; --overwrite replaces
+        fs::remove_file(source.join("c.json")).unwrap();
+        let report = store.import_directory(&source, true).unwrap();
+        assert_eq!(report.imported, ["work"]);
+        assert_eq!(report.skipped, ["home"]);
+        assert_eq!(
+            store
+                .load_by_name("work")
+                .unwrap()
+                .settings
+                .model
+                .as_deref(),
+            Some("glm-4.6")
+        );
+        assert_eq!(store.list().unwrap().len(), 3);
+
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_latest_is_newest_created() {
+        let dir = std::env::temp_dir().join(format!("ccs_snaplatest_{}", Uuid::new_v4()));
+        let store = SnapshotStore::new(dir.clone());
+        assert!(store.latest().unwrap().is_none());
+
+        for (name, minutes_ago) in [("middle", 10), ("newest", 1), ("oldest", 60)] {
+            let mut snapshot = Snapshot::new(
+                name.into(),
+                ClaudeSettings::new(),
+                SnapshotScope::Common,
+                None,
+            );
+            snapshot.created_at = (Utc::now() - Duration::minutes(minutes_ago))
+                .format("%Y-%m-%d %H:%M:%S UTC")
+                .to_string();
+            store.save(&snapshot).unwrap();
+        }
+        assert_eq!(store.latest().unwrap().unwrap().name, "newest");
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_prune_by_count_and_age() {
+        let dir = std::env::temp_dir().join(format!("ccs_snapprune_{}", Uuid::new_v4()));
+        let store = SnapshotStore::new(dir.clone());
+        // snap-0 is newest, snap-9 was created nine days ago
+        for day in 0..10 {
+            let mut snapshot = Snapshot::new(
+                format!("snap-{}", day),
+                ClaudeSettings::new(),
+                SnapshotScope::Common,
+                None,
+            );
+            snapshot.created_at = (Utc::now() - Duration::days(day) - Duration::minutes(1))
+                .format("%Y-%m-%d %H:%M:%S UTC")
+                .to_string();
+            store.save(&snapshot).unwrap();
+        }
+
+        let names = |snapshots: Vec<Snapshot>| -> Vec<String> {
+            snapshots.into_iter().map(|s| s.name).collect()
+        };
+        assert_eq!(
+            names(store.prune_candidates(Some(8), None).unwrap()),
+            vec!["snap-8", "snap-9"]
+        );
+        assert_eq!(
+            names(
+                store
+                    .prune_candidates(None, Some(Duration::days(7)))
+                    .unwrap()
+            ),
+            vec!["snap-7", "snap-8", "snap-9"]
+        );
+        assert!(store.prune_candidates(None, None).unwrap().is_empty());
+
+        let deleted = store.prune(Some(5), Some(Duration::days(3))).unwrap();
+        assert_eq!(
+            deleted,
+            vec![
+                "snap-3", "snap-4", "snap-5", "snap-6", "snap-7", "snap-8", "snap-9"
+            ]
+        );
+        assert_eq!(
+            store.list_names().unwrap(),
+            vec!["snap-0", "snap-1", "snap-2"]
+        );
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_snapshot_diff_reports_only_model() {
+        let mut settings = ClaudeSettings::new();
+        settings.model = Some("glm-4.6".into());
+        settings.env = Some(
+            [(
+                "ANTHROPIC_AUTH_TOKEN".to_string(),
+                "sk-very-secret-token-123456".to_string(),
+            )]
+            .into_iter()
+            .collect(),
+        );
+        let a = Snapshot::new("a".into(), settings.clone(), SnapshotScope::All, None);
+        settings.model = Some("glm-5".into());
+        let b = Snapshot::new("b".into(), settings, SnapshotScope::All, None);
+
+        let diff = a.settings.diff(&b.settings);
+        assert_eq!(diff.fields.len(), 1);
+        assert!(diff.fields.contains_key("model"));
+        assert!(diff.env.is_empty() && diff.permissions.is_empty());
+
+        let text = console::strip_ansi_codes(&a.format_diff(&b)).to_string();
+        assert!(text.contains("model: glm-4.6 → glm-5"));
+        assert!(!text.contains("scopes differ"));
+        assert!(!text.contains("sk-very-secret-token-123456"));
+
+        let env_only = Snapshot::new("c".into(), ClaudeSettings::new(), SnapshotScope::Env, None);
+        assert!(
+            a.format_diff(&env_only)
+                .contains("scopes differ (all vs env)")
+        );
+    }
+
+    #[test]
+    fn test_v1_snapshot_is_migrated_on_load() {
+        let dir = std::env::temp_dir().join(format!("ccs_snapmigrate_{}", Uuid::new_v4()));
+        let store = SnapshotStore::new(dir.clone());
+        store.ensure_dir().unwrap();
+        let v1 = r#"{
+            "id": "old",
+            "name": "legacy",
+            "description": "from v1",
+            "settings": {
+                "model": "glm-4.6",
+                "env": {
+                    "ANTHROPIC_SMALL_FAST_MODEL": "glm-4.5-air",
+                    "ANTHROPIC_BASE_URL": "https://open.bigmodel.cn/api/anthropic"
+                }
+            },
+            "created_at": "2025-01-01 00:00:00 UTC",
+            "updated_at": "2025-01-01 00:00:00 UTC",
+            "scope": {"Custom": [{"Field": "model"}, {"EnvKey": "ANTHROPIC_SMALL_FAST_MODEL"}]},
+            "version": 1
+        }"#;
+        fs::write(store.snapshot_path("old"), v1).unwrap();
+
+        let snapshot = store.load("old").unwrap();
+        assert_eq!(snapshot.version, CURRENT_SNAPSHOT_VERSION);
+        let env = snapshot.settings.env.as_ref().unwrap();
+        assert_eq!(env[DEFAULT_HAIKU_MODEL], "glm-4.5-air");
+        assert!(!env.contains_key(LEGACY_SMALL_FAST_MODEL));
+        assert_eq!(
+            env["ANTHROPIC_BASE_URL"],
+            "https://open.bigmodel.cn/api/anthropic"
+        );
+        assert_eq!(snapshot.settings.model.as_deref(), Some("glm-4.6"));
+        assert_eq!(snapshot.name, "legacy");
+        assert_eq!(snapshot.created_at, "2025-01-01 00:00:00 UTC");
+        assert_eq!(
+            snapshot.scope,
+            SnapshotScope::Custom(vec![
+                FieldSelector::Field("model".into()),
+                FieldSelector::EnvKey(DEFAULT_HAIKU_MODEL.into()),
+            ])
+        );
+
+        // Written back in the current format
+        let on_disk = fs::read_to_string(store.snapshot_path("old")).unwrap();
+        assert!(on_disk.contains(&format!("\"version\": {}", CURRENT_SNAPSHOT_VERSION)));
+
+        let mut future = snapshot.clone();
+        future.version = CURRENT_SNAPSHOT_VERSION + 1;
+        assert!(future.migrate().is_err());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_describe_sets_and_clears() {
+        let dir = std::env::temp_dir().join(format!("ccs_describe_{}", Uuid::new_v4()));
+        let store = SnapshotStore::new(dir.clone());
+        let mut snapshot = Snapshot::new(
+            "work".into(),
+            ClaudeSettings::new(),
+            SnapshotScope::Common,
+            Some("old".into()),
+        );
+        snapshot.updated_at = "2000-01-01 00:00:00 UTC".into();
+        store.save(&snapshot).unwrap();
+
+        store.describe("work", "  Work laptop, zai  ").unwrap();
+        let described = store.load_by_name("work").unwrap();
+        assert_eq!(described.description.as_deref(), Some("Work laptop, zai"));
+        assert_ne!(described.updated_at, snapshot.updated_at);
+        assert_eq!(described.id, snapshot.id);
+
+        store.describe("work", "").unwrap();
+        assert_eq!(store.load_by_name("work").unwrap().description, None);
+        assert!(store.describe("missing", "x").is_err());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_checksum_written_and_verified() {
+        let dir = std::env::temp_dir().join(format!("ccs_snapsum_{}", Uuid::new_v4()));
+        let store = SnapshotStore::new(dir.clone());
+        let mut settings = ClaudeSettings::new();
+        settings.model = Some("glm-4.6".into());
+        let snapshot = Snapshot::new("summed".into(), settings, SnapshotScope::All, None);
+        store.save(&snapshot).unwrap();
+
+        // match
+        let loaded = store.load(&snapshot.id).unwrap();
+        assert_eq!(loaded.checksum_matches(), Some(true));
+        assert!(store.checksum_warning(&loaded).is_none());
+
+        // mismatch: edited on disk, still loads
+        let path = store.snapshot_path(&snapshot.id);
+        let edited = fs::read_to_string(&path)
+            .unwrap()
+            .replace("glm-4.6", "glm-9");
+        fs::write(&path, edited).unwrap();
+        let tampered = store.load(&snapshot.id).unwrap();
+        assert_eq!(tampered.settings.model.as_deref(), Some("glm-9"));
+        assert_eq!(tampered.checksum_matches(), Some(false));
+        let warning = store.checksum_warning(&tampered).unwrap();
+        assert!(warning.contains("'summed'"), "{}", warning);
+
+        // missing: loads normally, gains one on the next save
+        let legacy = Snapshot {
+            checksum: None,
+            ..tampered
+        };
+        fs::write(&path, serde_json::to_string(&legacy).unwrap()).unwrap();
+        let loaded = store.load(&snapshot.id).unwrap();
+        assert_eq!(loaded.checksum_matches(), None);
+        store.save(&loaded).unwrap();
+        assert_eq!(
+            store.load(&snapshot.id).unwrap().checksum_matches(),
+            Some(true)
+        );
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_format_details() {
+        let mut settings = ClaudeSettings::new();
+        settings.model = Some("glm-5".into());
+        let snapshot = Snapshot::new(
+            "work".into(),
+            settings,
+            SnapshotScope::Common,
+            Some("daily".into()),
+        );
+
+        let details = snapshot.format_details();
+        assert!(details.contains("Snapshot: work (common)"));
+        assert!(details.contains("Description: daily"));
+        assert!(details.contains(&snapshot.id));
+        assert!(details.contains("glm-5"));
+    }
+
+    #[test]
+    fn test_show_api_key_toggles_masking() {
+        let mut settings = ClaudeSettings::new();
+        settings.env = Some(
+            [(
+                "ANTHROPIC_AUTH_TOKEN".to_string(),
+                "sk-very-secret-token-123456".to_string(),
+            )]
+            .into_iter()
+            .collect(),
+        );
+        let mut snapshot = Snapshot::new("keys".into(), settings, SnapshotScope::All, None);
+
+        let masked = snapshot.format_settings(true);
+        assert!(masked.contains("ANTHROPIC_AUTH_TOKEN"));
+        assert!(!masked.contains("sk-very-secret-token-123456"));
+
+        snapshot.show_api_key = true;
+        assert!(
+            snapshot
+                .format_settings(true)
+                .contains("sk-very-secret-token-123456")
+        );
+
+        // Runtime-only: never written to disk
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert!(!json.contains("show_api_key"));
+        let loaded: Snapshot = serde_json::from_str(&json).unwrap();
+        assert!(!loaded.show_api_key);
+    }
+
+    #[test]
+    fn test_scope_aliases() {
+        let cases = [
+            ("env", SnapshotScope::Env),
+            ("e", SnapshotScope::Env),
+            ("common", SnapshotScope::Common),
+            ("c", SnapshotScope::Common),
+            ("base", SnapshotScope::Common),
+            ("all", SnapshotScope::All),
+            ("a", SnapshotScope::All),
+            ("full", SnapshotScope::All),
+            ("everything", SnapshotScope::All),
+            (" Full\n", SnapshotScope::All),
+        ];
+        for (input, scope) in cases {
+            assert_eq!(
+                input.parse::<SnapshotScope>().unwrap(),
+                scope,
+                "{:?}",
+                input
+            );
+        }
+
+        let err = "most".parse::<SnapshotScope>().unwrap_err().to_string();
+        assert!(err.contains("Invalid scope 'most'"));
+        assert!(err.contains("env, common, all"));
+    }
+
+    #[test]
+    fn test_custom_scope_round_trip_and_filter() {
+        let scope: SnapshotScope = "custom:model,env.ANTHROPIC_MODEL".parse().unwrap();
+        assert_eq!(
+            scope,
+            SnapshotScope::Custom(vec![
+                FieldSelector::Field("model".into()),
+                FieldSelector::EnvKey("ANTHROPIC_MODEL".into()),
+            ])
+        );
+        assert_eq!(scope.to_string(), "custom:model,env.ANTHROPIC_MODEL");
+        assert_eq!(scope.to_string().parse::<SnapshotScope>().unwrap(), scope);
+        assert_eq!(
+            parse_field_selectors("effort-level").unwrap(),
+            vec![FieldSelector::Field("effortLevel".into())]
+        );
+        assert!(parse_field_selectors("nope").is_err());
+
+        let mut settings = ClaudeSettings::new();
+        settings.model = Some("glm-5".into());
+        settings.allow_tool("Bash");
+        settings.env = Some(
+            [
+                ("ANTHROPIC_MODEL".to_string(), "glm-5".to_string()),
+                ("ANTHROPIC_AUTH_TOKEN".to_string(), "sk-secret".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+        );
+
+        let filtered = settings.filter_by_scope(&scope);
+        assert_eq!(filtered.model.as_deref(), Some("glm-5"));
+        assert!(filtered.permissions.is_none());
+        let env = filtered.env.unwrap();
+        assert_eq!(env.len(), 1);
+        assert_eq!(env["ANTHROPIC_MODEL"], "glm-5");
+    }
+
+    #[test]
+    fn test_env_scope_merges_process_and_file_env() {
+        let mut settings = ClaudeSettings::new();
+        settings.env = Some(
+            [
+                ("ANTHROPIC_MODEL".to_string(), "glm-5".to_string()),
+                ("API_TIMEOUT_MS".to_string(), "600000".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        let process_env = ClaudeSettings::capture_environment_from([
+            (
+                "ANTHROPIC_BASE_URL".to_string(),
+                "https://example.com".to_string(),
+            ),
+            ("ANTHROPIC_MODEL".to_string(), "from-shell".to_string()),
+            ("PATH".to_string(), "/usr/bin".to_string()),
+        ]);
+
+        let captured = capture_settings_with(settings, &SnapshotScope::Env, process_env);
+        let env = captured.env.unwrap();
+        assert_eq!(env["ANTHROPIC_BASE_URL"], "https://example.com");
+        assert_eq!(env["API_TIMEOUT_MS"], "600000");
+        // The settings file wins over the shell
+        assert_eq!(env["ANTHROPIC_MODEL"], "glm-5");
+        assert!(!env.contains_key("PATH"));
+    }
+
+    #[test]
+    fn test_common_scope_round_trip_keeps_env() {
+        let mut settings = ClaudeSettings::new();
+        settings.model = Some("glm-5".to_string());
+        settings.effort_level = Some("high".to_string());
+        settings.env = Some(
+            [(
+                "ANTHROPIC_BASE_URL".to_string(),
+                "https://example.com".to_string(),
+            )]
+            .into_iter()
+            .collect(),
+        );
+
+        // Snapshot side
+        let captured = capture_settings(settings.clone(), &SnapshotScope::Common);
+        assert_eq!(captured.env, settings.env);
+        assert_eq!(captured.effort_level, settings.effort_level);
+
+        // Apply side, after a disk round trip
+        let dir = std::env::temp_dir().join(format!("ccs_snapscope_{}", Uuid::new_v4()));
+        let store = SnapshotStore::new(dir.clone());
+        let snapshot = Snapshot::new("common".into(), captured, SnapshotScope::Common, None);
+        SnapshotStore::save(&store, &snapshot).unwrap();
+        let applied = store
+            .load(&snapshot.id)
+            .unwrap()
+            .settings
+            .filter_by_scope(&SnapshotScope::Common);
+
+        assert_eq!(applied.env, settings.env);
+        assert_eq!(applied.model, settings.model);
+        assert_eq!(applied.effort_level, settings.effort_level);
+        assert_eq!(
+            filter_settings_by_scope(settings.clone(), &SnapshotScope::Common).env,
+            applied.env
+        );
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_snapshot_scope_against_requested_scope() {
+        let mut settings = ClaudeSettings::new();
+        settings.model = Some("glm-5".into());
+        settings.api_key_helper = Some("helper.sh".into());
+        settings.env = Some(
+            [("ANTHROPIC_MODEL".to_string(), "glm-5".to_string())]
+                .into_iter()
+                .collect(),
+        );
+        let model_only = SnapshotScope::Custom(vec![FieldSelector::Field("model".into())]);
+        let scopes = [
+            SnapshotScope::Env,
+            SnapshotScope::Common,
+            SnapshotScope::All,
+            model_only.clone(),
+        ];
+
+        for stored in &scopes {
+            let snapshot = Snapshot::new(
+                "snap".into(),
+                settings.clone().filter_by_scope(stored),
+                stored.clone(),
+                None,
+            );
+            for requested in &scopes {
+                let (applied, warning) = settings_for_scope(&snapshot, requested);
+                let in_both =
+                    |scope: &SnapshotScope| stored.covers(scope) && requested.covers(scope);
+                let expect_warning = !stored.covers(requested);
+
+                assert_eq!(
+                    warning.is_some(),
+                    expect_warning,
+                    "stored {stored}, requested {requested}"
+                );
+                if let Some(warning) = &warning {
+                    assert!(warning.contains(&format!("scope '{}'", stored)));
+                }
+                assert_eq!(
+                    applied.env.is_some(),
+                    in_both(&SnapshotScope::Env),
+                    "env: stored {stored}, requested {requested}"
+                );
+                assert_eq!(
+                    applied.model.is_some(),
+                    in_both(&model_only),
+                    "model: stored {stored}, requested {requested}"
+                );
+                assert_eq!(
+                    applied.api_key_helper.is_some(),
+                    *stored == SnapshotScope::All && *requested == SnapshotScope::All,
+                    "api_key_helper: stored {stored}, requested {requested}"
+                );
+            }
+        }
+
+        assert!(SnapshotScope::Common.covers(&SnapshotScope::Env));
+        assert!(!SnapshotScope::Env.covers(&SnapshotScope::Common));
+        assert!(
+            SnapshotScope::Env.covers(&SnapshotScope::Custom(vec![FieldSelector::EnvKey(
+                "ANTHROPIC_MODEL".into()
+            )]))
+        );
+    }
+
+    #[test]
+    fn test_list_is_cached_across_lookups() {
+        let dir = std::env::temp_dir().join(format!("ccs_snapcache_{}", Uuid::new_v4()));
+        let writer = SnapshotStore::new(dir.clone());
+        for i in 0..200 {
+            let snapshot = Snapshot::new(
+                format!("snap-{}", i),
+                ClaudeSettings::new(),
+                SnapshotScope::Common,
+                None,
+            );
+            SnapshotStore::save(&writer, &snapshot).unwrap();
+        }
+
+        let store = SnapshotStore::new(dir.clone());
+        assert!(store.exists_by_name("snap-0"));
+        assert!(store.exists_by_name("snap-199"));
+        assert!(!store.exists_by_name("missing"));
+        assert_eq!(store.list_names().unwrap().len(), 200);
+        assert_eq!(store.parse_count(), 200);
+
+        // Mutations invalidate the cache
+        let victim = store.load_by_name("snap-7").unwrap();
+        store.delete(&victim.id).unwrap();
+        assert!(!store.exists_by_name("snap-7"));
+        assert_eq!(store.parse_count(), 200 + 199);
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_parallel_load_matches_serial() {
+        let dir = std::env::temp_dir().join(format!("ccs_snappar_{}", Uuid::new_v4()));
+        let store = SnapshotStore::new(dir.clone());
+        for i in 0..300 {
+            let snapshot = Snapshot::new(
+                format!("snap-{}", i),
+                ClaudeSettings::new(),
+                SnapshotScope::Common,
+                None,
+            );
+            SnapshotStore::save(&store, &snapshot).unwrap();
+        }
+        fs::write(dir.join("broken.json"), "{ not json").unwrap();
+
+        let ids = store.snapshot_ids().unwrap();
+        let mut serial = store.parse_serial(&ids);
+        let mut parallel = store.parse_parallel(&ids);
+        sort_newest_first(&mut serial);
+        sort_newest_first(&mut parallel);
+
+        let serial_ids: Vec<&str> = serial.iter().map(|s| s.id.as_str()).collect();
+        let parallel_ids: Vec<&str> = parallel.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(serial_ids.len(), 300);
+        assert_eq!(serial_ids, parallel_ids);
+        assert_eq!(store.list().unwrap().len(), 300);
+
+        fs::remove_dir_all(&dir).ok();
     }
 }
@@ -0,0 +1,175 @@
+//! Collection-level storage abstractions built on [`crate::Storage`].
+//!
+//! The on-disk stores ([`crate::snapshots::SnapshotStore`],
+//! [`crate::credentials::SavedCredentialStore`]) keep one JSON file per item.
+//! [`InMemoryStore`] mirrors that behaviour without touching the filesystem so
+//! selectors can be exercised in unit tests.
+
+use crate::Storage;
+use crate::credentials::{SavedCredential, SavedCredentialStore};
+use crate::snapshots::{Snapshot, SnapshotStore};
+use anyhow::{Result, anyhow};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// An item that can be stored in an [`ItemStorage`].
+pub trait StorageItem: Clone + Send {
+    /// Stable identifier used as the storage key
+    fn storage_id(&self) -> &str;
+
+    /// Ordering key; `load` returns items newest first by this value
+    fn created_at(&self) -> &str;
+}
+
+impl StorageItem for Snapshot {
+    fn storage_id(&self) -> &str {
+        &self.id
+    }
+
+    fn created_at(&self) -> &str {
+        &self.created_at
+    }
+}
+
+impl StorageItem for SavedCredential {
+    fn storage_id(&self) -> &str {
+        self.id()
+    }
+
+    fn created_at(&self) -> &str {
+        SavedCredential::created_at(self)
+    }
+}
+
+/// A [`Storage`] over a collection of items, one entry per id.
+///
+/// `save` upserts every given item; `remove` deletes a single one.
+pub trait ItemStorage<T: StorageItem>: Storage<Vec<T>> {
+    /// Remove a single item by id
+    fn remove(&self, id: &str) -> Result<()>;
+
+    /// Insert or replace a single item
+    fn upsert(&self, item: &T) -> Result<()> {
+        self.save(&vec![item.clone()])
+    }
+}
+
+impl ItemStorage<Snapshot> for SnapshotStore {
+    fn remove(&self, id: &str) -> Result<()> {
+        self.delete(id)
+    }
+}
+
+impl ItemStorage<SavedCredential> for SavedCredentialStore {
+    fn remove(&self, id: &str) -> Result<()> {
+        self.delete(id)
+    }
+}
+
+/// Storage backend that keeps everything in memory (for tests).
+pub struct InMemoryStore<T> {
+    items: Mutex<HashMap<String, T>>,
+}
+
+impl<T> InMemoryStore<T> {
+    /// Create an empty store
+    pub fn new() -> Self {
+        Self {
+            items: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<T> Default for InMemoryStore<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: StorageItem> InMemoryStore<T> {
+    /// Create a store pre-populated with the given items
+    pub fn with_items(items: Vec<T>) -> Self {
+        let store = Self::new();
+        store
+            .items
+            .lock()
+            .expect("in-memory store poisoned")
+            .extend(items.into_iter().map(|i| (i.storage_id().to_string(), i)));
+        store
+    }
+}
+
+impl<T: StorageItem> Storage<Vec<T>> for InMemoryStore<T> {
+    fn load(&self) -> Result<Vec<T>> {
+        let items = self
+            .items
+            .lock()
+            .map_err(|_| anyhow!("In-memory store lock poisoned"))?;
+        let mut list: Vec<T> = items.values().cloned().collect();
+        list.sort_by(|a, b| {
+            b.created_at()
+                .cmp(a.created_at())
+                .then_with(|| a.storage_id().cmp(b.storage_id()))
+        });
+        Ok(list)
+    }
+
+    fn save(&self, data: &Vec<T>) -> Result<()> {
+        let mut items = self
+            .items
+            .lock()
+            .map_err(|_| anyhow!("In-memory store lock poisoned"))?;
+        for item in data {
+            items.insert(item.storage_id().to_string(), item.clone());
+        }
+        Ok(())
+    }
+
+    fn path(&self) -> PathBuf {
+        PathBuf::from(":memory:")
+    }
+}
+
+impl<T: StorageItem> ItemStorage<T> for InMemoryStore<T> {
+    fn remove(&self, id: &str) -> Result<()> {
+        self.items
+            .lock()
+            .map_err(|_| anyhow!("In-memory store lock poisoned"))?
+            .remove(id)
+            .map(|_| ())
+            .ok_or_else(|| anyhow!("Item '{}' not found", id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::ClaudeSettings;
+    use crate::snapshots::SnapshotScope;
+
+    #[test]
+    fn test_in_memory_store_roundtrip() {
+        let store: Box<dyn ItemStorage<Snapshot>> = Box::new(InMemoryStore::new());
+        let snapshot = Snapshot::new(
+            "mem".to_string(),
+            ClaudeSettings::new(),
+            SnapshotScope::Common,
+            None,
+        );
+
+        store.upsert(&snapshot).unwrap();
+        assert_eq!(store.load().unwrap().len(), 1);
+
+        let mut renamed = snapshot.clone();
+        renamed.name = "renamed".to_string();
+        store.upsert(&renamed).unwrap();
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].name, "renamed");
+
+        store.remove(&snapshot.id).unwrap();
+        assert!(store.load().unwrap().is_empty());
+        assert!(store.remove(&snapshot.id).is_err());
+    }
+}
@@ -1,10 +1,6 @@
 //! AnyRouter AI provider template implementation
 
-use crate::{
-    settings::{ClaudeSettings, Permissions},
-    snapshots::SnapshotScope,
-    templates::Template,
-};
+use crate::{settings::ClaudeSettings, snapshots::SnapshotScope, templates::Template};
 use anyhow::{Result, anyhow};
 use atty;
 use inquire::Select;
@@ -59,6 +55,22 @@ pub struct AnyRouterTemplate {
     region: AnyRouterRegion,
 }
 
+/// Targets that name one endpoint directly, and the region each picks
+const REGION_ALIASES: &[(&str, AnyRouterRegion)] = &[
+    ("anyrouter-china", AnyRouterRegion::China),
+    ("anyrouter-fast", AnyRouterRegion::China),
+    ("anyr-china", AnyRouterRegion::China),
+    ("anyr-fast", AnyRouterRegion::China),
+    ("ar-china", AnyRouterRegion::China),
+    ("ar-fast", AnyRouterRegion::China),
+    ("anyrouter-fallback", AnyRouterRegion::Fallback),
+    ("anyrouter-stable", AnyRouterRegion::Fallback),
+    ("anyr-fallback", AnyRouterRegion::Fallback),
+    ("anyr-stable", AnyRouterRegion::Fallback),
+    ("ar-fallback", AnyRouterRegion::Fallback),
+    ("ar-stable", AnyRouterRegion::Fallback),
+];
+
 impl AnyRouterTemplate {
     pub fn new(region: AnyRouterRegion) -> Self {
         Self { region }
@@ -98,10 +110,26 @@ impl Template for AnyRouterTemplate {
         Some(self.region.api_host())
     }
 
+    fn api_base(&self) -> Option<&'static str> {
+        Some(self.region.base_url())
+    }
+
     fn has_variants(&self) -> bool {
         true
     }
 
+    fn variant_aliases(&self) -> Vec<&'static str> {
+        REGION_ALIASES.iter().map(|(alias, _)| *alias).collect()
+    }
+
+    fn from_alias(alias: &str) -> Option<Self> {
+        let alias = crate::templates::normalize_alias(alias);
+        REGION_ALIASES
+            .iter()
+            .find(|(candidate, _)| *candidate == alias)
+            .map(|(_, region)| Self::new(region.clone()))
+    }
+
     fn get_variants() -> Result<Vec<Self>>
     where
         Self: Sized,
@@ -152,23 +180,7 @@ impl Template for AnyRouterTemplate {
             settings.model = Some(self.region.model_name().to_string());
             settings.effort_level = Some("max".to_string());
 
-            settings.permissions = Some(Permissions {
-                allow: Some(vec![
-                    "Bash".to_string(),
-                    "Read".to_string(),
-                    "Write".to_string(),
-                    "Edit".to_string(),
-                    "MultiEdit".to_string(),
-                    "Glob".to_string(),
-                    "Grep".to_string(),
-                    "WebFetch".to_string(),
-                ]),
-                ask: None,
-                deny: Some(vec!["WebSearch".to_string()]),
-                additional_directories: None,
-                default_mode: None,
-                disable_bypass_permissions_mode: None,
-            });
+            settings.permissions = Some(self.default_permissions());
         }
 
         if matches!(
@@ -180,7 +192,7 @@ impl Template for AnyRouterTemplate {
                 "ANTHROPIC_BASE_URL".to_string(),
                 self.region.base_url().to_string(),
             );
-            env.insert("ANTHROPIC_AUTH_TOKEN".to_string(), api_key.to_string());
+            self.auth_mode().insert_key(&mut env, api_key);
             env.insert(
                 "ANTHROPIC_MODEL".to_string(),
                 self.region.model_name().to_string(),
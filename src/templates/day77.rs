@@ -1,12 +1,11 @@
 //! Day77 AI provider template implementation
 
-use crate::{
-    settings::{ClaudeSettings, Permissions},
-    snapshots::SnapshotScope,
-    templates::Template,
-};
+use crate::{settings::ClaudeSettings, snapshots::SnapshotScope, templates::Template};
 use std::collections::HashMap;
 
+/// Anthropic-compatible API base URL
+const BASE_URL: &str = "https://api.day77.icu";
+
 /// Day77 AI provider template
 #[derive(Debug, Clone)]
 pub struct Day77Template;
@@ -32,29 +31,17 @@ impl Template for Day77Template {
         Some("api.day77.icu")
     }
 
+    fn api_base(&self) -> Option<&'static str> {
+        Some(BASE_URL)
+    }
+
     fn create_settings(&self, api_key: &str, scope: &SnapshotScope) -> ClaudeSettings {
         let mut settings = ClaudeSettings::new();
 
         if matches!(scope, SnapshotScope::Common | SnapshotScope::All) {
             settings.model = Some("kimi-k2.7-code".to_string());
 
-            settings.permissions = Some(Permissions {
-                allow: Some(vec![
-                    "Bash".to_string(),
-                    "Read".to_string(),
-                    "Write".to_string(),
-                    "Edit".to_string(),
-                    "MultiEdit".to_string(),
-                    "Glob".to_string(),
-                    "Grep".to_string(),
-                    "WebFetch".to_string(),
-                ]),
-                ask: None,
-                deny: Some(vec!["WebSearch".to_string()]),
-                additional_directories: None,
-                default_mode: None,
-                disable_bypass_permissions_mode: None,
-            });
+            settings.permissions = Some(self.default_permissions());
         }
 
         if matches!(
@@ -62,15 +49,9 @@ impl Template for Day77Template {
             SnapshotScope::Env | SnapshotScope::Common | SnapshotScope::All
         ) {
             let mut env = HashMap::new();
-            env.insert("ANTHROPIC_AUTH_TOKEN".to_string(), api_key.to_string());
-            env.insert(
-                "ANTHROPIC_BASE_URL".to_string(),
-                "https://api.day77.icu".to_string(),
-            );
-            env.insert(
-                "ANTHROPIC_MODEL".to_string(),
-                "kimi-k2.7-code".to_string(),
-            );
+            self.auth_mode().insert_key(&mut env, api_key);
+            env.insert("ANTHROPIC_BASE_URL".to_string(), BASE_URL.to_string());
+            env.insert("ANTHROPIC_MODEL".to_string(), "kimi-k2.7-code".to_string());
             env.insert(
                 "ANTHROPIC_DEFAULT_HAIKU_MODEL".to_string(),
                 "kimi-k2.7-code".to_string(),
@@ -1,12 +1,11 @@
 //! DeepSeek AI provider template implementation
 
-use crate::{
-    settings::{ClaudeSettings, Permissions},
-    snapshots::SnapshotScope,
-    templates::Template,
-};
+use crate::{settings::ClaudeSettings, snapshots::SnapshotScope, templates::Template};
 use std::collections::HashMap;
 
+/// Anthropic-compatible API base URL
+const BASE_URL: &str = "https://api.deepseek.com/anthropic";
+
 /// DeepSeek AI provider template
 #[derive(Debug, Clone)]
 pub struct DeepSeekTemplate;
@@ -28,6 +27,10 @@ impl Template for DeepSeekTemplate {
         "DeepSeek V4 API - Thinking mode enabled with V4 Pro/Flash models"
     }
 
+    fn supports_thinking(&self) -> bool {
+        true
+    }
+
     fn api_key_url(&self) -> Option<&'static str> {
         Some("https://platform.deepseek.com/api_keys")
     }
@@ -36,29 +39,17 @@ impl Template for DeepSeekTemplate {
         Some("api.deepseek.com")
     }
 
+    fn api_base(&self) -> Option<&'static str> {
+        Some(BASE_URL)
+    }
+
     fn create_settings(&self, api_key: &str, scope: &SnapshotScope) -> ClaudeSettings {
         let mut settings = ClaudeSettings::new();
 
         if matches!(scope, SnapshotScope::Common | SnapshotScope::All) {
             settings.model = Some("deepseek-v4-pro[1m]".to_string());
 
-            settings.permissions = Some(Permissions {
-                allow: Some(vec![
-                    "Bash".to_string(),
-                    "Read".to_string(),
-                    "Write".to_string(),
-                    "Edit".to_string(),
-                    "MultiEdit".to_string(),
-                    "Glob".to_string(),
-                    "Grep".to_string(),
-                    "WebFetch".to_string(),
-                ]),
-                ask: None,
-                deny: Some(vec!["WebSearch".to_string()]),
-                additional_directories: None,
-                default_mode: None,
-                disable_bypass_permissions_mode: None,
-            });
+            settings.permissions = Some(self.default_permissions());
         }
 
         if matches!(
@@ -66,11 +57,8 @@ impl Template for DeepSeekTemplate {
             SnapshotScope::Env | SnapshotScope::Common | SnapshotScope::All
         ) {
             let mut env = HashMap::new();
-            env.insert(
-                "ANTHROPIC_BASE_URL".to_string(),
-                "https://api.deepseek.com/anthropic".to_string(),
-            );
-            env.insert("ANTHROPIC_AUTH_TOKEN".to_string(), api_key.to_string());
+            env.insert("ANTHROPIC_BASE_URL".to_string(), BASE_URL.to_string());
+            self.auth_mode().insert_key(&mut env, api_key);
             env.insert("API_TIMEOUT_MS".to_string(), "600000".to_string());
             env.insert("ENABLE_THINKING".to_string(), "true".to_string());
             env.insert(
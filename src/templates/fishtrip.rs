@@ -1,12 +1,14 @@
 //! Fishtrip AI provider template implementation
 
-use crate::{
-    settings::{ClaudeSettings, Permissions},
-    snapshots::SnapshotScope,
-    templates::Template,
-};
+use crate::{settings::ClaudeSettings, snapshots::SnapshotScope, templates::Template};
 use std::collections::HashMap;
 
+/// Anthropic-compatible API base URL
+const BASE_URL: &str = "https://api.fishtrip.net";
+
+/// Model used for every model slot
+const MODEL: &str = "claude-opus-4-8[1m]";
+
 /// Fishtrip AI provider template
 #[derive(Debug, Clone)]
 pub struct FishtripTemplate;
@@ -32,30 +34,18 @@ impl Template for FishtripTemplate {
         Some("api.fishtrip.net")
     }
 
+    fn api_base(&self) -> Option<&'static str> {
+        Some(BASE_URL)
+    }
+
     fn create_settings(&self, api_key: &str, scope: &SnapshotScope) -> ClaudeSettings {
         let mut settings = ClaudeSettings::new();
 
         if matches!(scope, SnapshotScope::Common | SnapshotScope::All) {
-            settings.model = Some("claude-opus-4-8[1m]".to_string());
+            settings.model = Some(MODEL.to_string());
             settings.effort_level = Some("max".to_string());
 
-            settings.permissions = Some(Permissions {
-                allow: Some(vec![
-                    "Bash".to_string(),
-                    "Read".to_string(),
-                    "Write".to_string(),
-                    "Edit".to_string(),
-                    "MultiEdit".to_string(),
-                    "Glob".to_string(),
-                    "Grep".to_string(),
-                    "WebFetch".to_string(),
-                ]),
-                ask: None,
-                deny: Some(vec!["WebSearch".to_string()]),
-                additional_directories: None,
-                default_mode: None,
-                disable_bypass_permissions_mode: None,
-            });
+            settings.permissions = Some(self.default_permissions());
         }
 
         if matches!(
@@ -63,23 +53,20 @@ impl Template for FishtripTemplate {
             SnapshotScope::Env | SnapshotScope::Common | SnapshotScope::All
         ) {
             let mut env = HashMap::new();
-            env.insert("ANTHROPIC_AUTH_TOKEN".to_string(), api_key.to_string());
-            env.insert(
-                "ANTHROPIC_BASE_URL".to_string(),
-                "https://api.fishtrip.net".to_string(),
-            );
-            env.insert("ANTHROPIC_MODEL".to_string(), "claude-opus-4-8[1m]".to_string());
+            self.auth_mode().insert_key(&mut env, api_key);
+            env.insert("ANTHROPIC_BASE_URL".to_string(), BASE_URL.to_string());
+            env.insert("ANTHROPIC_MODEL".to_string(), MODEL.to_string());
             env.insert(
                 "ANTHROPIC_DEFAULT_HAIKU_MODEL".to_string(),
-                "claude-opus-4-8[1m]".to_string(),
+                MODEL.to_string(),
             );
             env.insert(
                 "ANTHROPIC_DEFAULT_SONNET_MODEL".to_string(),
-                "claude-opus-4-8[1m]".to_string(),
+                MODEL.to_string(),
             );
             env.insert(
                 "ANTHROPIC_DEFAULT_OPUS_MODEL".to_string(),
-                "claude-opus-4-8[1m]".to_string(),
+                MODEL.to_string(),
             );
             env.insert("CLAUDE_CODE_EFFORT_LEVEL".to_string(), "max".to_string());
             env.insert("API_TIMEOUT_MS".to_string(), "600000".to_string());
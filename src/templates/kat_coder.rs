@@ -1,11 +1,8 @@
 //! KatCoder (WanQing) AI provider template implementation
 
 use crate::{
-    credentials::CredentialStore,
-    selectors::template::get_endpoint_id_interactively,
-    settings::{ClaudeSettings, Permissions},
-    snapshots::SnapshotScope,
-    templates::Template,
+    credentials::CredentialStore, selectors::template::get_endpoint_id_interactively,
+    settings::ClaudeSettings, snapshots::SnapshotScope, templates::Template,
 };
 use anyhow::{Result, anyhow};
 use atty;
@@ -50,11 +47,26 @@ impl KatCoderVariant {
 #[derive(Debug, Clone)]
 pub struct KatCoderTemplate {
     variant: KatCoderVariant,
+    /// Endpoint ID set up front; otherwise read from the env or prompted for
+    endpoint_id: Option<String>,
 }
 
+/// Targets that name one variant directly, and the variant each picks
+const VARIANT_ALIASES: &[(&str, KatCoderVariant)] = &[
+    ("kat-coder-pro", KatCoderVariant::Pro),
+    ("katcoder-pro", KatCoderVariant::Pro),
+    ("katpro", KatCoderVariant::Pro),
+    ("kat-coder-air", KatCoderVariant::Air),
+    ("katcoder-air", KatCoderVariant::Air),
+    ("katair", KatCoderVariant::Air),
+];
+
 impl KatCoderTemplate {
     pub fn new(variant: KatCoderVariant) -> Self {
-        Self { variant }
+        Self {
+            variant,
+            endpoint_id: None,
+        }
     }
 
     pub fn pro() -> Self {
@@ -95,6 +107,18 @@ impl Template for KatCoderTemplate {
         true
     }
 
+    fn variant_aliases(&self) -> Vec<&'static str> {
+        VARIANT_ALIASES.iter().map(|(alias, _)| *alias).collect()
+    }
+
+    fn from_alias(alias: &str) -> Option<Self> {
+        let alias = crate::templates::normalize_alias(alias);
+        VARIANT_ALIASES
+            .iter()
+            .find(|(candidate, _)| *candidate == alias)
+            .map(|(_, variant)| Self::new(variant.clone()))
+    }
+
     fn get_variants() -> Result<Vec<Self>>
     where
         Self: Sized,
@@ -140,17 +164,27 @@ impl Template for KatCoderTemplate {
     }
 
     fn get_additional_config(&self) -> Result<HashMap<String, String>> {
-        let endpoint_id = get_kat_coder_endpoint_id()?;
+        let endpoint_id = match &self.endpoint_id {
+            Some(id) => id.clone(),
+            None => get_kat_coder_endpoint_id()?,
+        };
         let mut config = HashMap::new();
         config.insert("endpoint_id".to_string(), endpoint_id);
         Ok(config)
     }
 
+    fn set_additional_config(&mut self, config: &HashMap<String, String>) {
+        if let Some(id) = config.get("endpoint_id") {
+            self.endpoint_id = Some(id.clone());
+        }
+    }
+
     fn create_settings(&self, api_key: &str, scope: &SnapshotScope) -> ClaudeSettings {
         let mut settings = ClaudeSettings::new();
 
-        // Get endpoint ID for KatCoder
-        let endpoint_id = get_kat_coder_endpoint_id().unwrap_or_else(|_| "default".to_string());
+        // Callers that write settings resolve the endpoint ID up front
+        // (`get_additional_config`); previews fall back to the default
+        let endpoint_id = self.endpoint_id.as_deref().unwrap_or(DEFAULT_ENDPOINT_ID);
         let base_url = format!(
             "https://wanqing.streamlakeapi.com/api/gateway/v1/endpoints/{}/claude-code-proxy",
             endpoint_id
@@ -159,23 +193,7 @@ impl Template for KatCoderTemplate {
         if matches!(scope, SnapshotScope::Common | SnapshotScope::All) {
             settings.model = Some(self.variant.model_name().to_string());
 
-            settings.permissions = Some(Permissions {
-                allow: Some(vec![
-                    "Bash".to_string(),
-                    "Read".to_string(),
-                    "Write".to_string(),
-                    "Edit".to_string(),
-                    "MultiEdit".to_string(),
-                    "Glob".to_string(),
-                    "Grep".to_string(),
-                    "WebFetch".to_string(),
-                ]),
-                ask: None,
-                deny: Some(vec!["WebSearch".to_string()]),
-                additional_directories: None,
-                default_mode: None,
-                disable_bypass_permissions_mode: None,
-            });
+            settings.permissions = Some(self.default_permissions());
         }
 
         if matches!(
@@ -183,7 +201,7 @@ impl Template for KatCoderTemplate {
             SnapshotScope::Env | SnapshotScope::Common | SnapshotScope::All
         ) {
             let mut env = HashMap::new();
-            env.insert("ANTHROPIC_AUTH_TOKEN".to_string(), api_key.to_string());
+            self.auth_mode().insert_key(&mut env, api_key);
             env.insert("ANTHROPIC_BASE_URL".to_string(), base_url);
             env.insert(
                 "ANTHROPIC_MODEL".to_string(),
@@ -213,12 +231,52 @@ impl Template for KatCoderTemplate {
     }
 }
 
+/// Endpoint ID used in the base URL when none has been resolved
+pub const DEFAULT_ENDPOINT_ID: &str = "default";
+
+/// Check that `id` looks like a WanQing endpoint ID (`ep-xxx-xxx`).
+///
+/// The [`DEFAULT_ENDPOINT_ID`] fallback is accepted as well.
+pub fn validate_endpoint_id(id: &str) -> Result<()> {
+    if id == DEFAULT_ENDPOINT_ID {
+        return Ok(());
+    }
+    let well_formed = id.strip_prefix("ep-").is_some_and(|rest| {
+        rest.split('-')
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric()))
+    });
+    if well_formed {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "'{}' doesn't look like a KatCoder endpoint ID (expected ep-xxx-xxx)",
+            id
+        ))
+    }
+}
+
+/// Validate `id`: a bad ID is only a warning when the user can see and fix
+/// it, and an error otherwise.
+fn check_endpoint_id(id: String, interactive: bool) -> Result<String> {
+    match validate_endpoint_id(&id) {
+        Ok(()) => Ok(id),
+        Err(e) if interactive => {
+            eprintln!("  {} {}", console::style("⚠").yellow(), e);
+            Ok(id)
+        }
+        Err(e) => Err(e),
+    }
+}
+
 /// Get KatCoder endpoint ID from environment or prompt user
 fn get_kat_coder_endpoint_id() -> Result<String> {
     // Try to get from environment first
     let env_var = "WANQING_ENDPOINT_ID";
+    let interactive = atty::is(atty::Stream::Stdin);
 
     if let Ok(id) = std::env::var(env_var) {
+        let id =
+            check_endpoint_id(id, interactive).map_err(|e| anyhow!("{} (from {})", e, env_var))?;
         println!(
             "  ✓ Using endpoint ID from environment variable {}",
             env_var
@@ -227,7 +285,7 @@ fn get_kat_coder_endpoint_id() -> Result<String> {
     }
 
     // If not found and we're in non-interactive mode, error
-    if !atty::is(atty::Stream::Stdin) {
+    if !interactive {
         return Err(anyhow!(
             "Endpoint ID required for kat-coder template. Set {} environment variable or use interactive mode.",
             env_var
@@ -235,7 +293,10 @@ fn get_kat_coder_endpoint_id() -> Result<String> {
     }
 
     // Use interactive endpoint ID selector
-    let endpoint_id = get_endpoint_id_interactively(&crate::templates::TemplateType::KatCoder)?;
+    let endpoint_id = check_endpoint_id(
+        get_endpoint_id_interactively(&crate::templates::TemplateType::KatCoder)?,
+        true,
+    )?;
 
     // Auto-save the endpoint ID if it's new and we have credentials
     if let Ok(credential_store) = CredentialStore::new()
@@ -268,3 +329,43 @@ pub fn create_kat_coder_template(api_key: &str, scope: &SnapshotScope) -> Claude
     let template = KatCoderTemplate::pro(); // Default to Pro for backward compatibility
     template.create_settings(api_key, scope)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_endpoint_id_validation() {
+        assert!(validate_endpoint_id("ep-abc-123").is_ok());
+        assert!(validate_endpoint_id(DEFAULT_ENDPOINT_ID).is_ok());
+
+        let err = validate_endpoint_id("random").unwrap_err();
+        assert!(err.to_string().contains("ep-xxx-xxx"));
+        assert!(validate_endpoint_id("ep-").is_err());
+        assert!(validate_endpoint_id("ep-abc/../x").is_err());
+
+        // Only non-interactive use rejects a malformed ID
+        assert!(check_endpoint_id("random".into(), false).is_err());
+        assert_eq!(check_endpoint_id("random".into(), true).unwrap(), "random");
+    }
+
+    #[test]
+    fn create_settings_uses_the_resolved_endpoint_id() {
+        let base_url = |template: &KatCoderTemplate| {
+            template
+                .create_settings("sk", &SnapshotScope::Env)
+                .env
+                .unwrap()["ANTHROPIC_BASE_URL"]
+                .clone()
+        };
+
+        let mut template = KatCoderTemplate::pro();
+        assert!(base_url(&template).contains("/endpoints/default/"));
+
+        template.set_additional_config(&HashMap::from([(
+            "endpoint_id".to_string(),
+            "ep-abc-123".to_string(),
+        )]));
+        assert!(base_url(&template).contains("/endpoints/ep-abc-123/"));
+    }
+}
@@ -6,7 +6,7 @@
 //! - Kimi: Specialized coding AI
 
 use crate::{
-    settings::{ClaudeSettings, Permissions},
+    settings::{AuthMode, ClaudeSettings},
     snapshots::SnapshotScope,
     templates::Template,
 };
@@ -102,6 +102,15 @@ pub struct KimiTemplate {
     variant: KimiVariant,
 }
 
+/// Targets that name one service directly, and the service each picks
+const VARIANT_ALIASES: &[(&str, KimiVariant)] = &[
+    ("k2", KimiVariant::K2),
+    ("moonshot", KimiVariant::K2),
+    ("k2-thinking", KimiVariant::K2Thinking),
+    ("k2thinking", KimiVariant::K2Thinking),
+    ("kimi-for-coding", KimiVariant::KimiForCoding),
+];
+
 impl KimiTemplate {
     pub fn new(variant: KimiVariant) -> Self {
         Self { variant }
@@ -129,6 +138,18 @@ impl Template for KimiTemplate {
         self.variant.env_var_names()
     }
 
+    fn auth_mode(&self) -> AuthMode {
+        // Different authentication for different services
+        match self.variant {
+            KimiVariant::K2 => AuthMode::Both,
+            KimiVariant::K2Thinking | KimiVariant::KimiForCoding => AuthMode::AuthToken,
+        }
+    }
+
+    fn supports_thinking(&self) -> bool {
+        matches!(self.variant, KimiVariant::K2Thinking)
+    }
+
     fn display_name(&self) -> &'static str {
         self.variant.display_name()
     }
@@ -145,10 +166,31 @@ impl Template for KimiTemplate {
         Some(self.variant.api_host())
     }
 
+    fn api_base(&self) -> Option<&'static str> {
+        Some(self.variant.api_base())
+    }
+
     fn has_variants(&self) -> bool {
         true
     }
 
+    fn variant_aliases(&self) -> Vec<&'static str> {
+        VARIANT_ALIASES.iter().map(|(alias, _)| *alias).collect()
+    }
+
+    fn from_alias(alias: &str) -> Option<Self> {
+        let alias = crate::templates::normalize_alias(alias);
+        // Plain "kimi" is also the stored alias for Kimi For Coding, but as a
+        // target it still asks which service to use
+        if alias == "kimi" {
+            return Some(Self::kimi_for_coding());
+        }
+        VARIANT_ALIASES
+            .iter()
+            .find(|(candidate, _)| *candidate == alias)
+            .map(|(_, variant)| Self::new(variant.clone()))
+    }
+
     fn get_variants() -> Result<Vec<Self>>
     where
         Self: Sized,
@@ -198,23 +240,7 @@ impl Template for KimiTemplate {
         if matches!(scope, SnapshotScope::Common | SnapshotScope::All) {
             settings.model = Some(self.variant.model_name().to_string());
 
-            settings.permissions = Some(Permissions {
-                allow: Some(vec![
-                    "Bash".to_string(),
-                    "Read".to_string(),
-                    "Write".to_string(),
-                    "Edit".to_string(),
-                    "MultiEdit".to_string(),
-                    "Glob".to_string(),
-                    "Grep".to_string(),
-                    "WebFetch".to_string(),
-                ]),
-                ask: None,
-                deny: Some(vec!["WebSearch".to_string()]),
-                additional_directories: None,
-                default_mode: None,
-                disable_bypass_permissions_mode: None,
-            });
+            settings.permissions = Some(self.default_permissions());
         }
 
         if matches!(
@@ -223,16 +249,7 @@ impl Template for KimiTemplate {
         ) {
             let mut env = HashMap::new();
 
-            // Different authentication for different services
-            match self.variant {
-                KimiVariant::K2 => {
-                    env.insert("ANTHROPIC_API_KEY".to_string(), api_key.to_string());
-                    env.insert("ANTHROPIC_AUTH_TOKEN".to_string(), api_key.to_string());
-                }
-                KimiVariant::K2Thinking | KimiVariant::KimiForCoding => {
-                    env.insert("ANTHROPIC_AUTH_TOKEN".to_string(), api_key.to_string());
-                }
-            }
+            self.auth_mode().insert_key(&mut env, api_key);
 
             env.insert(
                 "ANTHROPIC_BASE_URL".to_string(),
@@ -1,15 +1,90 @@
 //! Longcat AI provider template implementation
+//!
+//! LongCat serves two model families behind the same endpoint:
+//! - Flash Chat: fast general-purpose chat
+//! - Flash Thinking: reasoning variant
 
-use crate::{
-    settings::{ClaudeSettings, Permissions},
-    snapshots::SnapshotScope,
-    templates::Template,
-};
+use crate::{settings::ClaudeSettings, snapshots::SnapshotScope, templates::Template};
+use anyhow::{Result, anyhow};
+use atty;
+use inquire::Select;
 use std::collections::HashMap;
 
+/// Anthropic-compatible API base URL
+const BASE_URL: &str = "https://api.longcat.chat/anthropic";
+
+/// Longcat model variants
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LongcatVariant {
+    FlashChat,
+    FlashThinking,
+}
+
+impl LongcatVariant {
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            LongcatVariant::FlashChat => "Longcat Flash Chat",
+            LongcatVariant::FlashThinking => "Longcat Flash Thinking",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            LongcatVariant::FlashChat => {
+                "Longcat Flash Chat API - Fast and efficient conversational AI"
+            }
+            LongcatVariant::FlashThinking => {
+                "Longcat Flash Thinking API - Reasoning model for harder tasks"
+            }
+        }
+    }
+
+    pub fn model_name(&self) -> &'static str {
+        match self {
+            LongcatVariant::FlashChat => "LongCat-Flash-Chat",
+            LongcatVariant::FlashThinking => "LongCat-Flash-Thinking",
+        }
+    }
+}
+
 /// Longcat AI provider template
 #[derive(Debug, Clone)]
-pub struct LongcatTemplate;
+pub struct LongcatTemplate {
+    variant: LongcatVariant,
+}
+
+/// Targets that name one model directly, and the variant each picks
+const VARIANT_ALIASES: &[(&str, LongcatVariant)] = &[
+    ("longcat-chat", LongcatVariant::FlashChat),
+    ("longcat-flash-chat", LongcatVariant::FlashChat),
+    ("longcat-thinking", LongcatVariant::FlashThinking),
+    ("longcat-flash-thinking", LongcatVariant::FlashThinking),
+];
+
+impl LongcatTemplate {
+    pub fn new(variant: LongcatVariant) -> Self {
+        Self { variant }
+    }
+
+    pub fn flash_chat() -> Self {
+        Self::new(LongcatVariant::FlashChat)
+    }
+
+    pub fn flash_thinking() -> Self {
+        Self::new(LongcatVariant::FlashThinking)
+    }
+
+    /// Get the current variant
+    pub fn variant(&self) -> LongcatVariant {
+        self.variant
+    }
+}
+
+impl Default for LongcatTemplate {
+    fn default() -> Self {
+        Self::flash_chat()
+    }
+}
 
 impl Template for LongcatTemplate {
     fn template_type(&self) -> crate::templates::TemplateType {
@@ -21,40 +96,76 @@ impl Template for LongcatTemplate {
     }
 
     fn display_name(&self) -> &'static str {
-        "Longcat"
+        self.variant.display_name()
     }
 
     fn description(&self) -> &'static str {
-        "Longcat Flash Chat API - Fast and efficient conversational AI"
+        self.variant.description()
     }
 
     fn api_host(&self) -> Option<&'static str> {
         Some("api.longcat.chat")
     }
 
+    fn api_base(&self) -> Option<&'static str> {
+        Some(BASE_URL)
+    }
+
+    fn has_variants(&self) -> bool {
+        true
+    }
+
+    fn variant_aliases(&self) -> Vec<&'static str> {
+        VARIANT_ALIASES.iter().map(|(alias, _)| *alias).collect()
+    }
+
+    fn from_alias(alias: &str) -> Option<Self> {
+        let alias = crate::templates::normalize_alias(alias);
+        VARIANT_ALIASES
+            .iter()
+            .find(|(candidate, _)| *candidate == alias)
+            .map(|(_, variant)| Self::new(*variant))
+    }
+
+    fn get_variants() -> Result<Vec<Self>>
+    where
+        Self: Sized,
+    {
+        Ok(vec![Self::flash_chat(), Self::flash_thinking()])
+    }
+
+    fn create_interactively() -> Result<Self>
+    where
+        Self: Sized,
+    {
+        if !atty::is(atty::Stream::Stdin) {
+            return Err(anyhow!(
+                "Longcat requires interactive mode to select a model. Use 'longcat-chat' or 'longcat-thinking' explicitly if not in interactive mode."
+            ));
+        }
+
+        let variants = Self::get_variants()?;
+        let options: Vec<String> = variants
+            .iter()
+            .map(|t| t.variant.display_name().to_string())
+            .collect();
+
+        let choice = Select::new("Select Longcat model:", options.clone())
+            .prompt()
+            .map_err(|e| anyhow!("Failed to get model selection: {}", e))?;
+
+        let idx = options.iter().position(|o| o == &choice).unwrap();
+        Ok(variants[idx].clone())
+    }
+
     fn create_settings(&self, api_key: &str, scope: &SnapshotScope) -> ClaudeSettings {
         let mut settings = ClaudeSettings::new();
+        let model = self.variant.model_name();
 
         if matches!(scope, SnapshotScope::Common | SnapshotScope::All) {
-            settings.model = Some("LongCat-Flash-Chat".to_string());
-
-            settings.permissions = Some(Permissions {
-                allow: Some(vec![
-                    "Bash".to_string(),
-                    "Read".to_string(),
-                    "Write".to_string(),
-                    "Edit".to_string(),
-                    "MultiEdit".to_string(),
-                    "Glob".to_string(),
-                    "Grep".to_string(),
-                    "WebFetch".to_string(),
-                ]),
-                ask: None,
-                deny: Some(vec!["WebSearch".to_string()]),
-                additional_directories: None,
-                default_mode: None,
-                disable_bypass_permissions_mode: None,
-            });
+            settings.model = Some(model.to_string());
+
+            settings.permissions = Some(self.default_permissions());
         }
 
         if matches!(
@@ -62,26 +173,20 @@ impl Template for LongcatTemplate {
             SnapshotScope::Env | SnapshotScope::Common | SnapshotScope::All
         ) {
             let mut env = HashMap::new();
-            env.insert("ANTHROPIC_AUTH_TOKEN".to_string(), api_key.to_string());
-            env.insert(
-                "ANTHROPIC_BASE_URL".to_string(),
-                "https://api.longcat.chat/anthropic".to_string(),
-            );
-            env.insert(
-                "ANTHROPIC_MODEL".to_string(),
-                "LongCat-Flash-Chat".to_string(),
-            );
+            self.auth_mode().insert_key(&mut env, api_key);
+            env.insert("ANTHROPIC_BASE_URL".to_string(), BASE_URL.to_string());
+            env.insert("ANTHROPIC_MODEL".to_string(), model.to_string());
             env.insert(
                 "ANTHROPIC_DEFAULT_HAIKU_MODEL".to_string(),
-                "LongCat-Flash-Chat".to_string(),
+                model.to_string(),
             );
             env.insert(
                 "ANTHROPIC_DEFAULT_SONNET_MODEL".to_string(),
-                "LongCat-Flash-Chat".to_string(),
+                model.to_string(),
             );
             env.insert(
                 "ANTHROPIC_DEFAULT_OPUS_MODEL".to_string(),
-                "LongCat-Flash-Chat".to_string(),
+                model.to_string(),
             );
             env.insert(
                 "CLAUDE_CODE_MAX_OUTPUT_TOKENS".to_string(),
@@ -99,7 +204,44 @@ impl Template for LongcatTemplate {
 }
 
 /// Create Longcat template settings (legacy compatibility function)
+/// Defaults to Flash Chat
 pub fn create_longcat_template(api_key: &str, scope: &SnapshotScope) -> ClaudeSettings {
-    let template = LongcatTemplate;
+    let template = LongcatTemplate::flash_chat();
     template.create_settings(api_key, scope)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model_of(template: &LongcatTemplate) -> (Option<String>, Option<String>) {
+        let settings = template.create_settings("sk-test", &SnapshotScope::Common);
+        let env_model = settings
+            .env
+            .as_ref()
+            .and_then(|e| e.get("ANTHROPIC_MODEL"))
+            .cloned();
+        (settings.model, env_model)
+    }
+
+    #[test]
+    fn flash_chat_sets_chat_model() {
+        let (model, env_model) = model_of(&LongcatTemplate::flash_chat());
+        assert_eq!(model.as_deref(), Some("LongCat-Flash-Chat"));
+        assert_eq!(env_model.as_deref(), Some("LongCat-Flash-Chat"));
+    }
+
+    #[test]
+    fn flash_thinking_sets_thinking_model() {
+        let (model, env_model) = model_of(&LongcatTemplate::flash_thinking());
+        assert_eq!(model.as_deref(), Some("LongCat-Flash-Thinking"));
+        assert_eq!(env_model.as_deref(), Some("LongCat-Flash-Thinking"));
+    }
+
+    #[test]
+    fn variants_cover_every_model() {
+        let variants = LongcatTemplate::get_variants().unwrap();
+        let models: Vec<_> = variants.iter().map(|t| t.variant().model_name()).collect();
+        assert_eq!(models, vec!["LongCat-Flash-Chat", "LongCat-Flash-Thinking"]);
+    }
+}
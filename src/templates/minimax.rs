@@ -1,10 +1,13 @@
 //! MiniMax AI provider template implementation
 
 use crate::{
-    settings::{ClaudeSettings, Permissions},
+    settings::{AuthMode, ClaudeSettings},
     snapshots::SnapshotScope,
     templates::Template,
 };
+use anyhow::{Result, anyhow};
+use atty;
+use inquire::Select;
 use std::collections::HashMap;
 
 /// MiniMax API region
@@ -17,6 +20,14 @@ pub enum MiniMaxRegion {
 }
 
 impl MiniMaxRegion {
+    /// Get the display name for this region
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            MiniMaxRegion::China => "MiniMax China",
+            MiniMaxRegion::International => "MiniMax International",
+        }
+    }
+
     /// Get the base URL for this region
     fn base_url(&self) -> &'static str {
         match self {
@@ -40,6 +51,17 @@ pub struct MiniMaxTemplate {
     region: MiniMaxRegion,
 }
 
+/// Targets that name one region directly, and the region each picks
+const REGION_ALIASES: &[(&str, MiniMaxRegion)] = &[
+    ("minimax-anthropic", MiniMaxRegion::China),
+    ("minimax-china", MiniMaxRegion::China),
+    ("minimax-ch", MiniMaxRegion::China),
+    ("minimax-international", MiniMaxRegion::International),
+    ("minimax-int", MiniMaxRegion::International),
+    ("minimax-intl", MiniMaxRegion::International),
+    ("minimax-io", MiniMaxRegion::International),
+];
+
 impl MiniMaxTemplate {
     /// Create a new MiniMax template with the specified region
     pub fn new(region: MiniMaxRegion) -> Self {
@@ -77,6 +99,10 @@ impl Template for MiniMaxTemplate {
         vec!["MINIMAX_API_KEY", "MINIMAX_TOKEN", "MINIMAX_AUTH_TOKEN"]
     }
 
+    fn auth_mode(&self) -> AuthMode {
+        AuthMode::Both
+    }
+
     fn display_name(&self) -> &'static str {
         "MiniMax"
     }
@@ -91,23 +117,7 @@ impl Template for MiniMaxTemplate {
         if matches!(scope, SnapshotScope::Common | SnapshotScope::All) {
             settings.model = Some("MiniMax-M2.5".to_string());
 
-            settings.permissions = Some(Permissions {
-                allow: Some(vec![
-                    "Bash".to_string(),
-                    "Read".to_string(),
-                    "Write".to_string(),
-                    "Edit".to_string(),
-                    "MultiEdit".to_string(),
-                    "Glob".to_string(),
-                    "Grep".to_string(),
-                    "WebFetch".to_string(),
-                ]),
-                ask: None,
-                deny: Some(vec!["WebSearch".to_string()]),
-                additional_directories: None,
-                default_mode: None,
-                disable_bypass_permissions_mode: None,
-            });
+            settings.permissions = Some(self.default_permissions());
         }
 
         if matches!(
@@ -115,8 +125,7 @@ impl Template for MiniMaxTemplate {
             SnapshotScope::Env | SnapshotScope::Common | SnapshotScope::All
         ) {
             let mut env = HashMap::new();
-            env.insert("ANTHROPIC_API_KEY".to_string(), api_key.to_string());
-            env.insert("ANTHROPIC_AUTH_TOKEN".to_string(), api_key.to_string());
+            self.auth_mode().insert_key(&mut env, api_key);
             env.insert(
                 "ANTHROPIC_BASE_URL".to_string(),
                 self.region.base_url().to_string(),
@@ -152,6 +161,57 @@ impl Template for MiniMaxTemplate {
     fn api_host(&self) -> Option<&'static str> {
         Some(self.region.api_host())
     }
+
+    fn api_base(&self) -> Option<&'static str> {
+        Some(self.region.base_url())
+    }
+
+    fn has_variants(&self) -> bool {
+        true
+    }
+
+    fn variant_aliases(&self) -> Vec<&'static str> {
+        REGION_ALIASES.iter().map(|(alias, _)| *alias).collect()
+    }
+
+    fn from_alias(alias: &str) -> Option<Self> {
+        let alias = crate::templates::normalize_alias(alias);
+        REGION_ALIASES
+            .iter()
+            .find(|(candidate, _)| *candidate == alias)
+            .map(|(_, region)| Self::new(*region))
+    }
+
+    fn get_variants() -> Result<Vec<Self>>
+    where
+        Self: Sized,
+    {
+        Ok(vec![Self::china(), Self::international()])
+    }
+
+    fn create_interactively() -> Result<Self>
+    where
+        Self: Sized,
+    {
+        if !atty::is(atty::Stream::Stdin) {
+            return Err(anyhow!(
+                "MiniMax requires interactive mode to select a region. Use 'minimax-china' or 'minimax-intl' explicitly if not in interactive mode."
+            ));
+        }
+
+        let variants = Self::get_variants()?;
+        let options: Vec<String> = variants
+            .iter()
+            .map(|t| t.region.display_name().to_string())
+            .collect();
+
+        let choice = Select::new("Select MiniMax region:", options.clone())
+            .prompt()
+            .map_err(|e| anyhow!("Failed to get region selection: {}", e))?;
+
+        let idx = options.iter().position(|o| o == &choice).unwrap();
+        Ok(variants[idx].clone())
+    }
 }
 
 /// Create MiniMax template settings (legacy compatibility function)
@@ -175,3 +235,48 @@ pub fn create_minimax_international_template(
     let template = MiniMaxTemplate::international();
     template.create_settings(api_key, scope)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::templates::{TemplateType, get_template_instance_with_input, get_template_type};
+
+    fn base_url(template: &dyn Template) -> String {
+        template
+            .create_settings("sk-test", &SnapshotScope::Env)
+            .env
+            .unwrap()
+            .remove("ANTHROPIC_BASE_URL")
+            .unwrap()
+    }
+
+    #[test]
+    fn intl_alias_uses_international_endpoint() {
+        let template_type = get_template_type("minimax-intl").unwrap();
+        assert_eq!(template_type, TemplateType::MiniMax);
+
+        let template = get_template_instance_with_input(&template_type, "minimax-intl");
+        assert!(base_url(template.as_ref()).contains("api.minimax.io"));
+        assert_eq!(template.api_host(), Some("api.minimax.io"));
+    }
+
+    #[test]
+    fn china_alias_uses_china_endpoint() {
+        let template_type = get_template_type("minimax-china").unwrap();
+        let template = get_template_instance_with_input(&template_type, "minimax-china");
+        assert!(base_url(template.as_ref()).contains("api.minimaxi.com"));
+    }
+
+    #[test]
+    fn variants_cover_both_regions() {
+        let regions: Vec<_> = MiniMaxTemplate::get_variants()
+            .unwrap()
+            .iter()
+            .map(MiniMaxTemplate::region)
+            .collect();
+        assert_eq!(
+            regions,
+            vec![MiniMaxRegion::China, MiniMaxRegion::International]
+        );
+    }
+}
@@ -3,7 +3,11 @@
 //! This module provides a modular approach to managing different AI provider templates.
 //! Each template is implemented as a separate module with the Template trait.
 
-use crate::{settings::ClaudeSettings, snapshots::SnapshotScope};
+use crate::{
+    error::{CcsError, CcsResult},
+    settings::{AuthMode, ClaudeSettings, Permissions},
+    snapshots::SnapshotScope,
+};
 use anyhow::{Result, anyhow};
 
 use serde::{Deserialize, Serialize};
@@ -78,17 +82,147 @@ impl std::str::FromStr for AutoCompactWindow {
     }
 }
 
+/// Tool the templates deny by default; `apply --allow-web-search` lifts it
+pub const WEB_SEARCH_TOOL: &str = "WebSearch";
+
+/// Permissions the templates write: the usual coding tools allowed and
+/// Claude's own web search denied, since most providers don't serve it
+pub fn default_coding_permissions() -> Permissions {
+    let tools = |names: &[&str]| Some(names.iter().map(|name| name.to_string()).collect());
+    Permissions {
+        allow: tools(&[
+            "Bash",
+            "Read",
+            "Write",
+            "Edit",
+            "MultiEdit",
+            "Glob",
+            "Grep",
+            "WebFetch",
+        ]),
+        ask: None,
+        deny: tools(&[WEB_SEARCH_TOOL]),
+        additional_directories: None,
+        default_mode: None,
+        disable_bypass_permissions_mode: None,
+    }
+}
+
+/// `apply --thinking/--no-thinking/--reasoning-effort/--max-thinking-tokens`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ThinkingOverrides {
+    /// `Some(true)` for --thinking, `Some(false)` for --no-thinking
+    pub enabled: Option<bool>,
+    pub reasoning_effort: Option<String>,
+    pub max_thinking_tokens: Option<u32>,
+}
+
+impl ThinkingOverrides {
+    pub fn from_flags(
+        thinking: bool,
+        no_thinking: bool,
+        reasoning_effort: Option<String>,
+        max_thinking_tokens: Option<u32>,
+    ) -> Self {
+        Self {
+            enabled: match (thinking, no_thinking) {
+                (true, _) => Some(true),
+                (_, true) => Some(false),
+                _ => None,
+            },
+            reasoning_effort,
+            max_thinking_tokens,
+        }
+    }
+
+    /// No thinking flag was passed
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Error if any flag was passed for `target`, which can't take them
+    pub fn check_supported(&self, supported: bool, target: &str) -> Result<()> {
+        if self.is_empty() || supported {
+            return Ok(());
+        }
+        Err(anyhow!(
+            "{} doesn't support thinking; drop --thinking, --no-thinking, \
+             --reasoning-effort and --max-thinking-tokens",
+            target
+        ))
+    }
+
+    /// Write the overrides into the env `template` produced. Turning thinking
+    /// off also drops the template's thinking budget settings.
+    pub fn apply(&self, template: &dyn Template, settings: &mut ClaudeSettings) -> Result<()> {
+        self.check_supported(template.supports_thinking(), template.display_name())?;
+        if self.is_empty() {
+            return Ok(());
+        }
+
+        let env = settings.env.get_or_insert_with(HashMap::new);
+        if let Some(enabled) = self.enabled {
+            env.insert("ENABLE_THINKING".to_string(), enabled.to_string());
+            if !enabled {
+                env.remove("REASONING_EFFORT");
+                env.remove("MAX_THINKING_TOKENS");
+            }
+        }
+        if let Some(effort) = &self.reasoning_effort {
+            env.insert("REASONING_EFFORT".to_string(), effort.clone());
+        }
+        if let Some(tokens) = self.max_thinking_tokens {
+            env.insert("MAX_THINKING_TOKENS".to_string(), tokens.to_string());
+        }
+        Ok(())
+    }
+
+    /// Merging keeps the thinking budget an earlier apply left in the file;
+    /// once --no-thinking has landed in `merged`, drop that budget too
+    pub fn drop_stale_budget(&self, merged: &mut ClaudeSettings) {
+        if self.enabled != Some(false) {
+            return;
+        }
+        if let Some(env) = merged.env.as_mut()
+            && env.get("ENABLE_THINKING").map(String::as_str) == Some("false")
+        {
+            env.remove("REASONING_EFFORT");
+            env.remove("MAX_THINKING_TOKENS");
+        }
+    }
+}
+
 /// Trait that all AI provider templates must implement
 pub trait Template {
     /// Get the template type identifier
     fn template_type(&self) -> TemplateType;
 
-    /// Get all supported environment variable names for this provider
+    /// Get all supported environment variable names for this provider, the
+    /// primary one first. Everything that looks up a provider's key env vars
+    /// goes through this (see [`get_env_var_names`]).
     fn env_var_names(&self) -> Vec<&'static str>;
 
+    /// Which env vars carry the API key; `create_settings` sets the key
+    /// through this so it is declared in one place
+    fn auth_mode(&self) -> AuthMode {
+        AuthMode::AuthToken
+    }
+
+    /// Permissions `create_settings` writes for the common scopes
+    fn default_permissions(&self) -> Permissions {
+        default_coding_permissions()
+    }
+
     /// Create Claude settings for this template
     fn create_settings(&self, api_key: &str, scope: &SnapshotScope) -> ClaudeSettings;
 
+    /// Whether the provider reads the thinking env vars (`ENABLE_THINKING`,
+    /// `REASONING_EFFORT`, `MAX_THINKING_TOKENS`), so `apply --thinking` and
+    /// friends can change them
+    fn supports_thinking(&self) -> bool {
+        false
+    }
+
     /// Auto-compaction thresholds supported by this template. Empty means the
     /// provider has no editable auto-compact option.
     fn supported_auto_compact_windows(&self) -> &'static [AutoCompactWindow] {
@@ -140,6 +274,12 @@ pub trait Template {
         None
     }
 
+    /// Base URL written as `ANTHROPIC_BASE_URL`, without building settings.
+    /// `None` when it depends on per-user config (KAT-Coder's endpoint ID).
+    fn api_base(&self) -> Option<&'static str> {
+        None
+    }
+
     /// Check if this template requires additional configuration (like endpoint ID)
     fn requires_additional_config(&self) -> bool {
         false
@@ -150,11 +290,29 @@ pub trait Template {
         Ok(HashMap::new())
     }
 
+    /// Use additional configuration collected up front (same keys as
+    /// `get_additional_config`) instead of asking for it in `create_settings`
+    fn set_additional_config(&mut self, _config: &HashMap<String, String>) {}
+
     /// Check if this template has sub-variants (like Pro/Air versions)
     fn has_variants(&self) -> bool {
         false
     }
 
+    /// Targets that pick one specific variant (e.g. `kat-coder-pro`), so
+    /// `apply` needn't ask. Empty for templates without variants.
+    fn variant_aliases(&self) -> Vec<&'static str> {
+        Vec::new()
+    }
+
+    /// The variant a target from [`Template::variant_aliases`] names
+    fn from_alias(_alias: &str) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
+
     /// Get available variants if this template supports them
     fn get_variants() -> Result<Vec<Self>>
     where
@@ -242,47 +400,118 @@ impl<'de> Deserialize<'de> for TemplateType {
     }
 }
 
+/// Canonical spelling of a template alias: trimmed, lowercase, with `_`
+/// treated as `-` (so `Kat_Coder_Pro` matches `kat-coder-pro`)
+pub fn normalize_alias(input: &str) -> String {
+    input.trim().to_lowercase().replace('_', "-")
+}
+
+/// Every accepted target string (in [`normalize_alias`] form) and the
+/// template it selects. `FromStr` and `ccs templates --aliases` both read
+/// this table.
+pub const TEMPLATE_ALIASES: &[(TemplateType, &[&str])] = &[
+    (TemplateType::DeepSeek, &["deepseek", "ds"]),
+    (
+        TemplateType::Zai,
+        &[
+            "glm",
+            "zhipu",
+            "zai",
+            "zai-china",
+            "zai-ch",
+            "zai-international",
+            "zai-int",
+        ],
+    ),
+    (
+        TemplateType::KatCoder,
+        &[
+            "kat-coder",
+            "katcoder",
+            "kat",
+            // Point to KatCoder with variant selection
+            "kat-coder-pro",
+            "katcoder-pro",
+            "katpro",
+            "kat-coder-air",
+            "katcoder-air",
+            "katair",
+        ],
+    ),
+    // K2 and K2 Thinking are now part of unified Kimi template
+    (
+        TemplateType::Kimi,
+        &[
+            "k2",
+            "moonshot",
+            "k2-thinking",
+            "k2thinking",
+            "kimi",
+            "kimi-for-coding",
+        ],
+    ),
+    (
+        TemplateType::Longcat,
+        &[
+            "longcat",
+            "longcat-chat",
+            "longcat-flash-chat",
+            "longcat-thinking",
+            "longcat-flash-thinking",
+        ],
+    ),
+    (TemplateType::Fishtrip, &["fishtrip", "fish"]),
+    (
+        TemplateType::MiniMax,
+        &[
+            "minimax",
+            "minimax-anthropic",
+            "minimax-china",
+            "minimax-ch",
+            "minimax-international",
+            "minimax-int",
+            "minimax-intl",
+            "minimax-io",
+        ],
+    ),
+    (TemplateType::SeedCode, &["seed-code", "seedcode"]),
+    (TemplateType::Zenmux, &["zenmux"]),
+    (TemplateType::Duojie, &["duojie", "dj"]),
+    (
+        TemplateType::AnyRouter,
+        &[
+            "anyrouter",
+            "anyr",
+            "ar",
+            "anyrouter-china",
+            "anyrouter-fast",
+            "anyr-china",
+            "anyr-fast",
+            "ar-china",
+            "ar-fast",
+            "anyrouter-fallback",
+            "anyrouter-stable",
+            "anyr-fallback",
+            "anyr-stable",
+            "ar-fallback",
+            "ar-stable",
+        ],
+    ),
+    (TemplateType::OpenRouter, &["openrouter", "or"]),
+    (TemplateType::BeeApi, &["beeapi", "bee"]),
+    (TemplateType::Day77, &["day77"]),
+];
+
 impl std::str::FromStr for TemplateType {
-    type Err = anyhow::Error;
+    type Err = CcsError;
 
-    fn from_str(s: &str) -> Result<Self> {
-        match s.to_lowercase().as_str() {
-            "deepseek" | "ds" => Ok(TemplateType::DeepSeek),
-            "glm" | "zhipu" | "zai" | "zai-china" | "zai-ch" | "zai-international" | "zai-int" => {
-                Ok(TemplateType::Zai)
-            }
-            // K2 and K2 Thinking are now part of unified Kimi template
-            "k2" | "moonshot" | "k2-thinking" | "k2thinking" | "kimi" | "kimi-for-coding" => {
-                Ok(TemplateType::Kimi)
-            }
-            "kat-coder" | "katcoder" | "kat" => Ok(TemplateType::KatCoder), // Unified KatCoder
-            "kat-coder-pro" | "katcoder-pro" | "katpro" => Ok(TemplateType::KatCoder), // Points to KatCoder with variant selection
-            "kat-coder-air" | "katcoder-air" | "katair" => Ok(TemplateType::KatCoder), // Points to KatCoder with variant selection
-            "longcat" => Ok(TemplateType::Longcat),
-            "fishtrip" | "fish" => Ok(TemplateType::Fishtrip),
-            "minimax"
-            | "minimax-anthropic"
-            | "minimax-china"
-            | "minimax-ch"
-            | "minimax-international"
-            | "minimax-int"
-            | "minimax-io" => Ok(TemplateType::MiniMax),
-            "seed-code" | "seedcode" | "seed_code" => Ok(TemplateType::SeedCode),
-            "zenmux" => Ok(TemplateType::Zenmux),
-            "duojie" | "dj" => Ok(TemplateType::Duojie),
-            "anyrouter" | "anyr" | "ar" | "anyrouter-china" | "anyrouter-fast" | "anyr-china"
-            | "anyr-fast" | "ar-china" | "ar-fast" | "anyrouter-fallback" | "anyrouter-stable"
-            | "anyr-fallback" | "anyr-stable" | "ar-fallback" | "ar-stable" => {
-                Ok(TemplateType::AnyRouter)
-            }
-            "openrouter" | "or" => Ok(TemplateType::OpenRouter),
-            "beeapi" | "bee" => Ok(TemplateType::BeeApi),
-            "day77" => Ok(TemplateType::Day77),
-            _ => Err(anyhow!(
-                "Unknown template: {}. Available templates: deepseek, glm, k2, k2-thinking, kat-coder, kimi, longcat, fishtrip, fish, minimax, seed-code, zenmux, duojie, anyrouter, openrouter, beeapi, day77",
-                s
-            )),
-        }
+    fn from_str(s: &str) -> CcsResult<Self> {
+        let alias = normalize_alias(s);
+        TEMPLATE_ALIASES
+            .iter()
+            .find(|(_, aliases)| aliases.contains(&alias.as_str()))
+            .map(|(template_type, _)| template_type.clone())
+            .ok_or_else(|| CcsError::InvalidTemplate(s.to_string()))
     }
 }
 
@@ -307,8 +536,18 @@ impl std::fmt::Display for TemplateType {
     }
 }
 
+/// The small/fast (Haiku-tier) model in settings created by a template
+pub fn small_fast_model(settings: &ClaudeSettings) -> Option<&str> {
+    settings
+        .env
+        .as_ref()?
+        .get("ANTHROPIC_DEFAULT_HAIKU_MODEL")
+        .map(String::as_str)
+        .filter(|model| !model.is_empty())
+}
+
 /// Get template type from string
-pub fn get_template_type(template_str: &str) -> Result<TemplateType> {
+pub fn get_template_type(template_str: &str) -> CcsResult<TemplateType> {
     template_str.parse()
 }
 
@@ -332,13 +571,20 @@ pub fn get_all_templates() -> Vec<TemplateType> {
     ]
 }
 
-/// Get the environment variable name for a template type
-/// Get all supported environment variable names for a template type
+/// Get all supported environment variable names for a template type.
+///
+/// [`Template::env_var_names`] is the single source of truth; this only
+/// instantiates the template's default variant and asks it.
 pub fn get_env_var_names(template_type: &TemplateType) -> Vec<&'static str> {
     let template_instance = get_template_instance(template_type);
     template_instance.env_var_names()
 }
 
+/// Get the primary (first) environment variable name for a template type
+pub fn get_env_var_name(template_type: &TemplateType) -> Option<&'static str> {
+    get_env_var_names(template_type).into_iter().next()
+}
+
 /// Get a template instance by type and original input string
 pub fn get_template_instance_with_input(
     template_type: &TemplateType,
@@ -347,57 +593,33 @@ pub fn get_template_instance_with_input(
     match template_type {
         TemplateType::DeepSeek => Box::new(deepseek::DeepSeekTemplate),
         TemplateType::Zai => {
-            // Check if specific region was requested
-            match input.to_lowercase().as_str() {
-                "zai-china" | "zai-ch" => Box::new(zai::ZaiTemplate::china()),
-                "zai-international" | "zai-int" => Box::new(zai::ZaiTemplate::international()),
-                _ => Box::new(zai::ZaiTemplate::china()), // Default to China for general "zai"
-            }
-        }
-        TemplateType::KatCoder => {
-            // Check if specific variant was requested
-            match input.to_lowercase().as_str() {
-                "kat-coder-pro" | "katcoder-pro" | "katpro" => {
-                    Box::new(kat_coder::KatCoderTemplate::pro())
-                }
-                "kat-coder-air" | "katcoder-air" | "katair" => {
-                    Box::new(kat_coder::KatCoderTemplate::air())
-                }
-                _ => Box::new(kat_coder::KatCoderTemplate::pro()), // Default to Pro for general "kat-coder"
-            }
+            // Default to China for general "zai"
+            Box::new(zai::ZaiTemplate::from_alias(input).unwrap_or_else(zai::ZaiTemplate::china))
         }
+        TemplateType::KatCoder => Box::new(
+            kat_coder::KatCoderTemplate::from_alias(input)
+                .unwrap_or_else(kat_coder::KatCoderTemplate::pro),
+        ),
         TemplateType::Kimi => {
-            // Check if specific Moonshot service was requested
-            match input.to_lowercase().as_str() {
-                "k2" | "moonshot" => Box::new(kimi::KimiTemplate::k2()),
-                "k2-thinking" | "k2thinking" => Box::new(kimi::KimiTemplate::k2_thinking()),
-                "kimi" | "kimi-for-coding" => Box::new(kimi::KimiTemplate::kimi_for_coding()),
-                _ => Box::new(kimi::KimiTemplate::k2()), // Default to K2 for general "kimi"
-            }
+            // Default to K2 for general Moonshot targets
+            Box::new(kimi::KimiTemplate::from_alias(input).unwrap_or_else(kimi::KimiTemplate::k2))
+        }
+        TemplateType::Longcat => {
+            Box::new(longcat::LongcatTemplate::from_alias(input).unwrap_or_default())
         }
-        TemplateType::Longcat => Box::new(longcat::LongcatTemplate),
         TemplateType::Fishtrip => Box::new(fishtrip::FishtripTemplate),
         TemplateType::MiniMax => {
-            // Check if specific region was requested
-            match input.to_lowercase().as_str() {
-                "minimax-international" | "minimax-int" | "minimax-io" => {
-                    Box::new(minimax::MiniMaxTemplate::international())
-                }
-                _ => Box::new(minimax::MiniMaxTemplate::china()), // Default to China
-            }
+            Box::new(minimax::MiniMaxTemplate::from_alias(input).unwrap_or_default())
         }
         TemplateType::SeedCode => Box::new(seed_code::SeedCodeTemplate),
         TemplateType::Zenmux => Box::new(zenmux::ZenmuxTemplate),
         TemplateType::Duojie => Box::new(duojie::DuojieTemplate),
         TemplateType::AnyRouter => {
-            // Check if specific region was requested
-            match input.to_lowercase().as_str() {
-                "anyrouter-china" | "anyrouter-fast" | "anyr-china" | "anyr-fast" | "ar-china"
-                | "ar-fast" => Box::new(anyrouter::AnyRouterTemplate::china()),
-                "anyrouter-fallback" | "anyrouter-stable" | "anyr-fallback" | "anyr-stable"
-                | "ar-fallback" | "ar-stable" => Box::new(anyrouter::AnyRouterTemplate::fallback()),
-                _ => Box::new(anyrouter::AnyRouterTemplate::china()), // Default to China for fast access
-            }
+            // Default to China for fast access
+            Box::new(
+                anyrouter::AnyRouterTemplate::from_alias(input)
+                    .unwrap_or_else(anyrouter::AnyRouterTemplate::china),
+            )
         }
         TemplateType::OpenRouter => Box::new(openrouter::OpenRouterTemplate::with_model(
             "anthropic/claude-3.5-sonnet",
@@ -407,7 +629,19 @@ pub fn get_template_instance_with_input(
     }
 }
 
+/// Template whose API host appears in `base_url`
+pub fn template_for_base_url(base_url: &str) -> Option<TemplateType> {
+    get_all_templates().into_iter().find(|tt| {
+        get_template_instance(tt)
+            .api_host()
+            .is_some_and(|host| base_url.contains(host))
+    })
+}
+
 /// Get a template instance by type (for backward compatibility)
+///
+/// Infallible: `TemplateType` is a closed enum, so unknown names are rejected
+/// earlier, when the target is parsed (`CcsError::InvalidTemplate`).
 pub fn get_template_instance(template_type: &TemplateType) -> Box<dyn Template> {
     get_template_instance_with_input(template_type, "")
 }
@@ -429,6 +663,8 @@ pub fn resolve_template_cli(
         TemplateType::Zai => "Use 'zai-china' or 'zai-international'",
         TemplateType::KatCoder => "Use 'kat-coder-pro' or 'kat-coder-air'",
         TemplateType::Kimi => "Use 'k2', 'k2-thinking', or 'moonshot'",
+        TemplateType::Longcat => "Use 'longcat-chat' or 'longcat-thinking'",
+        TemplateType::MiniMax => "Use 'minimax-china' or 'minimax-intl'",
         TemplateType::AnyRouter => "Use 'anyr-china' or 'anyr-fallback'",
         TemplateType::OpenRouter => "Specify a model directly or use interactive mode",
         _ => "Use a specific variant name",
@@ -460,6 +696,8 @@ pub fn resolve_template_interactive(
         )),
         TemplateType::Kimi => Ok(Box::new(kimi::KimiTemplate::create_interactively()?)),
         TemplateType::Zai => Ok(Box::new(zai::ZaiTemplate::create_interactively()?)),
+        TemplateType::Longcat => Ok(Box::new(longcat::LongcatTemplate::create_interactively()?)),
+        TemplateType::MiniMax => Ok(Box::new(minimax::MiniMaxTemplate::create_interactively()?)),
         TemplateType::AnyRouter => Ok(Box::new(
             anyrouter::AnyRouterTemplate::create_interactively()?,
         )),
@@ -470,24 +708,18 @@ pub fn resolve_template_interactive(
     }
 }
 
-/// Check if target is a generic name (no specific variant specified)
+/// Check if target is a generic name (no specific variant specified): it
+/// selects a template with variants but isn't one of its
+/// [`Template::variant_aliases`]
 pub fn is_generic_target(target: &str) -> bool {
-    matches!(
-        target.to_lowercase().as_str(),
-        "kat-coder"
-            | "katcoder"
-            | "kat"
-            | "kimi"
-            | "minimax"
-            | "zai"
-            | "glm"
-            | "zhipu"
-            | "anyrouter"
-            | "anyr"
-            | "ar"
-            | "openrouter"
-            | "or"
-    )
+    let Ok(template_type) = get_template_type(target) else {
+        return false;
+    };
+    let template = get_template_instance(&template_type);
+    template.has_variants()
+        && !template
+            .variant_aliases()
+            .contains(&normalize_alias(target).as_str())
 }
 
 /// Concrete variant aliases for a template that offers region/variant choice.
@@ -509,8 +741,12 @@ pub fn variant_options(template_type: &TemplateType) -> Vec<(&'static str, &'sta
             ("k2-thinking", "K2 Thinking"),
             ("kimi", "Kimi For Coding"),
         ],
+        TemplateType::Longcat => vec![
+            ("longcat-chat", "Longcat Flash Chat"),
+            ("longcat-thinking", "Longcat Flash Thinking"),
+        ],
         TemplateType::MiniMax => vec![
-            ("minimax", "MiniMax China"),
+            ("minimax-china", "MiniMax China"),
             ("minimax-international", "MiniMax International"),
         ],
         // OpenRouter is model-based (handled by create_with_model_selection);
@@ -570,3 +806,282 @@ pub use openrouter::*;
 pub use seed_code::*;
 pub use zai::*;
 pub use zenmux::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alias_table_parses_to_declared_types() {
+        for (template_type, aliases) in TEMPLATE_ALIASES {
+            for alias in *aliases {
+                assert_eq!(alias.parse::<TemplateType>().unwrap(), *template_type);
+                assert_eq!(normalize_alias(alias), *alias);
+            }
+        }
+        // Every template is reachable by its canonical name
+        for template_type in get_all_templates() {
+            assert!(
+                TEMPLATE_ALIASES
+                    .iter()
+                    .any(|(t, aliases)| *t == template_type
+                        && aliases.contains(&template_type.to_string().as_str()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_every_template_type_instantiates() {
+        for template_type in get_all_templates() {
+            assert_eq!(
+                get_template_instance(&template_type).template_type(),
+                template_type
+            );
+        }
+    }
+
+    #[test]
+    fn test_templates_write_the_default_coding_permissions() {
+        for template_type in get_all_templates() {
+            let settings = get_template_instance(&template_type)
+                .create_settings("sk-test", &SnapshotScope::Common);
+            assert_eq!(
+                settings.permissions,
+                Some(default_coding_permissions()),
+                "{}",
+                template_type
+            );
+        }
+    }
+
+    #[test]
+    fn test_thinking_overrides_flip_zai_env() {
+        let zai = get_template_instance(&TemplateType::Zai);
+        assert!(zai.supports_thinking());
+        let thinking =
+            |settings: &ClaudeSettings, key: &str| settings.env.as_ref().unwrap().get(key).cloned();
+
+        let mut settings = zai.create_settings("sk-test", &SnapshotScope::Common);
+        assert_eq!(
+            thinking(&settings, "ENABLE_THINKING").as_deref(),
+            Some("true")
+        );
+        ThinkingOverrides::from_flags(false, true, None, None)
+            .apply(zai.as_ref(), &mut settings)
+            .unwrap();
+        assert_eq!(
+            thinking(&settings, "ENABLE_THINKING").as_deref(),
+            Some("false")
+        );
+        assert_eq!(thinking(&settings, "REASONING_EFFORT"), None);
+
+        // A budget the settings file already had survives the merge; it goes
+        // once the "off" has landed
+        let mut merged = settings.clone();
+        let env = merged.env.as_mut().unwrap();
+        env.insert("REASONING_EFFORT".to_string(), "high".to_string());
+        env.insert("MAX_THINKING_TOKENS".to_string(), "16000".to_string());
+        ThinkingOverrides::default().drop_stale_budget(&mut merged);
+        assert_eq!(
+            thinking(&merged, "REASONING_EFFORT").as_deref(),
+            Some("high")
+        );
+        ThinkingOverrides::from_flags(false, true, None, None).drop_stale_budget(&mut merged);
+        assert_eq!(merged, settings);
+
+        ThinkingOverrides::from_flags(true, false, Some("high".into()), Some(16000))
+            .apply(zai.as_ref(), &mut settings)
+            .unwrap();
+        assert_eq!(
+            thinking(&settings, "ENABLE_THINKING").as_deref(),
+            Some("true")
+        );
+        assert_eq!(
+            thinking(&settings, "REASONING_EFFORT").as_deref(),
+            Some("high")
+        );
+        assert_eq!(
+            thinking(&settings, "MAX_THINKING_TOKENS").as_deref(),
+            Some("16000")
+        );
+
+        // No flags: nothing to check, even for providers without thinking
+        let minimax = get_template_instance(&TemplateType::MiniMax);
+        let mut settings = minimax.create_settings("sk-test", &SnapshotScope::Common);
+        let before = settings.clone();
+        ThinkingOverrides::default()
+            .apply(minimax.as_ref(), &mut settings)
+            .unwrap();
+        assert_eq!(settings, before);
+        let err = ThinkingOverrides::from_flags(true, false, None, None)
+            .apply(minimax.as_ref(), &mut settings)
+            .unwrap_err();
+        assert!(err.to_string().contains("doesn't support thinking"));
+    }
+
+    #[test]
+    fn test_template_aliases_are_spelling_tolerant() {
+        for input in ["kat_coder_pro", "Kat-Coder-Pro", "KATCODER_PRO"] {
+            assert_eq!(
+                input.parse::<TemplateType>().unwrap(),
+                TemplateType::KatCoder
+            );
+        }
+        // The variant is picked from the normalized spelling too
+        assert_eq!(
+            get_template_instance_with_input(&TemplateType::KatCoder, "Kat_Coder_Air")
+                .display_name(),
+            "KatCoder Air (WanQing)"
+        );
+        assert_eq!(
+            "  minimax  ".parse::<TemplateType>().unwrap(),
+            TemplateType::MiniMax
+        );
+        assert_eq!(
+            " deepseek ".parse::<TemplateType>().unwrap(),
+            TemplateType::DeepSeek
+        );
+        assert_eq!(
+            "seed_code".parse::<TemplateType>().unwrap(),
+            TemplateType::SeedCode
+        );
+        assert!(is_generic_target(" Kat_Coder "));
+
+        let err = "not-a-provider".parse::<TemplateType>().unwrap_err();
+        assert!(matches!(err, CcsError::InvalidTemplate(_)));
+        assert!(err.to_string().contains("Available templates: deepseek"));
+    }
+
+    #[test]
+    fn variant_aliases_pick_a_variant() {
+        for template_type in get_all_templates() {
+            let template = get_template_instance(&template_type);
+            for alias in template.variant_aliases() {
+                assert_eq!(alias.parse::<TemplateType>().unwrap(), template_type);
+                assert!(!is_generic_target(alias), "{}", alias);
+            }
+        }
+
+        assert_eq!(
+            get_template_instance_with_input(&TemplateType::KatCoder, "kat-coder-air")
+                .display_name(),
+            kat_coder::KatCoderTemplate::air().display_name()
+        );
+        assert!(kat_coder::KatCoderTemplate::from_alias("kat-coder").is_none());
+        assert!(kimi::KimiTemplate::from_alias("K2_Thinking").is_some());
+
+        for generic in [
+            "kat-coder",
+            "kat",
+            "kimi",
+            "zai",
+            "glm",
+            "minimax",
+            "ar",
+            "openrouter",
+        ] {
+            assert!(is_generic_target(generic), "{}", generic);
+        }
+        assert!(!is_generic_target("deepseek"));
+        assert!(!is_generic_target("not-a-template"));
+    }
+
+    #[test]
+    fn api_base_matches_documented_urls() {
+        let expected = [
+            ("deepseek", "https://api.deepseek.com/anthropic"),
+            ("zai-china", "https://open.bigmodel.cn/api/anthropic"),
+            ("zai-int", "https://api.z.ai/api/anthropic"),
+            ("k2", "https://api.moonshot.cn/v1"),
+            ("k2-thinking", "https://api.moonshot.cn/anthropic"),
+            ("kimi", "https://api.kimi.com/coding/"),
+            ("longcat", "https://api.longcat.chat/anthropic"),
+            ("fishtrip", "https://api.fishtrip.net"),
+            ("minimax-china", "https://api.minimaxi.com/anthropic"),
+            ("minimax-intl", "https://api.minimax.io/anthropic"),
+            ("seed-code", "https://ark.cn-beijing.volces.com/api/coding"),
+            ("zenmux", "https://zenmux.ai/api/anthropic"),
+            ("duojie", "https://api.duojie.games"),
+            (
+                "anyrouter-china",
+                "https://a-ocnfniawgw.cn-shanghai.fcapp.run",
+            ),
+            ("anyrouter-fallback", "https://anyrouter.top"),
+            ("openrouter", "https://openrouter.ai/api"),
+            ("beeapi", "https://beeapi.ai/anthropic"),
+            ("day77", "https://api.day77.icu"),
+        ];
+        for (target, url) in expected {
+            let template_type = get_template_type(target).unwrap();
+            let template = get_template_instance_with_input(&template_type, target);
+            assert_eq!(template.api_base(), Some(url), "{}", target);
+        }
+
+        // Whatever a template reports is what it writes
+        for template_type in get_all_templates() {
+            let template = get_template_instance(&template_type);
+            let settings = template.create_settings("sk-test", &SnapshotScope::Env);
+            let written = settings.env.unwrap().get("ANTHROPIC_BASE_URL").cloned();
+            match template.api_base() {
+                Some(base) => assert_eq!(written.as_deref(), Some(base), "{}", template_type),
+                None => assert_eq!(template_type, TemplateType::KatCoder),
+            }
+        }
+    }
+
+    #[test]
+    fn auth_mode_sets_and_reads_the_key() {
+        use crate::settings::{API_KEY_ENV, AUTH_TOKEN_ENV};
+
+        let mut instances: Vec<Box<dyn Template>> = get_all_templates()
+            .iter()
+            .map(get_template_instance)
+            .collect();
+        instances.push(get_template_instance_with_input(&TemplateType::Kimi, "k2"));
+
+        for template in instances {
+            let mode = template.auth_mode();
+            let settings = template.create_settings("sk-auth-test", &SnapshotScope::Env);
+            let env = settings.env.as_ref().unwrap();
+            for key in [API_KEY_ENV, AUTH_TOKEN_ENV] {
+                assert_eq!(
+                    env.get(key).is_some_and(|v| v == "sk-auth-test"),
+                    mode.env_keys().contains(&key),
+                    "{} ({:?}): {}",
+                    template.display_name(),
+                    mode,
+                    key
+                );
+            }
+            assert_eq!(
+                settings.get_api_key().as_deref(),
+                Some("sk-auth-test"),
+                "{}",
+                template.display_name()
+            );
+        }
+
+        assert_eq!(
+            get_template_instance_with_input(&TemplateType::Kimi, "k2").auth_mode(),
+            AuthMode::Both
+        );
+        assert_eq!(
+            get_template_instance(&TemplateType::DeepSeek).auth_mode(),
+            AuthMode::AuthToken
+        );
+    }
+
+    #[test]
+    fn env_var_lookups_agree_with_templates() {
+        for template_type in get_all_templates() {
+            let from_trait = get_template_instance(&template_type).env_var_names();
+            assert!(
+                !from_trait.is_empty(),
+                "{} has no env var names",
+                template_type
+            );
+            assert_eq!(get_env_var_names(&template_type), from_trait);
+            assert_eq!(get_env_var_name(&template_type), Some(from_trait[0]));
+        }
+    }
+}
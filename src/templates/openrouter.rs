@@ -3,17 +3,16 @@
 //! OpenRouter provides access to multiple AI models through a unified API.
 //! This template supports interactive model selection with free models prioritized.
 
-use crate::{
-    settings::{ClaudeSettings, Permissions},
-    snapshots::SnapshotScope,
-    templates::Template,
-};
+use crate::{settings::ClaudeSettings, snapshots::SnapshotScope, templates::Template};
 use anyhow::{Result, anyhow};
 use atty;
 use inquire::Select;
 use serde::Deserialize;
 use std::collections::HashMap;
 
+/// Anthropic-compatible API base URL
+const BASE_URL: &str = "https://openrouter.ai/api";
+
 /// OpenRouter model information
 #[derive(Debug, Clone, Deserialize)]
 pub struct OpenRouterModel {
@@ -199,6 +198,10 @@ impl Template for OpenRouterTemplate {
         Some("openrouter.ai")
     }
 
+    fn api_base(&self) -> Option<&'static str> {
+        Some(BASE_URL)
+    }
+
     fn has_variants(&self) -> bool {
         true
     }
@@ -224,23 +227,7 @@ impl Template for OpenRouterTemplate {
         if matches!(scope, SnapshotScope::Common | SnapshotScope::All) {
             settings.model = Some(self.model_id.clone());
 
-            settings.permissions = Some(Permissions {
-                allow: Some(vec![
-                    "Bash".to_string(),
-                    "Read".to_string(),
-                    "Write".to_string(),
-                    "Edit".to_string(),
-                    "MultiEdit".to_string(),
-                    "Glob".to_string(),
-                    "Grep".to_string(),
-                    "WebFetch".to_string(),
-                ]),
-                ask: None,
-                deny: Some(vec!["WebSearch".to_string()]),
-                additional_directories: None,
-                default_mode: None,
-                disable_bypass_permissions_mode: None,
-            });
+            settings.permissions = Some(self.default_permissions());
         }
 
         if matches!(
@@ -248,11 +235,8 @@ impl Template for OpenRouterTemplate {
             SnapshotScope::Env | SnapshotScope::Common | SnapshotScope::All
         ) {
             let mut env = HashMap::new();
-            env.insert(
-                "ANTHROPIC_BASE_URL".to_string(),
-                "https://openrouter.ai/api".to_string(),
-            );
-            env.insert("ANTHROPIC_AUTH_TOKEN".to_string(), api_key.to_string());
+            env.insert("ANTHROPIC_BASE_URL".to_string(), BASE_URL.to_string());
+            self.auth_mode().insert_key(&mut env, api_key);
             // ANTHROPIC_API_KEY must be empty to allow interactive model selection
             env.insert("ANTHROPIC_API_KEY".to_string(), "".to_string());
             env.insert("ANTHROPIC_MODEL".to_string(), self.model_id.clone());
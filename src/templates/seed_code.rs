@@ -1,12 +1,15 @@
 //! Seed Code (Volcengine) AI provider template implementation
 
 use crate::{
-    settings::{ClaudeSettings, Permissions},
+    settings::{AuthMode, ClaudeSettings},
     snapshots::SnapshotScope,
     templates::Template,
 };
 use std::collections::HashMap;
 
+/// Anthropic-compatible API base URL
+const BASE_URL: &str = "https://ark.cn-beijing.volces.com/api/coding";
+
 /// Seed Code AI provider template
 #[derive(Debug, Clone)]
 pub struct SeedCodeTemplate;
@@ -25,6 +28,10 @@ impl Template for SeedCodeTemplate {
         ]
     }
 
+    fn auth_mode(&self) -> AuthMode {
+        AuthMode::Both
+    }
+
     fn display_name(&self) -> &'static str {
         "Seed Code"
     }
@@ -41,29 +48,17 @@ impl Template for SeedCodeTemplate {
         Some("ark.cn-beijing.volces.com")
     }
 
+    fn api_base(&self) -> Option<&'static str> {
+        Some(BASE_URL)
+    }
+
     fn create_settings(&self, api_key: &str, scope: &SnapshotScope) -> ClaudeSettings {
         let mut settings = ClaudeSettings::new();
 
         if matches!(scope, SnapshotScope::Common | SnapshotScope::All) {
             settings.model = Some("doubao-seed-code-preview-latest".to_string());
 
-            settings.permissions = Some(Permissions {
-                allow: Some(vec![
-                    "Bash".to_string(),
-                    "Read".to_string(),
-                    "Write".to_string(),
-                    "Edit".to_string(),
-                    "MultiEdit".to_string(),
-                    "Glob".to_string(),
-                    "Grep".to_string(),
-                    "WebFetch".to_string(),
-                ]),
-                ask: None,
-                deny: Some(vec!["WebSearch".to_string()]),
-                additional_directories: None,
-                default_mode: None,
-                disable_bypass_permissions_mode: None,
-            });
+            settings.permissions = Some(self.default_permissions());
         }
 
         if matches!(
@@ -71,12 +66,8 @@ impl Template for SeedCodeTemplate {
             SnapshotScope::Env | SnapshotScope::Common | SnapshotScope::All
         ) {
             let mut env = HashMap::new();
-            env.insert("ANTHROPIC_API_KEY".to_string(), api_key.to_string());
-            env.insert("ANTHROPIC_AUTH_TOKEN".to_string(), api_key.to_string());
-            env.insert(
-                "ANTHROPIC_BASE_URL".to_string(),
-                "https://ark.cn-beijing.volces.com/api/coding".to_string(),
-            );
+            self.auth_mode().insert_key(&mut env, api_key);
+            env.insert("ANTHROPIC_BASE_URL".to_string(), BASE_URL.to_string());
             env.insert(
                 "ANTHROPIC_MODEL".to_string(),
                 "doubao-seed-code-preview-latest".to_string(),
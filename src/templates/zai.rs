@@ -1,7 +1,7 @@
 //! ZAI (GLM/Zhipu) AI provider template implementation
 
 use crate::{
-    settings::{ClaudeSettings, Permissions},
+    settings::ClaudeSettings,
     snapshots::SnapshotScope,
     templates::{AUTO_COMPACT_WINDOWS, AutoCompactWindow, Template},
 };
@@ -75,6 +75,14 @@ pub struct ZaiTemplate {
     region: ZaiRegion,
 }
 
+/// Targets that name one region directly, and the region each picks
+const REGION_ALIASES: &[(&str, ZaiRegion)] = &[
+    ("zai-china", ZaiRegion::China),
+    ("zai-ch", ZaiRegion::China),
+    ("zai-international", ZaiRegion::International),
+    ("zai-int", ZaiRegion::International),
+];
+
 impl ZaiTemplate {
     pub fn new(region: ZaiRegion) -> Self {
         Self { region }
@@ -107,23 +115,7 @@ impl ZaiTemplate {
             });
 
             // Use the new permissions format from the provided version
-            settings.permissions = Some(Permissions {
-                allow: Some(vec![
-                    "Bash".to_string(),
-                    "Read".to_string(),
-                    "Write".to_string(),
-                    "Edit".to_string(),
-                    "MultiEdit".to_string(),
-                    "Glob".to_string(),
-                    "Grep".to_string(),
-                    "WebFetch".to_string(),
-                ]),
-                ask: None,
-                deny: Some(vec!["WebSearch".to_string()]),
-                additional_directories: None,
-                default_mode: None,
-                disable_bypass_permissions_mode: None,
-            });
+            settings.permissions = Some(self.default_permissions());
         }
 
         if matches!(
@@ -131,7 +123,7 @@ impl ZaiTemplate {
             SnapshotScope::Env | SnapshotScope::Common | SnapshotScope::All
         ) {
             let mut env = HashMap::new();
-            env.insert("ANTHROPIC_AUTH_TOKEN".to_string(), api_key.to_string());
+            self.auth_mode().insert_key(&mut env, api_key);
             env.insert(
                 "ANTHROPIC_BASE_URL".to_string(),
                 self.region.base_url().to_string(),
@@ -206,14 +198,34 @@ impl Template for ZaiTemplate {
         Some(self.region.api_key_url())
     }
 
+    fn supports_thinking(&self) -> bool {
+        true
+    }
+
     fn api_host(&self) -> Option<&'static str> {
         Some(self.region.api_host())
     }
 
+    fn api_base(&self) -> Option<&'static str> {
+        Some(self.region.base_url())
+    }
+
     fn has_variants(&self) -> bool {
         true
     }
 
+    fn variant_aliases(&self) -> Vec<&'static str> {
+        REGION_ALIASES.iter().map(|(alias, _)| *alias).collect()
+    }
+
+    fn from_alias(alias: &str) -> Option<Self> {
+        let alias = crate::templates::normalize_alias(alias);
+        REGION_ALIASES
+            .iter()
+            .find(|(candidate, _)| *candidate == alias)
+            .map(|(_, region)| Self::new(region.clone()))
+    }
+
     fn get_variants() -> Result<Vec<Self>>
     where
         Self: Sized,
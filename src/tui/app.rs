@@ -7,15 +7,14 @@
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 
 use crate::CredentialManager;
-use crate::credentials::{
-    ApiKeyChoice, ApiKeySource, CredentialStore, collect_api_key_sources, mask_api_key,
-};
+use crate::credentials::{ApiKeyChoice, ApiKeySource, CredentialStore, collect_api_key_sources};
 use crate::prefs::{KeyRef, Prefs};
 use crate::snapshots::SnapshotScope;
 use crate::templates::{
     AutoCompactWindow, TemplateType, get_template_instance_with_input, is_generic_target,
     supports_auto_compact_option, variant_options,
 };
+use crate::utils::{MaskPolicy, mask_api_key};
 
 use super::input::TextInput;
 
@@ -333,7 +332,7 @@ impl App {
 
     pub fn masked_selected_key(&self) -> String {
         match self.selected_key.and_then(|i| self.sources.get(i)) {
-            Some(s) => mask_api_key(s.api_key()),
+            Some(s) => mask_api_key(s.api_key(), MaskPolicy::DEFAULT),
             None => "(none)".to_string(),
         }
     }
@@ -606,7 +605,7 @@ impl App {
                     if new_name != credential.name() {
                         match CredentialStore::new() {
                             Ok(store) => {
-                                if let Err(e) = store.update_name(credential.id(), new_name) {
+                                if let Err(e) = store.rename_by_id(credential.id(), &new_name) {
                                     self.mode = Mode::Message(format!("Rename failed: {e}"));
                                     return Outcome::Continue;
                                 }
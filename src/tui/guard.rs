@@ -0,0 +1,176 @@
+//! Raw-mode session guard: whatever ends the session — a normal return, an
+//! error propagated with `?`, or a panic — the terminal is put back the way
+//! it was. Release builds abort on panic (`panic = "abort"`), so no `Drop`
+//! runs there; a panic hook restores the terminal instead.
+
+use crossterm::{
+    cursor::{Hide, Show},
+    execute,
+    terminal::{
+        EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
+        is_raw_mode_enabled,
+    },
+};
+
+/// Switching the terminal into and out of the TUI's raw, full-screen mode.
+pub trait TerminalControl {
+    /// Raw mode on, alternate screen, cursor hidden
+    fn enter(&mut self) -> std::io::Result<()>;
+
+    /// Undo [`Self::enter`]; best effort, since it also runs while unwinding
+    fn restore(&mut self);
+}
+
+/// [`TerminalControl`] for the real terminal via crossterm
+pub struct CrosstermControl;
+
+impl TerminalControl for CrosstermControl {
+    fn enter(&mut self) -> std::io::Result<()> {
+        enable_raw_mode()?;
+        execute!(std::io::stdout(), EnterAlternateScreen, Hide)
+    }
+
+    fn restore(&mut self) {
+        disable_raw_mode().ok();
+        execute!(std::io::stdout(), LeaveAlternateScreen, Show).ok();
+    }
+}
+
+impl CrosstermControl {
+    /// Install (once per process) a panic hook that restores the terminal if
+    /// a TUI session left it in raw mode
+    pub fn install_panic_hook() {
+        static INSTALLED: std::sync::Once = std::sync::Once::new();
+        INSTALLED.call_once(|| {
+            install_panic_hook(|| {
+                if is_raw_mode_enabled().unwrap_or(false) {
+                    CrosstermControl.restore();
+                }
+            })
+        });
+    }
+}
+
+/// Run `restore` on panic, before the previous hook prints the message
+pub fn install_panic_hook(restore: impl Fn() + Send + Sync + 'static) {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        restore();
+        previous(info);
+    }));
+}
+
+/// Holds the terminal in raw mode until dropped.
+pub struct RawModeGuard<T: TerminalControl> {
+    control: T,
+    active: bool,
+}
+
+impl<T: TerminalControl> RawModeGuard<T> {
+    /// Enter raw mode. If that fails halfway, whatever was switched on is
+    /// restored before the error is returned.
+    pub fn enter(mut control: T) -> std::io::Result<Self> {
+        if let Err(e) = control.enter() {
+            control.restore();
+            return Err(e);
+        }
+        Ok(Self {
+            control,
+            active: true,
+        })
+    }
+
+    /// Restore the terminal now; dropping the guard afterwards does nothing
+    pub fn restore(&mut self) {
+        if std::mem::take(&mut self.active) {
+            self.control.restore();
+        }
+    }
+}
+
+impl<T: TerminalControl> Drop for RawModeGuard<T> {
+    fn drop(&mut self) {
+        self.restore();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    struct MockTerminal {
+        log: Rc<RefCell<Vec<&'static str>>>,
+        fail_enter: bool,
+    }
+
+    impl TerminalControl for MockTerminal {
+        fn enter(&mut self) -> std::io::Result<()> {
+            self.log.borrow_mut().push("enter");
+            if self.fail_enter {
+                return Err(std::io::Error::other("no tty"));
+            }
+            Ok(())
+        }
+
+        fn restore(&mut self) {
+            self.log.borrow_mut().push("restore");
+        }
+    }
+
+    fn mock(fail_enter: bool) -> (MockTerminal, Rc<RefCell<Vec<&'static str>>>) {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let terminal = MockTerminal {
+            log: log.clone(),
+            fail_enter,
+        };
+        (terminal, log)
+    }
+
+    #[test]
+    fn test_guard_restores_terminal_once() {
+        // Dropped early
+        let (terminal, log) = mock(false);
+        let guard = RawModeGuard::enter(terminal).unwrap();
+        assert_eq!(*log.borrow(), ["enter"]);
+        drop(guard);
+        assert_eq!(*log.borrow(), ["enter", "restore"]);
+
+        // Restored explicitly, then dropped
+        let (terminal, log) = mock(false);
+        let mut guard = RawModeGuard::enter(terminal).unwrap();
+        guard.restore();
+        drop(guard);
+        assert_eq!(*log.borrow(), ["enter", "restore"]);
+
+        // Failed enter still cleans up
+        let (terminal, log) = mock(true);
+        assert!(RawModeGuard::enter(terminal).is_err());
+        assert_eq!(*log.borrow(), ["enter", "restore"]);
+    }
+
+    #[test]
+    fn test_panic_hook_restores_before_previous_hook() {
+        use std::sync::{Arc, Mutex};
+
+        // Other tests may panic while the hook is installed; only count this
+        // thread's panics
+        let this_thread = std::thread::current().id();
+        let restored = Arc::new(Mutex::new(0));
+        let counter = restored.clone();
+        install_panic_hook(move || {
+            if std::thread::current().id() == this_thread {
+                *counter.lock().unwrap() += 1;
+            }
+        });
+
+        // The hook runs at the panic itself, which is also where an abort
+        // would happen, so unwinding plays no part in the restore
+        let result = std::panic::catch_unwind(|| panic!("render failed"));
+        drop(std::panic::take_hook());
+
+        assert!(result.is_err());
+        assert_eq!(*restored.lock().unwrap(), 1);
+    }
+}
@@ -3,24 +3,21 @@
 //! and returns an [`ApplySelection`]; it does not touch settings.json itself.
 
 mod app;
+mod guard;
 mod input;
 mod view;
 
 pub use app::{ApplySelection, Outcome};
 
 use anyhow::Result;
-use crossterm::{
-    cursor::{Hide, Show},
-    event::{self, Event, KeyEventKind},
-    execute,
-    terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
-};
+use crossterm::event::{self, Event, KeyEventKind};
 use ratatui::{Terminal, backend::CrosstermBackend};
 
 use crate::prefs::Prefs;
 use crate::templates::TemplateType;
 
 use app::App;
+use guard::{CrosstermControl, RawModeGuard};
 
 /// Open the apply TUI for a resolved target.
 ///
@@ -35,10 +32,10 @@ pub fn run_apply_tui(
     // Build state first (may error before we touch the terminal).
     let mut app = App::new(template_type, target, display_name, current_label, prefs)?;
 
-    enable_raw_mode()?;
-    let mut stdout = std::io::stdout();
-    execute!(stdout, EnterAlternateScreen, Hide)?;
-    let backend = CrosstermBackend::new(stdout);
+    // Restores the terminal on every way out, including `?` and panics
+    CrosstermControl::install_panic_hook();
+    let mut guard = RawModeGuard::enter(CrosstermControl)?;
+    let backend = CrosstermBackend::new(std::io::stdout());
     let mut terminal = Terminal::new(backend)?;
 
     let result = (|| -> Result<Option<ApplySelection>> {
@@ -61,9 +58,7 @@ pub fn run_apply_tui(
         }
     })();
 
-    // Restore the terminal regardless of outcome.
-    disable_raw_mode().ok();
-    execute!(terminal.backend_mut(), LeaveAlternateScreen, Show).ok();
+    guard.restore();
     terminal.show_cursor().ok();
 
     result
@@ -8,9 +8,10 @@ use ratatui::{
     widgets::{Block, Borders, Clear, Paragraph, Wrap},
 };
 
-use crate::credentials::{ApiKeySource, mask_api_key};
+use crate::credentials::ApiKeySource;
 use crate::tui::app::{App, Mode};
 use crate::tui::input::TextInput;
+use crate::utils::{MaskPolicy, mask_api_key};
 
 const CURSOR: &str = "❯ ";
 const NONE_CURSOR: &str = "  ";
@@ -56,6 +57,14 @@ pub fn render(frame: &mut Frame, app: &App) {
     render_help(frame, chunks[2]);
 
     render_popup(frame, app);
+
+    // --no-color / NO_COLOR: keep modifiers, drop colors.
+    if !console::colors_enabled() {
+        for cell in frame.buffer_mut().content.iter_mut() {
+            cell.fg = Color::Reset;
+            cell.bg = Color::Reset;
+        }
+    }
 }
 
 fn render_title(frame: &mut Frame, app: &App, area: Rect) {
@@ -96,10 +105,10 @@ fn render_body(frame: &mut Frame, app: &App, area: Rect) {
             } => (
                 "🌐",
                 env_var_name.clone(),
-                format!("{} (env)", mask_api_key(api_key)),
+                format!("{} (env)", mask_api_key(api_key, MaskPolicy::DEFAULT)),
             ),
             ApiKeySource::Saved { credential } => {
-                let mut d = mask_api_key(credential.api_key());
+                let mut d = mask_api_key(credential.api_key(), MaskPolicy::DEFAULT);
                 if credential.last_used_at().is_some() {
                     d.push_str("  · last used");
                 }
@@ -12,6 +12,55 @@ pub fn get_settings_path(settings_path: Option<PathBuf>) -> PathBuf {
     })
 }
 
+/// `--settings-path -`: read settings from stdin and write them to stdout
+pub fn is_stdio_path(path: &Path) -> bool {
+    path.as_os_str() == "-"
+}
+
+/// Stdin content for `--settings-path -`, read on first use
+static STDIN_SETTINGS: std::sync::Mutex<Option<String>> = std::sync::Mutex::new(None);
+
+/// Read all of stdin once; later calls return the same content, since a
+/// command may load the settings file more than once.
+pub fn read_stdin_once() -> std::io::Result<String> {
+    let mut cached = STDIN_SETTINGS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(content) = cached.as_ref() {
+        return Ok(content.clone());
+    }
+    let content = std::io::read_to_string(std::io::stdin())?;
+    *cached = Some(content.clone());
+    Ok(content)
+}
+
+/// Stand in for stdin in tests of `--settings-path -`
+#[cfg(test)]
+pub(crate) fn seed_stdin(content: &str) {
+    *STDIN_SETTINGS.lock().unwrap_or_else(|e| e.into_inner()) = Some(content.to_string());
+}
+
+/// Resolve the settings path from `--settings-path` / `--global` / `--local`.
+/// Without any of them, the project settings are used when the current
+/// directory has a `.claude` directory, the global settings otherwise.
+pub fn resolve_settings_path(settings_path: Option<PathBuf>, global: bool, local: bool) -> PathBuf {
+    if global {
+        get_global_settings_path()
+    } else if local {
+        get_local_settings_path()
+    } else if let Some(path) = settings_path {
+        path
+    } else if should_use_local_settings() {
+        get_local_settings_path()
+    } else {
+        get_global_settings_path()
+    }
+}
+
+/// Get the global (user-level) settings path: `~/.claude/settings.json`
+pub fn get_global_settings_path() -> PathBuf {
+    let home_dir = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
+    home_dir.join(".claude").join("settings.json")
+}
+
 /// Get the path to the environment-specific settings file
 pub fn get_env_var_path() -> PathBuf {
     PathBuf::from(".claude").join("settings.json")
@@ -35,13 +84,35 @@ pub fn confirm_action(message: &str, default: bool) -> Result<bool> {
         .map_err(|e| anyhow::anyhow!("Confirmation failed: {}", e))
 }
 
-/// Create a backup of current settings
+/// Backups kept per settings file: `settings.json.backup` is the newest,
+/// then `settings.json.backup.1` up to `.backup.{BACKUP_KEEP - 1}`
+pub const BACKUP_KEEP: usize = 5;
+
+/// Path of the `generation`-th newest backup of `settings_path`
+pub fn backup_path(settings_path: &Path, generation: usize) -> PathBuf {
+    if generation == 0 {
+        settings_path.with_extension("json.backup")
+    } else {
+        settings_path.with_extension(format!("json.backup.{}", generation))
+    }
+}
+
+/// Create a backup of current settings, shifting older backups down so the
+/// last [`BACKUP_KEEP`] are kept
 pub fn backup_settings(settings_path: &Path) -> Result<Option<PathBuf>> {
     if !settings_path.exists() {
         return Ok(None);
     }
 
-    let backup_path = settings_path.with_extension("json.backup");
+    for generation in (1..BACKUP_KEEP).rev() {
+        let older = backup_path(settings_path, generation - 1);
+        if older.exists() {
+            replace_file(&older, &backup_path(settings_path, generation))
+                .map_err(|e| anyhow!("Failed to rotate backup {}: {}", older.display(), e))?;
+        }
+    }
+
+    let backup_path = backup_path(settings_path, 0);
     std::fs::copy(settings_path, &backup_path)
         .map_err(|e| anyhow!("Failed to create backup: {}", e))?;
     Ok(Some(backup_path))
@@ -119,6 +190,46 @@ pub fn format_duration(seconds: i64) -> String {
     }
 }
 
+/// First of `base-2`, `base-3`, ... for which `taken` is false
+pub fn unique_name(base: &str, taken: impl Fn(&str) -> bool) -> String {
+    (2..)
+        .map(|n| format!("{}-{}", base, n))
+        .find(|candidate| !taken(candidate))
+        .expect("unbounded range")
+}
+
+/// Parse a duration like `30d`, `12h`, `2w`, `45m` or `90s`
+pub fn parse_duration(input: &str) -> Result<chrono::Duration> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+    let amount: i64 = number
+        .parse()
+        .map_err(|_| anyhow!("Invalid duration '{}'. Use e.g. 30d, 12h, 2w", input))?;
+    let duration = match unit {
+        "s" => chrono::Duration::try_seconds(amount),
+        "m" => chrono::Duration::try_minutes(amount),
+        "h" => chrono::Duration::try_hours(amount),
+        "d" => chrono::Duration::try_days(amount),
+        "w" => chrono::Duration::try_weeks(amount),
+        _ => {
+            return Err(anyhow!(
+                "Invalid duration unit in '{}'. Use s, m, h, d or w",
+                input
+            ));
+        }
+    };
+    duration.ok_or_else(|| anyhow!("Duration '{}' is out of range", input))
+}
+
+/// The moment `age` ago, or `None` when that is before the earliest
+/// representable time (so nothing can be older than it)
+pub fn cutoff_before(age: chrono::Duration) -> Option<chrono::DateTime<chrono::Utc>> {
+    chrono::Utc::now().checked_sub_signed(age)
+}
+
 /// Truncate text to a maximum length
 pub fn truncate_text(text: &str, max_length: usize) -> String {
     if text.len() <= max_length {
@@ -142,6 +253,115 @@ pub fn format_list(items: &[&str], separator: &str) -> String {
     items.join(separator)
 }
 
+/// Plain-text table whose columns line up however long the cells are.
+///
+/// Widths are measured without ANSI codes, so styled cells align too. The
+/// last column isn't padded, leaving no trailing spaces.
+#[derive(Default)]
+pub struct Table {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+    min_widths: Vec<usize>,
+    styles: Vec<Option<console::Style>>,
+}
+
+impl Table {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Header row, rendered bold
+    pub fn headers<S: Into<String>>(mut self, headers: impl IntoIterator<Item = S>) -> Self {
+        self.headers = headers.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Pad `column` to at least `width` characters
+    pub fn min_width(mut self, column: usize, width: usize) -> Self {
+        if self.min_widths.len() <= column {
+            self.min_widths.resize(column + 1, 0);
+        }
+        self.min_widths[column] = width;
+        self
+    }
+
+    /// Style every cell of `column` (a no-op when colors are off)
+    pub fn style(mut self, column: usize, style: console::Style) -> Self {
+        if self.styles.len() <= column {
+            self.styles.resize(column + 1, None);
+        }
+        self.styles[column] = Some(style);
+        self
+    }
+
+    /// Add a row
+    pub fn row<S: Into<String>>(&mut self, cells: impl IntoIterator<Item = S>) {
+        self.rows.push(cells.into_iter().map(Into::into).collect());
+    }
+
+    /// One line per row (header first, if any)
+    pub fn lines(&self) -> Vec<String> {
+        let columns = self
+            .rows
+            .iter()
+            .map(Vec::len)
+            .chain([self.headers.len()])
+            .max()
+            .unwrap_or(0);
+        let widths: Vec<usize> = (0..columns)
+            .map(|column| {
+                self.rows
+                    .iter()
+                    .chain([&self.headers])
+                    .filter_map(|row| row.get(column))
+                    .map(|cell| console::measure_text_width(cell))
+                    .chain([self.min_widths.get(column).copied().unwrap_or(0)])
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        let render = |cells: &[String], header: bool| {
+            let mut line = String::new();
+            for (column, width) in widths.iter().enumerate() {
+                let cell = cells.get(column).map(String::as_str).unwrap_or("");
+                let styled = match self.styles.get(column).cloned().flatten() {
+                    _ if header => style(cell).bold().to_string(),
+                    Some(column_style) => column_style.apply_to(cell).to_string(),
+                    None => cell.to_string(),
+                };
+                if column + 1 == columns {
+                    line.push_str(&styled);
+                } else {
+                    line.push_str(&console::pad_str(
+                        &styled,
+                        *width,
+                        console::Alignment::Left,
+                        None,
+                    ));
+                    line.push_str("  ");
+                }
+            }
+            line.trim_end().to_string()
+        };
+
+        let mut lines = Vec::new();
+        if !self.headers.is_empty() {
+            lines.push(render(&self.headers, true));
+        }
+        lines.extend(self.rows.iter().map(|row| render(row, false)));
+        lines
+    }
+
+    /// The table as text, one row per line
+    pub fn render(&self) -> String {
+        self.lines()
+            .iter()
+            .map(|line| format!("{}\n", line))
+            .collect()
+    }
+}
+
 /// Get the file size of a path
 pub fn get_file_size(path: &Path) -> Result<u64> {
     if path.exists() {
@@ -162,6 +382,348 @@ pub fn ensure_dir_exists(dir: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Whether colors should be turned off: `--no-color`, or a non-empty
+/// `NO_COLOR` (see https://no-color.org).
+pub fn colors_disabled(no_color_flag: bool, no_color_env: Option<&std::ffi::OsStr>) -> bool {
+    no_color_flag || no_color_env.is_some_and(|v| !v.is_empty())
+}
+
+/// Apply the color preference to all `console::style` output.
+pub fn configure_colors(no_color_flag: bool) {
+    if colors_disabled(no_color_flag, std::env::var_os("NO_COLOR").as_deref()) {
+        console::set_colors_enabled(false);
+        console::set_colors_enabled_stderr(false);
+    }
+}
+
+/// Parse a `.env` file: `KEY=VALUE` lines, optionally prefixed with
+/// `export`. Blank lines and `#` comments are skipped; values may be wrapped
+/// in single quotes (taken literally) or double quotes (`\n`, `\"`, `\\`
+/// escapes). Unquoted values end at an inline ` #` comment.
+pub fn parse_env_file(content: &str) -> std::collections::HashMap<String, String> {
+    let mut vars = std::collections::HashMap::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim();
+        if key.is_empty() {
+            continue;
+        }
+        vars.insert(key.to_string(), parse_env_value(value.trim()));
+    }
+    vars
+}
+
+fn parse_env_value(raw: &str) -> String {
+    if let Some(inner) = raw.strip_prefix('\'').and_then(|r| r.split('\'').next()) {
+        return inner.to_string();
+    }
+    if let Some(rest) = raw.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = rest.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => break,
+                '\\' => match chars.next() {
+                    Some('n') => value.push('\n'),
+                    Some(other) => value.push(other),
+                    None => break,
+                },
+                c => value.push(c),
+            }
+        }
+        return value;
+    }
+    match raw.find(" #") {
+        Some(idx) => raw[..idx].trim_end().to_string(),
+        None => raw.to_string(),
+    }
+}
+
+/// Parse a `KEY=VALUE` argument (`config set-env`). The value is taken as
+/// given, since the shell has already handled any quoting.
+pub fn parse_env_assignment(input: &str) -> Result<(String, String)> {
+    let (key, value) = input
+        .split_once('=')
+        .ok_or_else(|| anyhow!("Expected KEY=VALUE, got '{}'", input))?;
+    let key = key.trim();
+    if key.is_empty() || key.contains(char::is_whitespace) {
+        return Err(anyhow!("Invalid env variable name '{}'", key));
+    }
+    Ok((key.to_string(), value.to_string()))
+}
+
+/// Read and parse a `.env` file (see [`parse_env_file`])
+pub fn load_env_file(path: &Path) -> Result<std::collections::HashMap<String, String>> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read env file {}: {}", path.display(), e))?;
+    Ok(parse_env_file(&content))
+}
+
+/// Write `content` to `path` atomically.
+///
+/// The data goes to a temporary file in the same directory which is then
+/// renamed over `path`, so readers see either the old file or the complete
+/// new one, never a truncated write. An existing file keeps its permissions,
+/// and a symlink is followed so the file it points to is replaced instead of
+/// the link.
+pub fn write_atomic(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let resolved;
+    let path = if path.exists() {
+        resolved = std::fs::canonicalize(path)?;
+        resolved.as_path()
+    } else {
+        path
+    };
+    let permissions = std::fs::metadata(path).ok().map(|m| m.permissions());
+
+    let dir = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    let file_name = path.file_name().and_then(|n| n.to_str()).ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, "path has no file name")
+    })?;
+    let tmp_path = dir.join(format!(".{}.{}.tmp", file_name, uuid::Uuid::new_v4()));
+
+    let result = (|| -> std::io::Result<()> {
+        let mut file = std::fs::File::create(&tmp_path)?;
+        file.write_all(content)?;
+        file.sync_all()?;
+        drop(file);
+        if let Some(permissions) = permissions {
+            std::fs::set_permissions(&tmp_path, permissions)?;
+        }
+        replace_file(&tmp_path, path)
+    })();
+
+    if result.is_err() {
+        std::fs::remove_file(&tmp_path).ok();
+    }
+    result
+}
+
+/// How often to retry a filesystem write that failed transiently.
+///
+/// Networked or cloud-synced home directories can briefly answer `EAGAIN` or
+/// `EBUSY`; those writes are retried with exponential backoff. Other errors
+/// fail immediately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt
+    pub retries: u32,
+    /// Wait before the first retry; doubled for each following one
+    pub initial_delay: std::time::Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: 3,
+            initial_delay: std::time::Duration::from_millis(50),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Never retry
+    pub fn none() -> Self {
+        Self {
+            retries: 0,
+            initial_delay: std::time::Duration::ZERO,
+        }
+    }
+
+    /// Whether an error of this kind is worth retrying
+    pub fn is_retryable(kind: std::io::ErrorKind) -> bool {
+        use std::io::ErrorKind;
+        matches!(
+            kind,
+            ErrorKind::WouldBlock
+                | ErrorKind::ResourceBusy
+                | ErrorKind::Interrupted
+                | ErrorKind::TimedOut
+        )
+    }
+
+    /// Run `op`, retrying it on retryable errors
+    pub fn run<T>(&self, mut op: impl FnMut() -> std::io::Result<T>) -> std::io::Result<T> {
+        let mut delay = self.initial_delay;
+        let mut attempt = 0;
+        loop {
+            match op() {
+                Err(e) if attempt < self.retries && Self::is_retryable(e.kind()) => {
+                    attempt += 1;
+                    std::thread::sleep(delay);
+                    delay = delay.saturating_mul(2);
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// [`write_atomic`] retried under `policy`, holding the [`FileLock`] of the
+/// file's directory so concurrent ccs processes write one after the other
+pub fn write_atomic_retrying(
+    path: &Path,
+    content: &[u8],
+    policy: &RetryPolicy,
+) -> std::io::Result<()> {
+    let dir = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    let _lock = FileLock::acquire(dir, LOCK_TIMEOUT)?;
+    policy.run(|| write_atomic(path, content))
+}
+
+/// How long a write waits for another ccs process to release a lock
+pub const LOCK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+thread_local! {
+    /// Lock files this thread holds, so nested acquires don't deadlock
+    static HELD_LOCKS: std::cell::RefCell<Vec<PathBuf>> = const { std::cell::RefCell::new(Vec::new()) };
+}
+
+/// Advisory lock on the ccs files in a directory, taken on one hidden
+/// [`FileLock::FILE_NAME`] file in it (the files themselves are replaced on
+/// write, so they can't hold a lock). Stores hold it across
+/// load → modify → save; nested acquires on the same thread are no-ops.
+/// Released when the outermost guard is dropped.
+pub struct FileLock {
+    held: Option<(std::fs::File, PathBuf)>,
+}
+
+impl FileLock {
+    /// Name of the lock file inside the locked directory
+    pub const FILE_NAME: &str = ".ccs.lock";
+
+    /// Wait up to `timeout` for the lock on `dir`
+    pub fn acquire(dir: &Path, timeout: std::time::Duration) -> std::io::Result<Self> {
+        use std::fs::TryLockError;
+
+        let lock_path = dir.join(Self::FILE_NAME);
+        if HELD_LOCKS.with(|held| held.borrow().contains(&lock_path)) {
+            return Ok(Self { held: None });
+        }
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)?;
+
+        let deadline = std::time::Instant::now() + timeout;
+        loop {
+            match file.try_lock() {
+                Ok(()) => {
+                    HELD_LOCKS.with(|held| held.borrow_mut().push(lock_path.clone()));
+                    return Ok(Self {
+                        held: Some((file, lock_path)),
+                    });
+                }
+                Err(TryLockError::WouldBlock) if std::time::Instant::now() < deadline => {
+                    std::thread::sleep(std::time::Duration::from_millis(10));
+                }
+                Err(TryLockError::WouldBlock) => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        format!(
+                            "another ccs process holds the lock on {} (waited {:?})",
+                            dir.display(),
+                            timeout
+                        ),
+                    ));
+                }
+                Err(TryLockError::Error(e)) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        if let Some((_file, lock_path)) = self.held.take() {
+            HELD_LOCKS.with(|held| held.borrow_mut().retain(|path| path != &lock_path));
+        }
+    }
+}
+
+/// Move `from` over `to`, replacing any existing file.
+#[cfg(not(windows))]
+fn replace_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::rename(from, to)
+}
+
+/// Move `from` over `to`, replacing any existing file.
+///
+/// `rename` can fail on Windows when the target exists and is briefly held
+/// open (e.g. by an editor or antivirus); retry once after removing it.
+#[cfg(windows)]
+fn replace_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    match std::fs::rename(from, to) {
+        Ok(()) => Ok(()),
+        Err(_) if to.is_file() => {
+            std::fs::remove_file(to)?;
+            std::fs::rename(from, to)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// How much of an API key stays visible when it is displayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaskPolicy {
+    /// Leading characters shown
+    pub prefix_len: usize,
+    /// Trailing characters shown
+    pub suffix_len: usize,
+    /// Keys this long or shorter are masked entirely, without revealing
+    /// their length
+    pub min_full_mask_len: usize,
+}
+
+impl MaskPolicy {
+    /// First and last four characters; keys of eight or fewer fully masked
+    pub const DEFAULT: MaskPolicy = MaskPolicy {
+        prefix_len: 4,
+        suffix_len: 4,
+        min_full_mask_len: 8,
+    };
+}
+
+impl Default for MaskPolicy {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Mask an API key for display according to `policy`
+pub fn mask_api_key(api_key: &str, policy: MaskPolicy) -> String {
+    const BULLET: char = '•';
+    let chars: Vec<char> = api_key.chars().collect();
+    let shown = policy.prefix_len + policy.suffix_len;
+    if chars.len() <= policy.min_full_mask_len.max(shown) {
+        return BULLET.to_string().repeat(8);
+    }
+    let prefix: String = chars[..policy.prefix_len].iter().collect();
+    let suffix: String = chars[chars.len() - policy.suffix_len..].iter().collect();
+    format!(
+        "{}{}{}",
+        prefix,
+        BULLET.to_string().repeat(chars.len() - shown),
+        suffix
+    )
+}
+
 /// Check if a string is a valid UUID
 pub fn is_valid_uuid(uuid_str: &str) -> bool {
     uuid::Uuid::parse_str(uuid_str).is_ok()
@@ -277,3 +839,356 @@ pub fn format_settings_summary(settings: &ClaudeSettings) -> String {
 
     summary.trim_end().to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writer that fails with `EBUSY` a set number of times before accepting data
+    struct FlakyWriter {
+        failures_left: u32,
+        attempts: u32,
+        written: Vec<u8>,
+    }
+
+    impl std::io::Write for FlakyWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.attempts += 1;
+            if self.failures_left > 0 {
+                self.failures_left -= 1;
+                return Err(std::io::ErrorKind::ResourceBusy.into());
+            }
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_table_columns_line_up() {
+        let mut table = Table::new()
+            .headers(["NAME", "TEMPLATE", "KEY"])
+            .min_width(1, 10)
+            .style(0, console::Style::new().green().force_styling(true));
+        table.row(["a", "zai", "sk-1"]);
+        table.row(["a-very-long-credential-name", "deepseek (+2)", "sk-2"]);
+        table.row(["名前", "kimi", ""]);
+
+        let lines = table.lines();
+        assert!(lines[1].contains('\u{1b}'), "cells are styled");
+        let plain: Vec<String> = lines
+            .iter()
+            .map(|line| console::strip_ansi_codes(line).into_owned())
+            .collect();
+        assert_eq!(
+            plain,
+            [
+                "NAME                         TEMPLATE       KEY",
+                "a                            zai            sk-1",
+                "a-very-long-credential-name  deepseek (+2)  sk-2",
+                "名前                         kimi",
+            ]
+        );
+        // Every row starts its columns at the same display offsets
+        for line in &plain[1..3] {
+            assert_eq!(
+                console::measure_text_width(&line[..line.find("sk").unwrap_or(line.len())]),
+                44
+            );
+        }
+        assert_eq!(table.render().lines().count(), 4);
+    }
+
+    #[test]
+    fn test_mask_api_key_policy() {
+        let default = MaskPolicy::default();
+        assert_eq!(mask_api_key("sk-1234567890", default), "sk-1•••••7890");
+        assert_eq!(
+            mask_api_key("sk-ant-REDACTED", default),
+            "sk-a•••••••••••••••••••••mnop"
+        );
+        assert_eq!(mask_api_key("short", default), "••••••••");
+        assert_eq!(mask_api_key("12345678", default), "••••••••");
+        assert_eq!(mask_api_key("", default), "••••••••");
+        assert_eq!(
+            mask_api_key("密钥密钥密钥密钥密钥", default),
+            "密钥密钥••密钥密钥"
+        );
+
+        let narrow = MaskPolicy {
+            prefix_len: 3,
+            suffix_len: 2,
+            min_full_mask_len: 4,
+        };
+        assert_eq!(mask_api_key("sk-abcdef", narrow), "sk-••••ef");
+        assert_eq!(mask_api_key("abcd", narrow), "••••••••");
+        // Never reveals the whole key, whatever the threshold
+        assert_eq!(mask_api_key("sk-ab", narrow), "••••••••");
+    }
+
+    #[test]
+    fn test_retry_policy_recovers_from_transient_errors() {
+        use std::io::Write;
+
+        let policy = RetryPolicy {
+            retries: 3,
+            initial_delay: std::time::Duration::ZERO,
+        };
+        let mut writer = FlakyWriter {
+            failures_left: 2,
+            attempts: 0,
+            written: Vec::new(),
+        };
+        policy.run(|| writer.write_all(b"{}")).unwrap();
+        assert_eq!(writer.attempts, 3);
+        assert_eq!(writer.written, b"{}");
+
+        // Gives up once the retries are used up
+        let mut writer = FlakyWriter {
+            failures_left: 2,
+            attempts: 0,
+            written: Vec::new(),
+        };
+        let err = RetryPolicy::none()
+            .run(|| writer.write_all(b"{}"))
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::ResourceBusy);
+        assert_eq!(writer.attempts, 1);
+
+        // Non-transient errors are not retried
+        let mut attempts = 0;
+        let err = policy
+            .run(|| -> std::io::Result<()> {
+                attempts += 1;
+                Err(std::io::ErrorKind::PermissionDenied.into())
+            })
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30d").unwrap(), chrono::Duration::days(30));
+        assert_eq!(parse_duration("12h").unwrap(), chrono::Duration::hours(12));
+        assert_eq!(parse_duration("2w").unwrap(), chrono::Duration::weeks(2));
+        assert!(parse_duration("30").is_err());
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("3y").is_err());
+        assert!(parse_duration("99999999999999w").is_err());
+    }
+
+    #[test]
+    fn cutoff_before_does_not_overflow() {
+        let huge = parse_duration("9999999999d").unwrap();
+        assert!(cutoff_before(huge).is_none());
+        assert!(cutoff_before(chrono::Duration::days(1)).unwrap() < chrono::Utc::now());
+    }
+
+    #[test]
+    fn unique_name_skips_taken_suffixes() {
+        let taken = ["work", "work-2", "work-3"];
+        assert_eq!(unique_name("work", |n| taken.contains(&n)), "work-4");
+        assert_eq!(unique_name("home", |n| taken.contains(&n)), "home-2");
+    }
+
+    #[test]
+    fn global_flag_resolves_under_home() {
+        let path = resolve_settings_path(None, true, false);
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(path, home.join(".claude").join("settings.json"));
+    }
+
+    #[test]
+    fn local_flag_resolves_under_cwd() {
+        let path = resolve_settings_path(None, false, true);
+        assert!(path.is_relative());
+        assert_eq!(path, PathBuf::from(".claude").join("settings.json"));
+    }
+
+    #[test]
+    fn no_flag_auto_detects_project_or_global() {
+        let expected = if should_use_local_settings() {
+            get_local_settings_path()
+        } else {
+            get_global_settings_path()
+        };
+        assert_eq!(resolve_settings_path(None, false, false), expected);
+    }
+
+    #[test]
+    fn env_file_handles_quotes_and_comments() {
+        let vars = parse_env_file(
+            "# provider keys\n\
+             DEEPSEEK_API_KEY=sk-x\n\
+             export ZAI_API_KEY=\"sk \\\"quoted\\\"\" \n\
+             KIMI_API_KEY='sk-#literal'\n\
+             BASE=https://example.com # trailing comment\n\
+             not a pair\n",
+        );
+        assert_eq!(vars.len(), 4);
+        assert_eq!(vars["DEEPSEEK_API_KEY"], "sk-x");
+        assert_eq!(vars["ZAI_API_KEY"], "sk \"quoted\"");
+        assert_eq!(vars["KIMI_API_KEY"], "sk-#literal");
+        assert_eq!(vars["BASE"], "https://example.com");
+    }
+
+    #[test]
+    fn concurrent_locked_writes_are_serialized() {
+        let dir = std::env::temp_dir().join(format!("ccs_lock_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("settings.json");
+        let contents: Vec<String> = ["a", "b"]
+            .iter()
+            .map(|tag| serde_json::json!({ "tag": tag, "pad": tag.repeat(64 * 1024) }).to_string())
+            .collect();
+
+        std::thread::scope(|scope| {
+            let writers: Vec<_> = contents
+                .iter()
+                .map(|content| {
+                    let path = &path;
+                    scope.spawn(move || {
+                        for _ in 0..20 {
+                            write_atomic_retrying(path, content.as_bytes(), &RetryPolicy::none())
+                                .unwrap();
+                        }
+                    })
+                })
+                .collect();
+            for writer in writers {
+                writer.join().unwrap();
+            }
+        });
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert!(contents.contains(&written));
+
+        // One lock file per directory, not one per written file
+        let lock_files: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .filter(|name| name.to_string_lossy().ends_with(".lock"))
+            .collect();
+        assert_eq!(lock_files, [FileLock::FILE_NAME]);
+
+        // Re-acquiring on the same thread doesn't deadlock, and a write
+        // inside a held lock goes through
+        let held = FileLock::acquire(&dir, LOCK_TIMEOUT).unwrap();
+        let _nested = FileLock::acquire(&dir, std::time::Duration::ZERO).unwrap();
+        write_atomic_retrying(&path, b"{}", &RetryPolicy::none()).unwrap();
+
+        // ... while other threads give up with a clear error
+        let err = std::thread::scope(|scope| {
+            scope
+                .spawn(|| FileLock::acquire(&dir, std::time::Duration::from_millis(30)))
+                .join()
+                .unwrap()
+                .err()
+                .unwrap()
+        });
+        assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+        assert!(
+            err.to_string()
+                .contains("another ccs process holds the lock")
+        );
+        drop(held);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn backups_rotate_and_keep_the_newest() {
+        let dir = std::env::temp_dir().join(format!("ccs_backup_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("settings.json");
+        assert_eq!(backup_settings(&path).unwrap(), None);
+
+        for n in 0..BACKUP_KEEP + 2 {
+            std::fs::write(&path, format!("{{\"n\": {}}}", n)).unwrap();
+            assert_eq!(backup_settings(&path).unwrap(), Some(backup_path(&path, 0)));
+        }
+        let newest = BACKUP_KEEP + 1;
+        for generation in 0..BACKUP_KEEP {
+            assert_eq!(
+                std::fs::read_to_string(backup_path(&path, generation)).unwrap(),
+                format!("{{\"n\": {}}}", newest - generation)
+            );
+        }
+        assert!(!backup_path(&path, BACKUP_KEEP).exists());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn write_atomic_replaces_and_leaves_no_temp_files() {
+        let dir = std::env::temp_dir().join(format!("ccs_atomic_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("settings.json");
+
+        write_atomic(&path, b"{\"a\": 1}").unwrap();
+        write_atomic(&path, b"{\"a\": 2}").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{\"a\": 2}");
+
+        // A failed replace (target is a directory) keeps nothing half-written
+        let blocked = dir.join("blocked.json");
+        std::fs::create_dir_all(blocked.join("inner")).unwrap();
+        assert!(write_atomic(&blocked, b"{}").is_err());
+
+        let leftovers: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name().to_string_lossy().ends_with(".tmp"))
+            .collect();
+        assert!(leftovers.is_empty());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_atomic_keeps_mode_and_follows_symlinks() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("ccs_atomic_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let target = dir.join("real.json");
+        std::fs::write(&target, "{}").unwrap();
+        std::fs::set_permissions(&target, std::fs::Permissions::from_mode(0o600)).unwrap();
+        let link = dir.join("settings.json");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        write_atomic(&link, b"{\"a\": 1}").unwrap();
+        assert!(std::fs::symlink_metadata(&link).unwrap().is_symlink());
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "{\"a\": 1}");
+        let mode = std::fs::metadata(&target).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn no_color_disables_escape_sequences() {
+        assert!(colors_disabled(true, None));
+        assert!(colors_disabled(false, Some(std::ffi::OsStr::new("1"))));
+        assert!(!colors_disabled(false, Some(std::ffi::OsStr::new(""))));
+        assert!(!colors_disabled(false, None));
+
+        configure_colors(true);
+        let line = status_indicator(true, "settings ok");
+        let header = style("Settings").bold().green().to_string();
+        assert!(!line.contains('\x1b'), "{:?}", line);
+        assert!(!header.contains('\x1b'), "{:?}", header);
+    }
+
+    #[test]
+    fn explicit_path_wins_without_flags() {
+        let explicit = PathBuf::from("/tmp/custom/settings.json");
+        assert_eq!(
+            resolve_settings_path(Some(explicit.clone()), false, false),
+            explicit
+        );
+    }
+}